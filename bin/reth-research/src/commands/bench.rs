@@ -0,0 +1,71 @@
+//! `reth-research bench`: measure analysis throughput over exported blocks.
+
+use clap::Args;
+use reth_ethereum::{chainspec::ChainSpec, cli::chainspec::chain_value_parser};
+use reth_research::{
+    config::ResearchConfig,
+    offline::{self, OfflineAnalyzer},
+    timing::{AnalysisTimings, Phase},
+};
+use std::{path::PathBuf, sync::Arc, time::Instant};
+
+/// Time the dual execution analysis over exported blocks, without recording divergences.
+#[derive(Debug, Args)]
+pub(crate) struct BenchCommand {
+    /// File of concatenated RLP-encoded blocks, in order
+    #[arg(long)]
+    blocks: PathBuf,
+
+    /// JSON file with the accounts the first block executes against
+    #[arg(long)]
+    state: PathBuf,
+
+    /// Gas cost multiplier for experimental execution
+    #[arg(long = "gas-multiplier", default_value_t = 128)]
+    gas_multiplier: u64,
+
+    /// Chain the blocks belong to (name or path to a genesis file)
+    #[arg(long, default_value = "mainnet", value_parser = chain_value_parser)]
+    chain: Arc<ChainSpec>,
+}
+
+impl BenchCommand {
+    /// Execute the command.
+    pub(crate) fn run(self) -> eyre::Result<()> {
+        let config = ResearchConfig::builder().with_gas_multiplier(self.gas_multiplier).build()?;
+        // Read everything up front so decoding is not part of the measurement
+        let blocks =
+            offline::read_blocks(&self.blocks)?.into_iter().collect::<Result<Vec<_>, _>>()?;
+        let timings = Arc::new(AnalysisTimings::default());
+        let mut analyzer =
+            OfflineAnalyzer::new(self.chain, config, offline::read_state(&self.state)?)
+                .with_timings(timings.clone());
+
+        let start = Instant::now();
+        let mut divergences = 0;
+        for block in blocks {
+            divergences += analyzer.analyze_block(block)?.divergences.len();
+        }
+        let wall_time = start.elapsed();
+
+        let breakdown = timings.snapshot();
+        println!("Transactions analyzed:  {}", breakdown.transactions);
+        println!("Divergences found:      {divergences}");
+        println!("Wall time:              {wall_time:?}");
+        println!("Throughput:             {:.1} tx/s", breakdown.transactions_per_second());
+        println!("Average latency:        {:?}", breakdown.average_latency());
+        for (name, phase, elapsed) in [
+            ("Normal execution", Phase::NormalExecution, breakdown.normal_execution),
+            (
+                "Experimental execution",
+                Phase::ExperimentalExecution,
+                breakdown.experimental_execution,
+            ),
+            ("Comparison", Phase::Comparison, breakdown.comparison),
+        ] {
+            println!("  {name:<22}{elapsed:?} ({:.1}%)", breakdown.share(phase) * 100.0);
+        }
+
+        Ok(())
+    }
+}
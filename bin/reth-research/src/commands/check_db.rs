@@ -0,0 +1,31 @@
+//! `reth-research check-db`: verify the integrity of a divergence database.
+
+use clap::Args;
+use reth_research::database::DivergenceDatabase;
+use std::path::PathBuf;
+
+/// Run SQLite's integrity check over a divergence database, failing if it is corrupt.
+#[derive(Debug, Args)]
+pub(crate) struct CheckDbCommand {
+    /// Path to the divergence database
+    #[arg(long = "db-path", default_value = "./divergence.db")]
+    db_path: PathBuf,
+}
+
+impl CheckDbCommand {
+    /// Execute the command.
+    pub(crate) fn run(self) -> eyre::Result<()> {
+        // Opening a missing path read-only fails with a less helpful SQLite error
+        eyre::ensure!(self.db_path.exists(), "No database at {}", self.db_path.display());
+
+        // Checking must not create, migrate or otherwise modify the database
+        let db = DivergenceDatabase::open_read_only(&self.db_path)?;
+        eyre::ensure!(
+            db.integrity_check()?,
+            "{} failed the integrity check, see the logged problems",
+            self.db_path.display()
+        );
+        println!("{}: ok", self.db_path.display());
+        Ok(())
+    }
+}
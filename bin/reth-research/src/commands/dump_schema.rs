@@ -0,0 +1,16 @@
+//! `reth-research dump-schema`: print the divergence database schema.
+
+use clap::Args;
+use reth_research::database;
+
+/// Print the SQL schema of the divergence database, including its schema version.
+#[derive(Debug, Args)]
+pub(crate) struct DumpSchemaCommand;
+
+impl DumpSchemaCommand {
+    /// Execute the command.
+    pub(crate) fn run(self) -> eyre::Result<()> {
+        print!("{}", database::schema_sql());
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+//! `reth-research flamegraph`: fold a divergence's call tree into a gas profile.
+
+use clap::Args;
+use reth_research::{database::DivergenceDatabase, flamegraph};
+use std::path::PathBuf;
+
+/// Print the per-frame gas of a recorded divergence as folded stacks for flamegraph tools.
+#[derive(Debug, Args)]
+pub(crate) struct FlamegraphCommand {
+    /// Row id of the divergence, as printed by `watch`
+    divergence_id: i64,
+
+    /// Path to the divergence database
+    #[arg(long = "db-path", default_value = "./divergence.db")]
+    db_path: PathBuf,
+
+    /// Profile the normal execution instead of the experimental one
+    #[arg(long)]
+    normal: bool,
+}
+
+impl FlamegraphCommand {
+    /// Execute the command.
+    pub(crate) fn run(self) -> eyre::Result<()> {
+        let db = DivergenceDatabase::open(&self.db_path)?;
+        let Some(call_trees) = db.call_trees(self.divergence_id)? else {
+            eyre::bail!(
+                "divergence {} has no call trees; record with `--research.trace-detail detailed` \
+                 or `--research.persist-frame-gas`",
+                self.divergence_id
+            )
+        };
+
+        let frames = if self.normal { &call_trees.normal } else { &call_trees.experimental };
+        for line in flamegraph::folded_stacks(frames) {
+            println!("{line}");
+        }
+        Ok(())
+    }
+}
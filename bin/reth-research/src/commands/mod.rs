@@ -0,0 +1,82 @@
+//! Research tooling subcommands that operate on a divergence database or on exported blocks.
+//!
+//! These run without launching a node, e.g.:
+//!
+//! ```sh
+//! reth-research watch --db-path ./divergences.db --min-severity high
+//! reth-research top --db-path ./divergences.db
+//! reth-research offline --blocks ./blocks.rlp --state ./state.json --chain mainnet
+//! reth-research bench --blocks ./blocks.rlp --state ./state.json --gas-multiplier 128
+//! reth-research flamegraph 42 --db-path ./divergences.db | inferno-flamegraph > gas.svg
+//! reth-research report 42 --db-path ./divergences.db > report.md
+//! reth-research dump-schema > schema.sql
+//! reth-research check-db --db-path ./divergences.db
+//! ```
+
+mod bench;
+mod check_db;
+mod dump_schema;
+mod flamegraph;
+mod offline;
+mod report;
+mod top;
+mod watch;
+
+use clap::{Parser, Subcommand};
+
+/// Research tooling CLI.
+#[derive(Debug, Parser)]
+#[command(
+    name = "reth-research",
+    about = "Research tooling for divergence databases and exported blocks"
+)]
+pub(crate) struct ToolCli {
+    #[command(subcommand)]
+    command: ToolCommand,
+}
+
+/// Research tooling subcommands.
+#[derive(Debug, Subcommand)]
+enum ToolCommand {
+    /// Tail the divergence database and print new divergences as they are recorded
+    Watch(watch::WatchCommand),
+    /// Show a live dashboard of the divergence database (requires the `tui` feature)
+    Top(top::TopCommand),
+    /// Analyze blocks from an RLP file against a JSON pre-state, without running a node
+    Offline(offline::OfflineCommand),
+    /// Measure analysis throughput and per-phase latency over exported blocks
+    Bench(bench::BenchCommand),
+    /// Print a divergence's per-frame gas as folded stacks for flamegraph tools
+    Flamegraph(flamegraph::FlamegraphCommand),
+    /// Print a divergence as a Markdown report for sharing in an issue
+    Report(report::ReportCommand),
+    /// Print the SQL schema of the divergence database
+    DumpSchema(dump_schema::DumpSchemaCommand),
+    /// Verify the integrity of the divergence database
+    CheckDb(check_db::CheckDbCommand),
+}
+
+impl ToolCli {
+    /// Names of the tooling subcommands, used to route them away from the node CLI.
+    const COMMANDS: &'static [&'static str] =
+        &["watch", "top", "offline", "bench", "flamegraph", "report", "dump-schema", "check-db"];
+
+    /// Returns true if the process was invoked with one of the tooling subcommands.
+    pub(crate) fn is_invoked() -> bool {
+        std::env::args().nth(1).is_some_and(|arg| Self::COMMANDS.contains(&arg.as_str()))
+    }
+
+    /// Run the selected subcommand.
+    pub(crate) fn run(self) -> eyre::Result<()> {
+        match self.command {
+            ToolCommand::Watch(command) => command.run(),
+            ToolCommand::Top(command) => command.run(),
+            ToolCommand::Offline(command) => command.run(),
+            ToolCommand::Bench(command) => command.run(),
+            ToolCommand::Flamegraph(command) => command.run(),
+            ToolCommand::Report(command) => command.run(),
+            ToolCommand::DumpSchema(command) => command.run(),
+            ToolCommand::CheckDb(command) => command.run(),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! `reth-research offline`: analyze blocks from an RLP file without running a node.
+
+use clap::Args;
+use reth_ethereum::{chainspec::ChainSpec, cli::chainspec::chain_value_parser};
+use reth_research::{
+    config::ResearchConfig,
+    database::DivergenceDatabase,
+    executor::ResearchStats,
+    offline::{self, OfflineAnalyzer},
+};
+use std::{path::PathBuf, sync::Arc};
+
+/// Run the dual execution analysis over exported blocks.
+#[derive(Debug, Args)]
+pub(crate) struct OfflineCommand {
+    /// File of concatenated RLP-encoded blocks, in order
+    #[arg(long)]
+    blocks: PathBuf,
+
+    /// JSON file with the accounts the first block executes against
+    #[arg(long)]
+    state: PathBuf,
+
+    /// Path to the divergence database
+    #[arg(long = "db-path", default_value = "./divergence.db")]
+    db_path: PathBuf,
+
+    /// Gas cost multiplier for experimental execution
+    #[arg(long = "gas-multiplier", default_value_t = 128)]
+    gas_multiplier: u64,
+
+    /// Charge the experimental gas costs in the interpreter, halting where they run out, instead
+    /// of simulating them (requires the `real-gas` feature)
+    #[arg(long = "real-gas")]
+    real_gas: bool,
+
+    /// Chain the blocks belong to (name or path to a genesis file)
+    #[arg(long, default_value = "mainnet", value_parser = chain_value_parser)]
+    chain: Arc<ChainSpec>,
+}
+
+impl OfflineCommand {
+    /// Execute the command.
+    pub(crate) fn run(self) -> eyre::Result<()> {
+        let config = ResearchConfig::builder()
+            .with_gas_multiplier(self.gas_multiplier)
+            .with_real_gas(self.real_gas)
+            .with_divergence_db_path(self.db_path.clone())
+            .build()?;
+        let db = DivergenceDatabase::open(&self.db_path)?;
+        let run_id = db.record_run_start(&config)?;
+        let blocks = offline::read_blocks(&self.blocks)?;
+        let mut analyzer =
+            OfflineAnalyzer::new(self.chain, config, offline::read_state(&self.state)?);
+
+        let (mut analyzed, mut skipped, mut divergences) = (0u64, 0u64, 0u64);
+        for block in blocks {
+            // A bad block is reported and skipped; later blocks still run against the state
+            // left by the last good one
+            let result = block.and_then(|block| analyzer.analyze_block(block));
+            match result {
+                Ok(analysis) => {
+                    analyzed += 1;
+                    for divergence in &analysis.divergences {
+                        db.record_divergence(divergence)?;
+                    }
+                    db.record_block_summary(&analysis.summary)?;
+                    divergences += analysis.divergences.len() as u64;
+                }
+                Err(e) => {
+                    skipped += 1;
+                    eprintln!("Skipping block: {e}");
+                }
+            }
+        }
+
+        let stats = ResearchStats {
+            blocks_processed: analyzed,
+            divergences_found: divergences,
+            divergences_recorded: divergences,
+        };
+        db.record_run_end(run_id, &stats)?;
+
+        println!(
+            "Analyzed {analyzed} blocks ({skipped} skipped), recorded {divergences} divergences to {}",
+            self.db_path.display()
+        );
+
+        Ok(())
+    }
+}
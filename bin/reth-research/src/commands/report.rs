@@ -0,0 +1,36 @@
+//! `reth-research report`: render a recorded divergence as a Markdown issue report.
+
+use clap::Args;
+use reth_research::database::DivergenceDatabase;
+use std::path::PathBuf;
+
+/// Print a recorded divergence as a Markdown report, ready to paste into an issue.
+#[derive(Debug, Args)]
+pub(crate) struct ReportCommand {
+    /// Row id of the divergence, as printed by `watch`
+    divergence_id: i64,
+
+    /// Path to the divergence database
+    #[arg(long = "db-path", default_value = "./divergence.db")]
+    db_path: PathBuf,
+}
+
+impl ReportCommand {
+    /// Execute the command.
+    pub(crate) fn run(self) -> eyre::Result<()> {
+        let db = DivergenceDatabase::open_read_only(&self.db_path)?;
+        let Some(record) = db.divergence(self.divergence_id)? else {
+            eyre::bail!(
+                "no divergence with id {} in {}",
+                self.divergence_id,
+                self.db_path.display()
+            )
+        };
+
+        // Call trees are stored in their own table, include them when they were recorded
+        let mut divergence = record.divergence;
+        divergence.call_trees = db.call_trees(self.divergence_id)?;
+        print!("{}", divergence.to_markdown());
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+//! `reth-research top`: live dashboard of a divergence database.
+//!
+//! The terminal UI needs the `tui` feature; the panels are drawn from a
+//! [`DashboardState`](reth_research::dashboard::DashboardState) refreshed on an interval.
+
+use clap::Args;
+use std::path::PathBuf;
+
+/// Show a live-updating dashboard of the divergence database.
+#[derive(Debug, Args)]
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+pub(crate) struct TopCommand {
+    /// Path to the divergence database
+    #[arg(long = "db-path", default_value = "./divergence.db")]
+    db_path: PathBuf,
+
+    /// Refresh interval in seconds
+    #[arg(long, default_value_t = 2)]
+    interval: u64,
+}
+
+impl TopCommand {
+    /// Execute the command, refreshing until `q` or `Esc` is pressed.
+    pub(crate) fn run(self) -> eyre::Result<()> {
+        #[cfg(feature = "tui")]
+        {
+            tui::run(&self.db_path, std::time::Duration::from_secs(self.interval))
+        }
+        #[cfg(not(feature = "tui"))]
+        {
+            eyre::bail!("`reth-research top` requires building with the `tui` feature")
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+mod tui {
+    use crossterm::{
+        event::{self, Event, KeyCode, KeyEventKind},
+        execute,
+        terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    };
+    use ratatui::{
+        backend::{Backend, CrosstermBackend},
+        layout::{Constraint, Direction, Layout},
+        style::{Color, Modifier, Style},
+        widgets::{Block, Borders, List, ListItem, Paragraph, Sparkline},
+        Frame, Terminal,
+    };
+    use reth_research::{
+        dashboard::{DashboardState, RATE_BLOCKS},
+        database::DivergenceDatabase,
+    };
+    use std::{
+        io,
+        path::Path,
+        time::{Duration, Instant},
+    };
+
+    /// Number of contracts listed in the top contracts panel.
+    const TOP_CONTRACTS: usize = 20;
+
+    /// Show the dashboard of the database at `db_path`, refreshing every `interval`.
+    pub(super) fn run(db_path: &Path, interval: Duration) -> eyre::Result<()> {
+        // The node keeps the database in WAL mode, so reading here does not block its writes
+        let db = DivergenceDatabase::open_read_only(db_path)?;
+        let mut state = DashboardState::new();
+        state.refresh(&db)?;
+
+        // Setup backend
+        enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        execute!(stdout, EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+        let res = event_loop(&mut terminal, db_path, &db, &mut state, interval);
+
+        // Restore terminal
+        disable_raw_mode()?;
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+        terminal.show_cursor()?;
+        res
+    }
+
+    /// Draw the dashboard and refresh it every `interval` until the user quits.
+    fn event_loop<B: Backend>(
+        terminal: &mut Terminal<B>,
+        db_path: &Path,
+        db: &DivergenceDatabase,
+        state: &mut DashboardState,
+        interval: Duration,
+    ) -> eyre::Result<()> {
+        let mut last_refresh = Instant::now();
+        loop {
+            terminal.draw(|f| ui(f, db_path, state))?;
+
+            let timeout = interval.saturating_sub(last_refresh.elapsed());
+            if event::poll(timeout)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press &&
+                        matches!(key.code, KeyCode::Char('q' | 'Q') | KeyCode::Esc)
+                    {
+                        return Ok(())
+                    }
+                }
+            }
+
+            if last_refresh.elapsed() >= interval {
+                state.refresh(db)?;
+                last_refresh = Instant::now();
+            }
+        }
+    }
+
+    /// Render the dashboard panels.
+    fn ui(f: &mut Frame<'_>, db_path: &Path, state: &DashboardState) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Length(8), Constraint::Min(0)])
+            .split(f.area());
+
+        let header = Paragraph::new(format!(
+            "{} divergences in {} | [q] quit",
+            state.total(),
+            db_path.display()
+        ))
+        .block(Block::default().borders(Borders::ALL).title("reth-research top"))
+        .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD));
+        f.render_widget(header, rows[0]);
+
+        let rate = state.rate();
+        let sparkline = Sparkline::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("Divergences per block (last {RATE_BLOCKS} blocks)")),
+            )
+            .data(&rate)
+            .style(Style::default().fg(Color::Yellow));
+        f.render_widget(sparkline, rows[1]);
+
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .split(rows[2]);
+        let panels = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(columns[0]);
+
+        let contracts = state
+            .top_contracts(TOP_CONTRACTS)
+            .into_iter()
+            .map(|(contract, count)| ListItem::new(format!("{count:>8}  {contract}")))
+            .collect::<Vec<_>>();
+        let contracts = List::new(contracts)
+            .block(Block::default().borders(Borders::ALL).title("Top contracts"));
+        f.render_widget(contracts, panels[0]);
+
+        let mut types = state.by_type().iter().collect::<Vec<_>>();
+        types.sort_by(|a, b| b.1.cmp(a.1));
+        let types = types
+            .into_iter()
+            .map(|(divergence_type, count)| ListItem::new(format!("{count:>8}  {divergence_type}")))
+            .collect::<Vec<_>>();
+        let types = List::new(types).block(Block::default().borders(Borders::ALL).title("Types"));
+        f.render_widget(types, panels[1]);
+
+        let recent = state
+            .recent()
+            .map(|record| {
+                let divergence = &record.divergence;
+                ListItem::new(format!(
+                    "#{} {:?} {}",
+                    record.id,
+                    divergence.severity(),
+                    divergence.summary_line()
+                ))
+            })
+            .collect::<Vec<_>>();
+        let recent = List::new(recent)
+            .block(Block::default().borders(Borders::ALL).title("Recent divergences"));
+        f.render_widget(recent, columns[1]);
+    }
+}
@@ -0,0 +1,78 @@
+//! `reth-research watch`: print new divergences as they are recorded.
+
+use clap::Args;
+use reth_research::{
+    database::{DivergenceDatabase, DivergenceRecord},
+    divergence::{DivergenceType, Severity},
+};
+use std::{path::PathBuf, time::Duration};
+
+/// Maximum number of rows fetched per poll.
+const BATCH_SIZE: usize = 1000;
+
+/// Tail the divergence database and print newly-inserted divergences.
+#[derive(Debug, Args)]
+pub(crate) struct WatchCommand {
+    /// Path to the divergence database
+    #[arg(long = "db-path", default_value = "./divergence.db")]
+    db_path: PathBuf,
+
+    /// Poll interval in seconds
+    #[arg(long, default_value_t = 2)]
+    interval: u64,
+
+    /// Only print divergences that include this type (e.g. `status`)
+    #[arg(long = "type")]
+    divergence_type: Option<DivergenceType>,
+
+    /// Only print divergences at or above this severity (low, medium, high)
+    #[arg(long = "min-severity", default_value = "low")]
+    min_severity: Severity,
+
+    /// Print divergences already in the database before following new ones
+    #[arg(long = "from-start")]
+    from_start: bool,
+}
+
+impl WatchCommand {
+    /// Execute the command, polling until interrupted.
+    pub(crate) fn run(self) -> eyre::Result<()> {
+        // The node keeps the database in WAL mode, so reading here does not block its writes
+        let db = DivergenceDatabase::open_read_only(&self.db_path)?;
+        let mut last_id = if self.from_start { 0 } else { db.max_id()? };
+
+        eprintln!("Watching {} for new divergences (from id {last_id})", self.db_path.display());
+
+        loop {
+            let records = db.since_id(last_id, BATCH_SIZE)?;
+            let caught_up = records.len() < BATCH_SIZE;
+
+            for record in records {
+                last_id = record.id;
+                if self.matches(&record) {
+                    println!("{}", format_record(&record));
+                }
+            }
+
+            if caught_up {
+                std::thread::sleep(Duration::from_secs(self.interval));
+            }
+        }
+    }
+
+    /// Whether the record passes the `--type`/`--min-severity` filters.
+    fn matches(&self, record: &DivergenceRecord) -> bool {
+        let divergence = &record.divergence;
+        if let Some(dtype) = self.divergence_type {
+            if !divergence.divergence_types.contains(&dtype) {
+                return false
+            }
+        }
+        divergence.severity() >= self.min_severity
+    }
+}
+
+/// Format a divergence record as a single line.
+fn format_record(record: &DivergenceRecord) -> String {
+    format!("#{} {} {}", record.id, record.divergence.summary_line(), record.divergence.tx_hash)
+}
@@ -14,29 +14,54 @@
 //!   --research.gas-multiplier 128 \
 //!   --research.db-path ./divergences.db
 //! ```
+//!
+//! Tooling subcommands that run without a node (e.g. `watch`, `offline`) are available as
+//! `reth-research <command>`; see [`commands`].
+
+mod commands;
 
-use alloy_consensus::{transaction::TxHashRef, BlockHeader};
+use alloy_consensus::{transaction::TxHashRef, BlockHeader, Transaction};
+use alloy_primitives::{Address, B256};
+use clap::Parser;
 use futures::TryStreamExt;
 use reth_ethereum::{
     exex::{ExExContext, ExExEvent, ExExNotification},
     node::EthereumNode,
 };
-use reth_evm::{ConfigureEvm, Evm};
+use reth_evm::ConfigureEvm;
 use reth_node_api::{BlockTy, FullNodeComponents};
 use reth_primitives_traits::BlockBody;
-use reth_provider::StateProviderFactory;
+use reth_provider::{
+    AccountReader, BlockNumReader, ProviderResult, StateProviderBox, StateProviderFactory,
+};
 use reth_research::{
-    config::{ResearchConfig, TraceDetail},
-    database::DivergenceDatabase,
-    divergence::{CallTrees, Divergence, DivergenceType, EventLog, EventLogs, GasAnalysis},
-    inspector::GasResearchInspector,
+    analysis::{self, BlockContext, SequentialState, TxContext},
+    code_cache::{CachedCodeDatabase, CodeCache},
+    config::ResearchConfig,
+    database::{BlockSummary, Checkpoint, DivergenceWriter},
+    divergence::{Comparison, Divergence},
+    executor::{ResearchCounters, ResearchStats},
+    frequent::FrequentItems,
     metrics,
-    tracking_inspector::TrackingInspector,
+    progress::ProgressReporter,
+    provider::{self as research_provider, AnalyzedBlocks},
+    sampling::Sampler,
+    signatures::SignatureDatabase,
+    store::{self, DivergenceStore},
+    timing::{AnalysisTimings, Phase},
+};
+use reth_revm::database::StateProviderDatabase;
+use reth_tracing::tracing::{debug, info, trace, warn};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
 };
-use reth_revm::{database::StateProviderDatabase, db::CacheDB};
-use reth_tracing::tracing::{debug, info, warn};
-use tokio::sync::mpsc;
 
+/// Number of counters in the top contracts summary.
+const TOP_CONTRACTS_CAPACITY: usize = 256;
+
+/// Number of top contracts published as metrics.
+const TOP_CONTRACTS_REPORTED: usize = 10;
 
 /// Research ExEx that performs dual execution analysis on committed blocks.
 struct ResearchExEx<Node: FullNodeComponents> {
@@ -44,78 +69,157 @@ struct ResearchExEx<Node: FullNodeComponents> {
     ctx: ExExContext<Node>,
     /// Research configuration
     config: ResearchConfig,
-    /// Channel sender for async database writes
-    db_tx: Option<mpsc::UnboundedSender<Divergence>>,
-    /// Statistics
-    blocks_processed: u64,
-    divergences_found: u64,
+    /// Background database writer, flushed on shutdown
+    db_writer: Option<DivergenceWriter>,
+    /// Store and id of this run's entry in the run log, ended on shutdown
+    run: Option<(Arc<dyn DivergenceStore>, i64)>,
+    /// Hash of the research settings, recorded with each checkpoint
+    config_hash: B256,
+    /// Statistics, shared so they stay correct once blocks are processed in parallel
+    stats: Arc<ResearchCounters>,
+    /// Time spent in each phase of transaction analysis
+    timings: Arc<AnalysisTimings>,
+    /// Picks the transactions to analyze, biased towards recently divergent contracts
+    sampler: Sampler,
+    /// Contracts causing the most divergences in this run
+    top_contracts: FrequentItems<Address>,
+    /// Contract bytecode shared by the executions of all transactions
+    code_cache: Arc<CodeCache>,
+    /// Function names recorded with divergences, if a signature database is configured
+    signatures: Option<SignatureDatabase>,
+    /// Last block analyzed in this process, so overlapping notifications analyze blocks once
+    analyzed_blocks: AnalyzedBlocks,
+    /// Periodic progress log, if enabled
+    progress: Option<ProgressReporter>,
 }
 
 impl<Node: FullNodeComponents> ResearchExEx<Node> {
     /// Create a new research ExEx.
     fn new(
         ctx: ExExContext<Node>,
-        config: ResearchConfig,
+        mut config: ResearchConfig,
     ) -> eyre::Result<Self> {
         config.validate()?;
 
-        // Initialize database and async writer
-        let db_tx = if config.divergence_db_path.to_str() != Some(":memory:") {
-            let divergence_db = DivergenceDatabase::open(&config.divergence_db_path)?;
-
+        // Both executions charge normal gas costs, so any divergence is an inspector bug
+        if config.self_test {
+            config = config.self_test_config();
             info!(
                 target: "exex::research",
-                path = ?config.divergence_db_path,
-                "Research ExEx initialized with async divergence database writer"
+                "Research self-test: experimental execution runs at normal gas costs, any \
+                 divergence points to a bug in the inspector"
             );
+        }
 
-            // Spawn database writer task
-            let (tx, mut rx) = mpsc::unbounded_channel::<Divergence>();
-            tokio::spawn(async move {
-                let mut write_count = 0u64;
-                while let Some(divergence) = rx.recv().await {
-                    match divergence_db.record_divergence(&divergence) {
-                        Ok(_id) => {
-                            write_count += 1;
-                            if write_count % 100 == 0 {
-                                debug!(
-                                    target: "exex::research::db_writer",
-                                    total_writes = write_count,
-                                    "Database writer progress"
-                                );
-                            }
-                        }
-                        Err(e) => {
-                            warn!(
-                                target: "exex::research::db_writer",
-                                block = divergence.block_number,
-                                tx_idx = divergence.tx_index,
-                                error = %e,
-                                "Failed to record divergence to database"
-                            );
-                        }
-                    }
-                }
+        // A SQLite path or a PostgreSQL URL
+        let store = (config.divergence_db_path.to_str() != Some(":memory:"))
+            .then(|| store::open(&config.divergence_db_path))
+            .transpose()?;
+
+        // Continue after the last block finished by an earlier run
+        if config.resume {
+            let checkpoint =
+                store.as_ref().map(|store| store.last_checkpoint()).transpose()?.flatten();
+            let start_block = config.resume_start_block(checkpoint.as_ref())?;
+            if start_block != config.start_block {
                 info!(
-                    target: "exex::research::db_writer",
-                    total_writes = write_count,
-                    "Database writer task exiting"
+                    target: "exex::research",
+                    configured_start_block = config.start_block,
+                    start_block,
+                    "Resuming research from checkpoint"
                 );
-            });
+            }
+            config.start_block = start_block;
+        }
+
+        // Give immediate feedback if the configured blocks cannot (yet) be analyzed
+        let provider = ctx.provider();
+        let (earliest, tip) = (provider.earliest_block_number()?, provider.best_block_number()?);
+        if let Err(e) = research_provider::check_block_range(earliest, tip, &config) {
+            if config.strict_block_range {
+                return Err(e.into())
+            }
+            warn!(
+                target: "exex::research",
+                earliest,
+                tip,
+                error = %e,
+                "Research block range is outside the node's available blocks"
+            );
+        }
+
+        // Log the run with the block it actually starts at
+        let run = store
+            .as_ref()
+            .map(|store| store.record_run_start(&config).map(|run_id| (store.clone(), run_id)))
+            .transpose()?;
+
+        // Initialize the background database writer
+        let db_writer = if let Some(store) = store {
+            info!(
+                target: "exex::research",
+                path = ?config.divergence_db_path,
+                "Research ExEx initialized with background divergence database writer"
+            );
 
-            Some(tx)
+            Some(DivergenceWriter::spawn(store)?)
         } else {
             None
         };
 
+        let signatures = config.signatures_file.as_ref().map(SignatureDatabase::load).transpose()?;
+        if let Some(signatures) = &signatures {
+            info!(
+                target: "exex::research",
+                path = ?config.signatures_file,
+                signatures = signatures.len(),
+                "Loaded function signature database"
+            );
+        }
+
         // Register metrics
         metrics::register_metrics();
 
-        Ok(Self { ctx, config, db_tx, blocks_processed: 0, divergences_found: 0 })
+        // A flood of OOGs is the point of a gas limit multiplier below the gas multiplier
+        metrics::record_expected_oog_mode(config.expects_oog());
+        if config.expects_oog() {
+            info!(
+                target: "exex::research",
+                gas_multiplier = config.gas_multiplier,
+                gas_limit_multiplier = config.effective_gas_limit_multiplier(),
+                "Gas limits are inflated less than gas costs, so most transactions are expected \
+                 to run out of gas in experimental execution"
+            );
+        }
+
+        Ok(Self {
+            sampler: Sampler::new(&config),
+            progress: config
+                .progress_interval
+                .map(|interval| ProgressReporter::new(interval, config.end_block, Instant::now())),
+            config_hash: config.config_hash(),
+            ctx,
+            config,
+            db_writer,
+            run,
+            stats: Arc::default(),
+            timings: Arc::default(),
+            top_contracts: FrequentItems::new(TOP_CONTRACTS_CAPACITY),
+            code_cache: Arc::new(CodeCache::new(config.code_cache_size)),
+            signatures,
+            analyzed_blocks: AnalyzedBlocks::new(),
+        })
     }
 
-    /// Run the ExEx.
+    /// Run the ExEx until the notification stream ends or fails, then shut down gracefully.
     async fn run(mut self) -> eyre::Result<()> {
+        let result = self.process_notifications().await;
+        self.shutdown().await;
+        result
+    }
+
+    /// Process chain notifications until the stream ends.
+    async fn process_notifications(&mut self) -> eyre::Result<()> {
         info!(
             target: "exex::research",
             start_block = self.config.start_block,
@@ -131,7 +235,18 @@ impl<Node: FullNodeComponents> ResearchExEx<Node> {
                         let block_number = block.number();
 
                         // Check if research mode is enabled for this block
-                        if block_number < self.config.start_block {
+                        if !self.config.is_in_block_range(block_number) {
+                            continue;
+                        }
+
+                        // Notifications can overlap, e.g. after resuming from a checkpoint
+                        if !self.analyzed_blocks.claim(block_number) {
+                            debug!(
+                                target: "exex::research",
+                                block = block_number,
+                                last_analyzed = ?self.analyzed_blocks.last(),
+                                "Skipping block already analyzed"
+                            );
                             continue;
                         }
 
@@ -153,22 +268,26 @@ impl<Node: FullNodeComponents> ResearchExEx<Node> {
                             // Continue processing other blocks
                         }
 
-                        self.blocks_processed += 1;
+                        self.stats.record_block();
+                        self.report_progress(block_number);
                     }
 
                     // Send event to acknowledge processing
                     self.ctx.events.send(ExExEvent::FinishedHeight(new.tip().num_hash()))?;
                 }
-                ExExNotification::ChainReorged { old: _, new } => {
+                ExExNotification::ChainReorged { old, new } => {
                     // On reorg, process the new chain
                     info!(
                         target: "exex::research",
                         "Chain reorg detected, processing new chain"
                     );
+                    self.analyzed_blocks.revert(old.first().number());
 
                     for (_block_number, block) in new.blocks() {
                         let block_number = block.number();
-                        if block_number < self.config.start_block {
+                        if !self.config.is_in_block_range(block_number) ||
+                            !self.analyzed_blocks.claim(block_number)
+                        {
                             continue;
                         }
 
@@ -190,6 +309,7 @@ impl<Node: FullNodeComponents> ResearchExEx<Node> {
                         reverted_tip = old.tip().number(),
                         "Chain reverted"
                     );
+                    self.analyzed_blocks.revert(old.first().number());
                     // TODO: Remove divergences for reverted blocks from database
                 }
             }
@@ -198,6 +318,96 @@ impl<Node: FullNodeComponents> ResearchExEx<Node> {
         Ok(())
     }
 
+    /// Write all queued divergences to the database and log the final statistics.
+    async fn shutdown(&mut self) {
+        if let Some(writer) = self.db_writer.take() {
+            // Draining the queue blocks on database writes
+            match tokio::task::spawn_blocking(move || writer.shutdown()).await {
+                Ok(total_writes) => info!(
+                    target: "exex::research",
+                    total_writes,
+                    "Flushed divergence database writer"
+                ),
+                Err(e) => warn!(
+                    target: "exex::research",
+                    error = %e,
+                    "Failed to flush divergence database writer"
+                ),
+            }
+        }
+
+        let stats = self.stats();
+        if let Some((store, run_id)) = self.run.take() {
+            if let Err(e) = store.record_run_end(run_id, &stats) {
+                warn!(
+                    target: "exex::research",
+                    run_id,
+                    error = %e,
+                    "Failed to record the end of the run"
+                );
+            }
+        }
+        info!(
+            target: "exex::research",
+            blocks_processed = stats.blocks_processed,
+            divergences_found = stats.divergences_found,
+            "Research ExEx stopped"
+        );
+        if self.config.self_test {
+            if stats.divergences_found == 0 {
+                info!(
+                    target: "exex::research",
+                    blocks_processed = stats.blocks_processed,
+                    "Research self-test passed: no divergences at normal gas costs"
+                );
+            } else {
+                warn!(
+                    target: "exex::research",
+                    blocks_processed = stats.blocks_processed,
+                    divergences_found = stats.divergences_found,
+                    "Research self-test failed: the inspector changed execution results"
+                );
+            }
+        }
+
+        let timings = self.timings.snapshot();
+        info!(
+            target: "exex::research",
+            transactions = timings.transactions,
+            average_latency = ?timings.average_latency(),
+            normal_execution_share = timings.share(Phase::NormalExecution),
+            experimental_execution_share = timings.share(Phase::ExperimentalExecution),
+            comparison_share = timings.share(Phase::Comparison),
+            "Research analysis timings"
+        );
+    }
+
+    /// Snapshot of the blocks processed and divergences found so far.
+    fn stats(&self) -> ResearchStats {
+        self.stats.snapshot()
+    }
+
+    /// Log the progress of the run after `block_number` if the progress interval has passed.
+    fn report_progress(&mut self, block_number: u64) {
+        let stats = self.stats();
+        let Some(progress) = self
+            .progress
+            .as_mut()
+            .and_then(|reporter| reporter.record(Instant::now(), block_number, &stats))
+        else {
+            return;
+        };
+        info!(
+            target: "exex::research",
+            block = progress.block_number,
+            blocks_processed = progress.blocks_processed,
+            divergences_found = progress.divergences_found,
+            blocks_per_second = progress.blocks_per_second,
+            eta = ?progress.eta.map(|eta| Duration::from_secs(eta.as_secs())),
+            "Research progress"
+        );
+    }
+
     /// Analyze a single block using dual execution.
     async fn analyze_block(
         &mut self,
@@ -209,12 +419,22 @@ impl<Node: FullNodeComponents> ResearchExEx<Node> {
         let block_number = block.number();
         let block_start = std::time::Instant::now();
 
+        // Nothing to execute, but the block still counts towards the analyzed history
+        if block.body().transactions().is_empty() {
+            self.sampler.prune(block_number);
+            self.finish_block(block_summary(block, 0, None), 0, 0, block_start);
+            return Ok(());
+        }
+
         // Get provider for accessing historical state
         let provider = self.ctx.provider();
+        let evm_config = self.ctx.evm_config();
 
-        // Build EVM environment for the block
-        let evm_env = match self.ctx.evm_config().evm_env(block.header()) {
-            Ok(env) => env,
+        // Build EVM environments for the block
+        let block_ctx = match BlockContext::new(evm_config, &self.config, block.header()) {
+            Ok(block_ctx) => block_ctx
+                .with_warm_recipients(&self.config, block.body().transactions())
+                .with_timings(self.timings.clone()),
             Err(e) => {
                 warn!(
                     target: "exex::research",
@@ -226,330 +446,287 @@ impl<Node: FullNodeComponents> ResearchExEx<Node> {
             }
         };
 
-        // Process each transaction with dual execution (use recovered transactions)
-        for (tx_idx, tx) in block.transactions_recovered().enumerate() {
-            let tx_env = self.ctx.evm_config().tx_env(tx);
+        // With sequential state the block's transactions run on states carried from one to the
+        // next, which are unusable if the pre-block state was pruned
+        let mut sequential = None;
+        if self.config.sequential_state {
+            let states = research_provider::state_or_skip(
+                block_number,
+                0,
+                pre_block_state(&provider, block_number),
+            )?
+            .zip(research_provider::state_or_skip(
+                block_number,
+                0,
+                pre_block_state(&provider, block_number),
+            )?);
+            let Some((normal_state, experimental_state)) = states else {
+                self.sampler.prune(block_number);
+                self.finish_block(block_summary(block, 0, None), 0, 0, block_start);
+                return Ok(());
+            };
+            sequential = Some(SequentialState::new(
+                self.cached_code(normal_state),
+                self.cached_code(experimental_state),
+            ));
+        }
 
-            // Get state for normal execution
-            let normal_state = if block_number > 0 {
-                provider.history_by_block_number(block_number - 1)?
+        // Process each transaction with dual execution (use recovered transactions). The normal
+        // gas of the block's transactions is only summed when they execute in order
+        let (mut block_divergences, mut analyzed) = (0, 0);
+        let mut normal_gas_used = sequential.is_some().then_some(0u64);
+        for (tx_idx, tx) in block.transactions_recovered().enumerate() {
+            let recipient = tx.to();
+            let sampled = self.sampler.should_analyze(*tx.tx_hash(), recipient, block_number);
+            let tx_ctx = TxContext::new(evm_config, &block_ctx, tx_idx, tx);
+
+            let divergences = if let Some(state) = &mut sequential {
+                // Every transaction advances the states, whether it is analyzed or not
+                let divergences = if sampled {
+                    analyzed += 1;
+                    analysis::analyze_schedules(
+                        evm_config,
+                        &self.config,
+                        &block_ctx,
+                        &tx_ctx,
+                        &state.normal,
+                        &state.experimental,
+                    )
+                } else {
+                    Vec::new()
+                };
+                let normal = state.commit(evm_config, &self.config, &block_ctx, &tx_ctx);
+                if let (Some(total), Some(result)) = (&mut normal_gas_used, normal) {
+                    *total = total.saturating_add(result.gas_used());
+                }
+                divergences
             } else {
-                provider.latest()?
-            };
+                if !sampled {
+                    continue;
+                }
+                analyzed += 1;
 
-            // --- EXECUTION 1: Normal (with tracking inspector) ---
-            let normal_db = StateProviderDatabase(normal_state);
-            let mut normal_cache = CacheDB::new(normal_db);
-            let mut normal_inspector = TrackingInspector::new();
-            let mut normal_evm = self.ctx.evm_config().evm_with_env_and_inspector(
-                &mut normal_cache,
-                evm_env.clone(),
-                &mut normal_inspector,
-            );
-            let normal_result = match normal_evm.transact(tx_env.clone()) {
-                Ok(result) => result,
-                Err(e) => {
-                    debug!(
-                        target: "exex::research",
-                        block = block_number,
-                        tx_idx,
-                        error = ?e,
-                        "Normal execution failed"
-                    );
+                // Each execution gets its own state provider, skipping the transaction if it
+                // was pruned
+                let Some(normal_state) = research_provider::state_or_skip(
+                    block_number,
+                    tx_idx,
+                    pre_block_state(&provider, block_number),
+                )?
+                else {
+                    continue;
+                };
+
+                // Calls of a shape that did not diverge before are skipped, apart from
+                // re-checks. Contract creations have no deployed code to key a shape on, so
+                // they are always analyzed
+                let call_shape = match recipient {
+                    Some(to) if self.sampler.skips_non_divergent() => normal_state
+                        .basic_account(&to)?
+                        .and_then(|account| account.bytecode_hash)
+                        .map(|code_hash| (code_hash, tx_ctx.selector)),
+                    _ => None,
+                };
+                if call_shape.is_some_and(|(code_hash, selector)| {
+                    self.sampler.is_known_non_divergent(*tx.tx_hash(), code_hash, selector)
+                }) {
+                    metrics::record_non_divergent_skip();
                     continue;
                 }
-            };
 
-            // Drop normal EVM to release the inspector
-            drop(normal_evm);
+                let Some(experimental_state) = research_provider::state_or_skip(
+                    block_number,
+                    tx_idx,
+                    pre_block_state(&provider, block_number),
+                )?
+                else {
+                    continue;
+                };
 
-            // Get fresh state for experimental execution
-            let experimental_state = if block_number > 0 {
-                provider.history_by_block_number(block_number - 1)?
-            } else {
-                provider.latest()?
+                let divergences = analysis::analyze_schedules(
+                    evm_config,
+                    &self.config,
+                    &block_ctx,
+                    &tx_ctx,
+                    self.cached_code(normal_state),
+                    self.cached_code(experimental_state),
+                );
+                if divergences.is_empty() {
+                    if let Some((code_hash, selector)) = call_shape {
+                        self.sampler.record_non_divergent(code_hash, selector);
+                    }
+                }
+                divergences
             };
+            if divergences.is_empty() {
+                continue;
+            }
 
-            // --- EXECUTION 2: Experimental (with gas multiplier inspector) ---
-            let experimental_db = StateProviderDatabase(experimental_state);
-            let mut experimental_cache = CacheDB::new(experimental_db);
-            let mut experimental_inspector =
-                GasResearchInspector::new(self.config.clone(), block.header().gas_limit());
-
-            let mut experimental_evm = self.ctx.evm_config().evm_with_env_and_inspector(
-                &mut experimental_cache,
-                evm_env.clone(),
-                &mut experimental_inspector,
-            );
+            let mut diverged = false;
+            for mut divergence in divergences {
+                if let Some(signatures) = &self.signatures {
+                    divergence.function_name = signatures.resolve(&divergence);
+                }
 
-            let experimental_result = match experimental_evm.transact(tx_env.clone()) {
-                Ok(result) => result,
-                Err(e) => {
-                    // Experimental execution failed - this is a critical divergence!
-                    // Normal execution succeeded but experimental failed.
+                // Near misses matched and A/B comparisons are between two experimental
+                // schedules, so both are stored without counting towards divergence statistics
+                // or limits
+                if divergence.near_miss {
+                    metrics::record_near_miss();
+                }
+                if divergence.near_miss || divergence.comparison == Comparison::AB {
+                    self.store_divergence(&divergence);
+                    continue;
+                }
 
-                    // Drop EVM to release the inspector
-                    drop(experimental_evm);
+                diverged = true;
+                block_divergences += 1;
+                self.record_divergence(&divergence);
+                if let Some(contract) = divergence.contract() {
+                    self.top_contracts.record(contract);
+                }
+            }
+            if !diverged {
+                continue;
+            }
+            if let Some(recipient) = recipient {
+                self.sampler.record_divergence(recipient, block_number);
+            }
 
-                    warn!(
+            // Check max divergences limit
+            if let Some(max) = self.config.max_divergences_per_block {
+                if block_divergences >= max {
+                    debug!(
                         target: "exex::research",
                         block = block_number,
-                        tx_idx,
-                        error = ?e,
-                        "CRITICAL DIVERGENCE: Experimental execution failed while normal succeeded"
+                        "Reached max divergences per block limit: {}",
+                        max
                     );
+                    // The remaining transactions are not executed, so the sum is incomplete
+                    normal_gas_used = None;
+                    break;
+                }
+            }
+        }
 
-                    // Record as a critical divergence - experimental failure
-                    let call_trees = if matches!(self.config.trace_detail, TraceDetail::Detailed) {
-                        Some(CallTrees {
-                            normal: normal_inspector.call_frames().to_vec(),
-                            experimental: experimental_inspector.call_frames().to_vec(),
-                        })
-                    } else {
-                        None
-                    };
-
-                    let event_logs = if matches!(self.config.trace_detail, TraceDetail::Detailed) {
-                        Some(EventLogs {
-                            normal: normal_inspector
-                                .event_logs()
-                                .iter()
-                                .map(|e| EventLog {
-                                    log_index: e.log_index,
-                                    address: e.address,
-                                    topics: e.topics.clone(),
-                                    data: e.data.clone(),
-                                })
-                                .collect(),
-                            experimental: vec![],
-                        })
-                    } else {
-                        None
-                    };
-
-                    let divergence = Divergence {
-                        block_number,
-                        tx_index: tx_idx as u64,
-                        tx_hash: *tx.tx_hash(),
-                        timestamp: block.timestamp(),
-                        divergence_types: vec![DivergenceType::Status],
-                        gas_analysis: GasAnalysis {
-                            normal_gas_used: normal_result.result.gas_used(),
-                            experimental_gas_used: 0, // Failed before completion
-                            gas_efficiency_ratio: 0.0,
-                        },
-                        normal_ops: normal_inspector.operation_counts().clone(),
-                        experimental_ops: experimental_inspector.operation_counts().clone(),
-                        divergence_location: experimental_inspector.divergence_location().cloned(),
-                        oog_info: experimental_inspector.oog_info().cloned(),
-                        call_trees,
-                        event_logs,
-                    };
-
-                    self.record_divergence(&divergence);
-                    self.divergences_found += 1;
-
-                    // Check max divergences limit
-                    if let Some(max) = self.config.max_divergences_per_block {
-                        if self.divergences_found >= max as u64 {
-                            debug!(
-                                target: "exex::research",
-                                block = block_number,
-                                "Reached max divergences per block limit: {}",
-                                max
-                            );
-                            break;
-                        }
-                    }
+        self.sampler.prune(block_number);
 
-                    continue;
-                }
-            };
+        let top_counts: Vec<_> = self
+            .top_contracts(TOP_CONTRACTS_REPORTED)
+            .into_iter()
+            .map(|(_, count)| count)
+            .collect();
+        metrics::record_top_contracts(&top_counts, TOP_CONTRACTS_REPORTED);
 
-            // Drop the EVM to release the mutable borrow on the inspector
-            drop(experimental_evm);
+        self.finish_block(
+            block_summary(block, analyzed, normal_gas_used),
+            block.body().transactions().len(),
+            block_divergences,
+            block_start,
+        );
 
-            // --- COMPARE RESULTS ---
-            let mut divergence_types = Vec::new();
+        Ok(())
+    }
 
-            // 1. Compare execution status
-            let normal_success = normal_result.result.is_success();
-            let experimental_success = experimental_result.result.is_success();
+    /// Record the summary and metrics of a block whose analysis started at `block_start`.
+    fn finish_block(
+        &self,
+        summary: BlockSummary,
+        tx_count: usize,
+        divergences: usize,
+        block_start: std::time::Instant,
+    ) {
+        let BlockSummary { block_number, analyzed_tx_count: analyzed, .. } = summary;
+
+        if summary.gas_mismatch(self.config.gas_reconciliation_tolerance) {
+            metrics::record_gas_reconciliation_mismatch();
+            warn!(
+                target: "exex::research",
+                block = block_number,
+                header_gas_used = summary.header_gas_used,
+                normal_gas_used = summary.normal_gas_used,
+                "Summed normal gas of the block's transactions does not match its header"
+            );
+        }
 
-            if normal_success != experimental_success {
-                divergence_types.push(DivergenceType::Status);
-                info!(
+        // Record how many transactions were analyzed, for divergence rates over time
+        if let Some(ref writer) = self.db_writer {
+            if let Err(e) = writer.send_block_summary(summary) {
+                warn!(
                     target: "exex::research",
                     block = block_number,
-                    tx_idx,
-                    normal_success,
-                    experimental_success,
-                    "DIVERGENCE: Status differs"
+                    error = %e,
+                    "Failed to send block summary to database writer"
                 );
             }
 
-            // 2. Compare gas usage
-            let normal_gas = normal_result.result.gas_used();
-            let experimental_gas = experimental_result.result.gas_used();
-            let gas_ratio = GasAnalysis::calculate_ratio(
-                normal_gas,
-                experimental_gas,
-                self.config.gas_multiplier,
-            );
-            let gas_analysis = GasAnalysis {
-                normal_gas_used: normal_gas,
-                experimental_gas_used: experimental_gas,
-                gas_efficiency_ratio: gas_ratio,
-            };
-
-            if gas_analysis.is_structural_divergence() {
-                divergence_types.push(DivergenceType::GasPattern);
-                info!(
+            // Sent last, so a restart resumes after the block only once all of it is written
+            let checkpoint = Checkpoint { block_number, config_hash: self.config_hash };
+            if let Err(e) = writer.send_checkpoint(checkpoint) {
+                warn!(
                     target: "exex::research",
                     block = block_number,
-                    tx_idx,
-                    normal_gas,
-                    experimental_gas,
-                    gas_ratio,
-                    "DIVERGENCE: Gas pattern differs structurally"
+                    error = %e,
+                    "Failed to send checkpoint to database writer"
                 );
             }
-
-            // 3. Compare state changes
-            let normal_state_len = normal_result.state.len();
-            let experimental_state_len = experimental_result.state.len();
-
-            if normal_state_len != experimental_state_len {
-                divergence_types.push(DivergenceType::StateRoot);
-            } else {
-                // Compare individual account states
-                for (address, normal_account) in &normal_result.state {
-                    if let Some(experimental_account) = experimental_result.state.get(address) {
-                        if normal_account.storage != experimental_account.storage ||
-                            normal_account.info != experimental_account.info
-                        {
-                            divergence_types.push(DivergenceType::StateRoot);
-                            break;
-                        }
-                    } else {
-                        divergence_types.push(DivergenceType::StateRoot);
-                        break;
-                    }
-                }
-            }
-
-            // 4. Compare logs
-            let normal_logs = normal_result.result.logs();
-            let experimental_logs = experimental_result.result.logs();
-
-            if normal_logs.len() != experimental_logs.len() {
-                divergence_types.push(DivergenceType::EventLogs);
-            } else {
-                for (normal_log, experimental_log) in
-                    normal_logs.iter().zip(experimental_logs.iter())
-                {
-                    if normal_log.address != experimental_log.address ||
-                        normal_log.data.topics() != experimental_log.data.topics() ||
-                        normal_log.data.data != experimental_log.data.data
-                    {
-                        divergence_types.push(DivergenceType::EventLogs);
-                        break;
-                    }
-                }
-            }
-
-            // If divergences detected or OOG occurred, record it
-            if !divergence_types.is_empty() || experimental_inspector.oog_occurred() {
-                // Extract call trees and event logs if detailed tracing is enabled
-                let call_trees = if matches!(self.config.trace_detail, TraceDetail::Detailed) {
-                    Some(CallTrees {
-                        normal: normal_inspector.call_frames().to_vec(),
-                        experimental: experimental_inspector.call_frames().to_vec(),
-                    })
-                } else {
-                    None
-                };
-
-                let event_logs = if matches!(self.config.trace_detail, TraceDetail::Detailed) {
-                    Some(EventLogs {
-                        normal: normal_inspector
-                            .event_logs()
-                            .iter()
-                            .map(|e| EventLog {
-                                log_index: e.log_index,
-                                address: e.address,
-                                topics: e.topics.clone(),
-                                data: e.data.clone(),
-                            })
-                            .collect(),
-                        experimental: vec![], // GasResearchInspector doesn't track logs yet
-                    })
-                } else {
-                    None
-                };
-
-                let divergence = Divergence {
-                    block_number,
-                    tx_index: tx_idx as u64,
-                    tx_hash: *tx.tx_hash(),
-                    timestamp: block.timestamp(),
-                    divergence_types,
-                    gas_analysis,
-                    normal_ops: normal_inspector.operation_counts().clone(),
-                    experimental_ops: experimental_inspector.operation_counts().clone(),
-                    divergence_location: experimental_inspector.divergence_location().cloned(),
-                    oog_info: experimental_inspector.oog_info().cloned(),
-                    call_trees,
-                    event_logs,
-                };
-
-                self.record_divergence(&divergence);
-                self.divergences_found += 1;
-
-                // Check max divergences limit
-                if let Some(max) = self.config.max_divergences_per_block {
-                    if self.divergences_found >= max as u64 {
-                        debug!(
-                            target: "exex::research",
-                            block = block_number,
-                            "Reached max divergences per block limit: {}",
-                            max
-                        );
-                        break;
-                    }
-                }
-            }
         }
 
         let block_duration = block_start.elapsed().as_secs_f64();
-        let tx_count = block.body().transactions().len();
-        metrics::record_block_processed(block_number, tx_count, block_duration);
+        metrics::record_block_processed(block_number, tx_count, analyzed, block_duration);
 
         info!(
             target: "exex::research",
             block = block_number,
             tx_count,
+            analyzed,
+            divergences,
             duration_ms = block_duration * 1000.0,
             "Block analyzed in research mode"
         );
+    }
 
-        Ok(())
+    /// Database reading `state`, with contract code served from the shared code cache.
+    fn cached_code(
+        &self,
+        state: StateProviderBox,
+    ) -> CachedCodeDatabase<StateProviderDatabase<StateProviderBox>> {
+        CachedCodeDatabase::new(StateProviderDatabase(state), self.code_cache.clone())
+    }
+
+    /// The `n` contracts with the most divergences in this run, with their estimated counts.
+    ///
+    /// Counts come from a bounded [`FrequentItems`] summary and may undercount by at most
+    /// [`FrequentItems::max_error`].
+    fn top_contracts(&self, n: usize) -> Vec<(Address, u64)> {
+        self.top_contracts.top(n)
     }
 
-    /// Record a divergence to database and metrics.
+    /// Record a divergence to database and metrics, unless the total limit was reached.
     fn record_divergence(&self, divergence: &Divergence) {
-        // Record metrics
-        metrics::record_divergence(
-            &divergence.divergence_types,
-            divergence.gas_analysis.gas_efficiency_ratio,
-        );
-        if let Some(ref oog) = divergence.oog_info {
-            metrics::record_oog(oog.pattern);
+        if self.config.self_test {
+            warn!(
+                target: "exex::research",
+                block = divergence.block_number,
+                tx_idx = divergence.tx_index,
+                tx_hash = ?divergence.tx_hash,
+                summary = %divergence.summary_line(),
+                "Self-test divergence at normal gas costs, the inspector changed the result"
+            );
         }
 
-        // Send to async database writer if available
-        if let Some(ref tx) = self.db_tx {
-            if let Err(e) = tx.send(divergence.clone()) {
+        if !self.stats.record_found(divergence, self.config.max_divergences_total) {
+            return
+        }
+
+        self.store_divergence(divergence);
+    }
+
+    /// Queue a divergence for the database, or log it if no database is configured.
+    fn store_divergence(&self, divergence: &Divergence) {
+        // Send to the background database writer if available
+        if let Some(ref writer) = self.db_writer {
+            if let Err(e) = writer.send(divergence.clone()) {
                 warn!(
                     target: "exex::research",
                     block = divergence.block_number,
@@ -560,26 +737,58 @@ impl<Node: FullNodeComponents> ResearchExEx<Node> {
             } else {
                 debug!(
                     target: "exex::research",
-                    block = divergence.block_number,
-                    tx_idx = divergence.tx_index,
                     tx_hash = ?divergence.tx_hash,
-                    types = ?divergence.divergence_types,
-                    "Divergence queued for async database write"
+                    summary = %divergence.summary_line(),
+                    "Divergence queued for database write"
                 );
             }
+        } else if self.config.quiet {
+            trace!(
+                target: "exex::research",
+                tx_hash = ?divergence.tx_hash,
+                summary = %divergence.summary_line(),
+                "Divergence detected (no database configured)"
+            );
         } else {
             info!(
                 target: "exex::research",
-                block = divergence.block_number,
-                tx_idx = divergence.tx_index,
                 tx_hash = ?divergence.tx_hash,
-                types = ?divergence.divergence_types,
+                summary = %divergence.summary_line(),
                 "Divergence detected (no database configured)"
             );
         }
     }
 }
 
+/// Summary of a block with `analyzed` transactions.
+///
+/// `normal_gas_used` is the summed normal gas of all of the block's transactions, known only if
+/// they were executed in order with [`ResearchConfig::sequential_state`].
+fn block_summary<B: reth_primitives_traits::Block>(
+    block: &reth_primitives_traits::RecoveredBlock<B>,
+    analyzed: u64,
+    normal_gas_used: Option<u64>,
+) -> BlockSummary {
+    BlockSummary {
+        block_number: block.number(),
+        analyzed_tx_count: analyzed,
+        header_gas_used: block.header().gas_used(),
+        normal_gas_used,
+    }
+}
+
+/// Fetch the state a block's transactions execute against.
+fn pre_block_state<P: StateProviderFactory>(
+    provider: &P,
+    block_number: u64,
+) -> ProviderResult<StateProviderBox> {
+    if block_number > 0 {
+        provider.history_by_block_number(block_number - 1)
+    } else {
+        provider.latest()
+    }
+}
+
 async fn research_exex<Node: FullNodeComponents>(
     ctx: ExExContext<Node>,
     config: ResearchConfig,
@@ -588,27 +797,20 @@ async fn research_exex<Node: FullNodeComponents>(
 }
 
 fn main() -> eyre::Result<()> {
+    if commands::ToolCli::is_invoked() {
+        return commands::ToolCli::parse().run()
+    }
+
     reth_ethereum::cli::Cli::parse_args().run(|builder, _ext| {
         // Extract research config from node config
         let node_config = builder.config();
         let research_args = &node_config.research;
 
         // Build research config from the built-in research args
-        let config = ResearchConfig {
-            gas_multiplier: research_args.gas_multiplier,
-            divergence_db_path: research_args.db_path.clone(),
-            start_block: research_args.start_block,
-            max_divergences_per_block: None,
-            trace_detail: TraceDetail::Standard,
-            refund_multiplier: research_args.refund_multiplier,
-            stipend_multiplier: research_args.stipend_multiplier,
-            loop_detection_db_path: None,
-            gas_limit_multiplier: None,
-            detect_gas_loops: false,
-            max_parallel_txs: 48, // Placeholder - will be used when parallelization is implemented
-        };
+        let config = ResearchConfig::try_from(research_args);
 
         Box::pin(async move {
+            let config = config?;
             let handle = builder
                 .node(EthereumNode::default())
                 .install_exex("research", |ctx| async move {
@@ -1,7 +1,9 @@
 //! clap [Args](clap::Args) for gas price research mode
 
+use alloy_primitives::{hex, Address};
 use clap::Args;
-use std::path::PathBuf;
+use humantime::parse_duration;
+use std::{path::PathBuf, time::Duration};
 
 /// Parameters for gas price research mode
 #[derive(Debug, Clone, Args, PartialEq)]
@@ -19,7 +21,15 @@ pub struct ResearchArgs {
     #[arg(long = "research.start-block", default_value_t = 0, help_heading = "Research")]
     pub start_block: u64,
 
-    /// Path to divergence database file
+    /// Last block number to analyze (default: no limit)
+    #[arg(long = "research.end-block", help_heading = "Research")]
+    pub end_block: Option<u64>,
+
+    /// Fail at startup if the block range is outside the node's available blocks
+    #[arg(long = "research.strict-block-range", help_heading = "Research")]
+    pub strict_block_range: bool,
+
+    /// Path to divergence database file, or a `postgres://` URL (requires the `postgres` feature)
     #[arg(long = "research.db-path", default_value = "./divergence.db", help_heading = "Research")]
     pub db_path: PathBuf,
 
@@ -34,6 +44,319 @@ pub struct ResearchArgs {
         help_heading = "Research"
     )]
     pub stipend_multiplier: f64,
+
+    /// Multiplier for the intrinsic calldata cost in the experimental gas charged
+    #[arg(
+        long = "research.calldata-gas-multiplier",
+        default_value_t = 1,
+        help_heading = "Research"
+    )]
+    pub calldata_gas_multiplier: u64,
+
+    /// Comma-separated hex opcodes charged at their normal cost (e.g. `0x5b,0x60`)
+    #[arg(
+        long = "research.excluded-opcodes",
+        value_delimiter = ',',
+        value_parser = parse_opcode,
+        help_heading = "Research"
+    )]
+    pub excluded_opcodes: Vec<u8>,
+
+    /// Comma-separated addresses left out of the state comparison
+    #[arg(long = "research.ignore-addresses", value_delimiter = ',', help_heading = "Research")]
+    pub ignore_addresses: Vec<Address>,
+
+    /// Compare the block beneficiary's balance, which is otherwise left out of the state
+    /// comparison as the fees paid to it depend on the gas used
+    #[arg(long = "research.compare-beneficiary-balance", help_heading = "Research")]
+    pub compare_beneficiary_balance: bool,
+
+    /// Compare the accounts and storage slots accessed by both executions
+    #[arg(long = "research.compare-access-lists", help_heading = "Research")]
+    pub compare_access_lists: bool,
+
+    /// Path to the gas loop detection database
+    #[arg(long = "research.loop-detection-db-path", help_heading = "Research")]
+    pub loop_detection_db_path: Option<PathBuf>,
+
+    /// Path to a signature database (a 4byte directory dump) used to record the function name
+    /// of each divergence
+    #[arg(long = "research.signatures-file", help_heading = "Research")]
+    pub signatures_file: Option<PathBuf>,
+
+    /// Level of detail recorded for divergences
+    #[cfg(feature = "research")]
+    #[arg(
+        long = "research.trace-detail",
+        value_enum,
+        default_value_t = reth_research::config::TraceDetail::Standard,
+        help_heading = "Research"
+    )]
+    pub trace_detail: reth_research::config::TraceDetail,
+
+    /// Transaction gas limit multiplier (default: same as the gas multiplier)
+    #[arg(long = "research.gas-limit-multiplier", help_heading = "Research")]
+    pub gas_limit_multiplier: Option<u64>,
+
+    /// Maximum number of divergences to record per block (default: unlimited)
+    #[arg(long = "research.max-divergences-per-block", help_heading = "Research")]
+    pub max_divergences_per_block: Option<usize>,
+
+    /// Maximum number of divergences to record in a run (default: unlimited)
+    #[arg(long = "research.max-divergences-total", help_heading = "Research")]
+    pub max_divergences_total: Option<u64>,
+
+    /// Enable gas-dependent loop detection
+    #[arg(long = "research.detect-gas-loops", help_heading = "Research")]
+    pub detect_gas_loops: bool,
+
+    /// Maximum number of transactions to analyze in parallel (default: number of CPUs)
+    #[arg(long = "research.max-parallel-txs", help_heading = "Research")]
+    pub max_parallel_txs: Option<usize>,
+
+    /// Run the normal and experimental executions of each transaction on separate threads
+    #[arg(long = "research.concurrent-execution", help_heading = "Research")]
+    pub concurrent_execution: bool,
+
+    /// Number of contract bytecodes cached across blocks, 0 to disable
+    #[arg(long = "research.code-cache-size", default_value_t = 4096, help_heading = "Research")]
+    pub code_cache_size: usize,
+
+    /// Disable the operation count self-consistency guard
+    #[arg(long = "research.disable-op-count-check", help_heading = "Research")]
+    pub disable_op_count_check: bool,
+
+    /// Multiply the block base fee by this factor in experimental execution
+    #[arg(long = "research.base-fee-multiplier", help_heading = "Research")]
+    pub base_fee_multiplier: Option<f64>,
+
+    /// Persist call trees for every divergence to compare per-frame gas
+    #[arg(long = "research.persist-frame-gas", help_heading = "Research")]
+    pub persist_frame_gas: bool,
+
+    /// Use the raw multiplied gas limit without subtracting the extra intrinsic gas
+    #[arg(long = "research.disable-intrinsic-gas-adjustment", help_heading = "Research")]
+    pub disable_intrinsic_gas_adjustment: bool,
+
+    /// Log individual divergences at trace level, keeping per-block summaries and critical
+    /// divergences
+    #[arg(long = "research.quiet", help_heading = "Research")]
+    pub quiet: bool,
+
+    /// Log the block rate, divergences found and time left until the end block this often
+    /// (e.g. `30s`, `5m`)
+    #[arg(
+        long = "research.progress-interval",
+        value_parser = parse_duration,
+        help_heading = "Research"
+    )]
+    pub progress_interval: Option<Duration>,
+
+    /// Compare post-transaction state by digest before comparing account by account
+    #[arg(long = "research.state-digest", help_heading = "Research")]
+    pub state_digest: bool,
+
+    /// Warm the recipients of a block's transactions before analyzing each of them, an
+    /// approximation of the warming done by earlier transactions in the block
+    #[arg(long = "research.seed-contracts", help_heading = "Research")]
+    pub seed_contracts: bool,
+
+    /// Carry the experimental state between the transactions of a block, so later transactions
+    /// see the effects of earlier experimental executions instead of being analyzed in isolation
+    #[arg(long = "research.sequential-state", help_heading = "Research")]
+    pub sequential_state: bool,
+
+    /// Fraction of transactions analyzed, chosen deterministically by transaction hash
+    #[arg(long = "research.sample-rate", default_value_t = 1.0, help_heading = "Research")]
+    pub sample_rate: f64,
+
+    /// Analyze transactions to contracts that diverged recently at the divergent sample rate
+    #[arg(long = "research.adaptive-sampling", help_heading = "Research")]
+    pub adaptive_sampling: bool,
+
+    /// Number of blocks a divergence counts as recent for adaptive sampling
+    #[arg(long = "research.adaptive-window", default_value_t = 1000, help_heading = "Research")]
+    pub adaptive_window: u64,
+
+    /// Fraction of transactions analyzed when the recipient diverged recently
+    #[arg(
+        long = "research.divergent-sample-rate",
+        default_value_t = 1.0,
+        help_heading = "Research"
+    )]
+    pub divergent_sample_rate: f64,
+
+    /// Skip calls whose code hash and selector were already analyzed without diverging, apart
+    /// from random re-checks
+    #[arg(long = "research.skip-non-divergent", help_heading = "Research")]
+    pub skip_non_divergent: bool,
+
+    /// Size in bits of the bloom filter of non-divergent call shapes
+    #[arg(
+        long = "research.skip-filter-bits",
+        default_value_t = 16_777_216,
+        help_heading = "Research"
+    )]
+    pub skip_filter_bits: usize,
+
+    /// Fraction of calls of a known non-divergent shape analyzed anyway
+    #[arg(long = "research.recheck-rate", default_value_t = 0.01, help_heading = "Research")]
+    pub recheck_rate: f64,
+
+    /// Analyze system transactions, sent from or to the system contracts (EIP-4788, EIP-2935,
+    /// EIP-7002, EIP-7251), which are excluded by default
+    #[arg(long = "research.include-system-txs", help_heading = "Research")]
+    pub include_system_txs: bool,
+
+    /// Compare the state of the system contracts (EIP-4788, EIP-2935, EIP-7002, EIP-7251),
+    /// which is excluded by default
+    #[arg(long = "research.include-system-state", help_heading = "Research")]
+    pub include_system_state: bool,
+
+    /// Comma-separated hex function selectors (e.g. `0xa9059cbb`) whose transactions are always
+    /// traced in detail, regardless of `--research.trace-detail`
+    #[arg(
+        long = "research.deep-trace-selectors",
+        value_delimiter = ',',
+        value_parser = parse_selector,
+        help_heading = "Research"
+    )]
+    pub deep_trace_selectors: Vec<[u8; 4]>,
+
+    /// Whether experimental execution errors that are neither invalid transactions nor
+    /// execution results (e.g. database or custom EVM errors) skip the transaction or are
+    /// recorded as status divergences
+    #[arg(
+        long = "research.unknown-error-policy",
+        default_value = "skip",
+        value_parser = ["skip", "record"],
+        help_heading = "Research"
+    )]
+    pub unknown_error_policy: String,
+
+    /// Comma-separated divergence types (e.g. `status,state_root`); only divergences including
+    /// at least one of them are recorded
+    #[arg(long = "research.only-types", value_delimiter = ',', help_heading = "Research")]
+    pub only_types: Vec<String>,
+
+    /// Only record divergences in the gas pattern alone for blocks whose base fee is above
+    /// this many gwei; behavioral divergences are always recorded
+    #[arg(
+        long = "research.compare-gas-only-above",
+        value_name = "GWEI",
+        help_heading = "Research"
+    )]
+    pub compare_gas_only_above: Option<u64>,
+
+    /// Number of 32-byte words the peak memory of the two executions may differ by before it
+    /// is recorded as a divergence
+    #[arg(
+        long = "research.memory-tolerance-words",
+        default_value_t = 0,
+        help_heading = "Research"
+    )]
+    pub memory_tolerance_words: u64,
+
+    /// Gas the summed normal gas of a block's transactions may differ from the gas used in its
+    /// header by before it is flagged, with `--research.sequential-state`
+    #[arg(
+        long = "research.gas-reconciliation-tolerance",
+        default_value_t = 0,
+        help_heading = "Research"
+    )]
+    pub gas_reconciliation_tolerance: u64,
+
+    /// Replay transactions that fail in experimental execution only at normal gas costs on the
+    /// carried experimental state, and attribute those that fail there too to a non-gas cause
+    #[arg(
+        long = "research.confirm-failures",
+        requires = "sequential_state",
+        help_heading = "Research"
+    )]
+    pub confirm_failures: bool,
+
+    /// Skip experimental execution of transactions too far below their gas limit to run out of
+    /// gas, while `--research.only-types` is limited to `status` and `gas_pattern`
+    #[arg(long = "research.oog-fast-skip", help_heading = "Research")]
+    pub oog_fast_skip: bool,
+
+    /// Skip transactions that use less than this much gas in normal execution
+    #[arg(long = "research.min-gas-used", value_name = "GAS", help_heading = "Research")]
+    pub min_gas_used: Option<u64>,
+
+    /// Bytes of call frame and event log data stored per divergence, beyond which the rest is
+    /// dropped and the divergence marked truncated
+    #[arg(
+        long = "research.max-bytes-per-divergence",
+        value_name = "BYTES",
+        help_heading = "Research"
+    )]
+    pub max_bytes_per_divergence: Option<usize>,
+
+    /// Decimal places the logged, recorded and stored gas efficiency ratio is rounded to
+    #[arg(long = "research.ratio-decimals", default_value_t = 3, help_heading = "Research")]
+    pub ratio_decimals: u32,
+
+    /// Record transactions that do not diverge but finish experimental execution with less than
+    /// this much gas left as near misses
+    #[arg(
+        long = "research.near-miss-gas-threshold",
+        value_name = "GAS",
+        help_heading = "Research"
+    )]
+    pub near_miss_gas_threshold: Option<u64>,
+
+    /// Gas multiplier of a second experimental schedule (B), compared against normal execution
+    /// and against the primary schedule (A) in the same run
+    #[arg(long = "research.schedule-b", value_name = "MULTIPLIER", help_heading = "Research")]
+    pub schedule_b: Option<u64>,
+
+    /// Comma-separated hex opcodes charged at their normal cost under schedule B
+    #[arg(
+        long = "research.schedule-b-excluded-opcodes",
+        value_delimiter = ',',
+        value_parser = parse_opcode,
+        requires = "schedule_b",
+        help_heading = "Research"
+    )]
+    pub schedule_b_excluded_opcodes: Vec<u8>,
+
+    /// Continue after the last block analyzed by an earlier run with the same database, if it
+    /// is later than the start block
+    #[arg(long = "research.resume", help_heading = "Research")]
+    pub resume: bool,
+
+    /// Resume even if the earlier run used different research settings
+    #[arg(long = "research.force", requires = "resume", help_heading = "Research")]
+    pub force: bool,
+
+    /// Check the research inspector instead of a gas schedule: run experimental execution at
+    /// normal gas costs over the block range and report any divergence as an inspector bug
+    #[arg(long = "research.self-test", help_heading = "Research")]
+    pub self_test: bool,
+
+    /// Comma-separated gas multipliers each divergent transaction is re-analyzed at, recording
+    /// the multiplier at which each divergence type first appears
+    #[arg(
+        long = "research.sweep-multipliers",
+        value_delimiter = ',',
+        value_name = "MULTIPLIERS",
+        help_heading = "Research"
+    )]
+    pub sweep_multipliers: Vec<u64>,
+}
+
+/// Parses a single opcode given in hex, with or without a `0x` prefix.
+fn parse_opcode(value: &str) -> Result<u8, String> {
+    let value = value.trim();
+    let digits = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")).unwrap_or(value);
+    u8::from_str_radix(digits, 16).map_err(|err| format!("invalid opcode `{value}`: {err}"))
+}
+
+/// Parses a single 4-byte function selector given in hex, with or without a `0x` prefix.
+fn parse_selector(value: &str) -> Result<[u8; 4], String> {
+    let value = value.trim();
+    hex::decode_to_array(value).map_err(|err| format!("invalid selector `{value}`: {err}"))
 }
 
 impl Default for ResearchArgs {
@@ -42,25 +365,195 @@ impl Default for ResearchArgs {
             enabled: false,
             gas_multiplier: 128,
             start_block: 0,
+            end_block: None,
+            strict_block_range: false,
             db_path: PathBuf::from("./divergence.db"),
             refund_multiplier: 128.0,
             stipend_multiplier: 128.0,
+            calldata_gas_multiplier: 1,
+            excluded_opcodes: Vec::new(),
+            ignore_addresses: Vec::new(),
+            compare_beneficiary_balance: false,
+            compare_access_lists: false,
+            loop_detection_db_path: None,
+            signatures_file: None,
+            #[cfg(feature = "research")]
+            trace_detail: reth_research::config::TraceDetail::Standard,
+            gas_limit_multiplier: None,
+            max_divergences_per_block: None,
+            max_divergences_total: None,
+            detect_gas_loops: false,
+            max_parallel_txs: None,
+            concurrent_execution: false,
+            code_cache_size: 4096,
+            disable_op_count_check: false,
+            base_fee_multiplier: None,
+            persist_frame_gas: false,
+            disable_intrinsic_gas_adjustment: false,
+            quiet: false,
+            progress_interval: None,
+            state_digest: false,
+            seed_contracts: false,
+            sequential_state: false,
+            sample_rate: 1.0,
+            adaptive_sampling: false,
+            adaptive_window: 1000,
+            divergent_sample_rate: 1.0,
+            skip_non_divergent: false,
+            skip_filter_bits: 16_777_216,
+            recheck_rate: 0.01,
+            include_system_txs: false,
+            include_system_state: false,
+            deep_trace_selectors: Vec::new(),
+            unknown_error_policy: "skip".to_string(),
+            only_types: Vec::new(),
+            compare_gas_only_above: None,
+            memory_tolerance_words: 0,
+            gas_reconciliation_tolerance: 0,
+            confirm_failures: false,
+            oog_fast_skip: false,
+            min_gas_used: None,
+            max_bytes_per_divergence: None,
+            ratio_decimals: 3,
+            near_miss_gas_threshold: None,
+            schedule_b: None,
+            schedule_b_excluded_opcodes: Vec::new(),
+            resume: false,
+            force: false,
+            self_test: false,
+            sweep_multipliers: Vec::new(),
         }
     }
 }
 
 #[cfg(feature = "research")]
-impl ResearchArgs {
-    /// Converts ResearchArgs into a ResearchConfig
-    pub fn to_research_config(&self) -> reth_research::config::ResearchConfig {
-        reth_research::config::ResearchConfig {
-            gas_multiplier: self.gas_multiplier,
-            start_block: self.start_block,
-            refund_multiplier: self.refund_multiplier,
-            stipend_multiplier: self.stipend_multiplier,
-            divergence_db_path: self.db_path.clone(),
-            ..Default::default()
+impl TryFrom<&ResearchArgs> for reth_research::config::ResearchConfig {
+    type Error = reth_research::config::ConfigError;
+
+    fn try_from(args: &ResearchArgs) -> Result<Self, Self::Error> {
+        use reth_research::{
+            config::{ConfigError, GasSchedule, UnknownErrorPolicy},
+            divergence::DivergenceType,
+            sampling::{AdaptiveSampling, SkipFilter},
+        };
+
+        let unknown_error_policy: UnknownErrorPolicy =
+            args.unknown_error_policy.parse().map_err(ConfigError::InvalidUnknownErrorPolicy)?;
+
+        let mut builder = Self::builder()
+            .with_gas_multiplier(args.gas_multiplier)
+            .with_start_block(args.start_block)
+            .with_strict_block_range(args.strict_block_range)
+            .with_refund_multiplier(args.refund_multiplier)
+            .with_stipend_multiplier(args.stipend_multiplier)
+            .with_calldata_gas_multiplier(args.calldata_gas_multiplier)
+            .with_divergence_db_path(args.db_path.clone())
+            .with_trace_detail(args.trace_detail)
+            .with_ratio_decimals(args.ratio_decimals)
+            .with_detect_gas_loops(args.detect_gas_loops)
+            .with_concurrent_execution(args.concurrent_execution)
+            .with_code_cache_size(args.code_cache_size)
+            .with_check_op_count_consistency(!args.disable_op_count_check)
+            .with_persist_frame_gas(args.persist_frame_gas)
+            .with_excluded_opcodes(args.excluded_opcodes.iter().copied())
+            .with_ignore_addresses(args.ignore_addresses.iter().copied())
+            .with_compare_beneficiary_balance(args.compare_beneficiary_balance)
+            .with_compare_access_lists(args.compare_access_lists)
+            .with_adjust_intrinsic_gas(!args.disable_intrinsic_gas_adjustment)
+            .with_quiet(args.quiet)
+            .with_state_digest(args.state_digest)
+            .with_seed_warm_from_block(args.seed_contracts)
+            .with_sequential_state(args.sequential_state)
+            .with_sample_rate(args.sample_rate)
+            .with_include_system_txs(args.include_system_txs)
+            .with_include_system_state(args.include_system_state)
+            .with_deep_trace_selectors(args.deep_trace_selectors.iter().copied())
+            .with_unknown_error_policy(unknown_error_policy)
+            .with_memory_tolerance_words(args.memory_tolerance_words)
+            .with_gas_reconciliation_tolerance(args.gas_reconciliation_tolerance)
+            .with_confirm_failures(args.confirm_failures)
+            .with_oog_fast_skip(args.oog_fast_skip)
+            .with_resume(args.resume)
+            .with_force_resume(args.force)
+            .with_self_test(args.self_test)
+            .with_sweep_multipliers(args.sweep_multipliers.iter().copied());
+        if !args.only_types.is_empty() {
+            let only_types = args
+                .only_types
+                .iter()
+                .map(|divergence_type| divergence_type.parse())
+                .collect::<Result<Vec<DivergenceType>, _>>()
+                .map_err(ConfigError::InvalidDivergenceType)?;
+            builder = builder.with_only_types(only_types);
+        }
+        if let Some(gwei) = args.compare_gas_only_above {
+            builder = builder.with_economic_gas_threshold(gwei.saturating_mul(1_000_000_000));
+        }
+        if let Some(interval) = args.progress_interval {
+            builder = builder.with_progress_interval(interval);
+        }
+        if let Some(gas) = args.min_gas_used {
+            builder = builder.with_min_gas_used(gas);
+        }
+        if let Some(bytes) = args.max_bytes_per_divergence {
+            builder = builder.with_max_bytes_per_divergence(bytes);
+        }
+        if let Some(gas) = args.near_miss_gas_threshold {
+            builder = builder.with_near_miss_gas_threshold(gas);
+        }
+        if let Some(gas_multiplier) = args.schedule_b {
+            builder = builder.with_schedule_b(GasSchedule {
+                gas_multiplier,
+                excluded_opcodes: args.schedule_b_excluded_opcodes.iter().copied().collect(),
+            });
         }
+        if let Some(end_block) = args.end_block {
+            builder = builder.with_end_block(end_block);
+        }
+        if let Some(path) = &args.loop_detection_db_path {
+            builder = builder.with_loop_detection_db_path(path.clone());
+        }
+        if let Some(path) = &args.signatures_file {
+            builder = builder.with_signatures_file(path.clone());
+        }
+        if let Some(gas_limit_multiplier) = args.gas_limit_multiplier {
+            builder = builder.with_gas_limit_multiplier(gas_limit_multiplier);
+        }
+        if let Some(max) = args.max_divergences_per_block {
+            builder = builder.with_max_divergences_per_block(max);
+        }
+        if let Some(max) = args.max_divergences_total {
+            builder = builder.with_max_divergences_total(max);
+        }
+        if let Some(max_parallel_txs) = args.max_parallel_txs {
+            builder = builder.with_max_parallel_txs(max_parallel_txs);
+        }
+        if let Some(base_fee_multiplier) = args.base_fee_multiplier {
+            builder = builder.with_base_fee_multiplier(base_fee_multiplier);
+        }
+        if args.adaptive_sampling {
+            builder = builder.with_adaptive_sampling(AdaptiveSampling {
+                window_blocks: args.adaptive_window,
+                divergent_sample_rate: args.divergent_sample_rate,
+            });
+        }
+        if args.skip_non_divergent {
+            builder = builder.with_skip_filter(SkipFilter {
+                bits: args.skip_filter_bits,
+                recheck_rate: args.recheck_rate,
+            });
+        }
+        builder.build()
+    }
+}
+
+#[cfg(feature = "research")]
+impl ResearchArgs {
+    /// Converts ResearchArgs into a validated ResearchConfig
+    pub fn to_research_config(
+        &self,
+    ) -> Result<reth_research::config::ResearchConfig, reth_research::config::ConfigError> {
+        self.try_into()
     }
 
     /// Opens the divergence database
@@ -93,13 +586,348 @@ mod tests {
                 enabled: false,
                 gas_multiplier: 128,
                 start_block: 0,
+                end_block: None,
+                strict_block_range: false,
                 db_path: PathBuf::from("./divergence.db"),
                 refund_multiplier: 128.0,
                 stipend_multiplier: 128.0,
+                calldata_gas_multiplier: 1,
+                excluded_opcodes: Vec::new(),
+                ignore_addresses: Vec::new(),
+                compare_beneficiary_balance: false,
+                compare_access_lists: false,
+                loop_detection_db_path: None,
+                signatures_file: None,
+                #[cfg(feature = "research")]
+                trace_detail: reth_research::config::TraceDetail::Standard,
+                gas_limit_multiplier: None,
+                max_divergences_per_block: None,
+                max_divergences_total: None,
+                detect_gas_loops: false,
+                max_parallel_txs: None,
+                concurrent_execution: false,
+                code_cache_size: 4096,
+                disable_op_count_check: false,
+                base_fee_multiplier: None,
+                persist_frame_gas: false,
+                disable_intrinsic_gas_adjustment: false,
+                quiet: false,
+                progress_interval: None,
+                state_digest: false,
+                seed_contracts: false,
+                sequential_state: false,
+                sample_rate: 1.0,
+                adaptive_sampling: false,
+                adaptive_window: 1000,
+                divergent_sample_rate: 1.0,
+                skip_non_divergent: false,
+                skip_filter_bits: 16_777_216,
+                recheck_rate: 0.01,
+                include_system_txs: false,
+            include_system_state: false,
+                deep_trace_selectors: Vec::new(),
+                unknown_error_policy: "skip".to_string(),
+                only_types: Vec::new(),
+                compare_gas_only_above: None,
+                memory_tolerance_words: 0,
+                gas_reconciliation_tolerance: 0,
+                confirm_failures: false,
+                oog_fast_skip: false,
+                min_gas_used: None,
+                max_bytes_per_divergence: None,
+                ratio_decimals: 3,
+                near_miss_gas_threshold: None,
+                schedule_b: None,
+                schedule_b_excluded_opcodes: Vec::new(),
+                resume: false,
+                force: false,
+                self_test: false,
+                sweep_multipliers: Vec::new(),
             }
         );
     }
 
+    #[test]
+    fn test_parse_excluded_opcodes() {
+        let args = CommandParser::<ResearchArgs>::parse_from([
+            "reth",
+            "--research.excluded-opcodes",
+            "0x5b,60,0X5F",
+        ])
+        .args;
+        assert_eq!(args.excluded_opcodes, vec![0x5b, 0x60, 0x5f]);
+
+        assert!(CommandParser::<ResearchArgs>::try_parse_from([
+            "reth",
+            "--research.excluded-opcodes",
+            "0x100",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_deep_trace_selectors() {
+        let args = CommandParser::<ResearchArgs>::parse_from([
+            "reth",
+            "--research.deep-trace-selectors",
+            "0xa9059cbb,23b872dd",
+        ])
+        .args;
+        assert_eq!(
+            args.deep_trace_selectors,
+            vec![[0xa9, 0x05, 0x9c, 0xbb], [0x23, 0xb8, 0x72, 0xdd]]
+        );
+
+        assert!(CommandParser::<ResearchArgs>::try_parse_from([
+            "reth",
+            "--research.deep-trace-selectors",
+            "0xa9059c",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_force_requires_resume() {
+        let args = CommandParser::<ResearchArgs>::parse_from([
+            "reth",
+            "--research.resume",
+            "--research.force",
+        ])
+        .args;
+        assert!(args.resume && args.force);
+
+        assert!(
+            CommandParser::<ResearchArgs>::try_parse_from(["reth", "--research.force"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_confirm_failures_requires_sequential_state() {
+        let args = CommandParser::<ResearchArgs>::parse_from([
+            "reth",
+            "--research.sequential-state",
+            "--research.confirm-failures",
+        ])
+        .args;
+        assert!(args.sequential_state && args.confirm_failures);
+
+        assert!(CommandParser::<ResearchArgs>::try_parse_from([
+            "reth",
+            "--research.confirm-failures",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_schedule_b_excluded_opcodes_require_schedule_b() {
+        let args = CommandParser::<ResearchArgs>::parse_from([
+            "reth",
+            "--research.schedule-b",
+            "16",
+            "--research.schedule-b-excluded-opcodes",
+            "0x54",
+        ])
+        .args;
+        assert_eq!(args.schedule_b, Some(16));
+        assert_eq!(args.schedule_b_excluded_opcodes, vec![0x54]);
+
+        assert!(CommandParser::<ResearchArgs>::try_parse_from([
+            "reth",
+            "--research.schedule-b-excluded-opcodes",
+            "0x54",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "research")]
+    fn test_research_config_from_all_flags() {
+        use reth_research::{
+            config::{GasSchedule, ResearchConfig, TraceDetail, UnknownErrorPolicy},
+            divergence::DivergenceType,
+            sampling::{AdaptiveSampling, SkipFilter},
+        };
+        use std::collections::HashSet;
+
+        let args = CommandParser::<ResearchArgs>::parse_from([
+            "reth",
+            "--research.gas-multiplier",
+            "64",
+            "--research.start-block",
+            "100",
+            "--research.end-block",
+            "200",
+            "--research.strict-block-range",
+            "--research.db-path",
+            "/tmp/divergences.db",
+            "--research.refund-multiplier",
+            "2",
+            "--research.stipend-multiplier",
+            "3",
+            "--research.calldata-gas-multiplier",
+            "4",
+            "--research.excluded-opcodes",
+            "0x5b",
+            "--research.ignore-addresses",
+            "0x000f3df6d732807ef1319fb7b8bb8522d0beac02",
+            "--research.compare-beneficiary-balance",
+            "--research.compare-access-lists",
+            "--research.loop-detection-db-path",
+            "/tmp/loops.db",
+            "--research.signatures-file",
+            "/tmp/signatures.csv",
+            "--research.trace-detail",
+            "detailed",
+            "--research.gas-limit-multiplier",
+            "32",
+            "--research.max-divergences-per-block",
+            "10",
+            "--research.max-divergences-total",
+            "1000",
+            "--research.detect-gas-loops",
+            "--research.max-parallel-txs",
+            "4",
+            "--research.concurrent-execution",
+            "--research.code-cache-size",
+            "128",
+            "--research.disable-op-count-check",
+            "--research.base-fee-multiplier",
+            "1.5",
+            "--research.persist-frame-gas",
+            "--research.disable-intrinsic-gas-adjustment",
+            "--research.quiet",
+            "--research.progress-interval",
+            "30s",
+            "--research.state-digest",
+            "--research.seed-contracts",
+            "--research.sequential-state",
+            "--research.sample-rate",
+            "0.25",
+            "--research.adaptive-sampling",
+            "--research.adaptive-window",
+            "500",
+            "--research.divergent-sample-rate",
+            "0.9",
+            "--research.skip-non-divergent",
+            "--research.skip-filter-bits",
+            "65536",
+            "--research.recheck-rate",
+            "0.05",
+            "--research.include-system-txs",
+            "--research.include-system-state",
+            "--research.deep-trace-selectors",
+            "0xa9059cbb,095ea7b3",
+            "--research.unknown-error-policy",
+            "record",
+            "--research.only-types",
+            "status,state_root",
+            "--research.compare-gas-only-above",
+            "30",
+            "--research.memory-tolerance-words",
+            "4",
+            "--research.gas-reconciliation-tolerance",
+            "21000",
+            "--research.confirm-failures",
+            "--research.oog-fast-skip",
+            "--research.min-gas-used",
+            "50000",
+            "--research.max-bytes-per-divergence",
+            "65536",
+            "--research.ratio-decimals",
+            "5",
+            "--research.near-miss-gas-threshold",
+            "5000",
+            "--research.schedule-b",
+            "16",
+            "--research.schedule-b-excluded-opcodes",
+            "0x54,0x55",
+            "--research.resume",
+            "--research.force",
+            "--research.self-test",
+            "--research.sweep-multipliers",
+            "2,16,128",
+        ])
+        .args;
+
+        let config = ResearchConfig::try_from(&args).unwrap();
+        assert_eq!(config.gas_multiplier, 64);
+        assert_eq!(config.start_block, 100);
+        assert_eq!(config.end_block, Some(200));
+        assert!(config.strict_block_range);
+        assert_eq!(config.divergence_db_path, PathBuf::from("/tmp/divergences.db"));
+        assert_eq!(config.refund_multiplier, 2.0);
+        assert_eq!(config.stipend_multiplier, 3.0);
+        assert_eq!(config.calldata_gas_multiplier, 4);
+        assert_eq!(config.excluded_opcodes, HashSet::from([0x5b]));
+        assert_eq!(
+            config.ignore_addresses,
+            HashSet::from([alloy_primitives::address!(
+                "0x000f3df6d732807ef1319fb7b8bb8522d0beac02"
+            )])
+        );
+        assert!(config.compare_beneficiary_balance);
+        assert!(config.compare_access_lists);
+        assert_eq!(config.loop_detection_db_path, Some(PathBuf::from("/tmp/loops.db")));
+        assert_eq!(config.signatures_file, Some(PathBuf::from("/tmp/signatures.csv")));
+        assert_eq!(config.trace_detail, TraceDetail::Detailed);
+        assert_eq!(config.gas_limit_multiplier, Some(32));
+        assert_eq!(config.max_divergences_per_block, Some(10));
+        assert_eq!(config.max_divergences_total, Some(1000));
+        assert!(config.detect_gas_loops);
+        assert_eq!(config.max_parallel_txs, 4);
+        assert!(config.concurrent_execution);
+        assert_eq!(config.code_cache_size, 128);
+        assert!(!config.check_op_count_consistency);
+        assert_eq!(config.base_fee_multiplier, Some(1.5));
+        assert!(config.persist_frame_gas);
+        assert!(!config.adjust_intrinsic_gas);
+        assert!(config.quiet);
+        assert_eq!(config.progress_interval, Some(Duration::from_secs(30)));
+        assert!(config.state_digest);
+        assert!(config.seed_warm_from_block);
+        assert!(config.sequential_state);
+        assert_eq!(config.sample_rate, 0.25);
+        assert_eq!(
+            config.adaptive_sampling,
+            Some(AdaptiveSampling { window_blocks: 500, divergent_sample_rate: 0.9 })
+        );
+        assert_eq!(config.skip_filter, Some(SkipFilter { bits: 65_536, recheck_rate: 0.05 }));
+        assert!(config.include_system_txs);
+        assert!(config.include_system_state);
+        assert_eq!(
+            config.deep_trace_selectors,
+            HashSet::from([[0xa9, 0x05, 0x9c, 0xbb], [0x09, 0x5e, 0xa7, 0xb3]])
+        );
+        assert_eq!(config.unknown_error_policy, UnknownErrorPolicy::Record);
+        assert_eq!(
+            config.only_types,
+            Some(HashSet::from([DivergenceType::Status, DivergenceType::StateRoot]))
+        );
+        assert_eq!(config.economic_gas_threshold, Some(30_000_000_000));
+        assert_eq!(config.memory_tolerance_words, 4);
+        assert_eq!(config.gas_reconciliation_tolerance, 21_000);
+        assert!(config.confirm_failures);
+        assert!(config.oog_fast_skip);
+        assert_eq!(config.min_gas_used, Some(50_000));
+        assert_eq!(config.max_bytes_per_divergence, Some(65_536));
+        assert_eq!(config.ratio_decimals, 5);
+        assert_eq!(config.near_miss_gas_threshold, Some(5_000));
+        assert_eq!(
+            config.schedule_b,
+            Some(GasSchedule { gas_multiplier: 16, excluded_opcodes: HashSet::from([0x54, 0x55]) })
+        );
+        assert!(config.resume);
+        assert!(config.force_resume);
+        assert!(config.self_test);
+        assert_eq!(config.sweep_multipliers, vec![2, 16, 128]);
+
+        // Invalid combinations and unknown divergence types are rejected
+        let unknown_type = ResearchArgs { only_types: vec!["oog".to_string()], ..args.clone() };
+        assert!(ResearchConfig::try_from(&unknown_type).is_err());
+        let args = ResearchArgs { start_block: 300, ..args };
+        assert!(ResearchConfig::try_from(&args).is_err());
+    }
+
     #[test]
     fn research_args_default_sanity_test() {
         let default_args = ResearchArgs::default();
@@ -124,9 +952,63 @@ mod tests {
                 enabled: true,
                 gas_multiplier: 256,
                 start_block: 18000000,
+                end_block: None,
+                strict_block_range: false,
                 db_path: PathBuf::from("./divergence.db"),
                 refund_multiplier: 128.0,
                 stipend_multiplier: 128.0,
+                calldata_gas_multiplier: 1,
+                excluded_opcodes: Vec::new(),
+                ignore_addresses: Vec::new(),
+                compare_beneficiary_balance: false,
+                compare_access_lists: false,
+                loop_detection_db_path: None,
+                signatures_file: None,
+                #[cfg(feature = "research")]
+                trace_detail: reth_research::config::TraceDetail::Standard,
+                gas_limit_multiplier: None,
+                max_divergences_per_block: None,
+                max_divergences_total: None,
+                detect_gas_loops: false,
+                max_parallel_txs: None,
+                concurrent_execution: false,
+                code_cache_size: 4096,
+                disable_op_count_check: false,
+                base_fee_multiplier: None,
+                persist_frame_gas: false,
+                disable_intrinsic_gas_adjustment: false,
+                quiet: false,
+                progress_interval: None,
+                state_digest: false,
+                seed_contracts: false,
+                sequential_state: false,
+                sample_rate: 1.0,
+                adaptive_sampling: false,
+                adaptive_window: 1000,
+                divergent_sample_rate: 1.0,
+                skip_non_divergent: false,
+                skip_filter_bits: 16_777_216,
+                recheck_rate: 0.01,
+                include_system_txs: false,
+            include_system_state: false,
+                deep_trace_selectors: Vec::new(),
+                unknown_error_policy: "skip".to_string(),
+                only_types: Vec::new(),
+                compare_gas_only_above: None,
+                memory_tolerance_words: 0,
+                gas_reconciliation_tolerance: 0,
+                confirm_failures: false,
+                oog_fast_skip: false,
+                min_gas_used: None,
+                max_bytes_per_divergence: None,
+                ratio_decimals: 3,
+                near_miss_gas_threshold: None,
+                schedule_b: None,
+                schedule_b_excluded_opcodes: Vec::new(),
+                resume: false,
+                force: false,
+                self_test: false,
+                sweep_multipliers: Vec::new(),
             }
         );
     }
@@ -0,0 +1,2170 @@
+//! Dual execution of a single transaction and comparison of the results.
+//!
+//! This is the core of research mode: the ExEx and the offline analyzer both prepare the state
+//! for a transaction and hand it to [`analyze_transaction`], which runs it once with normal gas
+//! costs and once with the configured multipliers, and reports a [`Divergence`] if the two
+//! executions differ.
+
+use crate::{
+    compare::{self, ExperimentalError, GasFees, StateComparison},
+    config::{ResearchConfig, TraceDetail, UnknownErrorPolicy},
+    divergence::{
+        CallFrame, CallTrees, CalldataStats, Comparison, Create2Deployment, Create2Deployments,
+        Divergence, DivergenceCause, DivergenceType, EventLog, EventLogs, GasAnalysis,
+        OperationCounts,
+    },
+    inspector::GasResearchInspector,
+    metrics, system,
+    timing::{AnalysisTimings, TransactionTimer},
+    tracking_inspector::TrackingInspector,
+};
+use alloy_consensus::{transaction::TxHashRef, BlockHeader, Transaction};
+use alloy_primitives::{Address, B256};
+use reth_evm::{
+    env::BlockEnvironment, ConfigureEvm, Evm, EvmEnvFor, EvmErrorFor, HaltReasonFor, TxEnvFor,
+};
+use reth_primitives_traits::{HeaderTy, Recovered, TxTy};
+use revm::{
+    context::Block,
+    context_interface::result::{ExecutionResult, ResultAndState},
+    database::{CacheDB, DatabaseRef},
+    state::EvmState,
+};
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet},
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+};
+use tracing::{debug, info, trace, warn};
+
+/// Log an individual divergence at `info`, or at `trace` in quiet mode.
+macro_rules! divergence_event {
+    ($config:expr, $($arg:tt)+) => {
+        if $config.quiet {
+            trace!($($arg)+)
+        } else {
+            info!($($arg)+)
+        }
+    };
+}
+
+/// Block-level inputs shared by every transaction analyzed in a block.
+#[derive(Debug, Clone)]
+pub struct BlockContext<E: ConfigureEvm> {
+    /// Block number
+    pub block_number: u64,
+
+    /// Block timestamp
+    pub timestamp: u64,
+
+    /// Block gas limit
+    pub gas_limit: u64,
+
+    /// EVM environment for normal execution
+    pub evm_env: EvmEnvFor<E>,
+
+    /// EVM environment for experimental execution
+    pub experimental_evm_env: EvmEnvFor<E>,
+
+    /// Adjusted base fee used in experimental execution, if configured
+    pub experimental_base_fee: Option<u64>,
+
+    /// Accounts warmed before each transaction in both executions
+    pub warm_addresses: Vec<Address>,
+
+    /// Counters the time spent analyzing each transaction is added to, if set
+    pub timings: Option<Arc<AnalysisTimings>>,
+}
+
+impl<E: ConfigureEvm> BlockContext<E> {
+    /// Build the context for a block from its header.
+    pub fn new(
+        evm_config: &E,
+        config: &ResearchConfig,
+        header: &HeaderTy<E::Primitives>,
+    ) -> Result<Self, E::Error> {
+        let evm_env = evm_config.evm_env(header)?;
+
+        // Experimental execution optionally runs against an adjusted base fee
+        let mut experimental_evm_env = evm_env.clone();
+        let experimental_base_fee = config.experimental_base_fee(header.base_fee_per_gas());
+        if let Some(base_fee) = experimental_base_fee {
+            experimental_evm_env.block_env.inner_mut().basefee = base_fee;
+        }
+
+        Ok(Self {
+            block_number: header.number(),
+            timestamp: header.timestamp(),
+            gas_limit: header.gas_limit(),
+            evm_env,
+            experimental_evm_env,
+            experimental_base_fee,
+            warm_addresses: Vec::new(),
+            timings: None,
+        })
+    }
+
+    /// Add the time spent in each phase of analyzing the block's transactions to `timings`.
+    pub fn with_timings(mut self, timings: Arc<AnalysisTimings>) -> Self {
+        self.timings = Some(timings);
+        self
+    }
+
+    /// Warm the recipients of the block's transactions before every transaction if
+    /// [`ResearchConfig::seed_warm_from_block`] is set.
+    ///
+    /// Each transaction otherwise starts cold from the pre-block state, while in real execution
+    /// earlier transactions in the block have already warmed what they touched. This is only an
+    /// approximation of that: it warms the recipients of all transactions, including later ones,
+    /// and nothing else they touch.
+    pub fn with_warm_recipients<'a, T: Transaction + 'a>(
+        mut self,
+        config: &ResearchConfig,
+        transactions: impl IntoIterator<Item = &'a T>,
+    ) -> Self {
+        if config.seed_warm_from_block {
+            let recipients: BTreeSet<_> =
+                transactions.into_iter().filter_map(Transaction::to).collect();
+            self.warm_addresses = recipients.into_iter().collect();
+        }
+        self
+    }
+}
+
+/// Transaction-level inputs for [`analyze_transaction`].
+#[derive(Debug, Clone)]
+pub struct TxContext<E: ConfigureEvm> {
+    /// Transaction index within the block
+    pub index: usize,
+
+    /// Transaction hash
+    pub hash: B256,
+
+    /// Transaction sender
+    pub sender: Address,
+
+    /// Transaction recipient (None for contract creations)
+    pub to: Option<Address>,
+
+    /// Function selector of the top-level call (None for contract creations and calldata
+    /// shorter than four bytes)
+    pub selector: Option<[u8; 4]>,
+
+    /// Size and zero/non-zero byte split of the calldata
+    pub calldata: CalldataStats,
+
+    /// Gas limit of the transaction, which experimental execution runs under as well
+    pub gas_limit: u64,
+
+    /// Transaction environment shared by both executions
+    pub tx_env: TxEnvFor<E>,
+
+    /// Effective gas price at the normal base fee
+    pub normal_gas_price: u128,
+
+    /// Effective gas price at the experimental base fee
+    pub experimental_gas_price: u128,
+}
+
+impl<E: ConfigureEvm> TxContext<E> {
+    /// Build the context for the transaction at `index` in the block described by `block`.
+    pub fn new(
+        evm_config: &E,
+        block: &BlockContext<E>,
+        index: usize,
+        tx: Recovered<&TxTy<E::Primitives>>,
+    ) -> Self {
+        Self {
+            index,
+            hash: *tx.tx_hash(),
+            sender: tx.signer(),
+            to: tx.to(),
+            selector: tx_selector(*tx),
+            calldata: CalldataStats::new(tx.input()),
+            gas_limit: tx.gas_limit(),
+            normal_gas_price: tx.effective_gas_price(Some(block.evm_env.block_env.basefee())),
+            experimental_gas_price: tx
+                .effective_gas_price(Some(block.experimental_evm_env.block_env.basefee())),
+            tx_env: evm_config.tx_env(tx),
+        }
+    }
+}
+
+/// Whether `tx` deploys a contract rather than calling an address.
+///
+/// Contract creations have no recipient, so they are sampled without a recipient bias, never
+/// skipped as a known non-divergent call shape and have no selector. Divergences in their init
+/// code are attributed to the created address.
+pub fn is_contract_creation(tx: &impl Transaction) -> bool {
+    tx.kind().is_create()
+}
+
+/// Function selector called by `tx`, if it is a call with at least four bytes of calldata.
+pub(crate) fn tx_selector(tx: &impl Transaction) -> Option<[u8; 4]> {
+    if is_contract_creation(tx) {
+        return None
+    }
+    tx.input().get(..4).map(|selector| selector.try_into().expect("4 bytes"))
+}
+
+/// Execute a transaction normally and with the experimental gas costs, and compare the results.
+///
+/// `normal_db` and `experimental_db` must both provide the state the transaction executes
+/// against; each execution runs on its own cache so the two do not interfere, and with
+/// [`ResearchConfig::executes_concurrently`] on the rayon thread pool. Returns the detected
+/// divergence, or `None` if the executions match, the transaction is invalid under either
+/// execution (unless the adjusted base fee priced it out), or it is a
+/// [system transaction](crate::system) and those are excluded.
+///
+/// With [`ResearchConfig::sweep_multipliers`] set, a divergent transaction is analyzed again at
+/// each of them and the divergence carries the resulting [`Divergence::threshold_map`].
+pub fn analyze_transaction<E, DB>(
+    evm_config: &E,
+    config: &ResearchConfig,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    normal_db: DB,
+    experimental_db: DB,
+) -> Option<Divergence>
+where
+    E: ConfigureEvm,
+    DB: DatabaseRef + Sync,
+{
+    analyze_transaction_with_normal(evm_config, config, block, tx, normal_db, experimental_db).0
+}
+
+/// [`analyze_transaction`], also returning the result and state changes of the normal
+/// execution, if `tx` was executed normally and is valid.
+fn analyze_transaction_with_normal<E, DB>(
+    evm_config: &E,
+    config: &ResearchConfig,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    normal_db: DB,
+    experimental_db: DB,
+) -> (Option<Divergence>, Option<ResultAndState<HaltReasonFor<E>>>)
+where
+    E: ConfigureEvm,
+    DB: DatabaseRef + Sync,
+{
+    if config.sweep_multipliers.is_empty() {
+        return analyze_once(evm_config, config, block, tx, normal_db, experimental_db);
+    }
+
+    let (divergence, normal) =
+        analyze_once(evm_config, config, block, tx, &normal_db, &experimental_db);
+    let divergence = divergence.map(|divergence| {
+        let (threshold_map, _) = sweep(
+            evm_config,
+            config,
+            block,
+            tx,
+            &config.sweep_multipliers,
+            &normal_db,
+            &experimental_db,
+        );
+        Divergence { threshold_map: Some(threshold_map), ..divergence }
+    });
+    (divergence, normal)
+}
+
+/// Execute and compare `tx` once at the configured gas schedule, see [`analyze_transaction`].
+///
+/// Also returns the result and state changes of the normal execution, if `tx` was executed
+/// normally and is valid.
+fn analyze_once<E, DB>(
+    evm_config: &E,
+    config: &ResearchConfig,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    normal_db: DB,
+    experimental_db: DB,
+) -> (Option<Divergence>, Option<ResultAndState<HaltReasonFor<E>>>)
+where
+    E: ConfigureEvm,
+    DB: DatabaseRef + Sync,
+{
+    // System operations change state the same way under any gas costs
+    if !config.include_system_txs && system::is_system_tx(tx.sender, tx.to) {
+        trace!(
+            target: "exex::research",
+            block = block.block_number,
+            tx_idx = tx.index,
+            "Skipping system transaction"
+        );
+        return (None, None);
+    }
+
+    let mut timer = TransactionTimer::start(block.timings.as_deref());
+
+    // Frame input and output data are only kept for detailed traces, they can be large
+    let capture_call_data = matches!(config.trace_detail_for(tx.selector), TraceDetail::Detailed);
+
+    // --- EXECUTION 1: Normal (with tracking inspector) ---
+    // With concurrent execution the experimental execution runs on the rayon pool meanwhile.
+    // Each execution has its own cache and inspector, so nothing is shared but the read-only
+    // pre-state. Panics in the EVM are caught by each execution, rayon propagates anything else.
+    let run_experimental =
+        || execute_experimental(evm_config, config, block, tx, &experimental_db, capture_call_data);
+    let (normal, concurrent_experimental) = if config.executes_concurrently() {
+        let (normal, experimental) = rayon::join(
+            || execute_normal(evm_config, block, tx, &normal_db, capture_call_data),
+            run_experimental,
+        );
+        (normal, Some(experimental))
+    } else {
+        (execute_normal(evm_config, block, tx, normal_db, capture_call_data), None)
+    };
+    let Some(normal) = normal else { return (None, None) };
+    if skips_experimental(config, None, block, tx, normal.0.result.gas_used()) {
+        return (None, Some(normal.0));
+    }
+    timer.next_phase();
+
+    // --- EXECUTION 2: Experimental (with gas multiplier inspector) ---
+    let Some(experimental) = concurrent_experimental.unwrap_or_else(run_experimental) else {
+        return (None, Some(normal.0));
+    };
+
+    timer.next_phase();
+
+    let divergence =
+        compare_to_normal(evm_config, config, block, tx, &normal, experimental, &experimental_db);
+    (divergence, Some(normal.0))
+}
+
+/// Execute a transaction normally and under both experimental gas schedules of an A/B run, and
+/// compare each pair of executions.
+///
+/// With [`ResearchConfig::schedule_b`] set, the transaction runs once normally and once under
+/// each schedule, all on the same pre-state, and up to three divergences are returned in order:
+/// normal against A, normal against B, and A against B, tagged with their [`Comparison`]. With
+/// [`ResearchConfig::concurrent_execution`] the two experimental executions run concurrently.
+/// Without a second schedule this is [`analyze_transaction`].
+pub fn analyze_schedules<E, DB>(
+    evm_config: &E,
+    config: &ResearchConfig,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    normal_db: DB,
+    experimental_db: DB,
+) -> Vec<Divergence>
+where
+    E: ConfigureEvm,
+    DB: DatabaseRef + Sync,
+{
+    analyze_schedules_with_normal(evm_config, config, block, tx, normal_db, experimental_db).0
+}
+
+/// [`analyze_schedules`], also returning the result and state changes of the normal execution.
+///
+/// The normal execution is `None` if `tx` was not executed normally, as an excluded
+/// [system transaction](crate::system), or is invalid. A caller carrying the normal state forward
+/// commits the returned changes instead of executing `tx` once more with [`commit_normal`].
+pub fn analyze_schedules_with_normal<E, DB>(
+    evm_config: &E,
+    config: &ResearchConfig,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    normal_db: DB,
+    experimental_db: DB,
+) -> (Vec<Divergence>, Option<ResultAndState<HaltReasonFor<E>>>)
+where
+    E: ConfigureEvm,
+    DB: DatabaseRef + Sync,
+{
+    let Some(schedule_b) = &config.schedule_b else {
+        let (divergence, normal) = analyze_transaction_with_normal(
+            evm_config,
+            config,
+            block,
+            tx,
+            normal_db,
+            experimental_db,
+        );
+        return (divergence.into_iter().collect(), normal);
+    };
+    let config_b = config.with_schedule(schedule_b);
+
+    if !config.include_system_txs && system::is_system_tx(tx.sender, tx.to) {
+        trace!(
+            target: "exex::research",
+            block = block.block_number,
+            tx_idx = tx.index,
+            "Skipping system transaction"
+        );
+        return (Vec::new(), None);
+    }
+
+    let mut timer = TransactionTimer::start(block.timings.as_deref());
+    let capture_call_data = matches!(config.trace_detail_for(tx.selector), TraceDetail::Detailed);
+
+    let Some(normal) = execute_normal(evm_config, block, tx, normal_db, capture_call_data) else {
+        return (Vec::new(), None);
+    };
+    if skips_experimental(config, Some(&config_b), block, tx, normal.0.result.gas_used()) {
+        return (Vec::new(), Some(normal.0));
+    }
+    timer.next_phase();
+
+    let run_a =
+        || execute_experimental(evm_config, config, block, tx, &experimental_db, capture_call_data);
+    let run_b = || {
+        execute_experimental(evm_config, &config_b, block, tx, &experimental_db, capture_call_data)
+    };
+    let (a, b) = if config.concurrent_execution {
+        rayon::join(run_a, run_b)
+    } else {
+        (run_a(), run_b())
+    };
+    timer.next_phase();
+
+    let a_vs_b = a
+        .as_ref()
+        .zip(b.as_ref())
+        .and_then(|(a, b)| compare_schedules(config, &config_b, block, tx, a, b));
+    let mut divergences = Vec::new();
+    for (comparison, config, experimental) in
+        [(Comparison::NormalA, config, a), (Comparison::NormalB, &config_b, b)]
+    {
+        let divergence = experimental.and_then(|experimental| {
+            let db = &experimental_db;
+            compare_to_normal(evm_config, config, block, tx, &normal, experimental, db)
+        });
+        divergences.extend(divergence.map(|divergence| Divergence { comparison, ..divergence }));
+    }
+    divergences.extend(a_vs_b);
+    (divergences, Some(normal.0))
+}
+
+/// Normal and experimental states of a block analyzed with
+/// [`ResearchConfig::sequential_state`], advanced past each transaction in turn.
+#[derive(Debug)]
+pub struct SequentialState<DB> {
+    /// State left by the normal executions of the block's transactions so far
+    pub normal: CacheDB<DB>,
+
+    /// State left by the experimental executions of the block's transactions so far
+    pub experimental: CacheDB<DB>,
+}
+
+impl<DB: DatabaseRef> SequentialState<DB> {
+    /// Start from the pre-block states in `normal_db` and `experimental_db`.
+    pub fn new(normal_db: DB, experimental_db: DB) -> Self {
+        Self { normal: CacheDB::new(normal_db), experimental: CacheDB::new(experimental_db) }
+    }
+
+    /// Apply `tx` to both states, each with its own gas costs.
+    ///
+    /// Returns the result of the normal execution, or `None` if the transaction is invalid.
+    pub fn commit<E: ConfigureEvm>(
+        &mut self,
+        evm_config: &E,
+        config: &ResearchConfig,
+        block: &BlockContext<E>,
+        tx: &TxContext<E>,
+    ) -> Option<ExecutionResult<HaltReasonFor<E>>> {
+        let normal = commit_normal(evm_config, block, tx, &mut self.normal);
+        commit_experimental(evm_config, config, block, tx, &mut self.experimental);
+        normal
+    }
+}
+
+/// Execute `tx` with normal gas costs and apply its effects to `db`.
+///
+/// Returns the result, or `None` if the transaction is invalid and `db` was left unchanged.
+pub fn commit_normal<E, DB>(
+    evm_config: &E,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    db: &mut CacheDB<DB>,
+) -> Option<ExecutionResult<HaltReasonFor<E>>>
+where
+    E: ConfigureEvm,
+    DB: DatabaseRef,
+{
+    let mut evm = evm_config.evm_with_env(db, block.evm_env.clone());
+    evm.transact_commit(tx.tx_env.clone())
+        .inspect_err(|e| {
+            debug!(
+                target: "exex::research",
+                block = block.block_number,
+                tx_idx = tx.index,
+                error = ?e,
+                "Skipping state update for invalid transaction"
+            )
+        })
+        .ok()
+}
+
+/// Execute `tx` with the experimental gas costs and apply its effects to `db`.
+///
+/// This is a second experimental execution on top of the one analyzed, the cost of carrying the
+/// experimental state forward with [`ResearchConfig::sequential_state`]. Returns the result, or
+/// `None` if the transaction is invalid or the execution panicked and `db` was left unchanged.
+pub fn commit_experimental<E, DB>(
+    evm_config: &E,
+    config: &ResearchConfig,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    db: &mut CacheDB<DB>,
+) -> Option<ExecutionResult<HaltReasonFor<E>>>
+where
+    E: ConfigureEvm,
+    DB: DatabaseRef,
+{
+    let mut inspector = GasResearchInspector::new(config.clone(), block.gas_limit)
+        .with_warm_addresses(block.warm_addresses.clone());
+    let mut evm = evm_config.evm_with_env_and_inspector(
+        db,
+        block.experimental_evm_env.clone(),
+        &mut inspector,
+    );
+    match panic::catch_unwind(AssertUnwindSafe(|| evm.transact_commit(tx.tx_env.clone()))) {
+        Ok(Ok(result)) => Some(result),
+        Ok(Err(e)) => {
+            debug!(
+                target: "exex::research",
+                block = block.block_number,
+                tx_idx = tx.index,
+                error = ?e,
+                "Skipping experimental state update for invalid transaction"
+            );
+            None
+        }
+        Err(payload) => {
+            metrics::record_experimental_panic();
+            warn!(
+                target: "exex::research",
+                block = block.block_number,
+                tx_idx = tx.index,
+                panic = panic_message(payload.as_ref()),
+                "Experimental execution panicked, skipping state update"
+            );
+            None
+        }
+    }
+}
+
+/// Whether to skip the experimental executions of a transaction that used `normal_gas` in
+/// normal execution: it used less than the minimum gas, or the fast skip is on, only OOG types
+/// are recorded and it cannot run out of gas under `config` nor under `config_b`, if set.
+fn skips_experimental<E: ConfigureEvm>(
+    config: &ResearchConfig,
+    config_b: Option<&ResearchConfig>,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    normal_gas: u64,
+) -> bool {
+    let tx_idx = tx.index;
+
+    // Far below its gas limit the experimental execution cannot run out of gas, which is all a
+    // run recording only OOG types looks for
+    let cannot_oog = |config: &ResearchConfig| config.cannot_oog(normal_gas, tx.gas_limit);
+    if config.oog_fast_skip &&
+        config.only_oog_types() &&
+        cannot_oog(config) &&
+        config_b.is_none_or(cannot_oog)
+    {
+        metrics::record_oog_fast_skip();
+        trace!(
+            target: "exex::research",
+            block = block.block_number,
+            tx_idx,
+            normal_gas,
+            "Skipping experimental execution of a transaction that cannot run out of gas"
+        );
+        return true;
+    }
+
+    if config.min_gas_used.is_some_and(|min| normal_gas < min) {
+        trace!(
+            target: "exex::research",
+            block = block.block_number,
+            tx_idx,
+            normal_gas,
+            "Skipping transaction below the minimum gas used"
+        );
+        return true;
+    }
+    false
+}
+
+/// Compare an experimental execution of `tx` against its normal execution, and return the
+/// divergence if they differ.
+///
+/// A divergence is attributed to its cause by replaying the transaction at normal gas costs on
+/// `experimental_db`.
+fn compare_to_normal<E, DB>(
+    evm_config: &E,
+    config: &ResearchConfig,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    normal: &NormalExecution<E>,
+    experimental: ExperimentalExecution<E, DB>,
+    experimental_db: &DB,
+) -> Option<Divergence>
+where
+    E: ConfigureEvm,
+    DB: DatabaseRef,
+{
+    let block_number = block.block_number;
+    let tx_idx = tx.index;
+    let (normal_result, normal_inspector) = normal;
+    let (experimental_result, experimental_inspector) = experimental;
+
+    let experimental_result = match experimental_result {
+        Ok(result) => result,
+        Err(e) => {
+            // Normal execution succeeded but experimental failed before producing a result.
+            // Halts are results, so this is only a divergence when it is down to the fees or
+            // the policy for unknown errors says so.
+
+            // A transaction priced out by the adjusted base fee is an economic divergence,
+            // not an execution one
+            let divergence_type = match compare::classify_experimental_error(
+                &e,
+                block.experimental_base_fee.is_some(),
+            ) {
+                ExperimentalError::BaseFee => DivergenceType::BaseFee,
+                ExperimentalError::InvalidTransaction => {
+                    metrics::record_invalid_tx();
+                    debug!(
+                        target: "exex::research",
+                        block = block_number,
+                        tx_idx,
+                        error = ?e,
+                        "Transaction invalid in experimental execution only, skipping"
+                    );
+                    return None;
+                }
+                ExperimentalError::Unknown => {
+                    metrics::record_unknown_error();
+                    if config.unknown_error_policy == UnknownErrorPolicy::Skip {
+                        warn!(
+                            target: "exex::research",
+                            block = block_number,
+                            tx_idx,
+                            error = ?e,
+                            "Experimental execution failed with an unknown error, skipping"
+                        );
+                        return None;
+                    }
+                    DivergenceType::Status
+                }
+            };
+            if !config.records_types(&[divergence_type]) {
+                return None;
+            }
+
+            warn!(
+                target: "exex::research",
+                block = block_number,
+                tx_idx,
+                error = ?e,
+                "CRITICAL DIVERGENCE: Experimental execution failed while normal succeeded"
+            );
+
+            let gas_analysis = GasAnalysis {
+                normal_gas_used: normal_result.result.gas_used(),
+                experimental_gas_used: 0, // Failed before completion
+                gas_efficiency_ratio: 0.0,
+                normal_gas_charged: normal_result.result.gas_used(),
+                experimental_gas_charged: 0,
+            };
+
+            return Some(build_divergence(
+                config,
+                block,
+                tx,
+                vec![divergence_type],
+                gas_analysis,
+                &normal_inspector,
+                &experimental_inspector,
+            ));
+        }
+    };
+
+    if let Some(kind) = experimental_inspector.gas_loop_kind() {
+        metrics::record_gas_loop(kind);
+    }
+    if experimental_inspector.oog_occurred() &&
+        config.expects_tx_oog(normal_result.result.gas_used(), tx.gas_limit)
+    {
+        metrics::record_expected_oog();
+    }
+
+    // --- COMPARE RESULTS ---
+    let mut divergence_types = Vec::new();
+
+    // 1. Compare execution status
+    let normal_success = normal_result.result.is_success();
+    let experimental_success = experimental_result.result.is_success();
+
+    if !normal_success && experimental_success {
+        // Gas-guarded code paths can turn a revert into a success
+        divergence_types.push(DivergenceType::StatusImproved);
+        warn!(
+            target: "exex::research",
+            block = block_number,
+            tx_idx,
+            "CRITICAL DIVERGENCE: Experimental execution succeeded while normal reverted"
+        );
+    } else if normal_success && !experimental_success {
+        divergence_types.push(DivergenceType::Status);
+        warn!(
+            target: "exex::research",
+            block = block_number,
+            tx_idx,
+            "CRITICAL DIVERGENCE: Experimental execution reverted while normal succeeded"
+        );
+    }
+
+    // 2. Compare gas usage
+    let normal_gas = normal_result.result.gas_used();
+    let experimental_gas = experimental_result.result.gas_used();
+    let gas_ratio =
+        GasAnalysis::calculate_ratio(normal_gas, experimental_gas, config.gas_multiplier);
+    // The EVM already nets the capped refund out of gas used; only the experimental refund is
+    // scaled. The calldata is charged at its normal cost, the extra cost is added on top
+    let experimental_gas_charged = GasAnalysis::gas_charged(
+        experimental_gas,
+        gas_refunded(&experimental_result.result),
+        config.refund_multiplier,
+        experimental_inspector.max_refund_quotient(),
+    ) + config.extra_calldata_gas(&tx.calldata);
+    let gas_analysis = GasAnalysis {
+        normal_gas_used: normal_gas,
+        experimental_gas_used: experimental_gas,
+        gas_efficiency_ratio: gas_ratio,
+        normal_gas_charged: normal_gas,
+        experimental_gas_charged,
+    };
+    metrics::record_gas_charged_ratio(gas_analysis.gas_charged_ratio());
+
+    if gas_analysis.is_structural_divergence() {
+        divergence_types.push(DivergenceType::GasPattern);
+        divergence_event!(
+            config,
+            target: "exex::research",
+            block = block_number,
+            tx_idx,
+            normal_gas,
+            experimental_gas,
+            gas_ratio = config.quantize_ratio(gas_ratio),
+            "DIVERGENCE: Gas pattern differs structurally"
+        );
+    }
+
+    // 3. Compare state changes, ignoring balance differences explained by gas fees
+    let normal_basefee = block.evm_env.block_env.basefee();
+    let experimental_basefee = block.experimental_evm_env.block_env.basefee();
+    let normal_fees = GasFees::new(normal_gas, tx.normal_gas_price, normal_basefee);
+    let experimental_fees =
+        GasFees::new(experimental_gas, tx.experimental_gas_price, experimental_basefee);
+    let state_divergences = compare_states(
+        config,
+        block,
+        tx,
+        (&normal_result.state, normal_fees),
+        (&experimental_result.state, experimental_fees),
+    );
+    if !state_divergences.is_empty() {
+        divergence_event!(
+            config,
+            target: "exex::research",
+            block = block_number,
+            tx_idx,
+            types = ?state_divergences,
+            "DIVERGENCE: State differs"
+        );
+        divergence_types.extend(state_divergences);
+    }
+
+    // 4. Compare logs
+    if compare::logs_diverge(normal_result.result.logs(), experimental_result.result.logs()) {
+        divergence_types.push(DivergenceType::EventLogs);
+    }
+
+    // 5. Compare CREATE2 deployments
+    if compare::create2_deployments_diverge(
+        normal_inspector.create2_deployments(),
+        experimental_inspector.create2_deployments(),
+    ) {
+        divergence_types.push(DivergenceType::Create2Address);
+        divergence_event!(
+            config,
+            target: "exex::research",
+            block = block_number,
+            tx_idx,
+            "DIVERGENCE: CREATE2 deployment differs"
+        );
+    }
+
+    // 6. Compare the runtime code of a contract created by the transaction
+    let created_code =
+        compare::created_code_divergence(&normal_result.result, &experimental_result.result);
+    if let Some(created_code) = &created_code {
+        divergence_types.push(DivergenceType::Created);
+        divergence_event!(
+            config,
+            target: "exex::research",
+            block = block_number,
+            tx_idx,
+            address = %created_code.address,
+            normal_code_hash = %created_code.normal_code_hash,
+            experimental_code_hash = %created_code.experimental_code_hash,
+            "DIVERGENCE: Created contract code differs"
+        );
+    }
+
+    // 7. Compare the accounts and storage slots each execution accessed
+    let access_list_diff = config
+        .compare_access_lists
+        .then(|| {
+            compare::access_list_divergence(&normal_result.state, &experimental_result.state)
+        })
+        .flatten();
+    if let Some(access_list_diff) = &access_list_diff {
+        divergence_types.push(DivergenceType::AccessList);
+        divergence_event!(
+            config,
+            target: "exex::research",
+            block = block_number,
+            tx_idx,
+            normal_only_accounts = access_list_diff.normal_only.len(),
+            experimental_only_accounts = access_list_diff.experimental_only.len(),
+            "DIVERGENCE: Accessed accounts or storage slots differ"
+        );
+    }
+
+    // 8. Child frames that ran out of gas while their parent carried on
+    let child_frame_oogs = experimental_inspector.child_frame_oogs();
+    if !child_frame_oogs.is_empty() {
+        divergence_types.push(DivergenceType::CallTree);
+        divergence_event!(
+            config,
+            target: "exex::research",
+            block = block_number,
+            tx_idx,
+            call_indices = ?child_frame_oogs,
+            "DIVERGENCE: Child frame ran out of gas but its parent continued"
+        );
+    }
+
+    // 9. Compare peak memory
+    if compare::memory_peaks_diverge(
+        normal_inspector.operation_counts(),
+        experimental_inspector.operation_counts(),
+        config.memory_tolerance_words,
+    ) {
+        divergence_types.push(DivergenceType::Memory);
+        divergence_event!(
+            config,
+            target: "exex::research",
+            block = block_number,
+            tx_idx,
+            normal_memory_words = normal_inspector.operation_counts().memory_words_allocated,
+            experimental_memory_words =
+                experimental_inspector.operation_counts().memory_words_allocated,
+            "DIVERGENCE: Peak memory differs"
+        );
+    }
+
+    // 10. Self-consistency guard: op counts differ but nothing was flagged
+    if config.check_op_count_consistency &&
+        compare::check_op_count_consistency(
+            normal_inspector.operation_counts(),
+            experimental_inspector.operation_counts(),
+            &divergence_types,
+            experimental_inspector.oog_occurred(),
+        )
+    {
+        debug!(
+            target: "exex::research",
+            block = block_number,
+            tx_idx,
+            normal_ops = ?normal_inspector.operation_counts(),
+            experimental_ops = ?experimental_inspector.operation_counts(),
+            "Operation counts differ but no divergence was flagged"
+        );
+    }
+
+    // If divergences detected or OOG occurred, report it. Matching executions that only just
+    // fit in the gas limit are reported as near misses.
+    let matched = divergence_types.is_empty() && !experimental_inspector.oog_occurred();
+    let near_miss = matched &&
+        config
+            .near_miss_gas_threshold
+            .zip(experimental_inspector.gas_remaining())
+            .is_some_and(|(threshold, remaining)| remaining < threshold);
+    if matched && !near_miss {
+        return None;
+    }
+    if near_miss {
+        debug!(
+            target: "exex::research",
+            block = block_number,
+            tx_idx,
+            gas_remaining = experimental_inspector.gas_remaining(),
+            "Near miss: experimental execution finished with little gas left"
+        );
+    }
+
+    // Gas-only divergences are not worth recording while gas is cheap
+    let base_fee = block.evm_env.block_env.basefee();
+    if divergence_types == [DivergenceType::GasPattern] && !config.records_gas_divergences(base_fee)
+    {
+        trace!(
+            target: "exex::research",
+            block = block_number,
+            tx_idx,
+            base_fee,
+            "Skipping gas-only divergence below the economic gas threshold"
+        );
+        return None;
+    }
+
+    // Focused studies only record the types they are after
+    if !config.records_types(&divergence_types) {
+        trace!(
+            target: "exex::research",
+            block = block_number,
+            tx_idx,
+            types = ?divergence_types,
+            "Skipping divergence of types that are not recorded"
+        );
+        return None;
+    }
+
+    // 11. Replay at normal gas costs on the experimental pre-state to attribute the divergence,
+    // and to confirm that a failure seen only in experimental execution is down to the gas costs.
+    // Without sequential state both executions start from the same state, so the replay would
+    // just repeat normal execution and attribute everything to the gas costs.
+    let confirm_failure = config.sequential_state &&
+        config.confirm_failures &&
+        divergence_types.contains(&DivergenceType::Status);
+    let include_cause =
+        config.sequential_state && config.trace_detail_for(tx.selector).include_cause();
+    let cause = (confirm_failure || include_cause).then(|| {
+        let mut replay_cache = CacheDB::new(experimental_db);
+        let mut replay_inspector =
+            TrackingInspector::new().with_warm_addresses(block.warm_addresses.clone());
+        let mut replay_evm = evm_config.evm_with_env_and_inspector(
+            &mut replay_cache,
+            block.evm_env.clone(),
+            &mut replay_inspector,
+        );
+        let replay = replay_evm.transact(tx.tx_env.clone()).ok();
+
+        if confirm_failure && replay.as_ref().is_some_and(|replay| !replay.result.is_success()) {
+            metrics::record_non_gas_failure();
+            debug!(
+                target: "exex::research",
+                block = block_number,
+                tx_idx,
+                "Experimental failure reproduced at normal gas costs, not caused by the multiplier"
+            );
+            return DivergenceCause::NonGas;
+        }
+        compare::divergence_cause(&normal_result, replay.as_ref())
+    });
+
+    let detailed = matches!(config.trace_detail_for(tx.selector), TraceDetail::Detailed);
+    Some(Divergence {
+        cause,
+        near_miss,
+        created_code: created_code.filter(|_| detailed),
+        access_list_diff: access_list_diff.filter(|_| detailed),
+        ..build_divergence(
+            config,
+            block,
+            tx,
+            divergence_types,
+            gas_analysis,
+            &normal_inspector,
+            &experimental_inspector,
+        )
+    })
+}
+
+/// Compare the executions of `tx` under schedule A (`config`) and schedule B (`config_b`), and
+/// return a [`Comparison::AB`] divergence if they differ.
+///
+/// Only executions that both produced a result are compared, failures before that are reported
+/// against normal execution.
+fn compare_schedules<E, DB>(
+    config: &ResearchConfig,
+    config_b: &ResearchConfig,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    a: &ExperimentalExecution<E, DB>,
+    b: &ExperimentalExecution<E, DB>,
+) -> Option<Divergence>
+where
+    E: ConfigureEvm,
+    DB: DatabaseRef,
+{
+    let ((Ok(a_result), a_inspector), (Ok(b_result), b_inspector)) = (a, b) else { return None };
+    let mut divergence_types = Vec::new();
+
+    match (a_result.result.is_success(), b_result.result.is_success()) {
+        (false, true) => divergence_types.push(DivergenceType::StatusImproved),
+        (true, false) => divergence_types.push(DivergenceType::Status),
+        _ => {}
+    }
+
+    // Gas is compared at the same scale, B's multiplier relative to A's
+    let (a_gas, b_gas) = (a_result.result.gas_used(), b_result.result.gas_used());
+    let gas_analysis = GasAnalysis {
+        normal_gas_used: a_gas,
+        experimental_gas_used: b_gas,
+        gas_efficiency_ratio: GasAnalysis::calculate_ratio(
+            a_gas,
+            b_gas.saturating_mul(config.gas_multiplier),
+            config_b.gas_multiplier,
+        ),
+        normal_gas_charged: GasAnalysis::gas_charged(
+            a_gas,
+            gas_refunded(&a_result.result),
+            config.refund_multiplier,
+            a_inspector.max_refund_quotient(),
+        ) + config.extra_calldata_gas(&tx.calldata),
+        experimental_gas_charged: GasAnalysis::gas_charged(
+            b_gas,
+            gas_refunded(&b_result.result),
+            config_b.refund_multiplier,
+            b_inspector.max_refund_quotient(),
+        ) + config_b.extra_calldata_gas(&tx.calldata),
+    };
+    if gas_analysis.is_structural_divergence() {
+        divergence_types.push(DivergenceType::GasPattern);
+    }
+
+    let basefee = block.experimental_evm_env.block_env.basefee();
+    divergence_types.extend(compare_states(
+        config,
+        block,
+        tx,
+        (&a_result.state, GasFees::new(a_gas, tx.experimental_gas_price, basefee)),
+        (&b_result.state, GasFees::new(b_gas, tx.experimental_gas_price, basefee)),
+    ));
+    if compare::logs_diverge(a_result.result.logs(), b_result.result.logs()) {
+        divergence_types.push(DivergenceType::EventLogs);
+    }
+    if compare::create2_deployments_diverge(
+        a_inspector.create2_deployments(),
+        b_inspector.create2_deployments(),
+    ) {
+        divergence_types.push(DivergenceType::Create2Address);
+    }
+    let created_code = compare::created_code_divergence(&a_result.result, &b_result.result);
+    if created_code.is_some() {
+        divergence_types.push(DivergenceType::Created);
+    }
+    let access_list_diff = config
+        .compare_access_lists
+        .then(|| compare::access_list_divergence(&a_result.state, &b_result.state))
+        .flatten();
+    if access_list_diff.is_some() {
+        divergence_types.push(DivergenceType::AccessList);
+    }
+    if compare::memory_peaks_diverge(
+        a_inspector.operation_counts(),
+        b_inspector.operation_counts(),
+        config.memory_tolerance_words,
+    ) {
+        divergence_types.push(DivergenceType::Memory);
+    }
+
+    if divergence_types.is_empty() || !config.records_types(&divergence_types) {
+        return None;
+    }
+    divergence_event!(
+        config,
+        target: "exex::research",
+        block = block.block_number,
+        tx_idx = tx.index,
+        types = ?divergence_types,
+        "DIVERGENCE: Schedules A and B differ"
+    );
+
+    let detailed = matches!(config.trace_detail_for(tx.selector), TraceDetail::Detailed);
+    Some(Divergence {
+        comparison: Comparison::AB,
+        created_code: created_code.filter(|_| detailed),
+        access_list_diff: access_list_diff.filter(|_| detailed),
+        ..build_divergence(
+            config,
+            block,
+            tx,
+            divergence_types,
+            gas_analysis,
+            a_inspector,
+            b_inspector,
+        )
+    })
+}
+
+/// Analyze a transaction at each of `multipliers` and record where each divergence type first
+/// appears.
+///
+/// Runs [`analyze_transaction`] once per multiplier in ascending order, overriding
+/// `gas_multiplier` in `config`. Returns the divergence found at the highest diverging
+/// multiplier, with [`Divergence::threshold_map`] mapping each multiplier to the divergence types
+/// that were not present at any lower multiplier.
+pub fn sweep_transaction<E, DB>(
+    evm_config: &E,
+    config: &ResearchConfig,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    multipliers: &[u64],
+    db: DB,
+) -> Option<Divergence>
+where
+    E: ConfigureEvm,
+    DB: DatabaseRef + Sync,
+{
+    let (threshold_map, last_divergence) =
+        sweep(evm_config, config, block, tx, multipliers, &db, &db);
+    last_divergence
+        .map(|divergence| Divergence { threshold_map: Some(threshold_map), ..divergence })
+}
+
+/// Analyze `tx` once per multiplier in ascending order, returning the divergence types first
+/// found at each multiplier and the divergence at the highest diverging multiplier.
+fn sweep<E, DB>(
+    evm_config: &E,
+    config: &ResearchConfig,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    multipliers: &[u64],
+    normal_db: &DB,
+    experimental_db: &DB,
+) -> (BTreeMap<u64, Vec<DivergenceType>>, Option<Divergence>)
+where
+    E: ConfigureEvm,
+    DB: DatabaseRef + Sync,
+{
+    let mut multipliers = multipliers.to_vec();
+    multipliers.sort_unstable();
+    multipliers.dedup();
+
+    let mut seen = BTreeSet::new();
+    let mut threshold_map = BTreeMap::new();
+    let mut last_divergence = None;
+
+    for gas_multiplier in multipliers {
+        let config = ResearchConfig { gas_multiplier, ..config.clone() };
+        let (Some(divergence), _) =
+            analyze_once(evm_config, &config, block, tx, normal_db, experimental_db)
+        else {
+            continue;
+        };
+
+        let new_types: Vec<_> = divergence
+            .divergence_types
+            .iter()
+            .copied()
+            .filter(|divergence_type| seen.insert(*divergence_type))
+            .collect();
+        if !new_types.is_empty() {
+            threshold_map.insert(gas_multiplier, new_types);
+        }
+
+        last_divergence = Some(divergence);
+    }
+
+    (threshold_map, last_divergence)
+}
+
+/// Compare the post-transaction states of two executions of `tx`, ignoring balance differences
+/// explained by the gas fees each of them moved.
+fn compare_states<E: ConfigureEvm>(
+    config: &ResearchConfig,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    (normal, normal_fees): (&EvmState, GasFees),
+    (experimental, experimental_fees): (&EvmState, GasFees),
+) -> Vec<DivergenceType> {
+    let beneficiary = block.evm_env.block_env.beneficiary();
+
+    // Matching digests make the per-account comparison unnecessary
+    let digests_match = config.state_digest &&
+        compare::state_digest(normal, tx.sender, beneficiary, normal_fees) ==
+            compare::state_digest(experimental, tx.sender, beneficiary, experimental_fees);
+    if digests_match {
+        return Vec::new();
+    }
+    config.state_comparator.compare(&StateComparison {
+        normal,
+        experimental,
+        sender: tx.sender,
+        beneficiary,
+        normal_fees,
+        experimental_fees,
+        ignore_addresses: &config.ignore_addresses,
+        compare_beneficiary_balance: config.compare_beneficiary_balance,
+        include_system: config.include_system_state,
+    })
+}
+
+/// Refund already netted out of a transaction's gas used.
+fn gas_refunded<H>(result: &ExecutionResult<H>) -> u64 {
+    match result {
+        ExecutionResult::Success { gas_refunded, .. } => *gas_refunded,
+        _ => 0,
+    }
+}
+
+/// Result of a normal execution together with the inspector that observed it.
+type NormalExecution<E> = (ResultAndState<HaltReasonFor<E>>, TrackingInspector);
+
+/// Result of an experimental execution together with the inspector that observed it.
+type ExperimentalExecution<E, DB> = (
+    Result<ResultAndState<HaltReasonFor<E>>, EvmErrorFor<E, <DB as DatabaseRef>::Error>>,
+    GasResearchInspector,
+);
+
+/// Execute `tx` with normal gas costs on its own cache over `db`.
+///
+/// Returns `None` if the transaction is invalid.
+fn execute_normal<E, DB>(
+    evm_config: &E,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    db: DB,
+    capture_call_data: bool,
+) -> Option<(ResultAndState<HaltReasonFor<E>>, TrackingInspector)>
+where
+    E: ConfigureEvm,
+    DB: DatabaseRef,
+{
+    let mut cache = CacheDB::new(db);
+    let mut inspector = TrackingInspector::new()
+        .with_warm_addresses(block.warm_addresses.clone())
+        .with_call_data(capture_call_data);
+    let mut evm =
+        evm_config.evm_with_env_and_inspector(&mut cache, block.evm_env.clone(), &mut inspector);
+    let result = match evm.transact(tx.tx_env.clone()) {
+        Ok(result) => result,
+        Err(e) => {
+            debug!(
+                target: "exex::research",
+                block = block.block_number,
+                tx_idx = tx.index,
+                error = ?e,
+                "Normal execution failed"
+            );
+            return None;
+        }
+    };
+
+    // Drop the EVM to release the inspector
+    drop(evm);
+    Some((result, inspector))
+}
+
+/// Execute `tx` with the experimental gas costs on its own cache over `db`.
+///
+/// The experimental state is isolated in its own cache, so a panic (e.g. an inspector bug) only
+/// loses this transaction: it is caught and `None` is returned.
+fn execute_experimental<E, DB>(
+    evm_config: &E,
+    config: &ResearchConfig,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    db: &DB,
+    capture_call_data: bool,
+) -> Option<ExperimentalExecution<E, DB>>
+where
+    E: ConfigureEvm,
+    DB: DatabaseRef,
+{
+    let mut cache = CacheDB::new(db);
+    let mut inspector = GasResearchInspector::new(config.clone(), block.gas_limit)
+        .with_warm_addresses(block.warm_addresses.clone())
+        .with_call_data(capture_call_data)
+        .with_gas_observations(capture_call_data)
+        .with_real_gas(config.real_gas);
+    let transact = || {
+        let mut evm = evm_config.evm_with_env_and_inspector(
+            &mut cache,
+            block.experimental_evm_env.clone(),
+            &mut inspector,
+        );
+        panic::catch_unwind(AssertUnwindSafe(|| evm.transact(tx.tx_env.clone())))
+    };
+    let result = match transact_experimental(config, transact) {
+        Some(result) => result,
+        None => {
+            warn!(
+                target: "exex::research",
+                block = block.block_number,
+                tx_idx = tx.index,
+                "Real gas execution needs an EVM config built on RealGasEvmFactory, skipping \
+                 transaction"
+            );
+            return None;
+        }
+    };
+
+    let result = match result {
+        Ok(result) => result,
+        Err(payload) => {
+            metrics::record_experimental_panic();
+            warn!(
+                target: "exex::research",
+                block = block.block_number,
+                tx_idx = tx.index,
+                panic = panic_message(payload.as_ref()),
+                "Experimental execution panicked, skipping transaction"
+            );
+            return None;
+        }
+    };
+
+    Some((result, inspector))
+}
+
+/// Run the experimental execution `transact`, charging the experimental gas costs in the
+/// interpreter with [`ResearchConfig::real_gas`].
+///
+/// Returns `None` if real gas execution was asked for but `transact` did not run on a
+/// `real_gas::RealGasEvmFactory` EVM.
+fn transact_experimental<R>(config: &ResearchConfig, transact: impl FnOnce() -> R) -> Option<R> {
+    #[cfg(feature = "real-gas")]
+    if config.real_gas {
+        return crate::real_gas::with_schedule(config, transact)
+    }
+    debug_assert!(!config.real_gas, "real gas execution requires the `real-gas` feature");
+    Some(transact())
+}
+
+/// Assemble a [`Divergence`] from the inspectors of both executions.
+///
+/// `normal_inspector` traced the execution the experimental one is compared against: normal
+/// execution, or schedule A in a [`Comparison::AB`] comparison.
+fn build_divergence<E: ConfigureEvm>(
+    config: &ResearchConfig,
+    block: &BlockContext<E>,
+    tx: &TxContext<E>,
+    divergence_types: Vec<DivergenceType>,
+    gas_analysis: GasAnalysis,
+    normal_inspector: &impl BaselineExecution,
+    experimental_inspector: &GasResearchInspector,
+) -> Divergence {
+    let trace_detail = config.trace_detail_for(tx.selector);
+    let detailed = matches!(trace_detail, TraceDetail::Detailed);
+
+    // Extract call trees and event logs if detailed tracing is enabled
+    let call_trees = config.record_call_trees(trace_detail).then(|| CallTrees {
+        normal: normal_inspector.call_frames().to_vec(),
+        experimental: experimental_inspector.call_frames().to_vec(),
+    });
+
+    // GasResearchInspector doesn't track logs yet
+    let event_logs = detailed
+        .then(|| normal_inspector.event_logs())
+        .flatten()
+        .map(|normal| EventLogs { normal, experimental: vec![] });
+
+    let create2_deployments = detailed.then(|| Create2Deployments {
+        normal: normal_inspector.create2_deployments().to_vec(),
+        experimental: experimental_inspector.create2_deployments().to_vec(),
+    });
+
+    let gas_observations = detailed.then(|| experimental_inspector.gas_observations());
+
+    let mut divergence = Divergence {
+        block_number: block.block_number,
+        tx_index: tx.index as u64,
+        tx_hash: tx.hash,
+        timestamp: block.timestamp,
+        divergence_types,
+        // Detection is done, the ratio is only reported from here on
+        gas_analysis: GasAnalysis {
+            gas_efficiency_ratio: config.quantize_ratio(gas_analysis.gas_efficiency_ratio),
+            ..gas_analysis
+        },
+        normal_ops: normal_inspector.operation_counts().clone(),
+        experimental_ops: experimental_inspector.operation_counts().clone(),
+        divergence_location: experimental_inspector.divergence_location().cloned(),
+        oog_info: experimental_inspector.oog_info().cloned(),
+        griefing: experimental_inspector.griefing_info().cloned(),
+        reverting_selector: experimental_inspector.reverting_selector(),
+        cause: None,
+        call_trees,
+        event_logs,
+        create2_deployments,
+        created_code: None,
+        gas_observations,
+        threshold_map: None,
+        truncated: false,
+        near_miss: false,
+        comparison: Default::default(),
+        function_name: None,
+        calldata: Some(tx.calldata),
+        access_list_diff: None,
+    };
+    if let Some(max_bytes) = config.max_bytes_per_divergence {
+        divergence.limit_stored_bytes(max_bytes);
+    }
+    divergence
+}
+
+/// What a divergence records about the execution the experimental one is compared against.
+trait BaselineExecution {
+    /// Operation counts of the execution
+    fn operation_counts(&self) -> &OperationCounts;
+
+    /// Call frames of the execution
+    fn call_frames(&self) -> &[CallFrame];
+
+    /// CREATE2 deployments of the execution
+    fn create2_deployments(&self) -> &[Create2Deployment];
+
+    /// Event logs emitted by the execution, if they were tracked
+    fn event_logs(&self) -> Option<Vec<EventLog>>;
+}
+
+impl BaselineExecution for TrackingInspector {
+    fn operation_counts(&self) -> &OperationCounts {
+        Self::operation_counts(self)
+    }
+
+    fn call_frames(&self) -> &[CallFrame] {
+        Self::call_frames(self)
+    }
+
+    fn create2_deployments(&self) -> &[Create2Deployment] {
+        Self::create2_deployments(self)
+    }
+
+    fn event_logs(&self) -> Option<Vec<EventLog>> {
+        let logs = Self::event_logs(self).iter().map(|e| EventLog {
+            log_index: e.log_index,
+            address: e.address,
+            topics: e.topics.clone(),
+            data: e.data.clone(),
+        });
+        Some(logs.collect())
+    }
+}
+
+impl BaselineExecution for GasResearchInspector {
+    fn operation_counts(&self) -> &OperationCounts {
+        Self::operation_counts(self)
+    }
+
+    fn call_frames(&self) -> &[CallFrame] {
+        Self::call_frames(self)
+    }
+
+    fn create2_deployments(&self) -> &[Create2Deployment] {
+        Self::create2_deployments(self)
+    }
+
+    fn event_logs(&self) -> Option<Vec<EventLog>> {
+        None
+    }
+}
+
+/// The message of a panic payload, if it is a string.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("<non-string panic payload>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::GasSchedule,
+        divergence::{AccessSet, OogPattern},
+        oog::{OogClassifier, OogContext},
+        test_utils::{counter_value, tx_context},
+    };
+    use alloy_eips::eip4788::BEACON_ROOTS_ADDRESS;
+    use alloy_primitives::{keccak256, Bytes, TxKind, U256};
+    use metrics_util::debugging::DebuggingRecorder;
+    use reth_evm_ethereum::EthEvmConfig;
+    use revm::{
+        database::EmptyDB,
+        state::{AccountInfo, Bytecode},
+    };
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+    };
+    use tracing::Level;
+    use tracing_subscriber::{
+        layer::{Context, SubscriberExt},
+        Layer,
+    };
+
+    const CONTRACT: Address = Address::repeat_byte(0xc0);
+
+    /// Sweep a call to a contract that burns 100 JUMPDESTs, then succeeds only if
+    /// `78,000 <compare> GAS` and reverts otherwise. 79,000 gas is left after intrinsic gas, so
+    /// the guard sees about 78,900 gas at 1x and 2x (200 gas burnt) but less than 78,000 at 16x
+    /// (1,600 gas burnt).
+    fn sweep_gas_guard(
+        config: &ResearchConfig,
+        compare: u8,
+        multipliers: &[u64],
+    ) -> Option<Divergence> {
+        sweep_call(config, gas_guard_code(compare), &[], &[], multipliers)
+    }
+
+    /// Code of the gas guard contract swept by [`sweep_gas_guard`].
+    fn gas_guard_code(compare: u8) -> Vec<u8> {
+        let mut code = vec![0x5b; 100];
+        code.extend_from_slice(&[
+            0x5a, // GAS
+            0x62, 0x01, 0x30, 0xb0, // PUSH3 78000
+            compare, 0x60, 0x71, // PUSH1 113
+            0x57, // JUMPI
+            0x60, 0x00, 0x80, 0xfd, // PUSH1 0 DUP1 REVERT
+            0x5b, 0x00, // JUMPDEST STOP
+        ]);
+        code
+    }
+
+    /// Sweep a 100,000 gas call with `input` to a contract running `code` with `storage` set.
+    fn sweep_call(
+        config: &ResearchConfig,
+        code: Vec<u8>,
+        storage: &[(U256, U256)],
+        input: &[u8],
+        multipliers: &[u64],
+    ) -> Option<Divergence> {
+        let (evm_config, block, tx) = call_context(config, input);
+        let state = call_state(tx.sender, code, storage);
+        sweep_transaction(&evm_config, config, &block, &tx, multipliers, &state)
+    }
+
+    /// State with a funded `sender` and a contract running `code` with `storage` set.
+    fn call_state(sender: Address, code: Vec<u8>, storage: &[(U256, U256)]) -> CacheDB<EmptyDB> {
+        let code = Bytecode::new_raw(Bytes::from(code));
+
+        let mut state = CacheDB::new(EmptyDB::default());
+        state.insert_account_info(
+            sender,
+            AccountInfo { balance: U256::from(10u128.pow(18)), ..Default::default() },
+        );
+        state.insert_account_info(
+            CONTRACT,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+        );
+        for (slot, value) in storage {
+            state.insert_account_storage(CONTRACT, *slot, *value).unwrap();
+        }
+        state
+    }
+
+    /// Block and transaction contexts of a 100,000 gas call with `input` to the contract.
+    fn call_context(
+        config: &ResearchConfig,
+        input: &[u8],
+    ) -> (EthEvmConfig, BlockContext<EthEvmConfig>, TxContext<EthEvmConfig>) {
+        tx_context(config, TxKind::Call(CONTRACT), input)
+    }
+
+    /// Collects the levels of `exex::research` events.
+    struct LevelCapture(Arc<Mutex<Vec<Level>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for LevelCapture {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            if event.metadata().target() == "exex::research" {
+                self.0.lock().unwrap().push(*event.metadata().level());
+            }
+        }
+    }
+
+    /// Levels of the `exex::research` events emitted while running `f`.
+    fn research_event_levels(f: impl FnOnce()) -> Vec<Level> {
+        let levels = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry().with(LevelCapture(levels.clone()));
+        tracing::subscriber::with_default(subscriber, f);
+        let levels = levels.lock().unwrap().clone();
+        levels
+    }
+
+    #[test]
+    fn test_sweep_threshold_map() {
+        // LT: the guard passes while more than 78,000 gas is left
+        let divergence = sweep_gas_guard(&ResearchConfig::default(), 0x10, &[16, 2]).unwrap();
+
+        assert!(divergence.divergence_types.contains(&DivergenceType::Status));
+        assert_eq!(
+            divergence.threshold_map,
+            Some(BTreeMap::from([
+                (2, vec![DivergenceType::GasPattern]),
+                (16, vec![DivergenceType::Status]),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_system_tx_excluded_by_default() {
+        let config = ResearchConfig { gas_multiplier: 16, ..Default::default() };
+        let (evm_config, block, tx) = tx_context(&config, TxKind::Call(BEACON_ROOTS_ADDRESS), &[]);
+        let code = Bytecode::new_raw(Bytes::from(gas_guard_code(0x10)));
+        let mut state = call_state(tx.sender, vec![0x00], &[]);
+        state.insert_account_info(
+            BEACON_ROOTS_ADDRESS,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+        );
+
+        assert!(analyze_transaction(&evm_config, &config, &block, &tx, &state, &state).is_none());
+
+        let config = ResearchConfig { include_system_txs: true, ..config };
+        let divergence =
+            analyze_transaction(&evm_config, &config, &block, &tx, &state, &state).unwrap();
+        assert!(divergence.divergence_types.contains(&DivergenceType::Status));
+    }
+
+    #[test]
+    fn test_analyze_transaction_sweeps_divergences() {
+        let config = ResearchConfig { sweep_multipliers: vec![16, 2], ..Default::default() };
+        let (evm_config, block, tx) = call_context(&config, &[]);
+        let state = call_state(tx.sender, gas_guard_code(0x10), &[]);
+
+        let divergence =
+            analyze_transaction(&evm_config, &config, &block, &tx, &state, &state).unwrap();
+
+        // The divergence is the one at the configured 128x, the sweep only adds the map
+        assert!(divergence.divergence_types.contains(&DivergenceType::Status));
+        assert_eq!(
+            divergence.threshold_map,
+            Some(BTreeMap::from([
+                (2, vec![DivergenceType::GasPattern]),
+                (16, vec![DivergenceType::Status]),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_ratio_decimals() {
+        // At 2x the guard still passes, the unscaled intrinsic gas leaving the ratio just above
+        // 0.5, a gas pattern divergence
+        let analyze = |ratio_decimals| {
+            let config = ResearchConfig { ratio_decimals, ..Default::default() };
+            sweep_gas_guard(&config, 0x10, &[2]).unwrap()
+        };
+
+        let gas = analyze(3).gas_analysis;
+        let ratio = GasAnalysis::calculate_ratio(gas.normal_gas_used, gas.experimental_gas_used, 2);
+        assert_ne!(gas.gas_efficiency_ratio, ratio);
+        assert_eq!(gas.gas_efficiency_ratio, (ratio * 1000.0).round() / 1000.0);
+
+        // Rounded to a whole number the recorded ratio is 1.0, detection still used the full ratio
+        let divergence = analyze(0);
+        assert_eq!(divergence.gas_analysis.gas_efficiency_ratio, 1.0);
+        assert_eq!(divergence.divergence_types, vec![DivergenceType::GasPattern]);
+    }
+
+    #[test]
+    fn test_status_improved() {
+        // GT: the guard passes only once less than 78,000 gas is left, so the call reverts
+        // normally and succeeds at 16x
+        let divergence = sweep_gas_guard(&ResearchConfig::default(), 0x11, &[16]).unwrap();
+
+        assert!(divergence.divergence_types.contains(&DivergenceType::StatusImproved));
+        assert!(!divergence.divergence_types.contains(&DivergenceType::Status));
+        // Only attributed with detailed tracing
+        assert_eq!(divergence.cause, None);
+    }
+
+    #[test]
+    fn test_divergence_cause_with_sequential_state() {
+        let config = ResearchConfig::builder()
+            .with_trace_detail(TraceDetail::Detailed)
+            .with_sequential_state(true)
+            .with_gas_multiplier(16)
+            .build()
+            .unwrap();
+        let (evm_config, block, tx) = call_context(&config, &[]);
+        let guard = call_state(tx.sender, gas_guard_code(0x10), &[]);
+        let analyze = |config: &ResearchConfig, experimental: &CacheDB<EmptyDB>| {
+            analyze_transaction(&evm_config, config, &block, &tx, &guard, experimental).unwrap()
+        };
+
+        // LT: the guard reverts at 16x only because of the extra gas burnt before it, the
+        // experimental state is still the same as the normal one
+        let divergence = analyze(&config, &guard);
+        assert!(divergence.divergence_types.contains(&DivergenceType::Status));
+        assert_eq!(divergence.cause, Some(DivergenceCause::GasDirect));
+
+        // An earlier experimental execution left a contract that reverts unconditionally, so the
+        // replay at normal gas costs differs from normal execution as well
+        let reverting = call_state(tx.sender, vec![0x60, 0x00, 0x80, 0xfd], &[]);
+        let divergence = analyze(&config, &reverting);
+        assert!(divergence.divergence_types.contains(&DivergenceType::Status));
+        assert_eq!(divergence.cause, Some(DivergenceCause::StateCascade));
+
+        // Without sequential state both executions start from the same state, nothing to replay
+        let config = ResearchConfig { sequential_state: false, ..config };
+        assert_eq!(analyze(&config, &guard).cause, None);
+    }
+
+    #[test]
+    fn test_oog_fast_skip() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let fast_skip = ResearchConfig { oog_fast_skip: true, ..Default::default() };
+        let config = ResearchConfig {
+            only_types: Some(HashSet::from([DivergenceType::Status])),
+            ..fast_skip.clone()
+        };
+
+        ::metrics::with_local_recorder(&recorder, || {
+            // At 16x the call might not fit its 100,000 gas limit, so it is executed and the
+            // guard's revert found
+            assert!(sweep_gas_guard(&config, 0x10, &[16]).is_some());
+
+            // At 2x it fits with room to spare, so with only OOG types recorded its experimental
+            // execution is skipped
+            assert!(sweep_gas_guard(&config, 0x10, &[2]).is_none());
+
+            // Recording other types, the fast skip does not apply and the gas pattern is found
+            assert!(sweep_gas_guard(&fast_skip, 0x10, &[2]).is_some());
+        });
+
+        assert_eq!(counter_value(&snapshotter, "reth_research_oog_fast_skip_total", None), 1);
+    }
+
+    #[test]
+    fn test_expected_oog() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let config = ResearchConfig::default();
+        let (evm_config, block, tx) = call_context(&config, &[]);
+
+        ::metrics::with_local_recorder(&recorder, || {
+            // 1,000 JUMPDESTs at 128x cannot fit in the 100,000 gas limit
+            let state = call_state(tx.sender, vec![0x5b; 1_000], &[]);
+            let divergence =
+                analyze_transaction(&evm_config, &config, &block, &tx, &state, &state).unwrap();
+            assert!(divergence.oog_info.is_some());
+        });
+
+        assert_eq!(counter_value(&snapshotter, "reth_research_expected_oog_total", None), 1);
+    }
+
+    #[test]
+    fn test_min_gas_used() {
+        let config = ResearchConfig { min_gas_used: Some(30_000), ..Default::default() };
+
+        // PUSH1 0 PUSH1 0 SSTORE STOP: clearing a set slot uses 21,206 gas after the refund
+        let clear = vec![0x60, 0x00, 0x60, 0x00, 0x55, 0x00];
+        let storage = [(U256::ZERO, U256::from(1))];
+        let default = ResearchConfig::default();
+        assert!(sweep_call(&default, clear.clone(), &storage, &[], &[2]).is_some());
+        assert!(sweep_call(&config, clear, &storage, &[], &[2]).is_none());
+
+        // PUSH1 1 PUSH1 0 SSTORE STOP: setting a fresh slot uses 43,106 gas
+        let set = vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00];
+        let divergence = sweep_call(&config, set, &[], &[], &[2]).unwrap();
+        assert_eq!(divergence.gas_analysis.normal_gas_used, 43_106);
+    }
+
+    #[test]
+    fn test_near_miss() {
+        // JUMPDEST PUSH2 100 GAS GT PUSH1 0 JUMPI STOP: loop until at most 100 gas is left, so
+        // the call finishes with between 62 and 84 gas
+        let burner = vec![0x5b, 0x61, 0x00, 0x64, 0x5a, 0x11, 0x60, 0x00, 0x57, 0x00];
+        let sweep = |config: &ResearchConfig| sweep_call(config, burner.clone(), &[], &[], &[1]);
+
+        // Both executions match
+        assert!(sweep(&ResearchConfig::default()).is_none());
+        let config = ResearchConfig { near_miss_gas_threshold: Some(50), ..Default::default() };
+        assert!(sweep(&config).is_none());
+
+        let config = ResearchConfig { near_miss_gas_threshold: Some(1_000), ..Default::default() };
+        let divergence = sweep(&config).unwrap();
+        assert!(divergence.near_miss);
+        assert!(divergence.divergence_types.is_empty());
+        assert!(divergence.oog_info.is_none());
+    }
+
+    #[test]
+    fn test_concurrent_execution_matches_sequential() {
+        let sequential =
+            ResearchConfig { trace_detail: TraceDetail::Detailed, ..Default::default() };
+        let concurrent = ResearchConfig { concurrent_execution: true, ..sequential.clone() };
+
+        let (evm_config, block, tx) = call_context(&sequential, &[]);
+        let state = call_state(tx.sender, gas_guard_code(0x10), &[]);
+
+        // Nothing diverges at 1x, only the gas pattern at 2x, and the guard reverts at 16x
+        for gas_multiplier in [1, 2, 16] {
+            let analyze = |config: &ResearchConfig| {
+                let config = ResearchConfig { gas_multiplier, ..config.clone() };
+                analyze_transaction(&evm_config, &config, &block, &tx, &state, &state)
+                    .map(|divergence| divergence.to_json().unwrap())
+            };
+            let divergence = analyze(&sequential);
+            assert_eq!(divergence.is_some(), gas_multiplier > 1);
+            assert_eq!(divergence, analyze(&concurrent));
+        }
+    }
+
+    #[test]
+    fn test_created_code_divergence() {
+        // A constructor that burns 100 JUMPDESTs, then deploys the single byte `44,500 < GAS`.
+        // About 45,000 gas is left at the guard at 1x and 2x but less than 44,500 at 16x, so the
+        // contract is created in both executions but with different runtime code.
+        let mut init_code = vec![0x5b; 100];
+        init_code.extend_from_slice(&[
+            0x5a, // GAS
+            0x61, 0xad, 0xd4, // PUSH2 44500
+            0x10, // LT
+            0x60, 0x00, 0x53, // PUSH1 0 MSTORE8
+            0x60, 0x01, 0x60, 0x00, 0xf3, // PUSH1 1 PUSH1 0 RETURN
+        ]);
+        let config = ResearchConfig { trace_detail: TraceDetail::Detailed, ..Default::default() };
+        let (evm_config, block, tx) = tx_context(&config, TxKind::Create, &init_code);
+        let state = call_state(tx.sender, vec![0x00], &[]);
+        let analyze = |gas_multiplier| {
+            let config = ResearchConfig { gas_multiplier, ..config.clone() };
+            analyze_transaction(&evm_config, &config, &block, &tx, &state, &state)
+        };
+
+        let divergence = analyze(16).unwrap();
+        assert!(divergence.divergence_types.contains(&DivergenceType::Created));
+        assert!(!divergence.divergence_types.contains(&DivergenceType::Status));
+        let created_code = divergence.created_code.unwrap();
+        assert_eq!(created_code.address, tx.sender.create(0));
+        assert_eq!(created_code.normal_code_hash, keccak256([0x01]));
+        assert_eq!(created_code.experimental_code_hash, keccak256([0x00]));
+
+        // At 2x the guard still passes and the same code is deployed
+        assert!(analyze(2).is_none_or(|divergence| divergence.created_code.is_none() &&
+            !divergence.divergence_types.contains(&DivergenceType::Created)));
+    }
+
+    #[test]
+    fn test_contract_creation_divergence() {
+        // Init code that stores 1 in a fresh slot: about 46,900 gas is left after intrinsic gas,
+        // enough for the 22,100 gas SSTORE at 1x and 2x but not at 4x
+        let init_code = [0x60, 0x01, 0x60, 0x00, 0x55, 0x00]; // PUSH1 1 PUSH1 0 SSTORE STOP
+        let config = ResearchConfig { gas_multiplier: 4, ..Default::default() };
+        let (evm_config, block, tx) = tx_context(&config, TxKind::Create, &init_code);
+        assert_eq!((tx.to, tx.selector), (None, None));
+        let state = call_state(tx.sender, vec![0x00], &[]);
+
+        // The creation is analyzed and the divergence is attributed to the created address
+        let divergence =
+            analyze_transaction(&evm_config, &config, &block, &tx, &state, &state).unwrap();
+        assert!(divergence.divergence_types.contains(&DivergenceType::Status));
+        let created = tx.sender.create(0);
+        assert_eq!(divergence.divergence_location.as_ref().unwrap().contract, created);
+        assert_eq!(divergence.oog_info.as_ref().unwrap().contract, created);
+        assert_eq!(divergence.contract(), Some(created));
+    }
+
+    #[test]
+    fn test_schedule_comparison() {
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let (evm_config, block, tx) = call_context(&config, &[]);
+        let state = call_state(tx.sender, gas_guard_code(0x10), &[]);
+        let analyze = |multiplier_b: u64| {
+            let schedule_b = Some(GasSchedule::new(multiplier_b));
+            let config = ResearchConfig { schedule_b, ..config.clone() };
+            analyze_schedules(&evm_config, &config, &block, &tx, &state, &state)
+        };
+
+        // The guard passes under A at 2x but reverts under B at 16x, so the schedules diverge
+        // from each other as well as from normal execution
+        let divergences = analyze(16);
+        let comparisons: Vec<_> = divergences.iter().map(|d| d.comparison).collect();
+        assert_eq!(comparisons, [Comparison::NormalA, Comparison::NormalB, Comparison::AB]);
+        let a_vs_b = &divergences[2];
+        assert!(a_vs_b.divergence_types.contains(&DivergenceType::Status));
+        assert_ne!(a_vs_b.uid(), divergences[0].uid());
+
+        // Identical schedules only diverge from normal execution
+        let comparisons: Vec<_> = analyze(2).iter().map(|d| d.comparison).collect();
+        assert_eq!(comparisons, [Comparison::NormalA, Comparison::NormalB]);
+    }
+
+    #[test]
+    fn test_confirm_failures() {
+        let config = ResearchConfig {
+            confirm_failures: true,
+            sequential_state: true,
+            gas_multiplier: 16,
+            ..Default::default()
+        };
+        let (evm_config, block, tx) = call_context(&config, &[]);
+        let guard = call_state(tx.sender, gas_guard_code(0x10), &[]);
+        let analyze = |config: &ResearchConfig, normal: &CacheDB<EmptyDB>, experimental| {
+            analyze_transaction(&evm_config, config, &block, &tx, normal, experimental).unwrap()
+        };
+
+        // The guard reverts at 16x but passes when replayed at normal gas costs, so the failure
+        // is confirmed as gas-caused even without detailed tracing
+        let divergence = analyze(&config, &guard, &guard);
+        assert!(divergence.divergence_types.contains(&DivergenceType::Status));
+        assert_eq!(divergence.cause, Some(DivergenceCause::GasDirect));
+
+        // An earlier experimental execution left a contract that reverts unconditionally, so the
+        // replay fails too and the failure is not down to the multiplier
+        let normal = call_state(tx.sender, vec![0x00], &[]);
+        let experimental = call_state(tx.sender, vec![0x60, 0x00, 0x80, 0xfd], &[]);
+        let divergence = analyze(&config, &normal, &experimental);
+        assert!(divergence.divergence_types.contains(&DivergenceType::Status));
+        assert_eq!(divergence.cause, Some(DivergenceCause::NonGas));
+
+        // Without confirmation, or without sequential state, the failure is left unattributed
+        let unconfirmed = ResearchConfig { confirm_failures: false, ..config.clone() };
+        assert_eq!(analyze(&unconfirmed, &normal, &experimental).cause, None);
+        let isolated = ResearchConfig { sequential_state: false, ..config };
+        assert_eq!(analyze(&isolated, &guard, &guard).cause, None);
+    }
+
+    #[test]
+    fn test_deep_trace_selectors() {
+        // Under standard tracing, only the call with an interesting selector is traced in detail
+        let interesting = [0xa9, 0x05, 0x9c, 0xbb];
+        let config = ResearchConfig::builder()
+            .with_deep_trace_selectors([interesting])
+            .with_sequential_state(true)
+            .build()
+            .unwrap();
+        assert_eq!(config.trace_detail, TraceDetail::Standard);
+
+        let sweep =
+            |input: &[u8]| sweep_call(&config, gas_guard_code(0x10), &[], input, &[16]).unwrap();
+
+        let divergence = sweep(&interesting);
+        assert!(divergence.call_trees.is_some());
+        assert!(divergence.event_logs.is_some());
+        assert_eq!(divergence.cause, Some(DivergenceCause::GasDirect));
+
+        let divergence = sweep(&[0x12, 0x34, 0x56, 0x78, 0x00]);
+        assert!(divergence.call_trees.is_none());
+        assert!(divergence.event_logs.is_none());
+        assert_eq!(divergence.cause, None);
+
+        // Calldata shorter than a selector never matches
+        assert!(sweep(&interesting[..3]).call_trees.is_none());
+    }
+
+    #[test]
+    fn test_economic_gas_threshold() {
+        // The block's base fee is 1 gwei
+        let threshold = |gwei: u64| ResearchConfig {
+            economic_gas_threshold: Some(gwei * 1_000_000_000),
+            ..Default::default()
+        };
+
+        // At 2x the guard still passes, so only the gas pattern diverges: recorded when the
+        // base fee is above the threshold, suppressed when it is not
+        let divergence = sweep_gas_guard(&threshold(0), 0x10, &[2]).unwrap();
+        assert_eq!(divergence.divergence_types, vec![DivergenceType::GasPattern]);
+        assert!(sweep_gas_guard(&threshold(1), 0x10, &[2]).is_none());
+        assert!(sweep_gas_guard(&threshold(100), 0x10, &[2]).is_none());
+
+        // Behavioral divergences are recorded regardless
+        let divergence = sweep_gas_guard(&threshold(100), 0x10, &[16]).unwrap();
+        assert!(divergence.divergence_types.contains(&DivergenceType::Status));
+    }
+
+    #[test]
+    fn test_only_types() {
+        let config = ResearchConfig {
+            only_types: Some(HashSet::from([DivergenceType::Status])),
+            ..Default::default()
+        };
+
+        // At 2x only the gas pattern diverges, which is not recorded
+        assert!(sweep_gas_guard(&ResearchConfig::default(), 0x10, &[2]).is_some());
+        assert!(sweep_gas_guard(&config, 0x10, &[2]).is_none());
+
+        // At 16x the guard reverts, so the divergence is recorded
+        let divergence = sweep_gas_guard(&config, 0x10, &[16]).unwrap();
+        assert!(divergence.divergence_types.contains(&DivergenceType::Status));
+    }
+
+    #[test]
+    fn test_memory_peak_divergence() {
+        // Only the branch taken while the guard passes writes at offset 1024, expanding memory
+        // to 33 words
+        let mut code = gas_guard_code(0x10);
+        code.pop();
+        code.extend_from_slice(&[0x60, 0x01, 0x61, 0x04, 0x00, 0x52, 0x00]); // MSTORE STOP
+
+        // At 2x both executions take that branch and reach the same peak
+        let divergence = sweep_call(&ResearchConfig::default(), code.clone(), &[], &[], &[2]);
+        assert!(!divergence.unwrap().divergence_types.contains(&DivergenceType::Memory));
+
+        // At 16x the experimental execution reverts before touching memory
+        let divergence =
+            sweep_call(&ResearchConfig::default(), code.clone(), &[], &[], &[16]).unwrap();
+        assert!(divergence.divergence_types.contains(&DivergenceType::Memory));
+        assert_eq!(divergence.normal_ops.memory_words_allocated, 33);
+        assert_eq!(divergence.experimental_ops.memory_words_allocated, 0);
+
+        // Unless the difference is within the tolerance
+        let config = ResearchConfig { memory_tolerance_words: 33, ..Default::default() };
+        let divergence = sweep_call(&config, code, &[], &[], &[16]).unwrap();
+        assert!(!divergence.divergence_types.contains(&DivergenceType::Memory));
+    }
+
+    #[test]
+    fn test_gas_charged_after_refund() {
+        // PUSH1 0 PUSH1 0 SSTORE STOP: clearing a set slot refunds 4,800 gas
+        let code = vec![0x60, 0x00, 0x60, 0x00, 0x55, 0x00];
+        let storage = [(U256::ZERO, U256::from(1))];
+        let config = ResearchConfig { refund_multiplier: 2.0, ..Default::default() };
+        let divergence = sweep_call(&config, code, &storage, &[], &[2]).unwrap();
+        let gas = divergence.gas_analysis;
+
+        // Normal: 21,000 intrinsic + 6 for the pushes + 5,000 for the cold SSTORE, less the
+        // refund, which is below the cap of a fifth of the gross gas
+        assert_eq!(gas.normal_gas_used, 26_006 - 4_800);
+        assert_eq!(gas.normal_gas_charged, 26_006 - 4_800);
+
+        // Experimental: execution costs double, and the doubled refund of 9,600 is capped
+        let gross = 21_000 + 2 * 5_006;
+        assert_eq!(gas.experimental_gas_used, gross - 4_800);
+        assert_eq!(gas.experimental_gas_charged, gross - gross / 5);
+        assert!(gas.gas_charged_ratio() > 1.0);
+    }
+
+    #[test]
+    fn test_calldata_gas_multiplier() {
+        // Clearing a set slot as above, called with 600 zero and 400 non-zero bytes of calldata
+        let code = vec![0x60, 0x00, 0x60, 0x00, 0x55, 0x00];
+        let storage = [(U256::ZERO, U256::from(1))];
+        let mut input = vec![0x00; 600];
+        input.extend([0xff; 400]);
+        let analyze = |calldata_gas_multiplier| {
+            let config = ResearchConfig { calldata_gas_multiplier, ..Default::default() };
+            sweep_call(&config, code.clone(), &storage, &input, &[2]).unwrap()
+        };
+
+        let divergence = analyze(1);
+        let calldata = divergence.calldata.unwrap();
+        assert_eq!(calldata, CalldataStats { len: 1000, zero_bytes: 600, nonzero_bytes: 400 });
+        assert_eq!(calldata.intrinsic_gas(1), 600 * 4 + 400 * 16);
+
+        // Only the gas charged for the calldata scales, execution is unaffected
+        let scaled = analyze(3);
+        let (gas, scaled_gas) = (divergence.gas_analysis, scaled.gas_analysis);
+        assert_eq!(scaled_gas.experimental_gas_used, gas.experimental_gas_used);
+        assert_eq!(
+            scaled_gas.experimental_gas_charged,
+            gas.experimental_gas_charged + 2 * calldata.intrinsic_gas(1)
+        );
+        assert_eq!(scaled_gas.normal_gas_charged, gas.normal_gas_charged);
+    }
+
+    #[test]
+    fn test_access_list_divergence() {
+        // The gas guard above, reading slot 1 if `GAS < 78,000` and slot 2 otherwise
+        let mut code = vec![0x5b; 100];
+        code.extend_from_slice(&[
+            0x5a, // GAS
+            0x62, 0x01, 0x30, 0xb0, // PUSH3 78000
+            0x10, 0x60, 0x71, // LT PUSH1 113
+            0x57, // JUMPI
+            0x60, 0x02, 0x54, 0x00, // PUSH1 2 SLOAD STOP
+            0x5b, 0x60, 0x01, 0x54, 0x00, // JUMPDEST PUSH1 1 SLOAD STOP
+        ]);
+        let analyze = |compare_access_lists| {
+            let config = ResearchConfig {
+                compare_access_lists,
+                trace_detail: TraceDetail::Detailed,
+                ..Default::default()
+            };
+            sweep_call(&config, code.clone(), &[], &[], &[16]).unwrap()
+        };
+
+        // Normal execution reads slot 2, experimental execution at 16x slot 1
+        let divergence = analyze(true);
+        assert!(divergence.divergence_types.contains(&DivergenceType::AccessList));
+        let diff = divergence.access_list_diff.unwrap();
+        let slot = |slot: u64| BTreeSet::from([B256::from(U256::from(slot))]);
+        assert_eq!(diff.normal_only, AccessSet::from([(CONTRACT, slot(2))]));
+        assert_eq!(diff.experimental_only, AccessSet::from([(CONTRACT, slot(1))]));
+
+        // Access lists are only compared when enabled
+        let divergence = analyze(false);
+        assert!(!divergence.divergence_types.contains(&DivergenceType::AccessList));
+        assert!(divergence.access_list_diff.is_none());
+    }
+
+    #[test]
+    fn test_experimental_panic_skips_transaction() {
+        #[derive(Debug)]
+        struct PanickingClassifier;
+
+        impl OogClassifier for PanickingClassifier {
+            fn classify(&self, _ctx: &OogContext<'_>) -> OogPattern {
+                panic!("classifier bug")
+            }
+        }
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let config = ResearchConfig::builder().with_oog_classifier(PanickingClassifier).build();
+
+        ::metrics::with_local_recorder(&recorder, || {
+            // 100 JUMPDESTs at 1000x run out of gas, which panics in the classifier
+            assert!(sweep_gas_guard(&config.unwrap(), 0x10, &[1000]).is_none());
+            // Analysis carries on with the next transaction
+            assert!(sweep_gas_guard(&ResearchConfig::default(), 0x10, &[16]).is_some());
+        });
+
+        assert_eq!(counter_value(&snapshotter, "reth_research_experimental_panic_total", None), 1);
+    }
+
+    #[test]
+    fn test_insufficient_funds_not_a_divergence() {
+        let config = ResearchConfig::default();
+        let (evm_config, block, tx) = call_context(&config, &[]);
+        let state = call_state(tx.sender, vec![0x00], &[]);
+
+        // The sender cannot pay for the gas limit in the experimental state only
+        let mut poor = state.clone();
+        poor.insert_account_info(
+            tx.sender,
+            AccountInfo { balance: U256::from(1), ..Default::default() },
+        );
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        ::metrics::with_local_recorder(&recorder, || {
+            assert!(analyze_transaction(&evm_config, &config, &block, &tx, &state, &poor).is_none());
+        });
+
+        assert_eq!(counter_value(&snapshotter, "reth_research_invalid_tx_total", None), 1);
+    }
+
+    #[test]
+    fn test_quiet_mode_logging() {
+        // The gas pattern divergence at 2x is logged individually, the status flip at 16x is
+        // critical
+        let levels = research_event_levels(|| {
+            sweep_gas_guard(&ResearchConfig::default(), 0x10, &[2, 16]);
+        });
+        assert!(levels.contains(&Level::INFO));
+        assert!(levels.contains(&Level::WARN));
+
+        let config = ResearchConfig { quiet: true, ..Default::default() };
+        let levels = research_event_levels(|| {
+            sweep_gas_guard(&config, 0x10, &[2, 16]);
+        });
+        assert!(!levels.contains(&Level::INFO));
+        assert!(levels.contains(&Level::TRACE));
+        assert!(levels.contains(&Level::WARN));
+    }
+}
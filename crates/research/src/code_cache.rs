@@ -0,0 +1,213 @@
+//! Contract bytecode shared across transactions and blocks.
+//!
+//! Every analyzed transaction executes on fresh [`CacheDB`](revm::database::CacheDB)s, so
+//! without sharing, the code of a hot contract is fetched from the state provider and analyzed
+//! again for every transaction calling it. Code never changes for a given hash, so a single
+//! [`CodeCache`] can serve it for the whole run; [`CachedCodeDatabase`] layers it under a
+//! database. Accounts and storage are not cached, as they change between blocks.
+
+use alloy_primitives::{Address, B256, U256};
+use revm::{
+    database::DatabaseRef,
+    state::{AccountInfo, Bytecode},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+/// Bounded cache of contract bytecode by code hash, evicting the oldest code first.
+#[derive(Debug)]
+pub struct CodeCache {
+    /// Maximum number of codes kept
+    capacity: usize,
+
+    /// Cached codes and their insertion order
+    inner: Mutex<CodeCacheInner>,
+}
+
+#[derive(Debug, Default)]
+struct CodeCacheInner {
+    codes: HashMap<B256, Bytecode>,
+    order: VecDeque<B256>,
+}
+
+impl CodeCache {
+    /// Create a cache keeping up to `capacity` codes. A capacity of zero caches nothing.
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, inner: Mutex::default() }
+    }
+
+    /// Number of codes cached.
+    pub fn len(&self) -> usize {
+        self.inner.lock().expect("code cache poisoned").codes.len()
+    }
+
+    /// Whether no code is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The code with `code_hash`, fetched with `fetch` and cached if not cached yet.
+    fn get_or_fetch<E>(
+        &self,
+        code_hash: B256,
+        fetch: impl FnOnce() -> Result<Bytecode, E>,
+    ) -> Result<Bytecode, E> {
+        if let Some(code) = self.inner.lock().expect("code cache poisoned").codes.get(&code_hash) {
+            return Ok(code.clone())
+        }
+
+        // Fetched without holding the lock, another thread may cache the same code meanwhile
+        let code = fetch()?;
+        if self.capacity > 0 {
+            let mut inner = self.inner.lock().expect("code cache poisoned");
+            if inner.codes.insert(code_hash, code.clone()).is_none() {
+                inner.order.push_back(code_hash);
+                if inner.order.len() > self.capacity {
+                    let oldest = inner.order.pop_front().expect("order is not empty");
+                    inner.codes.remove(&oldest);
+                }
+            }
+        }
+        Ok(code)
+    }
+}
+
+/// Database serving contract bytecode from a shared [`CodeCache`] before `DB`.
+#[derive(Debug, Clone)]
+pub struct CachedCodeDatabase<DB> {
+    db: DB,
+    cache: Arc<CodeCache>,
+}
+
+impl<DB> CachedCodeDatabase<DB> {
+    /// Serve the code of `db` from `cache`.
+    pub const fn new(db: DB, cache: Arc<CodeCache>) -> Self {
+        Self { db, cache }
+    }
+}
+
+impl<DB: DatabaseRef> DatabaseRef for CachedCodeDatabase<DB> {
+    type Error = DB::Error;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        self.db.basic_ref(address)
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.cache.get_or_fetch(code_hash, || self.db.code_by_hash_ref(code_hash))
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        self.db.storage_ref(address, index)
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        self.db.block_hash_ref(number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::analyze_transaction, config::ResearchConfig, test_utils::tx_context};
+    use alloy_primitives::{Bytes, TxKind};
+    use revm::database::{CacheDB, EmptyDB};
+    use std::{
+        convert::Infallible,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    const CONTRACT: Address = Address::repeat_byte(0xc0);
+
+    /// Provider counting how often code is fetched from it.
+    #[derive(Debug)]
+    struct CountingDb {
+        state: CacheDB<EmptyDB>,
+        code_fetches: AtomicUsize,
+    }
+
+    impl DatabaseRef for CountingDb {
+        type Error = Infallible;
+
+        fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+            // Like a state provider, accounts come without their code
+            let info = self.state.basic_ref(address)?;
+            Ok(info.map(|info| AccountInfo { code: None, ..info }))
+        }
+
+        fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+            self.code_fetches.fetch_add(1, Ordering::Relaxed);
+            self.state.code_by_hash_ref(code_hash)
+        }
+
+        fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+            self.state.storage_ref(address, index)
+        }
+
+        fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+            self.state.block_hash_ref(number)
+        }
+    }
+
+    #[test]
+    fn test_repeated_calls_hit_shared_cache() {
+        let config = ResearchConfig::default();
+        let (evm_config, block, tx) = tx_context(&config, TxKind::Call(CONTRACT), &[]);
+
+        let mut state = CacheDB::new(EmptyDB::default());
+        state.insert_account_info(
+            tx.sender,
+            AccountInfo { balance: U256::from(10u128.pow(18)), ..Default::default() },
+        );
+        let code = Bytecode::new_raw(Bytes::from_static(&[0x5b, 0x00])); // JUMPDEST STOP
+        state.insert_account_info(
+            CONTRACT,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+        );
+        let provider = CountingDb { state, code_fetches: AtomicUsize::new(0) };
+
+        // Each analysis executes twice on fresh databases, as for every transaction in the ExEx
+        let cache = Arc::new(CodeCache::new(16));
+        for _ in 0..3 {
+            let db = CachedCodeDatabase::new(&provider, cache.clone());
+            analyze_transaction(&evm_config, &config, &block, &tx, db.clone(), db);
+        }
+
+        // Only the first execution fetched the code from the provider
+        assert_eq!(provider.code_fetches.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_cache_is_bounded() {
+        let cache = CodeCache::new(2);
+        let fetch = |byte| {
+            let code = Bytecode::new_raw(Bytes::from(vec![byte]));
+            cache.get_or_fetch(B256::repeat_byte(byte), || Ok::<_, Infallible>(code))
+        };
+        for byte in 0..3 {
+            fetch(byte).unwrap();
+        }
+        assert_eq!(cache.len(), 2);
+
+        // The oldest code was evicted
+        let fetched = AtomicUsize::new(0);
+        let refetch = |byte| {
+            cache.get_or_fetch(B256::repeat_byte(byte), || {
+                fetched.fetch_add(1, Ordering::Relaxed);
+                Ok::<_, Infallible>(Bytecode::default())
+            })
+        };
+        refetch(2).unwrap();
+        assert_eq!(fetched.load(Ordering::Relaxed), 0);
+        refetch(0).unwrap();
+        assert_eq!(fetched.load(Ordering::Relaxed), 1);
+
+        // Nothing is kept without capacity
+        let disabled = CodeCache::new(0);
+        disabled.get_or_fetch(B256::ZERO, || Ok::<_, Infallible>(Bytecode::default())).unwrap();
+        assert!(disabled.is_empty());
+    }
+}
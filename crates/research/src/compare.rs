@@ -0,0 +1,884 @@
+//! Helpers for comparing normal and experimental execution results.
+
+use crate::{
+    divergence::{
+        AccessListDiff, AccessSet, Create2Deployment, CreatedCode, DivergenceCause,
+        DivergenceType, OperationCounts,
+    },
+    metrics, system,
+};
+use alloy_primitives::{keccak256, Address, Keccak256, Log, B256, U256};
+use reth_evm::{EvmError, InvalidTxError};
+use revm::{
+    context_interface::result::{ExecutionResult, InvalidTransaction, Output, ResultAndState},
+    state::{AccountInfo, EvmState},
+};
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Debug,
+};
+use tracing::debug;
+
+/// Check that differing operation counts are explained by a detected divergence.
+///
+/// If the two executions recorded different operation counts but none of the comparison
+/// checks fired and no OOG occurred, the detector likely missed something. In that case the
+/// `reth_research_unexplained_opcount_diff_total` counter is incremented and `true` is
+/// returned so the caller can log the offending transaction.
+pub fn check_op_count_consistency(
+    normal_ops: &OperationCounts,
+    experimental_ops: &OperationCounts,
+    divergence_types: &[DivergenceType],
+    oog_occurred: bool,
+) -> bool {
+    if normal_ops == experimental_ops || !divergence_types.is_empty() || oog_occurred {
+        return false;
+    }
+
+    metrics::record_unexplained_opcount_diff();
+    true
+}
+
+/// Whether peak memory differs between the two executions by more than `tolerance_words`.
+///
+/// An execution only reaches its peak along the branches it takes, so a difference points to
+/// diverging control flow even when nothing else observable changed.
+pub const fn memory_peaks_diverge(
+    normal_ops: &OperationCounts,
+    experimental_ops: &OperationCounts,
+    tolerance_words: u64,
+) -> bool {
+    normal_ops.memory_words_allocated.abs_diff(experimental_ops.memory_words_allocated) >
+        tolerance_words
+}
+
+/// Compare CREATE2 deployments between the two executions.
+///
+/// CREATE2 addresses are deterministic from caller, salt and init code, so this returns `true`
+/// if any deployment landed at an address other than the predicted one, if a deployment only
+/// happened in one execution (the init code or salt changed), or if the same deployment succeeded
+/// in one execution but not the other.
+pub fn create2_deployments_diverge(
+    normal: &[Create2Deployment],
+    experimental: &[Create2Deployment],
+) -> bool {
+    if normal.iter().chain(experimental).any(Create2Deployment::address_mismatch) {
+        return true
+    }
+
+    if normal.len() != experimental.len() {
+        return true
+    }
+
+    normal.iter().any(|n| {
+        !experimental
+            .iter()
+            .any(|e| e.predicted_address == n.predicted_address && e.succeeded() == n.succeeded())
+    })
+}
+
+/// Whether the two executions emitted different logs, compared in order by address, topics and
+/// data.
+pub fn logs_diverge(normal: &[Log], experimental: &[Log]) -> bool {
+    normal.len() != experimental.len() ||
+        normal.iter().zip(experimental).any(|(normal_log, experimental_log)| {
+            normal_log.address != experimental_log.address ||
+                normal_log.data.topics() != experimental_log.data.topics() ||
+                normal_log.data.data != experimental_log.data.data
+        })
+}
+
+/// The runtime code hashes of the contract created by both executions, if they differ.
+///
+/// Only creations that succeeded in both executions are compared, whether the creation succeeded
+/// at all is left to the status comparison.
+pub fn created_code_divergence<H>(
+    normal: &ExecutionResult<H>,
+    experimental: &ExecutionResult<H>,
+) -> Option<CreatedCode> {
+    let created = |result: &ExecutionResult<H>| match result {
+        ExecutionResult::Success { output: Output::Create(code, Some(address)), .. } => {
+            Some((*address, keccak256(code)))
+        }
+        _ => None,
+    };
+    let ((address, normal_code_hash), (_, experimental_code_hash)) =
+        created(normal).zip(created(experimental))?;
+    (normal_code_hash != experimental_code_hash).then_some(CreatedCode {
+        address,
+        normal_code_hash,
+        experimental_code_hash,
+    })
+}
+
+/// Gas fees moved by a transaction in one execution.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GasFees {
+    /// Total fee deducted from the sender
+    pub sender_fee: U256,
+
+    /// Priority fee credited to the block beneficiary
+    pub beneficiary_reward: U256,
+}
+
+impl GasFees {
+    /// Fees for `gas_used` (after refunds) at the given effective gas price and block base fee.
+    pub fn new(gas_used: u64, effective_gas_price: u128, base_fee: u64) -> Self {
+        let gas_used = U256::from(gas_used);
+        let priority_fee = effective_gas_price.saturating_sub(base_fee as u128);
+        Self {
+            sender_fee: gas_used * U256::from(effective_gas_price),
+            beneficiary_reward: gas_used * U256::from(priority_fee),
+        }
+    }
+}
+
+/// The post-transaction states of both executions and what is needed to compare them.
+#[derive(Debug, Clone, Copy)]
+pub struct StateComparison<'a> {
+    /// State after normal execution
+    pub normal: &'a EvmState,
+
+    /// State after experimental execution
+    pub experimental: &'a EvmState,
+
+    /// Transaction sender, who paid the fees
+    pub sender: Address,
+
+    /// Block beneficiary, who received the priority fees
+    pub beneficiary: Address,
+
+    /// Fees moved by normal execution
+    pub normal_fees: GasFees,
+
+    /// Fees moved by experimental execution
+    pub experimental_fees: GasFees,
+
+    /// Accounts to leave out of the comparison
+    pub ignore_addresses: &'a HashSet<Address>,
+
+    /// Compare the beneficiary's balance beyond the fees, rather than leaving it out
+    pub compare_beneficiary_balance: bool,
+
+    /// Compare the [system addresses](system::SYSTEM_ADDRESSES) too
+    pub include_system: bool,
+}
+
+impl StateComparison<'_> {
+    /// Find an account outside [`Self::ignore_addresses`], and outside the system addresses unless
+    /// [`Self::include_system`] is set, whose state differs between the two executions.
+    ///
+    /// Balance differences of the sender and beneficiary are ignored when they are exactly
+    /// explained by the fees charged in each execution, and the beneficiary's balance is not
+    /// compared at all unless [`Self::compare_beneficiary_balance`] is set. Accounts touched by
+    /// only one execution count as differing.
+    pub fn divergent_account(&self) -> Option<Address> {
+        let (sender, beneficiary) = (self.sender, self.beneficiary);
+        let compared = |address: &&Address| {
+            !self.ignore_addresses.contains(*address) &&
+                (self.include_system || !system::is_system_address(**address))
+        };
+
+        let only_experimental = self
+            .experimental
+            .keys()
+            .filter(compared)
+            .find(|address| !self.normal.contains_key(*address));
+        if let Some(address) = only_experimental {
+            return Some(*address)
+        }
+
+        self.normal
+            .iter()
+            .filter(|(address, _)| compared(address))
+            .find(|(address, normal_account)| {
+                let Some(experimental_account) = self.experimental.get(*address) else {
+                    return true
+                };
+                if normal_account.storage != experimental_account.storage {
+                    return true
+                }
+                if normal_account.info == experimental_account.info {
+                    return false
+                }
+                if **address == beneficiary && !self.compare_beneficiary_balance {
+                    let without_balance =
+                        |info: &AccountInfo| AccountInfo { balance: U256::ZERO, ..info.clone() };
+                    return without_balance(&normal_account.info) !=
+                        without_balance(&experimental_account.info)
+                }
+                if **address != sender && **address != beneficiary {
+                    return true
+                }
+
+                // Undo the fee payments so only execution-driven balance changes remain
+                let pre_fee_info = |info: &AccountInfo, fees: GasFees| AccountInfo {
+                    balance: pre_fee_balance(**address, info.balance, sender, beneficiary, fees),
+                    ..info.clone()
+                };
+                pre_fee_info(&normal_account.info, self.normal_fees) !=
+                    pre_fee_info(&experimental_account.info, self.experimental_fees)
+            })
+            .map(|(address, _)| *address)
+    }
+}
+
+/// Decides which divergence types the post-transaction states of the two executions exhibit.
+///
+/// [`DefaultStateComparator`] is used unless another comparator is set with
+/// [`ResearchConfigBuilder::with_state_comparator`](crate::ResearchConfigBuilder::with_state_comparator).
+pub trait StateComparator: Debug + Send + Sync {
+    /// Compare the two states, returning the detected divergence types (empty if they match).
+    fn compare(&self, states: &StateComparison<'_>) -> Vec<DivergenceType>;
+}
+
+/// Reports [`DivergenceType::StateRoot`] if any account outside the ignore list differs beyond
+/// the fees, see [`StateComparison::divergent_account`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultStateComparator;
+
+impl StateComparator for DefaultStateComparator {
+    fn compare(&self, states: &StateComparison<'_>) -> Vec<DivergenceType> {
+        match states.divergent_account() {
+            Some(account) => {
+                debug!(target: "reth::research", %account, "First divergent account");
+                vec![DivergenceType::StateRoot]
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Accounts and storage slots an execution accessed, from its post-transaction state.
+///
+/// The state holds every account the execution loaded and every slot it read or wrote in them,
+/// so this is the access list generated from the execution.
+pub fn access_list(state: &EvmState) -> AccessSet {
+    state
+        .iter()
+        .map(|(address, account)| {
+            (*address, account.storage.keys().map(|slot| B256::from(*slot)).collect())
+        })
+        .collect()
+}
+
+/// Compare the access lists generated from the two executions, returning the accesses only one
+/// of them made if they differ.
+pub fn access_list_divergence(
+    normal: &EvmState,
+    experimental: &EvmState,
+) -> Option<AccessListDiff> {
+    let normal = access_list(normal);
+    let experimental = access_list(experimental);
+    let diff = AccessListDiff {
+        normal_only: access_set_difference(&normal, &experimental),
+        experimental_only: access_set_difference(&experimental, &normal),
+    };
+    (!diff.is_empty()).then_some(diff)
+}
+
+/// Accesses in `a` that are not in `b`.
+fn access_set_difference(a: &AccessSet, b: &AccessSet) -> AccessSet {
+    a.iter()
+        .filter_map(|(address, slots)| match b.get(address) {
+            None => Some((*address, slots.clone())),
+            Some(other) => {
+                let only: BTreeSet<_> = slots.difference(other).copied().collect();
+                (!only.is_empty()).then_some((*address, only))
+            }
+        })
+        .collect()
+}
+
+/// Attribute a divergence from a replay of the transaction at normal gas costs on the state the
+/// experimental execution started from.
+///
+/// A replay with the same status, gas, output, logs and state as normal execution rules the
+/// experimental state out, leaving the gas costs as the direct cause. `None` is a replay that
+/// could not be run.
+pub fn divergence_cause<H>(
+    normal: &ResultAndState<H>,
+    replay: Option<&ResultAndState<H>>,
+) -> DivergenceCause {
+    let Some(replay) = replay else { return DivergenceCause::Unknown };
+
+    let same_outcome = normal.result.is_success() == replay.result.is_success() &&
+        normal.result.gas_used() == replay.result.gas_used() &&
+        normal.result.output() == replay.result.output() &&
+        normal.result.logs() == replay.result.logs() &&
+        normal.state == replay.state;
+    if same_outcome {
+        DivergenceCause::GasDirect
+    } else {
+        DivergenceCause::StateCascade
+    }
+}
+
+/// Digest of a post-transaction state with the fee payments undone.
+///
+/// The digest covers every account's balance, nonce, code hash and storage values, and does not
+/// depend on map iteration order. Two executions with equal digests left the same state apart
+/// from the fees, so the per-account comparison in [`StateComparison::divergent_account`] can
+/// be skipped.
+pub fn state_digest(
+    state: &EvmState,
+    sender: Address,
+    beneficiary: Address,
+    fees: GasFees,
+) -> B256 {
+    let mut addresses: Vec<_> = state.keys().collect();
+    addresses.sort_unstable();
+
+    let mut hasher = Keccak256::new();
+    for address in addresses {
+        let account = &state[address];
+        let balance = pre_fee_balance(*address, account.info.balance, sender, beneficiary, fees);
+        hasher.update(address);
+        hasher.update(balance.to_be_bytes::<32>());
+        hasher.update(account.info.nonce.to_be_bytes());
+        hasher.update(account.info.code_hash);
+
+        let mut slots: Vec<_> = account.storage.iter().collect();
+        slots.sort_unstable_by_key(|&(slot, _)| *slot);
+        hasher.update((slots.len() as u64).to_be_bytes());
+        for (slot, value) in slots {
+            hasher.update(slot.to_be_bytes::<32>());
+            hasher.update(value.original_value.to_be_bytes::<32>());
+            hasher.update(value.present_value.to_be_bytes::<32>());
+        }
+    }
+    hasher.finalize()
+}
+
+/// Balance of `address` before the fee payments of an execution.
+fn pre_fee_balance(
+    address: Address,
+    balance: U256,
+    sender: Address,
+    beneficiary: Address,
+    fees: GasFees,
+) -> U256 {
+    let mut balance = balance;
+    if address == sender {
+        balance = balance.wrapping_add(fees.sender_fee);
+    }
+    if address == beneficiary {
+        balance = balance.wrapping_sub(fees.beneficiary_reward);
+    }
+    balance
+}
+
+/// Whether a transaction was rejected because it cannot afford the block's base fee.
+///
+/// Used to attribute experimental failures to an adjusted base fee rather than to execution.
+pub const fn is_base_fee_rejection(error: &InvalidTransaction) -> bool {
+    matches!(
+        error,
+        InvalidTransaction::GasPriceLessThanBasefee |
+            InvalidTransaction::LackOfFundForMaxFee { .. }
+    )
+}
+
+/// Kind of error that failed an experimental execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExperimentalError {
+    /// Rejected for not affording the adjusted base fee, an economic divergence
+    BaseFee,
+
+    /// Rejected as an invalid transaction (e.g. insufficient funds or a nonce mismatch), which
+    /// is not a change in the contract's behavior
+    InvalidTransaction,
+
+    /// Any other error, handled according to the
+    /// [`UnknownErrorPolicy`](crate::config::UnknownErrorPolicy)
+    Unknown,
+}
+
+/// Classify the error that failed an experimental execution, with `base_fee_adjusted` set if
+/// the experimental base fee differs from the block's.
+pub fn classify_experimental_error<E: EvmError>(
+    error: &E,
+    base_fee_adjusted: bool,
+) -> ExperimentalError {
+    match error.as_invalid_tx_err() {
+        Some(err)
+            if base_fee_adjusted && err.as_invalid_tx_err().is_some_and(is_base_fee_rejection) =>
+        {
+            ExperimentalError::BaseFee
+        }
+        Some(_) => ExperimentalError::InvalidTransaction,
+        None => ExperimentalError::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::ResearchConfig,
+        inspector::GasResearchInspector,
+        test_utils::{counter_value, TestEnv, CALLER, CONTRACT},
+    };
+    use alloy_primitives::B256;
+    use metrics_util::debugging::DebuggingRecorder;
+    use revm::{context_interface::result::EVMError, inspector::NoOpInspector, state::Account};
+
+    #[test]
+    fn test_unexplained_opcount_diff_fires() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        let normal = OperationCounts { total_ops: 100, ..Default::default() };
+        let experimental = OperationCounts { total_ops: 90, ..Default::default() };
+
+        ::metrics::with_local_recorder(&recorder, || {
+            assert!(check_op_count_consistency(&normal, &experimental, &[], false));
+        });
+
+        assert_eq!(
+            counter_value(&snapshotter, "reth_research_unexplained_opcount_diff_total", None),
+            1
+        );
+    }
+
+    #[test]
+    fn test_explained_opcount_diff_does_not_fire() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        let normal = OperationCounts { total_ops: 100, ..Default::default() };
+        let experimental = OperationCounts { total_ops: 90, ..Default::default() };
+
+        ::metrics::with_local_recorder(&recorder, || {
+            // Explained by a flagged divergence
+            assert!(!check_op_count_consistency(
+                &normal,
+                &experimental,
+                &[DivergenceType::ExecutionTrace],
+                false
+            ));
+            // Explained by OOG
+            assert!(!check_op_count_consistency(&normal, &experimental, &[], true));
+            // Identical counts
+            assert!(!check_op_count_consistency(&normal, &normal, &[], false));
+        });
+
+        assert_eq!(
+            counter_value(&snapshotter, "reth_research_unexplained_opcount_diff_total", None),
+            0
+        );
+    }
+
+    #[test]
+    fn test_memory_peaks_diverge() {
+        let normal = OperationCounts { memory_words_allocated: 40, ..Default::default() };
+        let experimental = OperationCounts { memory_words_allocated: 8, ..Default::default() };
+
+        assert!(memory_peaks_diverge(&normal, &experimental, 0));
+        assert!(memory_peaks_diverge(&experimental, &normal, 31));
+        assert!(!memory_peaks_diverge(&normal, &experimental, 32));
+        assert!(!memory_peaks_diverge(&normal, &normal, 0));
+    }
+
+    #[test]
+    fn test_create2_success_flip_diverges() {
+        let caller = Address::repeat_byte(0x11);
+        let init_code = [0x60, 0x00, 0x60, 0x00, 0xf3];
+
+        let mut normal = Create2Deployment::new(caller, B256::ZERO, &init_code);
+        normal.actual_address = Some(normal.predicted_address);
+
+        // Same deployment fails under the multiplier
+        let experimental = Create2Deployment::new(caller, B256::ZERO, &init_code);
+
+        assert!(create2_deployments_diverge(
+            std::slice::from_ref(&normal),
+            std::slice::from_ref(&experimental)
+        ));
+        assert!(!create2_deployments_diverge(
+            std::slice::from_ref(&normal),
+            std::slice::from_ref(&normal)
+        ));
+    }
+
+    #[test]
+    fn test_create2_address_mismatch_diverges() {
+        let caller = Address::repeat_byte(0x11);
+        let mut deployment = Create2Deployment::new(caller, B256::ZERO, &[0x00]);
+        deployment.actual_address = Some(Address::repeat_byte(0x22));
+
+        assert!(create2_deployments_diverge(
+            std::slice::from_ref(&deployment),
+            std::slice::from_ref(&deployment)
+        ));
+    }
+
+    #[test]
+    fn test_fee_only_state_difference_is_not_a_divergence() {
+        let base_fee = 1_000_000_000;
+        let gas_price = 3_000_000_000;
+        let beneficiary = Address::repeat_byte(0xbe);
+
+        // PUSH1 1 PUSH1 0 SSTORE STOP
+        let env = TestEnv::new()
+            .with_contract(CONTRACT, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00])
+            .with_base_fee(base_fee)
+            .with_beneficiary(beneficiary);
+        let mut tx = TestEnv::call_tx(CONTRACT, vec![], 1_000_000);
+        tx.gas_price = gas_price;
+
+        let normal = env.transact(tx.clone(), NoOpInspector).unwrap();
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let experimental = env.transact(tx, GasResearchInspector::new(config, 1_000_000)).unwrap();
+
+        let normal_gas = normal.result.gas_used();
+        let experimental_gas = experimental.result.gas_used();
+        assert_ne!(normal_gas, experimental_gas);
+        // The raw account info differs only because of the fees
+        assert_ne!(normal.state[&CALLER].info, experimental.state[&CALLER].info);
+
+        let normal_fees = GasFees::new(normal_gas, gas_price, base_fee);
+        let experimental_fees = GasFees::new(experimental_gas, gas_price, base_fee);
+        let divergent = |experimental_fees| {
+            StateComparison {
+                normal: &normal.state,
+                experimental: &experimental.state,
+                sender: CALLER,
+                beneficiary,
+                normal_fees,
+                experimental_fees,
+                ignore_addresses: &HashSet::new(),
+                compare_beneficiary_balance: true,
+                include_system: true,
+            }
+            .divergent_account()
+        };
+        assert_eq!(divergent(experimental_fees), None);
+
+        // A balance difference not explained by the fees is still a divergence
+        assert!(divergent(normal_fees).is_some());
+    }
+
+    #[test]
+    fn test_divergent_account() {
+        let base_fee = 1_000_000_000;
+        let gas_price = 3_000_000_000;
+        let beneficiary = Address::repeat_byte(0xbe);
+
+        // PUSH1 1 PUSH1 0 SSTORE STOP
+        let env = TestEnv::new()
+            .with_contract(CONTRACT, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00])
+            .with_base_fee(base_fee)
+            .with_beneficiary(beneficiary);
+        let mut tx = TestEnv::call_tx(CONTRACT, vec![], 1_000_000);
+        tx.gas_price = gas_price;
+
+        let normal = env.transact(tx.clone(), NoOpInspector).unwrap();
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let mut experimental =
+            env.transact(tx, GasResearchInspector::new(config, 1_000_000)).unwrap();
+        let normal_fees = GasFees::new(normal.result.gas_used(), gas_price, base_fee);
+        let experimental_fees = GasFees::new(experimental.result.gas_used(), gas_price, base_fee);
+
+        let divergent = |experimental: &EvmState| {
+            StateComparison {
+                normal: &normal.state,
+                experimental,
+                sender: CALLER,
+                beneficiary,
+                normal_fees,
+                experimental_fees,
+                ignore_addresses: &HashSet::new(),
+                compare_beneficiary_balance: true,
+                include_system: true,
+            }
+            .divergent_account()
+        };
+
+        // Identical state diffs up to the fees have no divergent account
+        assert_eq!(divergent(&experimental.state), None);
+
+        // A storage difference is located at the account holding the slot
+        experimental
+            .state
+            .get_mut(&CONTRACT)
+            .unwrap()
+            .storage
+            .get_mut(&U256::ZERO)
+            .unwrap()
+            .present_value = U256::from(2);
+        assert_eq!(divergent(&experimental.state), Some(CONTRACT));
+    }
+
+    #[test]
+    fn test_state_digest() {
+        let base_fee = 1_000_000_000;
+        let gas_price = 3_000_000_000;
+        let beneficiary = Address::repeat_byte(0xbe);
+
+        // PUSH1 1 PUSH1 0 SSTORE STOP
+        let env = TestEnv::new()
+            .with_contract(CONTRACT, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00])
+            .with_base_fee(base_fee)
+            .with_beneficiary(beneficiary);
+        let mut tx = TestEnv::call_tx(CONTRACT, vec![], 1_000_000);
+        tx.gas_price = gas_price;
+
+        let normal = env.transact(tx.clone(), NoOpInspector).unwrap();
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let mut experimental =
+            env.transact(tx, GasResearchInspector::new(config, 1_000_000)).unwrap();
+        let normal_fees = GasFees::new(normal.result.gas_used(), gas_price, base_fee);
+        let experimental_fees = GasFees::new(experimental.result.gas_used(), gas_price, base_fee);
+
+        // Identical state diffs up to the fees have equal digests
+        let normal_digest = state_digest(&normal.state, CALLER, beneficiary, normal_fees);
+        assert_eq!(normal_digest, state_digest(&normal.state, CALLER, beneficiary, normal_fees));
+        assert_eq!(
+            normal_digest,
+            state_digest(&experimental.state, CALLER, beneficiary, experimental_fees)
+        );
+
+        // A storage difference changes the digest
+        experimental
+            .state
+            .get_mut(&CONTRACT)
+            .unwrap()
+            .storage
+            .get_mut(&U256::ZERO)
+            .unwrap()
+            .present_value = U256::from(2);
+        assert_ne!(
+            normal_digest,
+            state_digest(&experimental.state, CALLER, beneficiary, experimental_fees)
+        );
+    }
+
+    #[test]
+    fn test_divergence_cause() {
+        // PUSH1 1 PUSH1 0 SSTORE STOP
+        let code = vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00];
+        let env = TestEnv::new().with_contract(CONTRACT, code);
+        let tx = || TestEnv::call_tx(CONTRACT, vec![], 100_000);
+        let normal = env.transact(tx(), NoOpInspector).unwrap();
+
+        let replay = env.transact(tx(), NoOpInspector).unwrap();
+        assert_eq!(divergence_cause(&normal, Some(&replay)), DivergenceCause::GasDirect);
+
+        // The slot already holds 1 in the other state, so the same store is cheaper
+        let other = env.clone().with_storage(CONTRACT, U256::ZERO, U256::from(1));
+        let replay = other.transact(tx(), NoOpInspector).unwrap();
+        assert_eq!(divergence_cause(&normal, Some(&replay)), DivergenceCause::StateCascade);
+
+        assert_eq!(divergence_cause(&normal, None), DivergenceCause::Unknown);
+    }
+
+    #[test]
+    fn test_access_list_divergence() {
+        // PUSH1 1 PUSH1 0 SSTORE STOP
+        let env = TestEnv::new().with_contract(CONTRACT, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00]);
+        let normal = env.transact(TestEnv::call_tx(CONTRACT, vec![], 100_000), NoOpInspector);
+        let normal = normal.unwrap().state;
+        assert_eq!(access_list(&normal)[&CONTRACT], BTreeSet::from([B256::ZERO]));
+        assert_eq!(access_list_divergence(&normal, &normal.clone()), None);
+
+        // The experimental execution read another slot and loaded another account
+        let mut experimental = normal.clone();
+        let mut contract = experimental[&CONTRACT].clone();
+        let slot = contract.storage[&U256::ZERO].clone();
+        contract.storage.insert(U256::from(1), slot);
+        experimental.insert(CONTRACT, contract);
+        let other = Address::repeat_byte(0x0a);
+        experimental.insert(other, Account::default());
+
+        let diff = access_list_divergence(&normal, &experimental).unwrap();
+        assert!(diff.normal_only.is_empty());
+        assert_eq!(
+            diff.experimental_only,
+            AccessSet::from([
+                (CONTRACT, BTreeSet::from([B256::from(U256::from(1))])),
+                (other, BTreeSet::new()),
+            ])
+        );
+        let reversed = access_list_divergence(&experimental, &normal).unwrap();
+        assert_eq!(reversed.normal_only, diff.experimental_only);
+    }
+
+    #[test]
+    fn test_system_state_excluded_by_default() {
+        let env = TestEnv::new().with_contract(CONTRACT, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00]);
+        let normal = env.transact(TestEnv::call_tx(CONTRACT, vec![], 100_000), NoOpInspector);
+        let normal = normal.unwrap();
+
+        // Only the beacon roots contract differs
+        let mut experimental = normal.state.clone();
+        let mut beacon_roots = experimental[&CONTRACT].clone();
+        beacon_roots.info.nonce = 7;
+        experimental.insert(alloy_eips::eip4788::BEACON_ROOTS_ADDRESS, beacon_roots);
+
+        let fees = GasFees::new(normal.result.gas_used(), 0, 0);
+        let compare = |include_system| {
+            DefaultStateComparator.compare(&StateComparison {
+                normal: &normal.state,
+                experimental: &experimental,
+                sender: CALLER,
+                beneficiary: Address::ZERO,
+                normal_fees: fees,
+                experimental_fees: fees,
+                ignore_addresses: &HashSet::new(),
+                compare_beneficiary_balance: true,
+                include_system,
+            })
+        };
+
+        let config = ResearchConfig::default();
+        assert!(compare(config.include_system_state).is_empty());
+        let config = ResearchConfig::builder().with_include_system_state(true).build().unwrap();
+        assert_eq!(compare(config.include_system_state), vec![DivergenceType::StateRoot]);
+    }
+
+    #[test]
+    fn test_default_state_comparator() {
+        let base_fee = 1_000_000_000;
+        let gas_price = 3_000_000_000;
+        let beneficiary = Address::repeat_byte(0xbe);
+        let system_contract = Address::repeat_byte(0x5c);
+
+        // PUSH1 1 PUSH1 0 SSTORE STOP
+        let env = TestEnv::new()
+            .with_contract(CONTRACT, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00])
+            .with_base_fee(base_fee)
+            .with_beneficiary(beneficiary);
+        let mut tx = TestEnv::call_tx(CONTRACT, vec![], 1_000_000);
+        tx.gas_price = gas_price;
+
+        let normal = env.transact(tx.clone(), NoOpInspector).unwrap();
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let mut experimental =
+            env.transact(tx, GasResearchInspector::new(config, 1_000_000)).unwrap();
+        let normal_fees = GasFees::new(normal.result.gas_used(), gas_price, base_fee);
+        let experimental_fees = GasFees::new(experimental.result.gas_used(), gas_price, base_fee);
+        let ignore_addresses = HashSet::from([system_contract]);
+        let compare = |state: &EvmState, ignore_addresses: &HashSet<Address>| {
+            DefaultStateComparator.compare(&StateComparison {
+                normal: &normal.state,
+                experimental: state,
+                sender: CALLER,
+                beneficiary,
+                normal_fees,
+                experimental_fees,
+                ignore_addresses,
+                compare_beneficiary_balance: true,
+                include_system: false,
+            })
+        };
+
+        // Fee-only differences match
+        assert!(compare(&experimental.state, &ignore_addresses).is_empty());
+
+        // Accounts only touched experimentally diverge unless ignored
+        let mut touched = experimental.state.clone();
+        touched.insert(system_contract, touched[&CONTRACT].clone());
+        assert_eq!(compare(&touched, &HashSet::new()), vec![DivergenceType::StateRoot]);
+        assert!(compare(&touched, &ignore_addresses).is_empty());
+
+        // Storage differences diverge unless the account is ignored
+        experimental
+            .state
+            .get_mut(&CONTRACT)
+            .unwrap()
+            .storage
+            .get_mut(&U256::ZERO)
+            .unwrap()
+            .present_value = U256::from(2);
+        assert_eq!(
+            compare(&experimental.state, &ignore_addresses),
+            vec![DivergenceType::StateRoot]
+        );
+        assert!(compare(&experimental.state, &HashSet::from([CONTRACT])).is_empty());
+    }
+
+    #[test]
+    fn test_beneficiary_balance_excluded_by_default() {
+        let beneficiary = Address::repeat_byte(0xbe);
+        let env = TestEnv::new()
+            .with_contract(CONTRACT, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00])
+            .with_beneficiary(beneficiary);
+        let normal = env.transact(TestEnv::call_tx(CONTRACT, vec![], 100_000), NoOpInspector);
+        let normal = normal.unwrap();
+        let fees = GasFees::new(normal.result.gas_used(), 0, 0);
+
+        // Only the beneficiary's balance differs, by more than the fees explain
+        let with_beneficiary = |balance: u64, nonce| {
+            let mut state = normal.state.clone();
+            let info = AccountInfo { balance: U256::from(balance), nonce, ..Default::default() };
+            state.insert(beneficiary, Account { info, ..Default::default() });
+            state
+        };
+        let normal_state = with_beneficiary(0, 0);
+        let compare = |experimental: &EvmState, compare_beneficiary_balance| {
+            DefaultStateComparator.compare(&StateComparison {
+                normal: &normal_state,
+                experimental,
+                sender: CALLER,
+                beneficiary,
+                normal_fees: fees,
+                experimental_fees: fees,
+                ignore_addresses: &HashSet::new(),
+                compare_beneficiary_balance,
+                include_system: false,
+            })
+        };
+
+        let config = ResearchConfig::default();
+        let balance_only = with_beneficiary(1, 0);
+        assert!(compare(&balance_only, config.compare_beneficiary_balance).is_empty());
+        assert_eq!(compare(&balance_only, true), vec![DivergenceType::StateRoot]);
+
+        // Anything but the balance is still compared
+        assert_eq!(compare(&with_beneficiary(1, 1), false), vec![DivergenceType::StateRoot]);
+    }
+
+    #[test]
+    fn test_tx_invalid_at_adjusted_base_fee() {
+        let config = ResearchConfig { base_fee_multiplier: Some(4.0), ..Default::default() };
+        let normal_base_fee = 1_000_000_000;
+        let experimental_base_fee = config.experimental_base_fee(Some(normal_base_fee)).unwrap();
+
+        let mut tx = TestEnv::call_tx(Address::repeat_byte(0x42), vec![], 21_000);
+        tx.gas_price = 2_000_000_000;
+
+        let normal = TestEnv::new().with_base_fee(normal_base_fee);
+        assert!(normal.transact(tx.clone(), NoOpInspector).unwrap().result.is_success());
+
+        let experimental = TestEnv::new().with_base_fee(experimental_base_fee);
+        match experimental.transact(tx, NoOpInspector) {
+            Err(EVMError::Transaction(err)) => assert!(is_base_fee_rejection(&err)),
+            other => panic!("expected base fee rejection, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_classify_experimental_error() {
+        type Error = EVMError<std::convert::Infallible>;
+
+        let no_funds = Error::Transaction(InvalidTransaction::LackOfFundForMaxFee {
+            fee: Box::new(U256::from(2)),
+            balance: Box::new(U256::from(1)),
+        });
+        assert_eq!(
+            classify_experimental_error(&no_funds, false),
+            ExperimentalError::InvalidTransaction
+        );
+        assert_eq!(classify_experimental_error(&no_funds, true), ExperimentalError::BaseFee);
+
+        let nonce = Error::Transaction(InvalidTransaction::NonceTooLow { tx: 1, state: 2 });
+        assert_eq!(
+            classify_experimental_error(&nonce, true),
+            ExperimentalError::InvalidTransaction
+        );
+
+        let custom = Error::Custom("precompile failure".to_string());
+        assert_eq!(classify_experimental_error(&custom, false), ExperimentalError::Unknown);
+    }
+}
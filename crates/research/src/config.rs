@@ -1,7 +1,24 @@
 //! Configuration types for research mode.
 
+use crate::{
+    compare::{DefaultStateComparator, StateComparator},
+    database::Checkpoint,
+    divergence::{CalldataStats, DivergenceType},
+    oog::{DefaultOogClassifier, OogClassifier},
+    sampling::{AdaptiveSampling, SkipFilter},
+};
+use alloy_primitives::{keccak256, Address, B256};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{collections::HashSet, path::PathBuf, sync::Arc, time::Duration};
+use tracing::warn;
+
+/// Factor by which the multiplied normal gas of a transaction must stay below its gas limit for
+/// [`ResearchConfig::oog_fast_skip`] to skip it.
+pub const OOG_FAST_SKIP_MARGIN: u64 = 2;
+
+/// Largest [`ResearchConfig::ratio_decimals`], beyond which an `f64` ratio has no more precision
+/// to round to.
+pub const MAX_RATIO_DECIMALS: u32 = 15;
 
 /// Configuration for research mode execution.
 #[derive(Debug, Clone)]
@@ -12,6 +29,13 @@ pub struct ResearchConfig {
     /// Block number to start research mode (default: 0)
     pub start_block: u64,
 
+    /// Last block number to analyze, inclusive (default: no limit)
+    pub end_block: Option<u64>,
+
+    /// Fail at startup instead of warning if the block range is outside the node's available
+    /// blocks (default: false)
+    pub strict_block_range: bool,
+
     /// Multiply gas refunds by this factor (default: 1.0)
     pub refund_multiplier: f64,
 
@@ -19,22 +43,39 @@ pub struct ResearchConfig {
     /// Set to 1.0 to keep it unchanged (this is the interesting case)
     pub stipend_multiplier: f64,
 
-    /// Path to the divergence database
+    /// Multiply the intrinsic calldata cost (4 gas per zero and 16 per non-zero byte, EIP-2028)
+    /// by this factor in the experimental gas charged, independently of `gas_multiplier`
+    /// (default: 1)
+    /// The EVM charges intrinsic gas at its normal cost, so the extra cost is added on top
+    pub calldata_gas_multiplier: u64,
+
+    /// Path to the divergence database, or a `postgres://` URL for a PostgreSQL store
     pub divergence_db_path: PathBuf,
 
     /// Path to the gas loop detection database
     pub loop_detection_db_path: Option<PathBuf>,
 
+    /// Path to a signature database (a 4byte directory dump) resolving the function names of
+    /// recorded divergences, see [`SignatureDatabase`] (default: none)
+    ///
+    /// [`SignatureDatabase`]: crate::signatures::SignatureDatabase
+    pub signatures_file: Option<PathBuf>,
+
     /// Level of detail for divergence traces
     pub trace_detail: TraceDetail,
 
     /// Inflate transaction gas limits by this factor (default: same as gas_multiplier)
-    /// This prevents trivial OOG failures
+    /// This prevents trivial OOG failures; a lower factor makes them expected, see
+    /// [`Self::expects_oog`]
     pub gas_limit_multiplier: Option<u64>,
 
     /// Maximum number of divergences to record per block (default: unlimited)
     pub max_divergences_per_block: Option<usize>,
 
+    /// Maximum number of divergences to record in a run, after which recording stops (default:
+    /// unlimited)
+    pub max_divergences_total: Option<u64>,
+
     /// Enable gas-dependent loop detection
     pub detect_gas_loops: bool,
 
@@ -42,6 +83,196 @@ pub struct ResearchConfig {
     /// Set to 1 for sequential processing, or higher to utilize multiple cores
     /// Recommended: number of physical cores for CPU-bound workloads
     pub max_parallel_txs: usize,
+
+    /// Run the experimental execution of each transaction on the rayon thread pool while the
+    /// normal execution runs, rather than one after the other (default: false)
+    /// Transactions are still executed one after the other while they can be skipped by their
+    /// normal gas used, see [`Self::executes_concurrently`].
+    pub concurrent_execution: bool,
+
+    /// Charge the experimental gas costs in the interpreter itself, so experimental execution
+    /// halts where they run out instead of being simulated by the inspector (default: false)
+    /// Requires the `real-gas` feature and an EVM config built on
+    /// `real_gas::RealGasEvmFactory`, like the offline analyzer's.
+    pub real_gas: bool,
+
+    /// Number of contract bytecodes kept in memory across blocks, so hot contracts are not
+    /// fetched from the state provider for every transaction; 0 disables the cache
+    /// (default: 4096)
+    pub code_cache_size: usize,
+
+    /// Warn when operation counts differ between executions but no divergence was flagged
+    /// This is a self-consistency guard for the divergence detector
+    pub check_op_count_consistency: bool,
+
+    /// Multiply the block base fee by this factor in experimental execution (default: unchanged)
+    /// Transactions that can no longer afford the adjusted fee are flagged as base fee divergences
+    pub base_fee_multiplier: Option<f64>,
+
+    /// Persist call trees for every divergence so per-frame normal and simulated gas can be
+    /// compared side by side, even when `trace_detail` is not detailed
+    pub persist_frame_gas: bool,
+
+    /// Opcodes charged at their normal cost in experimental execution (default: none)
+    /// Exclusion takes precedence over any multiplier that would otherwise apply
+    pub excluded_opcodes: HashSet<u8>,
+
+    /// Subtract the extra intrinsic gas (`21000 * (gas_multiplier - 1)`) from the simulated gas
+    /// limit (default: true)
+    /// When disabled the simulated limit is the raw `gas_limit * effective_gas_limit_multiplier`
+    pub adjust_intrinsic_gas: bool,
+
+    /// Log individual divergences at `trace` instead of `info` (default: false)
+    /// Per-block summaries stay at `info` and critical divergences at `warn`
+    pub quiet: bool,
+
+    /// Log an aggregate progress line with the block rate, divergences found and the time left
+    /// until `end_block` at most once per interval (default: none)
+    pub progress_interval: Option<Duration>,
+
+    /// Compare post-transaction state by digest, falling back to the per-account comparison
+    /// only when the digests differ (default: false)
+    pub state_digest: bool,
+
+    /// Warm the recipients of all of a block's transactions before analyzing each of them, to
+    /// approximate the warming done by earlier transactions in the block (default: false)
+    pub seed_warm_from_block: bool,
+
+    /// Carry the experimental state between the transactions of a block, so later transactions
+    /// see the effects of earlier experimental executions as on a chain with the experimental
+    /// gas costs (default: false)
+    /// Transactions are then no longer analyzed in isolation: a divergence can be inherited from
+    /// an earlier transaction of the block. The experimental state restarts from the normal
+    /// state at each block, follows schedule A of an A/B run, and is advanced by every
+    /// transaction of the block, sampled or not.
+    pub sequential_state: bool,
+
+    /// Classifier assigning a pattern to experimental OOGs (default: [`DefaultOogClassifier`])
+    pub oog_classifier: Arc<dyn OogClassifier>,
+
+    /// Accounts left out of the post-transaction state comparison, in addition to the system
+    /// contracts unless `include_system_state` is set (default: none)
+    pub ignore_addresses: HashSet<Address>,
+
+    /// Compare the block beneficiary's balance, allowing only for the priority fees each
+    /// execution paid it (default: false)
+    /// The fees paid to the beneficiary depend on the gas used, so by default its balance is left
+    /// out of the state comparison; its nonce, code and storage are still compared.
+    pub compare_beneficiary_balance: bool,
+
+    /// Compare the accounts and storage slots each execution accessed, i.e. the access lists
+    /// generated from them (default: false)
+    /// A gas change can alter what is accessed before it changes any state; the accesses only
+    /// one execution made are stored with detailed traces.
+    pub compare_access_lists: bool,
+
+    /// Comparator deciding whether post-transaction states diverge
+    /// (default: [`DefaultStateComparator`])
+    pub state_comparator: Arc<dyn StateComparator>,
+
+    /// Fraction of transactions analyzed, chosen deterministically by transaction hash
+    /// (default: 1.0, analyze every transaction)
+    pub sample_rate: f64,
+
+    /// Analyze transactions to contracts that diverged recently at a higher rate than
+    /// `sample_rate` (default: disabled)
+    pub adaptive_sampling: Option<AdaptiveSampling>,
+
+    /// Skip calls whose `(code_hash, selector)` shape was already analyzed without diverging,
+    /// apart from random re-checks (default: disabled)
+    pub skip_filter: Option<SkipFilter>,
+
+    /// Analyze system transactions, see [`crate::system`] (default: false)
+    pub include_system_txs: bool,
+
+    /// Compare the state of the system contracts, see [`crate::system`] (default: false)
+    pub include_system_state: bool,
+
+    /// Function selectors whose transactions are always traced at [`TraceDetail::Detailed`],
+    /// matched against the first four bytes of the top-level calldata (default: none)
+    pub deep_trace_selectors: HashSet<[u8; 4]>,
+
+    /// Handling of experimental execution errors that are neither invalid transactions nor
+    /// execution results, e.g. database or custom EVM errors (default: skip)
+    pub unknown_error_policy: UnknownErrorPolicy,
+
+    /// Only record divergences that are purely in the gas pattern for blocks whose base fee in
+    /// wei is above this threshold; behavioral divergences are always recorded (default: none)
+    pub economic_gas_threshold: Option<u64>,
+
+    /// Only record divergences including at least one of these types, for studies focused on
+    /// one phenomenon (default: all types)
+    /// Divergences recorded only because experimental execution ran out of gas, or nearly did,
+    /// have no type and are not recorded either
+    pub only_types: Option<HashSet<DivergenceType>>,
+
+    /// Number of 32-byte words the peak memory of the two executions may differ by before it is
+    /// recorded as a divergence (default: 0)
+    pub memory_tolerance_words: u64,
+
+    /// Gas the summed normal gas of a block's transactions may differ from the gas used in its
+    /// header by before it is flagged, where transactions are executed in order (default: 0)
+    pub gas_reconciliation_tolerance: u64,
+
+    /// Replay transactions that fail in experimental execution only at normal gas costs on the
+    /// experimental pre-state, attributing those that fail there too to
+    /// [`DivergenceCause::NonGas`](crate::divergence::DivergenceCause::NonGas) (default: false)
+    /// Requires [`Self::sequential_state`]: otherwise the experimental pre-state is the normal one
+    /// and the replay would just repeat normal execution.
+    pub confirm_failures: bool,
+
+    /// Skip experimental execution of transactions whose normal gas used is so far below their
+    /// gas limit that they cannot run out of gas, see [`Self::cannot_oog`] (default: false)
+    /// Only applies while [`Self::only_types`] is limited to the types an OOG produces, see
+    /// [`Self::only_oog_types`]; other divergences of skipped transactions would go undetected.
+    pub oog_fast_skip: bool,
+
+    /// Skip experimental execution of transactions whose normal gas used is below this, to
+    /// focus the analysis on complex transactions (default: none)
+    pub min_gas_used: Option<u64>,
+
+    /// Bytes of call frame input/output and event log data stored per divergence, beyond which
+    /// the rest is dropped and the divergence marked truncated (default: unlimited)
+    pub max_bytes_per_divergence: Option<usize>,
+
+    /// Decimal places the gas efficiency ratio is rounded to where it is logged, recorded in
+    /// metrics and stored (default: 3)
+    /// Ratios near 1.0 otherwise scatter across histogram buckets and export groups from float
+    /// rounding alone. Structural divergences are still detected from the full-precision ratio.
+    pub ratio_decimals: u32,
+
+    /// Record transactions that do not diverge but finish experimental execution with less than
+    /// this much gas left as near misses, see [`Divergence::near_miss`] (default: none)
+    ///
+    /// [`Divergence::near_miss`]: crate::divergence::Divergence::near_miss
+    pub near_miss_gas_threshold: Option<u64>,
+
+    /// Continue after the last block finished by an earlier run with the same divergence
+    /// database if that is later than `start_block`, see [`Self::resume_start_block`]
+    /// (default: false)
+    pub resume: bool,
+
+    /// Resume even if the earlier run used different settings (default: false)
+    pub force_resume: bool,
+
+    /// Second experimental gas schedule (B), executed alongside the schedule set by
+    /// `gas_multiplier` and `excluded_opcodes` (A) to compare the two in one run, see
+    /// [`analyze_schedules`](crate::analysis::analyze_schedules) (default: none)
+    pub schedule_b: Option<GasSchedule>,
+
+    /// Check the inspector instead of a gas schedule: run experimental execution with every gas
+    /// adjustment neutral, see [`Self::self_test_config`] (default: false)
+    /// Both executions then charge the same gas, so any divergence is spurious and points to a
+    /// bug in the inspector, such as a wrong halt or state leaking between executions.
+    pub self_test: bool,
+
+    /// Gas multipliers a divergent transaction is re-analyzed at to find where each of its
+    /// divergence types first appears, see [`Divergence::threshold_map`] (default: none)
+    /// Each multiplier is a full re-execution of the transaction, so only divergent
+    /// transactions are swept.
+    ///
+    /// [`Divergence::threshold_map`]: crate::divergence::Divergence::threshold_map
+    pub sweep_multipliers: Vec<u64>,
 }
 
 impl Default for ResearchConfig {
@@ -49,32 +280,378 @@ impl Default for ResearchConfig {
         Self {
             gas_multiplier: 128,
             start_block: 0,
+            end_block: None,
+            strict_block_range: false,
             refund_multiplier: 1.0,
             stipend_multiplier: 1.0,
+            calldata_gas_multiplier: 1,
             divergence_db_path: PathBuf::from("divergence.db"),
             loop_detection_db_path: None,
+            signatures_file: None,
             trace_detail: TraceDetail::Standard,
             gas_limit_multiplier: None,
             max_divergences_per_block: None,
+            max_divergences_total: None,
             detect_gas_loops: true,
             max_parallel_txs: num_cpus::get(),
+            concurrent_execution: false,
+            real_gas: false,
+            code_cache_size: 4096,
+            check_op_count_consistency: true,
+            base_fee_multiplier: None,
+            persist_frame_gas: false,
+            excluded_opcodes: HashSet::new(),
+            adjust_intrinsic_gas: true,
+            quiet: false,
+            progress_interval: None,
+            state_digest: false,
+            seed_warm_from_block: false,
+            sequential_state: false,
+            oog_classifier: Arc::new(DefaultOogClassifier),
+            ignore_addresses: HashSet::new(),
+            compare_beneficiary_balance: false,
+            compare_access_lists: false,
+            state_comparator: Arc::new(DefaultStateComparator),
+            sample_rate: 1.0,
+            adaptive_sampling: None,
+            skip_filter: None,
+            include_system_txs: false,
+            include_system_state: false,
+            deep_trace_selectors: HashSet::new(),
+            unknown_error_policy: UnknownErrorPolicy::Skip,
+            economic_gas_threshold: None,
+            only_types: None,
+            memory_tolerance_words: 0,
+            gas_reconciliation_tolerance: 0,
+            confirm_failures: false,
+            oog_fast_skip: false,
+            min_gas_used: None,
+            max_bytes_per_divergence: None,
+            ratio_decimals: 3,
+            near_miss_gas_threshold: None,
+            resume: false,
+            force_resume: false,
+            schedule_b: None,
+            self_test: false,
+            sweep_multipliers: Vec::new(),
         }
     }
 }
 
 impl ResearchConfig {
+    /// Create a builder starting from the default configuration.
+    pub fn builder() -> ResearchConfigBuilder {
+        ResearchConfigBuilder::default()
+    }
+
+    /// Whether `block_number` falls within the configured block range.
+    pub fn is_in_block_range(&self, block_number: u64) -> bool {
+        block_number >= self.start_block && self.end_block.is_none_or(|end| block_number <= end)
+    }
+
     /// Get the effective gas limit multiplier.
     /// Defaults to the gas multiplier if not explicitly set.
     pub fn effective_gas_limit_multiplier(&self) -> u64 {
         self.gas_limit_multiplier.unwrap_or(self.gas_multiplier)
     }
 
+    /// Whether gas limits are inflated less than gas costs, so most transactions are expected
+    /// to run out of gas in experimental execution.
+    ///
+    /// This is a deliberate setup for finding the multiplier at which transactions stop fitting
+    /// their gas limit, not a misconfiguration.
+    pub fn expects_oog(&self) -> bool {
+        self.effective_gas_limit_multiplier() < self.gas_multiplier
+    }
+
+    /// Whether a transaction that used `normal_gas` in normal execution is expected to run out
+    /// of gas in experimental execution under its own `gas_limit`.
+    ///
+    /// A transaction whose normal gas times the gas multiplier exceeds its gas limit cannot fit
+    /// it. Such OOGs are the multiplier at work, not a bug.
+    pub fn expects_tx_oog(&self, normal_gas: u64, gas_limit: u64) -> bool {
+        normal_gas.saturating_mul(self.gas_multiplier) > gas_limit
+    }
+
+    /// Get the gas limit experimental execution is measured against for a transaction or block
+    /// with `gas_limit`.
+    pub fn simulated_gas_limit(&self, gas_limit: u64) -> u64 {
+        let limit = gas_limit.saturating_mul(self.effective_gas_limit_multiplier());
+        if self.adjust_intrinsic_gas {
+            limit.saturating_sub(21000u64.saturating_mul(self.gas_multiplier.saturating_sub(1)))
+        } else {
+            limit
+        }
+    }
+
+    /// Get the intrinsic gas experimental execution is charged for `calldata` on top of its
+    /// normal cost, at the [`calldata_gas_multiplier`](Self::calldata_gas_multiplier).
+    pub const fn extra_calldata_gas(&self, calldata: &CalldataStats) -> u64 {
+        calldata.intrinsic_gas(self.calldata_gas_multiplier.saturating_sub(1))
+    }
+
+    /// Round a gas efficiency `ratio` to [`ratio_decimals`](Self::ratio_decimals) decimal places
+    /// for logging, metrics and storage.
+    pub fn quantize_ratio(&self, ratio: f64) -> f64 {
+        let scale = 10f64.powi(self.ratio_decimals as i32);
+        (ratio * scale).round() / scale
+    }
+
+    /// Get the base fee for experimental execution.
+    /// Returns None if no adjustment is configured or the block predates EIP-1559.
+    pub fn experimental_base_fee(&self, base_fee: Option<u64>) -> Option<u64> {
+        let multiplier = self.base_fee_multiplier?;
+        Some((base_fee? as f64 * multiplier) as u64)
+    }
+
+    /// Get the gas multiplier applied to an opcode in experimental execution.
+    /// Excluded opcodes are always charged at 1x.
+    pub fn opcode_multiplier(&self, opcode: u8) -> u64 {
+        if self.excluded_opcodes.contains(&opcode) {
+            1
+        } else {
+            self.gas_multiplier
+        }
+    }
+
+    /// Get the trace detail for a transaction whose top-level call has `selector`.
+    /// Transactions calling one of the `deep_trace_selectors` are always traced in detail.
+    pub fn trace_detail_for(&self, selector: Option<[u8; 4]>) -> TraceDetail {
+        if selector.is_some_and(|selector| self.deep_trace_selectors.contains(&selector)) {
+            TraceDetail::Detailed
+        } else {
+            self.trace_detail
+        }
+    }
+
+    /// Whether a transaction that used `normal_gas` in normal execution cannot run out of gas in
+    /// experimental execution under its own `gas_limit`.
+    ///
+    /// This holds when the normal gas, multiplied by the gas multiplier and by
+    /// [`OOG_FAST_SKIP_MARGIN`], is still below the gas limit, which experimental execution
+    /// keeps.
+    pub fn cannot_oog(&self, normal_gas: u64, gas_limit: u64) -> bool {
+        normal_gas.saturating_mul(self.gas_multiplier).saturating_mul(OOG_FAST_SKIP_MARGIN) <
+            gas_limit
+    }
+
+    /// Whether [`Self::only_types`] is limited to the divergence types an experimental OOG
+    /// produces: the [status](DivergenceType::Status) flip of the halt and the
+    /// [gas pattern](DivergenceType::GasPattern) of the gas it burns.
+    pub fn only_oog_types(&self) -> bool {
+        self.only_types.as_ref().is_some_and(|only_types| {
+            only_types.iter().all(|divergence_type| {
+                matches!(divergence_type, DivergenceType::Status | DivergenceType::GasPattern)
+            })
+        })
+    }
+
+    /// Whether the experimental execution of a transaction runs concurrently with its normal
+    /// execution. Not while [`Self::min_gas_used`] or [`Self::oog_fast_skip`] can skip the
+    /// experimental execution, as that is only known once the normal execution finished.
+    pub fn executes_concurrently(&self) -> bool {
+        self.concurrent_execution &&
+            self.min_gas_used.is_none() &&
+            !(self.oog_fast_skip && self.only_oog_types())
+    }
+
+    /// The experimental gas schedule (A) set by `gas_multiplier` and `excluded_opcodes`.
+    pub fn schedule(&self) -> GasSchedule {
+        GasSchedule {
+            gas_multiplier: self.gas_multiplier,
+            excluded_opcodes: self.excluded_opcodes.clone(),
+        }
+    }
+
+    /// This configuration with experimental execution under `schedule` and no second schedule.
+    pub fn with_schedule(&self, schedule: &GasSchedule) -> Self {
+        Self {
+            gas_multiplier: schedule.gas_multiplier,
+            excluded_opcodes: schedule.excluded_opcodes.clone(),
+            schedule_b: None,
+            ..self.clone()
+        }
+    }
+
+    /// This configuration with experimental execution at normal gas costs, for a
+    /// [`self_test`](Self::self_test) run.
+    ///
+    /// Every multiplier is 1 and no opcode, gas limit or base fee adjustment applies. Options
+    /// that would hide divergences, such as the OOG fast skip (which skips every transaction at
+    /// 1x) or type and base fee filters, are disabled.
+    pub fn self_test_config(&self) -> Self {
+        Self {
+            gas_multiplier: 1,
+            refund_multiplier: 1.0,
+            stipend_multiplier: 1.0,
+            calldata_gas_multiplier: 1,
+            gas_limit_multiplier: None,
+            base_fee_multiplier: None,
+            excluded_opcodes: HashSet::new(),
+            economic_gas_threshold: None,
+            only_types: None,
+            oog_fast_skip: false,
+            near_miss_gas_threshold: None,
+            schedule_b: None,
+            self_test: true,
+            sweep_multipliers: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Whether gas-only divergences are recorded in a block with `base_fee` wei.
+    pub fn records_gas_divergences(&self, base_fee: u64) -> bool {
+        self.economic_gas_threshold.is_none_or(|threshold| base_fee > threshold)
+    }
+
+    /// Whether a divergence of `divergence_types` is recorded under [`Self::only_types`].
+    pub fn records_types(&self, divergence_types: &[DivergenceType]) -> bool {
+        self.only_types.as_ref().is_none_or(|only_types| {
+            divergence_types.iter().any(|divergence_type| only_types.contains(divergence_type))
+        })
+    }
+
+    /// Check if call trees should be recorded for divergences of a transaction traced at
+    /// `trace_detail`.
+    pub const fn record_call_trees(&self, trace_detail: TraceDetail) -> bool {
+        self.persist_frame_gas || trace_detail.include_call_trees()
+    }
+
+    /// The settings that affect which divergences are found and recorded, as `name=value` pairs
+    /// separated by `;`.
+    ///
+    /// The block range, output paths, parallelism and logging are left out, as are custom OOG
+    /// classifiers and state comparators, which cannot be described.
+    pub fn settings(&self) -> String {
+        let mut excluded_opcodes: Vec<_> = self.excluded_opcodes.iter().collect();
+        excluded_opcodes.sort_unstable();
+        let mut ignore_addresses: Vec<_> = self.ignore_addresses.iter().collect();
+        ignore_addresses.sort_unstable();
+        let mut deep_trace_selectors: Vec<_> = self.deep_trace_selectors.iter().collect();
+        deep_trace_selectors.sort_unstable();
+        let schedule_b = self.schedule_b.as_ref().map(|schedule| {
+            let mut excluded_opcodes: Vec<_> = schedule.excluded_opcodes.iter().collect();
+            excluded_opcodes.sort_unstable();
+            (schedule.gas_multiplier, excluded_opcodes)
+        });
+
+        let mut sweep_multipliers = self.sweep_multipliers.clone();
+        sweep_multipliers.sort_unstable();
+        sweep_multipliers.dedup();
+
+        let only_types = self.only_types.as_ref().map(|only_types| {
+            let mut only_types: Vec<_> = only_types.iter().collect();
+            only_types.sort_unstable();
+            only_types
+        });
+
+        format!(
+            "gas_multiplier={};refund_multiplier={:?};stipend_multiplier={:?};\
+             calldata_gas_multiplier={};\
+             trace_detail={:?};gas_limit_multiplier={:?};max_divergences_per_block={:?};\
+             max_divergences_total={:?};detect_gas_loops={};base_fee_multiplier={:?};\
+             persist_frame_gas={};excluded_opcodes={:?};adjust_intrinsic_gas={};\
+             state_digest={};seed_warm_from_block={};sequential_state={};ignore_addresses={:?};\
+             compare_beneficiary_balance={};compare_access_lists={};sample_rate={:?};\
+             adaptive_sampling={:?};\
+             skip_filter={:?};include_system_txs={};include_system_state={};\
+             deep_trace_selectors={:?};unknown_error_policy={:?};economic_gas_threshold={:?};\
+             only_types={:?};memory_tolerance_words={};confirm_failures={};oog_fast_skip={};\
+             min_gas_used={:?};max_bytes_per_divergence={:?};ratio_decimals={};\
+             near_miss_gas_threshold={:?};schedule_b={:?};sweep_multipliers={:?};real_gas={}",
+            self.gas_multiplier,
+            self.refund_multiplier,
+            self.stipend_multiplier,
+            self.calldata_gas_multiplier,
+            self.trace_detail,
+            self.gas_limit_multiplier,
+            self.max_divergences_per_block,
+            self.max_divergences_total,
+            self.detect_gas_loops,
+            self.base_fee_multiplier,
+            self.persist_frame_gas,
+            excluded_opcodes,
+            self.adjust_intrinsic_gas,
+            self.state_digest,
+            self.seed_warm_from_block,
+            self.sequential_state,
+            ignore_addresses,
+            self.compare_beneficiary_balance,
+            self.compare_access_lists,
+            self.sample_rate,
+            self.adaptive_sampling,
+            self.skip_filter,
+            self.include_system_txs,
+            self.include_system_state,
+            deep_trace_selectors,
+            self.unknown_error_policy,
+            self.economic_gas_threshold,
+            only_types,
+            self.memory_tolerance_words,
+            self.confirm_failures,
+            self.oog_fast_skip,
+            self.min_gas_used,
+            self.max_bytes_per_divergence,
+            self.ratio_decimals,
+            self.near_miss_gas_threshold,
+            schedule_b,
+            sweep_multipliers,
+            self.real_gas,
+        )
+    }
+
+    /// Hash of [`Self::settings`].
+    ///
+    /// Runs with the same hash produce comparable results.
+    pub fn config_hash(&self) -> B256 {
+        keccak256(self.settings())
+    }
+
+    /// Get the block to start analyzing at, given the last `checkpoint` of the divergence
+    /// database.
+    ///
+    /// Without `resume` this is always `start_block`. Resuming from a checkpoint written with a
+    /// different [`Self::config_hash`] would mix incomparable results, so it is refused unless
+    /// `force_resume` is set.
+    pub fn resume_start_block(&self, checkpoint: Option<&Checkpoint>) -> Result<u64, ConfigError> {
+        let Some(checkpoint) = checkpoint.filter(|_| self.resume) else {
+            return Ok(self.start_block)
+        };
+
+        let config_hash = self.config_hash();
+        if checkpoint.config_hash != config_hash {
+            if !self.force_resume {
+                return Err(ConfigError::CheckpointMismatch {
+                    checkpoint: checkpoint.config_hash,
+                    current: config_hash,
+                });
+            }
+            warn!(
+                target: "reth::research",
+                checkpoint_block = checkpoint.block_number,
+                checkpoint_hash = %checkpoint.config_hash,
+                current_hash = %config_hash,
+                "Resuming from a checkpoint written with different settings"
+            );
+        }
+
+        Ok(self.start_block.max(checkpoint.block_number.saturating_add(1)))
+    }
+
     /// Validate the configuration.
     pub fn validate(&self) -> Result<(), ConfigError> {
         if self.gas_multiplier == 0 {
             return Err(ConfigError::InvalidMultiplier("gas_multiplier must be > 0"));
         }
 
+        if self.real_gas && !cfg!(feature = "real-gas") {
+            return Err(ConfigError::RealGasUnsupported);
+        }
+
+        if self.schedule_b.as_ref().is_some_and(|schedule| schedule.gas_multiplier == 0) {
+            return Err(ConfigError::InvalidMultiplier("schedule_b gas_multiplier must be > 0"));
+        }
+
         if self.refund_multiplier < 0.0 {
             return Err(ConfigError::InvalidMultiplier("refund_multiplier must be >= 0"));
         }
@@ -83,12 +660,78 @@ impl ResearchConfig {
             return Err(ConfigError::InvalidMultiplier("stipend_multiplier must be >= 0"));
         }
 
+        if self.calldata_gas_multiplier == 0 {
+            return Err(ConfigError::InvalidMultiplier("calldata_gas_multiplier must be > 0"));
+        }
+
+        if self.ratio_decimals > MAX_RATIO_DECIMALS {
+            return Err(ConfigError::InvalidRatioDecimals(self.ratio_decimals));
+        }
+
+        if self.confirm_failures && !self.sequential_state {
+            return Err(ConfigError::ConfirmFailuresWithoutSequentialState)
+        }
+
+        if self.sweep_multipliers.contains(&0) {
+            return Err(ConfigError::InvalidMultiplier("sweep_multipliers must be > 0"));
+        }
+
+        if self.base_fee_multiplier.is_some_and(|m| m < 0.0) {
+            return Err(ConfigError::InvalidMultiplier("base_fee_multiplier must be >= 0"));
+        }
+
+        if !(0.0..=1.0).contains(&self.sample_rate) {
+            return Err(ConfigError::InvalidSampling("sample_rate must be between 0 and 1"));
+        }
+
+        if let Some(adaptive) = &self.adaptive_sampling {
+            if adaptive.window_blocks == 0 {
+                return Err(ConfigError::InvalidSampling("adaptive window must be > 0 blocks"));
+            }
+            if !(0.0..=1.0).contains(&adaptive.divergent_sample_rate) {
+                return Err(ConfigError::InvalidSampling(
+                    "divergent_sample_rate must be between 0 and 1",
+                ));
+            }
+        }
+
+        if let Some(filter) = &self.skip_filter {
+            if filter.bits == 0 {
+                return Err(ConfigError::InvalidSampling("skip filter must be > 0 bits"));
+            }
+            if !(0.0..=1.0).contains(&filter.recheck_rate) {
+                return Err(ConfigError::InvalidSampling("recheck_rate must be between 0 and 1"));
+            }
+        }
+
+        if let Some(end_block) = self.end_block.filter(|end| *end < self.start_block) {
+            return Err(ConfigError::InvalidBlockRange { start: self.start_block, end: end_block });
+        }
+
         Ok(())
     }
 }
 
+/// Gas costs charged in an experimental execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasSchedule {
+    /// Multiply all gas costs by this factor
+    pub gas_multiplier: u64,
+
+    /// Opcodes charged at their normal cost, taking precedence over the multiplier
+    pub excluded_opcodes: HashSet<u8>,
+}
+
+impl GasSchedule {
+    /// A schedule multiplying the cost of every opcode by `gas_multiplier`.
+    pub fn new(gas_multiplier: u64) -> Self {
+        Self { gas_multiplier, excluded_opcodes: HashSet::new() }
+    }
+}
+
 /// Level of detail for divergence traces.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
 #[serde(rename_all = "lowercase")]
 pub enum TraceDetail {
     /// Minimal: Only record divergence type and gas metrics
@@ -111,6 +754,11 @@ impl TraceDetail {
     pub const fn include_event_logs(self) -> bool {
         matches!(self, TraceDetail::Detailed)
     }
+
+    /// Check if divergences should be attributed to a cause by replaying the transaction.
+    pub const fn include_cause(self) -> bool {
+        matches!(self, TraceDetail::Detailed)
+    }
 }
 
 impl std::str::FromStr for TraceDetail {
@@ -126,6 +774,396 @@ impl std::str::FromStr for TraceDetail {
     }
 }
 
+/// Handling of experimental execution errors that are neither invalid transactions nor
+/// execution results.
+///
+/// Halts such as running out of gas or reverting are execution results and always compared,
+/// and invalid transactions are counted separately. Other errors come from the database or a
+/// custom EVM and say nothing about the contract's behavior, unless the custom EVM reports
+/// execution failures this way.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnknownErrorPolicy {
+    /// Skip the transaction
+    Skip,
+
+    /// Record a status divergence
+    Record,
+}
+
+impl std::str::FromStr for UnknownErrorPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(Self::Skip),
+            "record" => Ok(Self::Record),
+            _ => Err(format!("Invalid unknown error policy: {}", s)),
+        }
+    }
+}
+
+/// Builder for [`ResearchConfig`].
+///
+/// Fields that are not set keep their [`Default`] values.
+#[derive(Debug, Clone, Default)]
+pub struct ResearchConfigBuilder {
+    config: ResearchConfig,
+}
+
+impl ResearchConfigBuilder {
+    /// Set the gas cost multiplier.
+    pub const fn with_gas_multiplier(mut self, gas_multiplier: u64) -> Self {
+        self.config.gas_multiplier = gas_multiplier;
+        self
+    }
+
+    /// Set the block number to start research mode at.
+    pub const fn with_start_block(mut self, start_block: u64) -> Self {
+        self.config.start_block = start_block;
+        self
+    }
+
+    /// Set the last block number to analyze.
+    pub const fn with_end_block(mut self, end_block: u64) -> Self {
+        self.config.end_block = Some(end_block);
+        self
+    }
+
+    /// Fail instead of warning when the block range is outside the node's available blocks.
+    pub const fn with_strict_block_range(mut self, strict: bool) -> Self {
+        self.config.strict_block_range = strict;
+        self
+    }
+
+    /// Set the gas refund multiplier.
+    pub const fn with_refund_multiplier(mut self, refund_multiplier: f64) -> Self {
+        self.config.refund_multiplier = refund_multiplier;
+        self
+    }
+
+    /// Set the gas stipend multiplier.
+    pub const fn with_stipend_multiplier(mut self, stipend_multiplier: f64) -> Self {
+        self.config.stipend_multiplier = stipend_multiplier;
+        self
+    }
+
+    /// Set the intrinsic calldata gas multiplier.
+    pub const fn with_calldata_gas_multiplier(mut self, calldata_gas_multiplier: u64) -> Self {
+        self.config.calldata_gas_multiplier = calldata_gas_multiplier;
+        self
+    }
+
+    /// Set the path to the divergence database.
+    pub fn with_divergence_db_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.divergence_db_path = path.into();
+        self
+    }
+
+    /// Set the path to the gas loop detection database.
+    pub fn with_loop_detection_db_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.loop_detection_db_path = Some(path.into());
+        self
+    }
+
+    /// Set the path to the signature database resolving function names.
+    pub fn with_signatures_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.signatures_file = Some(path.into());
+        self
+    }
+
+    /// Set the level of detail for divergence traces.
+    pub const fn with_trace_detail(mut self, trace_detail: TraceDetail) -> Self {
+        self.config.trace_detail = trace_detail;
+        self
+    }
+
+    /// Set the transaction gas limit multiplier.
+    pub const fn with_gas_limit_multiplier(mut self, gas_limit_multiplier: u64) -> Self {
+        self.config.gas_limit_multiplier = Some(gas_limit_multiplier);
+        self
+    }
+
+    /// Set the maximum number of divergences recorded per block.
+    pub const fn with_max_divergences_per_block(mut self, max: usize) -> Self {
+        self.config.max_divergences_per_block = Some(max);
+        self
+    }
+
+    /// Set the maximum number of divergences recorded in a run.
+    pub const fn with_max_divergences_total(mut self, max: u64) -> Self {
+        self.config.max_divergences_total = Some(max);
+        self
+    }
+
+    /// Enable or disable gas-dependent loop detection.
+    pub const fn with_detect_gas_loops(mut self, detect_gas_loops: bool) -> Self {
+        self.config.detect_gas_loops = detect_gas_loops;
+        self
+    }
+
+    /// Set the maximum number of transactions analyzed in parallel per block.
+    pub const fn with_max_parallel_txs(mut self, max_parallel_txs: usize) -> Self {
+        self.config.max_parallel_txs = max_parallel_txs;
+        self
+    }
+
+    /// Enable or disable running the two executions of a transaction concurrently.
+    pub const fn with_concurrent_execution(mut self, concurrent: bool) -> Self {
+        self.config.concurrent_execution = concurrent;
+        self
+    }
+
+    /// Enable or disable charging the experimental gas costs in the interpreter.
+    pub const fn with_real_gas(mut self, real_gas: bool) -> Self {
+        self.config.real_gas = real_gas;
+        self
+    }
+
+    /// Set the number of contract bytecodes cached across blocks.
+    pub const fn with_code_cache_size(mut self, size: usize) -> Self {
+        self.config.code_cache_size = size;
+        self
+    }
+
+    /// Enable or disable the operation count self-consistency guard.
+    pub const fn with_check_op_count_consistency(mut self, check: bool) -> Self {
+        self.config.check_op_count_consistency = check;
+        self
+    }
+
+    /// Set the experimental base fee multiplier.
+    pub const fn with_base_fee_multiplier(mut self, base_fee_multiplier: f64) -> Self {
+        self.config.base_fee_multiplier = Some(base_fee_multiplier);
+        self
+    }
+
+    /// Enable or disable persisting call trees for per-frame gas comparison.
+    pub const fn with_persist_frame_gas(mut self, persist_frame_gas: bool) -> Self {
+        self.config.persist_frame_gas = persist_frame_gas;
+        self
+    }
+
+    /// Set the opcodes charged at their normal cost.
+    pub fn with_excluded_opcodes(mut self, opcodes: impl IntoIterator<Item = u8>) -> Self {
+        self.config.excluded_opcodes = opcodes.into_iter().collect();
+        self
+    }
+
+    /// Enable or disable the intrinsic gas adjustment of the simulated gas limit.
+    pub const fn with_adjust_intrinsic_gas(mut self, adjust_intrinsic_gas: bool) -> Self {
+        self.config.adjust_intrinsic_gas = adjust_intrinsic_gas;
+        self
+    }
+
+    /// Enable or disable quiet per-divergence logging.
+    pub const fn with_quiet(mut self, quiet: bool) -> Self {
+        self.config.quiet = quiet;
+        self
+    }
+
+    /// Set the interval between progress log lines.
+    pub const fn with_progress_interval(mut self, interval: Duration) -> Self {
+        self.config.progress_interval = Some(interval);
+        self
+    }
+
+    /// Enable or disable comparing post-transaction state by digest.
+    pub const fn with_state_digest(mut self, state_digest: bool) -> Self {
+        self.config.state_digest = state_digest;
+        self
+    }
+
+    /// Enable or disable warming the block's transaction recipients.
+    pub const fn with_seed_warm_from_block(mut self, seed: bool) -> Self {
+        self.config.seed_warm_from_block = seed;
+        self
+    }
+
+    /// Enable or disable carrying the experimental state between a block's transactions.
+    pub const fn with_sequential_state(mut self, sequential: bool) -> Self {
+        self.config.sequential_state = sequential;
+        self
+    }
+
+    /// Set the classifier assigning a pattern to experimental OOGs.
+    pub fn with_oog_classifier(mut self, classifier: impl OogClassifier + 'static) -> Self {
+        self.config.oog_classifier = Arc::new(classifier);
+        self
+    }
+
+    /// Set the accounts left out of the post-transaction state comparison.
+    pub fn with_ignore_addresses(mut self, addresses: impl IntoIterator<Item = Address>) -> Self {
+        self.config.ignore_addresses = addresses.into_iter().collect();
+        self
+    }
+
+    /// Enable or disable comparing the block beneficiary's balance.
+    pub const fn with_compare_beneficiary_balance(mut self, compare: bool) -> Self {
+        self.config.compare_beneficiary_balance = compare;
+        self
+    }
+
+    /// Enable or disable comparing the access lists of both executions.
+    pub const fn with_compare_access_lists(mut self, compare: bool) -> Self {
+        self.config.compare_access_lists = compare;
+        self
+    }
+
+    /// Set the comparator deciding whether post-transaction states diverge.
+    pub fn with_state_comparator(mut self, comparator: impl StateComparator + 'static) -> Self {
+        self.config.state_comparator = Arc::new(comparator);
+        self
+    }
+
+    /// Set the fraction of transactions analyzed.
+    pub const fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+        self.config.sample_rate = sample_rate;
+        self
+    }
+
+    /// Bias sampling towards contracts that diverged recently.
+    pub const fn with_adaptive_sampling(mut self, adaptive: AdaptiveSampling) -> Self {
+        self.config.adaptive_sampling = Some(adaptive);
+        self
+    }
+
+    /// Skip calls of shapes already analyzed without diverging.
+    pub const fn with_skip_filter(mut self, filter: SkipFilter) -> Self {
+        self.config.skip_filter = Some(filter);
+        self
+    }
+
+    /// Analyze or skip system transactions.
+    pub const fn with_include_system_txs(mut self, include: bool) -> Self {
+        self.config.include_system_txs = include;
+        self
+    }
+
+    /// Include or exclude system contract state from the state comparison.
+    pub const fn with_include_system_state(mut self, include: bool) -> Self {
+        self.config.include_system_state = include;
+        self
+    }
+
+    /// Only record divergences including at least one of `divergence_types`.
+    pub fn with_only_types(
+        mut self,
+        divergence_types: impl IntoIterator<Item = DivergenceType>,
+    ) -> Self {
+        self.config.only_types = Some(divergence_types.into_iter().collect());
+        self
+    }
+
+    /// Set the function selectors whose transactions are always traced in detail.
+    pub fn with_deep_trace_selectors(
+        mut self,
+        selectors: impl IntoIterator<Item = [u8; 4]>,
+    ) -> Self {
+        self.config.deep_trace_selectors = selectors.into_iter().collect();
+        self
+    }
+
+    /// Set the handling of experimental errors that are neither invalid transactions nor
+    /// execution results.
+    pub const fn with_unknown_error_policy(mut self, policy: UnknownErrorPolicy) -> Self {
+        self.config.unknown_error_policy = policy;
+        self
+    }
+
+    /// Only record gas-only divergences for blocks whose base fee is above `threshold` wei.
+    pub const fn with_economic_gas_threshold(mut self, threshold: u64) -> Self {
+        self.config.economic_gas_threshold = Some(threshold);
+        self
+    }
+
+    /// Set how many words the peak memory of the executions may differ by.
+    pub const fn with_memory_tolerance_words(mut self, words: u64) -> Self {
+        self.config.memory_tolerance_words = words;
+        self
+    }
+
+    /// Set how much gas a block's summed normal gas may differ from its header's gas used by.
+    pub const fn with_gas_reconciliation_tolerance(mut self, gas: u64) -> Self {
+        self.config.gas_reconciliation_tolerance = gas;
+        self
+    }
+
+    /// Enable or disable confirming experimental-only failures at normal gas costs.
+    pub const fn with_confirm_failures(mut self, confirm: bool) -> Self {
+        self.config.confirm_failures = confirm;
+        self
+    }
+
+    /// Enable or disable skipping experimental execution of transactions that cannot run out of
+    /// gas.
+    pub const fn with_oog_fast_skip(mut self, skip: bool) -> Self {
+        self.config.oog_fast_skip = skip;
+        self
+    }
+
+    /// Set the normal gas used below which transactions are skipped.
+    pub const fn with_min_gas_used(mut self, gas: u64) -> Self {
+        self.config.min_gas_used = Some(gas);
+        self
+    }
+
+    /// Set how many bytes of call frame and event log data are stored per divergence.
+    pub const fn with_max_bytes_per_divergence(mut self, bytes: usize) -> Self {
+        self.config.max_bytes_per_divergence = Some(bytes);
+        self
+    }
+
+    /// Set the decimal places the logged, recorded and stored gas efficiency ratio is rounded to.
+    pub const fn with_ratio_decimals(mut self, decimals: u32) -> Self {
+        self.config.ratio_decimals = decimals;
+        self
+    }
+
+    /// Set the gas left below which matching transactions are recorded as near misses.
+    pub const fn with_near_miss_gas_threshold(mut self, gas: u64) -> Self {
+        self.config.near_miss_gas_threshold = Some(gas);
+        self
+    }
+
+    /// Compare a second experimental gas schedule against the primary one in the same run.
+    pub fn with_schedule_b(mut self, schedule: GasSchedule) -> Self {
+        self.config.schedule_b = Some(schedule);
+        self
+    }
+
+    /// Enable or disable the inspector self-test mode.
+    pub const fn with_self_test(mut self, self_test: bool) -> Self {
+        self.config.self_test = self_test;
+        self
+    }
+
+    /// Set the gas multipliers divergent transactions are swept across.
+    pub fn with_sweep_multipliers(mut self, multipliers: impl IntoIterator<Item = u64>) -> Self {
+        self.config.sweep_multipliers = multipliers.into_iter().collect();
+        self
+    }
+
+    /// Continue after the last block finished by an earlier run if that is later than the
+    /// start block.
+    pub const fn with_resume(mut self, resume: bool) -> Self {
+        self.config.resume = resume;
+        self
+    }
+
+    /// Resume even if the earlier run used different settings.
+    pub const fn with_force_resume(mut self, force: bool) -> Self {
+        self.config.force_resume = force;
+        self
+    }
+
+    /// Validate and build the configuration.
+    pub fn build(self) -> Result<ResearchConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
 /// Configuration errors.
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
@@ -133,9 +1171,54 @@ pub enum ConfigError {
     #[error("Invalid multiplier: {0}")]
     InvalidMultiplier(&'static str),
 
+    /// More ratio decimal places than an `f64` ratio can hold
+    #[error("Invalid ratio_decimals {0}, must be at most {max}", max = MAX_RATIO_DECIMALS)]
+    InvalidRatioDecimals(u32),
+
     /// Invalid file path
     #[error("Invalid path: {0}")]
     InvalidPath(String),
+
+    /// End block precedes the start block
+    #[error("Invalid block range: end block {end} is before start block {start}")]
+    InvalidBlockRange {
+        /// Configured start block
+        start: u64,
+        /// Configured end block
+        end: u64,
+    },
+
+    /// Real gas execution not available in this build
+    #[error("Real gas execution requires the `real-gas` feature")]
+    RealGasUnsupported,
+
+    /// Failures can only be confirmed on a carried experimental state
+    #[error("Confirming failures requires sequential state")]
+    ConfirmFailuresWithoutSequentialState,
+
+    /// Unrecognized unknown error policy
+    #[error("{0}")]
+    InvalidUnknownErrorPolicy(String),
+
+    /// Unrecognized divergence type
+    #[error("{0}")]
+    InvalidDivergenceType(String),
+
+    /// Invalid sample rate or adaptive sampling window
+    #[error("Invalid sampling: {0}")]
+    InvalidSampling(&'static str),
+
+    /// The checkpoint to resume from was written with different settings
+    #[error(
+        "Checkpoint was written with config hash {checkpoint}, but the current config hash is \
+         {current}; resume with force to continue anyway"
+    )]
+    CheckpointMismatch {
+        /// Config hash of the checkpoint
+        checkpoint: B256,
+        /// Config hash of the current configuration
+        current: B256,
+    },
 }
 
 #[cfg(test)]
@@ -158,6 +1241,86 @@ mod tests {
             ..Default::default()
         };
         assert_eq!(config.effective_gas_limit_multiplier(), 256);
+        assert!(!config.expects_oog());
+    }
+
+    #[test]
+    fn test_expects_oog() {
+        assert!(!ResearchConfig::default().expects_oog());
+
+        let config = ResearchConfig {
+            gas_multiplier: 128,
+            gas_limit_multiplier: Some(1),
+            ..Default::default()
+        };
+        assert!(config.expects_oog());
+        assert!(config.validate().is_ok());
+
+        let config = ResearchConfig { gas_limit_multiplier: Some(128), ..config };
+        assert!(!config.expects_oog());
+    }
+
+    #[test]
+    fn test_expects_tx_oog() {
+        let config = ResearchConfig { gas_multiplier: 128, ..Default::default() };
+
+        // 128 * 100,000 gas does not fit a 1,000,000 gas limit, 128 * 7,000 gas does
+        assert!(config.expects_tx_oog(100_000, 1_000_000));
+        assert!(!config.expects_tx_oog(7_000, 1_000_000));
+    }
+
+    #[test]
+    fn test_cannot_oog() {
+        // A 30M gas limit leaves room for 100,000 * 128 * 2, but not for 1,000,000 * 128 * 2
+        let config = ResearchConfig::default();
+        assert!(config.cannot_oog(100_000, 30_000_000));
+        assert!(!config.cannot_oog(1_000_000, 30_000_000));
+
+        // Experimental execution keeps the transaction gas limit whatever the simulated one
+        let config = ResearchConfig { gas_limit_multiplier: Some(1_024), ..config };
+        assert!(!config.cannot_oog(1_000_000, 30_000_000));
+    }
+
+    #[test]
+    fn test_only_oog_types() {
+        assert!(!ResearchConfig::default().only_oog_types());
+
+        let only = |only_types: &[DivergenceType]| ResearchConfig {
+            only_types: Some(only_types.iter().copied().collect()),
+            ..Default::default()
+        };
+        assert!(only(&[DivergenceType::Status]).only_oog_types());
+        assert!(only(&[DivergenceType::Status, DivergenceType::GasPattern]).only_oog_types());
+        assert!(!only(&[DivergenceType::Status, DivergenceType::StateRoot]).only_oog_types());
+    }
+
+    #[test]
+    fn test_executes_concurrently() {
+        assert!(!ResearchConfig::default().executes_concurrently());
+
+        let concurrent = ResearchConfig { concurrent_execution: true, ..Default::default() };
+        assert!(concurrent.executes_concurrently());
+        assert!(!ResearchConfig { min_gas_used: Some(50_000), ..concurrent.clone() }
+            .executes_concurrently());
+
+        let oog_only = ResearchConfig {
+            only_types: Some([DivergenceType::Status].into_iter().collect()),
+            ..concurrent.clone()
+        };
+        assert!(oog_only.executes_concurrently());
+        assert!(!ResearchConfig { oog_fast_skip: true, ..oog_only }.executes_concurrently());
+        // The fast skip only applies while only OOG types are analyzed
+        assert!(ResearchConfig { oog_fast_skip: true, ..concurrent }.executes_concurrently());
+    }
+
+    #[test]
+    fn test_real_gas_requires_feature() {
+        let config = ResearchConfig::builder().with_real_gas(true).build();
+        if cfg!(feature = "real-gas") {
+            assert!(config.unwrap().real_gas);
+        } else {
+            assert!(matches!(config, Err(ConfigError::RealGasUnsupported)));
+        }
     }
 
     #[test]
@@ -166,6 +1329,132 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_experimental_base_fee() {
+        let config = ResearchConfig::default();
+        assert_eq!(config.experimental_base_fee(Some(10)), None);
+
+        let config = ResearchConfig { base_fee_multiplier: Some(2.5), ..Default::default() };
+        assert_eq!(config.experimental_base_fee(Some(10)), Some(25));
+        // Pre-1559 blocks have no base fee to adjust
+        assert_eq!(config.experimental_base_fee(None), None);
+
+        let config = ResearchConfig { base_fee_multiplier: Some(-1.0), ..Default::default() };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_excluded_opcode_multiplier() {
+        let config = ResearchConfig {
+            gas_multiplier: 64,
+            excluded_opcodes: HashSet::from([0x5b]),
+            ..Default::default()
+        };
+        assert_eq!(config.opcode_multiplier(0x5b), 1); // JUMPDEST
+        assert_eq!(config.opcode_multiplier(0x54), 64); // SLOAD
+    }
+
+    #[test]
+    fn test_invalid_sampling() {
+        let config = ResearchConfig { sample_rate: 1.5, ..Default::default() };
+        assert!(config.validate().is_err());
+
+        let adaptive = AdaptiveSampling { window_blocks: 0, ..Default::default() };
+        assert!(ResearchConfig::builder().with_adaptive_sampling(adaptive).build().is_err());
+
+        let filter = SkipFilter { recheck_rate: -0.1, ..Default::default() };
+        assert!(ResearchConfig::builder().with_skip_filter(filter).build().is_err());
+    }
+
+    #[test]
+    fn test_builder() {
+        let config = ResearchConfig::builder()
+            .with_gas_multiplier(64)
+            .with_start_block(100)
+            .with_divergence_db_path("/tmp/divergences.db")
+            .with_trace_detail(TraceDetail::Detailed)
+            .with_excluded_opcodes([0x5b])
+            .build()
+            .unwrap();
+
+        assert_eq!(config.gas_multiplier, 64);
+        assert_eq!(config.start_block, 100);
+        assert_eq!(config.divergence_db_path, PathBuf::from("/tmp/divergences.db"));
+        assert_eq!(config.trace_detail, TraceDetail::Detailed);
+        assert_eq!(config.excluded_opcodes, HashSet::from([0x5b]));
+        // Unset fields keep their defaults
+        assert_eq!(config.refund_multiplier, 1.0);
+        assert!(config.check_op_count_consistency);
+    }
+
+    #[test]
+    fn test_builder_validates() {
+        let err = ResearchConfig::builder().with_gas_multiplier(0).build().unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidMultiplier(_)));
+
+        let err =
+            ResearchConfig::builder().with_start_block(10).with_end_block(5).build().unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidBlockRange { start: 10, end: 5 }));
+
+        let err = ResearchConfig::builder().with_base_fee_multiplier(-1.0).build().unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidMultiplier(_)));
+
+        let err = ResearchConfig::builder().with_ratio_decimals(16).build().unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidRatioDecimals(16)));
+
+        let err = ResearchConfig::builder().with_confirm_failures(true).build().unwrap_err();
+        assert!(matches!(err, ConfigError::ConfirmFailuresWithoutSequentialState));
+        let config = ResearchConfig::builder()
+            .with_confirm_failures(true)
+            .with_sequential_state(true)
+            .build()
+            .unwrap();
+        assert!(config.confirm_failures);
+    }
+
+    #[test]
+    fn test_resume_start_block() {
+        let config = ResearchConfig { start_block: 100, resume: true, ..Default::default() };
+        let checkpoint = |block_number, config_hash| Some(Checkpoint { block_number, config_hash });
+        let current = config.config_hash();
+
+        // Continue after the checkpoint, but never before the start block
+        assert_eq!(config.resume_start_block(checkpoint(200, current).as_ref()).unwrap(), 201);
+        assert_eq!(config.resume_start_block(checkpoint(50, current).as_ref()).unwrap(), 100);
+        assert_eq!(config.resume_start_block(None).unwrap(), 100);
+
+        // Checkpoints of runs with other settings are refused unless forced
+        let other = ResearchConfig { gas_multiplier: 64, ..Default::default() }.config_hash();
+        assert_ne!(other, current);
+        let err = config.resume_start_block(checkpoint(200, other).as_ref()).unwrap_err();
+        assert!(matches!(err, ConfigError::CheckpointMismatch { checkpoint, current: hash }
+            if checkpoint == other && hash == current));
+        let forced = ResearchConfig { force_resume: true, ..config.clone() };
+        assert_eq!(forced.resume_start_block(checkpoint(200, other).as_ref()).unwrap(), 201);
+
+        // Without resume the checkpoint is ignored
+        let fresh = ResearchConfig { resume: false, ..config };
+        assert_eq!(fresh.resume_start_block(checkpoint(200, other).as_ref()).unwrap(), 100);
+    }
+
+    #[test]
+    fn test_config_hash_ignores_run_settings() {
+        let config = ResearchConfig::default();
+        let moved = ResearchConfig {
+            start_block: 100,
+            divergence_db_path: PathBuf::from("/tmp/other.db"),
+            max_parallel_txs: 1,
+            quiet: true,
+            progress_interval: Some(Duration::from_secs(60)),
+            resume: true,
+            ..Default::default()
+        };
+        assert_eq!(moved.config_hash(), config.config_hash());
+
+        let excluded = ResearchConfig { excluded_opcodes: HashSet::from([0x5b]), ..config };
+        assert_ne!(excluded.config_hash(), moved.config_hash());
+    }
+
     #[test]
     fn test_trace_detail_parsing() {
         assert_eq!("minimal".parse::<TraceDetail>().unwrap(), TraceDetail::Minimal);
@@ -173,4 +1462,11 @@ mod tests {
         assert_eq!("detailed".parse::<TraceDetail>().unwrap(), TraceDetail::Detailed);
         assert!("invalid".parse::<TraceDetail>().is_err());
     }
+
+    #[test]
+    fn test_unknown_error_policy_parsing() {
+        assert_eq!("skip".parse::<UnknownErrorPolicy>().unwrap(), UnknownErrorPolicy::Skip);
+        assert_eq!("Record".parse::<UnknownErrorPolicy>().unwrap(), UnknownErrorPolicy::Record);
+        assert!("ignore".parse::<UnknownErrorPolicy>().is_err());
+    }
 }
@@ -0,0 +1,234 @@
+//! Live aggregates of a divergence database for dashboards.
+//!
+//! [`DashboardState`] follows a database the way `reth-research watch` does: each
+//! [`DashboardState::refresh`] only fetches the rows recorded since the previous one, and folds
+//! them into the aggregates the `reth-research top` dashboard shows: the divergence rate over
+//! the latest blocks, the contracts causing the most divergences, the breakdown by type and the
+//! latest divergences.
+
+use crate::{
+    database::{DatabaseError, DivergenceDatabase, DivergenceRecord},
+    divergence::DivergenceType,
+    frequent::FrequentItems,
+};
+use alloy_primitives::Address;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Maximum number of rows fetched per query while refreshing.
+const REFRESH_BATCH_SIZE: usize = 1000;
+
+/// Number of counters tracking the contracts causing the most divergences.
+const CONTRACTS_CAPACITY: usize = 256;
+
+/// Number of latest blocks the divergence rate covers.
+pub const RATE_BLOCKS: u64 = 120;
+
+/// Number of latest divergences kept.
+pub const RECENT_DIVERGENCES: usize = 50;
+
+/// Aggregates of the divergences recorded in a database, updated incrementally.
+#[derive(Debug, Clone)]
+pub struct DashboardState {
+    /// Row id of the last divergence aggregated
+    last_id: i64,
+
+    /// Number of divergences aggregated
+    total: u64,
+
+    /// Number of divergences including each type
+    by_type: BTreeMap<DivergenceType, u64>,
+
+    /// Contracts the divergences are attributed to
+    contracts: FrequentItems<Address>,
+
+    /// Number of divergences per block, for the latest [`RATE_BLOCKS`] blocks
+    per_block: BTreeMap<u64, u64>,
+
+    /// Latest divergences, newest first
+    recent: VecDeque<DivergenceRecord>,
+}
+
+impl Default for DashboardState {
+    fn default() -> Self {
+        Self {
+            last_id: 0,
+            total: 0,
+            by_type: BTreeMap::new(),
+            contracts: FrequentItems::new(CONTRACTS_CAPACITY),
+            per_block: BTreeMap::new(),
+            recent: VecDeque::with_capacity(RECENT_DIVERGENCES),
+        }
+    }
+}
+
+impl DashboardState {
+    /// Create an empty state that aggregates a database from its first row.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Aggregate the divergences recorded in `db` since the last refresh, returning how many
+    /// there were.
+    pub fn refresh(&mut self, db: &DivergenceDatabase) -> Result<usize, DatabaseError> {
+        let mut fetched = 0;
+        loop {
+            let records = db.since_id(self.last_id, REFRESH_BATCH_SIZE)?;
+            let caught_up = records.len() < REFRESH_BATCH_SIZE;
+            fetched += records.len();
+            for record in records {
+                self.record(record);
+            }
+            if caught_up {
+                return Ok(fetched)
+            }
+        }
+    }
+
+    /// Fold one divergence into the aggregates, skipping rows that are not
+    /// [counted](crate::divergence::Divergence::is_counted).
+    fn record(&mut self, record: DivergenceRecord) {
+        let divergence = &record.divergence;
+        self.last_id = record.id;
+        if !divergence.is_counted() {
+            return
+        }
+        self.total += 1;
+        for divergence_type in divergence.sorted_types() {
+            *self.by_type.entry(divergence_type).or_default() += 1;
+        }
+        if let Some(contract) = divergence.contract() {
+            self.contracts.record(contract);
+        }
+
+        // Divergences can be recorded out of block order, the window follows the latest block
+        *self.per_block.entry(divergence.block_number).or_default() += 1;
+        if let Some(first) = self.first_rate_block() {
+            self.per_block = self.per_block.split_off(&first);
+        }
+
+        if self.recent.len() == RECENT_DIVERGENCES {
+            self.recent.pop_back();
+        }
+        self.recent.push_front(record);
+    }
+
+    /// First block of the divergence rate window, if any divergence was aggregated.
+    fn first_rate_block(&self) -> Option<u64> {
+        let (latest, _) = self.per_block.last_key_value()?;
+        Some(latest.saturating_sub(RATE_BLOCKS - 1))
+    }
+
+    /// Number of divergences aggregated.
+    pub const fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Number of divergences including each type.
+    pub const fn by_type(&self) -> &BTreeMap<DivergenceType, u64> {
+        &self.by_type
+    }
+
+    /// The `n` contracts causing the most divergences, most frequent first.
+    ///
+    /// Counts come from a bounded [`FrequentItems`] summary and may undercount by at most
+    /// [`FrequentItems::max_error`].
+    pub fn top_contracts(&self, n: usize) -> Vec<(Address, u64)> {
+        self.contracts.top(n)
+    }
+
+    /// Number of divergences in each of the last [`RATE_BLOCKS`] blocks up to the latest one
+    /// with a divergence (fewer near genesis), oldest first.
+    pub fn rate(&self) -> Vec<u64> {
+        let Some(first) = self.first_rate_block() else { return Vec::new() };
+        let (&latest, _) = self.per_block.last_key_value().expect("window has a latest block");
+        (first..=latest).map(|block| self.per_block.get(&block).copied().unwrap_or(0)).collect()
+    }
+
+    /// Latest divergences, newest first.
+    pub fn recent(&self) -> impl Iterator<Item = &DivergenceRecord> {
+        self.recent.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::DivergenceBuilder;
+
+    #[test]
+    fn test_refresh_aggregates_seeded_divergences() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let mut state = DashboardState::new();
+        assert_eq!(state.refresh(&db).unwrap(), 0);
+        assert!(state.rate().is_empty());
+
+        let (a, b) = (Address::repeat_byte(0xaa), Address::repeat_byte(0xbb));
+        let status = [DivergenceType::Status, DivergenceType::GasPattern];
+        for divergence in [
+            DivergenceBuilder::new(10).with_types(status).with_contract(a),
+            DivergenceBuilder::new(10).with_contract(b),
+            DivergenceBuilder::new(12).with_types(status).with_contract(a),
+            DivergenceBuilder::new(13).with_types([DivergenceType::GasPattern]),
+        ] {
+            db.record_divergence(&divergence.build()).unwrap();
+        }
+
+        assert_eq!(state.refresh(&db).unwrap(), 4);
+        assert_eq!(state.total(), 4);
+        assert_eq!(
+            state.by_type(),
+            &BTreeMap::from([
+                (DivergenceType::StateRoot, 1),
+                (DivergenceType::Status, 2),
+                (DivergenceType::GasPattern, 3),
+            ])
+        );
+        assert_eq!(
+            state.top_contracts(2),
+            vec![(a, 2), (b, 1)]
+        );
+        let recent: Vec<_> = state.recent().map(|record| record.divergence.block_number).collect();
+        assert_eq!(recent, vec![13, 12, 10, 10]);
+
+        // The rate runs from the first block up to the latest one, zero-filled
+        let rate = state.rate();
+        assert_eq!(rate.len(), 14);
+        assert_eq!(rate[10..], [2, 0, 1, 1]);
+
+        // Only new rows are aggregated on the next refresh
+        assert_eq!(state.refresh(&db).unwrap(), 0);
+        let memory = DivergenceBuilder::new(14).with_types([DivergenceType::Memory]).build();
+        db.record_divergence(&memory).unwrap();
+        assert_eq!(state.refresh(&db).unwrap(), 1);
+        assert_eq!(state.total(), 5);
+        assert_eq!(state.by_type()[&DivergenceType::Memory], 1);
+    }
+
+    #[test]
+    fn test_refresh_bounds_windows() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let blocks = RATE_BLOCKS + RECENT_DIVERGENCES as u64;
+        for block_number in 0..blocks {
+            let divergence =
+                DivergenceBuilder::new(block_number).with_types([DivergenceType::Status]).build();
+            db.record_divergence(&divergence).unwrap();
+        }
+
+        let mut state = DashboardState::new();
+        assert_eq!(state.refresh(&db).unwrap(), blocks as usize);
+        assert_eq!(state.total(), blocks);
+
+        // Only the latest blocks and divergences are kept
+        assert_eq!(state.rate(), vec![1; RATE_BLOCKS as usize]);
+        assert_eq!(state.recent().count(), RECENT_DIVERGENCES);
+        assert_eq!(state.recent().next().unwrap().divergence.block_number, blocks - 1);
+
+        // A late divergence for a block before the window is counted but not charted
+        let late =
+            DivergenceBuilder::new(0).with_tx_index(1).with_types([DivergenceType::Status]).build();
+        db.record_divergence(&late).unwrap();
+        assert_eq!(state.refresh(&db).unwrap(), 1);
+        assert_eq!(state.total(), blocks + 1);
+        assert_eq!(state.rate(), vec![1; RATE_BLOCKS as usize]);
+    }
+}
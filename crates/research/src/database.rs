@@ -1,12 +1,32 @@
 //! Database for storing divergence data.
 
-use crate::divergence::{CallFrame, Divergence, DivergenceType, EventLog};
-use rusqlite::{params, Connection};
+use crate::{
+    config::ResearchConfig,
+    divergence::{
+        CallFrame, CallTrees, CalldataStats, Create2Deployment, CreatedCode, Divergence,
+        DivergenceLocation, DivergenceType, EventLog, GasAnalysis, GasObservation,
+        OperationCounts, OutOfGasInfo,
+    },
+    executor::ResearchStats,
+    inspector::opcode_mnemonic,
+    metrics,
+    store::DivergenceStore,
+};
+use alloy_primitives::{Address, Bytes, B256};
+use rusqlite::{params, types::Type, Connection, ErrorCode, OpenFlags, OptionalExtension, Row};
+use serde::{de::DeserializeOwned, Serialize};
 use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
     path::Path,
-    sync::{Arc, Mutex},
+    str::FromStr,
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
 };
 use thiserror::Error;
+use tracing::{debug, info, warn};
 
 /// Errors that can occur when working with the divergence database.
 #[derive(Debug, Error)]
@@ -21,11 +41,750 @@ pub enum DatabaseError {
 
     /// JSON serialization/deserialization error
     #[error("Serialization error: {0}")]
-    Serialization(String),
+    Serialization(#[from] serde_json::Error),
 
     /// Database has not been initialized
     #[error("Database not initialized")]
     NotInitialized,
+
+    /// Compression format not available in this build
+    #[error("{0:?} compression requires the `compression` feature")]
+    UnsupportedCompression(Compression),
+
+    /// Query parameters out of range
+    #[error("Invalid query: {0}")]
+    InvalidQuery(&'static str),
+
+    /// Storage backend not available in this build
+    #[error("PostgreSQL storage requires the `postgres` feature")]
+    PostgresUnsupported,
+
+    /// Background writer has shut down and accepts no more rows
+    #[error("Database writer closed")]
+    WriterClosed,
+
+    /// Database written with a schema version this build cannot read or migrate
+    #[error(
+        "Unsupported database schema version {0}, expected at most {max}",
+        max = DATABASE_SCHEMA_VERSION
+    )]
+    UnsupportedSchemaVersion(u32),
+
+    /// Divergence insert ignored as a duplicate although no row has its uid
+    #[error("Divergence {0} was ignored as a duplicate but is not recorded")]
+    DuplicateNotFound(B256),
+
+    /// PostgreSQL database error
+    #[cfg(feature = "postgres")]
+    #[error("PostgreSQL error: {0}")]
+    Postgres(#[from] tokio_postgres::Error),
+}
+
+/// Compression applied to JSONL divergence exports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// Plain JSONL
+    #[default]
+    None,
+
+    /// Gzip-compressed JSONL (`.jsonl.gz`)
+    Gzip,
+
+    /// Zstandard-compressed JSONL (`.jsonl.zst`)
+    Zstd,
+}
+
+impl Compression {
+    /// Detect the compression from a file's extension, defaulting to none.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Self::Gzip,
+            Some("zst") => Self::Zstd,
+            _ => Self::None,
+        }
+    }
+}
+
+/// Columns selected when loading a [`Divergence`] from the `divergences` table.
+const DIVERGENCE_COLUMNS: &str = "id, block_number, tx_index, tx_hash, timestamp, divergence_types,
+    normal_gas_used, experimental_gas_used, gas_efficiency_ratio,
+    normal_sload_count, normal_sstore_count, normal_call_count,
+    normal_log_count, normal_total_ops, normal_memory_words, normal_create_count,
+    exp_sload_count, exp_sstore_count, exp_call_count,
+    exp_log_count, exp_total_ops, exp_memory_words, exp_create_count,
+    divergence_contract, divergence_function_selectors_json, divergence_pc,
+    divergence_call_depth, divergence_opcode, divergence_opcode_name,
+    oog_opcode, oog_opcode_name, oog_pc,
+    oog_contract, oog_call_depth, oog_gas_remaining, oog_pattern,
+    normal_extcode_count, exp_extcode_count, oog_gas_trajectory_json, threshold_map_json,
+    normal_tload_count, exp_tload_count, normal_tstore_count, exp_tstore_count,
+    normal_mcopy_count, exp_mcopy_count, divergence_code_hash,
+    normal_gas_charged, exp_gas_charged, reverting_selector, divergence_cause, truncated,
+    oog_precompile, near_miss, comparison,
+    created_address, normal_created_code_hash, exp_created_code_hash, function_name,
+    calldata_len, calldata_zero_bytes, calldata_nonzero_bytes, access_list_diff_json,
+    divergence_code_address, oog_code_address, griefing_json";
+
+/// A divergence loaded from the database together with its row id.
+///
+/// Call trees, event logs and CREATE2 deployments are stored in separate tables and are not
+/// loaded.
+#[derive(Debug, Clone)]
+pub struct DivergenceRecord {
+    /// Row id in the `divergences` table
+    pub id: i64,
+
+    /// The stored divergence
+    pub divergence: Divergence,
+}
+
+impl DivergenceRecord {
+    /// Build a record from a row selected with [`DIVERGENCE_COLUMNS`].
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let tx_hash: Vec<u8> = row.get(3)?;
+        let types: String = row.get(5)?;
+
+        let normal_ops = OperationCounts {
+            sload_count: row.get::<_, i64>(9)? as u64,
+            sstore_count: row.get::<_, i64>(10)? as u64,
+            call_count: row.get::<_, i64>(11)? as u64,
+            log_count: row.get::<_, i64>(12)? as u64,
+            total_ops: row.get::<_, i64>(13)? as u64,
+            memory_words_allocated: row.get::<_, i64>(14)? as u64,
+            create_count: row.get::<_, i64>(15)? as u64,
+            extcode_count: row.get::<_, Option<i64>>(36)?.unwrap_or_default() as u64,
+            tload_count: row.get::<_, Option<i64>>(40)?.unwrap_or_default() as u64,
+            tstore_count: row.get::<_, Option<i64>>(42)?.unwrap_or_default() as u64,
+            mcopy_count: row.get::<_, Option<i64>>(44)?.unwrap_or_default() as u64,
+        };
+        let experimental_ops = OperationCounts {
+            sload_count: row.get::<_, i64>(16)? as u64,
+            sstore_count: row.get::<_, i64>(17)? as u64,
+            call_count: row.get::<_, i64>(18)? as u64,
+            log_count: row.get::<_, i64>(19)? as u64,
+            total_ops: row.get::<_, i64>(20)? as u64,
+            memory_words_allocated: row.get::<_, i64>(21)? as u64,
+            create_count: row.get::<_, i64>(22)? as u64,
+            extcode_count: row.get::<_, Option<i64>>(37)?.unwrap_or_default() as u64,
+            tload_count: row.get::<_, Option<i64>>(41)?.unwrap_or_default() as u64,
+            tstore_count: row.get::<_, Option<i64>>(43)?.unwrap_or_default() as u64,
+            mcopy_count: row.get::<_, Option<i64>>(45)?.unwrap_or_default() as u64,
+        };
+
+        let divergence_location = match row.get::<_, Option<Vec<u8>>>(23)? {
+            Some(contract) => {
+                Some(DivergenceLocation {
+                    contract: Address::from_slice(&contract),
+                    code_address: row
+                        .get::<_, Option<Vec<u8>>>(63)?
+                        .map(|address| Address::from_slice(&address))
+                        .unwrap_or_default(),
+                    function_selectors: read_json_column(row, 24)?.unwrap_or_default(),
+                    pc: row.get::<_, i64>(25)? as usize,
+                    call_depth: row.get::<_, i64>(26)? as usize,
+                    opcode: row.get::<_, i64>(27)? as u8,
+                    opcode_name: row.get(28)?,
+                    code_hash: row
+                        .get::<_, Option<Vec<u8>>>(46)?
+                        .map(|hash| B256::from_slice(&hash))
+                        .unwrap_or_default(),
+                })
+            }
+            None => None,
+        };
+
+        let oog_info = match row.get::<_, Option<i64>>(29)? {
+            Some(opcode) => {
+                let contract: Vec<u8> = row.get(32)?;
+                let pattern: String = row.get(35)?;
+                Some(OutOfGasInfo {
+                    opcode: opcode as u8,
+                    opcode_name: row.get(30)?,
+                    pc: row.get::<_, i64>(31)? as usize,
+                    contract: Address::from_slice(&contract),
+                    code_address: row
+                        .get::<_, Option<Vec<u8>>>(64)?
+                        .map(|address| Address::from_slice(&address))
+                        .unwrap_or_default(),
+                    call_depth: row.get::<_, i64>(33)? as usize,
+                    gas_remaining: row.get::<_, i64>(34)? as u64,
+                    pattern: parse_column(35, &pattern)?,
+                    gas_trajectory: read_json_column(row, 38)?.unwrap_or_default(),
+                    precompile: row
+                        .get::<_, Option<Vec<u8>>>(52)?
+                        .map(|address| Address::from_slice(&address)),
+                })
+            }
+            None => None,
+        };
+
+        let created_code = match row.get::<_, Option<Vec<u8>>>(55)? {
+            Some(address) => {
+                let normal_code_hash: Vec<u8> = row.get(56)?;
+                let experimental_code_hash: Vec<u8> = row.get(57)?;
+                Some(CreatedCode {
+                    address: Address::from_slice(&address),
+                    normal_code_hash: B256::from_slice(&normal_code_hash),
+                    experimental_code_hash: B256::from_slice(&experimental_code_hash),
+                })
+            }
+            None => None,
+        };
+
+        let calldata = match row.get::<_, Option<i64>>(59)? {
+            Some(len) => Some(CalldataStats {
+                len: len as u64,
+                zero_bytes: row.get::<_, i64>(60)? as u64,
+                nonzero_bytes: row.get::<_, i64>(61)? as u64,
+            }),
+            None => None,
+        };
+
+        let divergence_types = types
+            .split(',')
+            .filter(|t| !t.is_empty())
+            .map(|t| parse_column(5, t))
+            .collect::<rusqlite::Result<_>>()?;
+
+        let reverting_selector = row
+            .get::<_, Option<Vec<u8>>>(49)?
+            .map(|selector| {
+                selector.try_into().map_err(|selector: Vec<u8>| {
+                    decode_error(49, Type::Blob, format!("selector of {} bytes", selector.len()))
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            id: row.get(0)?,
+            divergence: Divergence {
+                block_number: row.get::<_, i64>(1)? as u64,
+                tx_index: row.get::<_, i64>(2)? as u64,
+                tx_hash: B256::from_slice(&tx_hash),
+                timestamp: row.get::<_, i64>(4)? as u64,
+                divergence_types,
+                gas_analysis: GasAnalysis {
+                    normal_gas_used: row.get::<_, i64>(6)? as u64,
+                    experimental_gas_used: row.get::<_, i64>(7)? as u64,
+                    gas_efficiency_ratio: row.get(8)?,
+                    normal_gas_charged: row.get::<_, Option<i64>>(47)?.unwrap_or_default() as u64,
+                    experimental_gas_charged: row.get::<_, Option<i64>>(48)?.unwrap_or_default()
+                        as u64,
+                },
+                normal_ops,
+                experimental_ops,
+                divergence_location,
+                oog_info,
+                griefing: read_json_column(row, 65)?,
+                reverting_selector,
+                cause: row
+                    .get::<_, Option<String>>(50)?
+                    .map(|cause| parse_column(50, &cause))
+                    .transpose()?,
+                call_trees: None,
+                event_logs: None,
+                create2_deployments: None,
+                created_code,
+                gas_observations: None,
+                threshold_map: read_json_column(row, 39)?,
+                truncated: row.get::<_, Option<bool>>(51)?.unwrap_or_default(),
+                near_miss: row.get::<_, Option<bool>>(53)?.unwrap_or_default(),
+                comparison: row
+                    .get::<_, Option<String>>(54)?
+                    .map(|comparison| parse_column(54, &comparison))
+                    .transpose()?
+                    .unwrap_or_default(),
+                function_name: row.get(58)?,
+                calldata,
+                access_list_diff: read_json_column(row, 62)?,
+            },
+        })
+    }
+}
+
+/// Decode the JSON stored in column `idx`, if it is set.
+fn read_json_column<T: DeserializeOwned>(
+    row: &Row<'_>,
+    idx: usize,
+) -> rusqlite::Result<Option<T>> {
+    row.get::<_, Option<String>>(idx)?
+        .map(|json| serde_json::from_str(&json).map_err(|err| decode_error(idx, Type::Text, err)))
+        .transpose()
+}
+
+/// Parse `text` read from column `idx`.
+fn parse_column<T: FromStr<Err = String>>(idx: usize, text: &str) -> rusqlite::Result<T> {
+    text.parse().map_err(|err: String| decode_error(idx, Type::Text, err))
+}
+
+/// Error for a value of column `idx` that does not decode to the type it is stored for.
+fn decode_error(
+    idx: usize,
+    column_type: Type,
+    err: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(idx, column_type, err.into())
+}
+
+/// Gas used by a single call frame in both executions, matched by call index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameGas {
+    /// Index of the frame in call order
+    pub call_index: usize,
+
+    /// Gas used by the frame in normal execution (None if the frame only ran experimentally)
+    pub normal_gas_used: Option<u64>,
+
+    /// Simulated gas used by the frame in experimental execution (None if it only ran normally)
+    pub simulated_gas_used: Option<u64>,
+}
+
+/// Divergences attributed to a single bytecode hash, across every contract running that code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeHashGroup {
+    /// Hash of the bytecode executing at the divergence
+    pub code_hash: B256,
+
+    /// Number of divergences located in this bytecode
+    pub divergence_count: u64,
+
+    /// Number of distinct contract addresses running this bytecode
+    pub contract_count: u64,
+}
+
+/// Number of transactions analyzed in a block, recorded whether or not any diverged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockSummary {
+    /// Block number
+    pub block_number: u64,
+
+    /// Number of transactions dual-executed in the block
+    pub analyzed_tx_count: u64,
+
+    /// Gas used by the block according to its header
+    pub header_gas_used: u64,
+
+    /// Gas used by the block's transactions in normal execution, summed, if they were executed
+    /// in order against the block's state
+    pub normal_gas_used: Option<u64>,
+}
+
+impl BlockSummary {
+    /// Whether the summed normal gas differs from the header's gas used by more than
+    /// `tolerance`, which points to a bug in how gas is accounted per transaction.
+    ///
+    /// Always false if the normal gas was not summed.
+    pub fn gas_mismatch(&self, tolerance: u64) -> bool {
+        self.normal_gas_used.is_some_and(|gas| gas.abs_diff(self.header_gas_used) > tolerance)
+    }
+}
+
+/// The last block a run finished analyzing, and the settings it ran with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Last block whose analysis finished
+    pub block_number: u64,
+
+    /// [`ResearchConfig::config_hash`](crate::config::ResearchConfig::config_hash) of the run
+    pub config_hash: B256,
+}
+
+/// A run recorded in the `runs` table.
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    /// Row id in the `runs` table
+    pub id: i64,
+
+    /// [`ResearchConfig::config_hash`] of the run
+    pub config_hash: B256,
+
+    /// [`ResearchConfig::settings`] of the run
+    pub settings: String,
+
+    /// First block the run analyzed, after resuming from a checkpoint
+    pub start_block: u64,
+
+    /// Last block the run was configured to analyze, if limited
+    pub end_block: Option<u64>,
+
+    /// Unix time the run started at
+    pub started_at: u64,
+
+    /// Unix time the run ended at (None if it is still running or did not shut down cleanly)
+    pub ended_at: Option<u64>,
+
+    /// Statistics the run ended with (None if it has not ended)
+    pub stats: Option<ResearchStats>,
+}
+
+/// Version of the database schema, stored as SQLite's `user_version`.
+///
+/// Bump this with every schema change and add the migration from the previous version to
+/// `migrate_schema`.
+///
+/// - 0: tables created before schema versioning, without the divergences columns in
+///   `DIVERGENCES_COLUMNS_V1`.
+/// - 1: initial versioned schema.
+/// - 2: the divergences columns in `DIVERGENCES_COLUMNS_V2` and the `gas_observations` table.
+pub const DATABASE_SCHEMA_VERSION: u32 = 2;
+
+/// Columns added to the divergences table before schema versioning, i.e. missing from databases
+/// at version 0, as name and type.
+const DIVERGENCES_COLUMNS_V1: &[(&str, &str)] = &[
+    ("normal_gas_charged", "INTEGER"),
+    ("exp_gas_charged", "INTEGER"),
+    ("divergence_code_hash", "BLOB"),
+    ("oog_gas_trajectory_json", "TEXT"),
+    ("reverting_selector", "BLOB"),
+    ("divergence_cause", "TEXT"),
+    ("threshold_map_json", "TEXT"),
+    ("normal_extcode_count", "INTEGER"),
+    ("exp_extcode_count", "INTEGER"),
+    ("normal_tload_count", "INTEGER"),
+    ("exp_tload_count", "INTEGER"),
+    ("normal_tstore_count", "INTEGER"),
+    ("exp_tstore_count", "INTEGER"),
+    ("normal_mcopy_count", "INTEGER"),
+    ("exp_mcopy_count", "INTEGER"),
+];
+
+/// Columns added to the divergences table in schema version 2, as name and type.
+const DIVERGENCES_COLUMNS_V2: &[(&str, &str)] = &[
+    ("divergence_uid", "BLOB"),
+    ("oog_precompile", "BLOB"),
+    ("truncated", "BOOLEAN"),
+    ("near_miss", "BOOLEAN"),
+    ("comparison", "TEXT"),
+    ("created_address", "BLOB"),
+    ("normal_created_code_hash", "BLOB"),
+    ("exp_created_code_hash", "BLOB"),
+    ("function_name", "TEXT"),
+    ("calldata_len", "INTEGER"),
+    ("calldata_zero_bytes", "INTEGER"),
+    ("calldata_nonzero_bytes", "INTEGER"),
+    ("access_list_diff_json", "TEXT"),
+    ("divergence_code_address", "BLOB"),
+    ("oog_code_address", "BLOB"),
+    ("griefing_json", "TEXT"),
+];
+
+/// How long SQLite itself waits on a lock held by another connection before failing with
+/// `SQLITE_BUSY`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of times a write failing with `SQLITE_BUSY` or `SQLITE_LOCKED` is retried.
+const WRITE_RETRIES: u32 = 5;
+
+/// Backoff before the first retry of a write, doubled for every further retry.
+const RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Main divergences table
+const DIVERGENCES_TABLE: &str = "CREATE TABLE IF NOT EXISTS divergences (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    block_number INTEGER NOT NULL,
+    tx_index INTEGER NOT NULL,
+    tx_hash BLOB NOT NULL,
+    timestamp INTEGER NOT NULL,
+
+    -- Content-addressed id, equal for the same divergence in every database
+    divergence_uid BLOB,
+
+    -- Divergence classification (comma-separated types)
+    divergence_types TEXT NOT NULL,
+
+    -- Gas analysis
+    normal_gas_used INTEGER NOT NULL,
+    experimental_gas_used INTEGER NOT NULL,
+    gas_efficiency_ratio REAL NOT NULL,
+    normal_gas_charged INTEGER,
+    exp_gas_charged INTEGER,
+
+    -- Operation counts (normal)
+    normal_sload_count INTEGER,
+    normal_sstore_count INTEGER,
+    normal_call_count INTEGER,
+    normal_log_count INTEGER,
+    normal_total_ops INTEGER,
+    normal_memory_words INTEGER,
+    normal_create_count INTEGER,
+
+    -- Operation counts (experimental)
+    exp_sload_count INTEGER,
+    exp_sstore_count INTEGER,
+    exp_call_count INTEGER,
+    exp_log_count INTEGER,
+    exp_total_ops INTEGER,
+    exp_memory_words INTEGER,
+    exp_create_count INTEGER,
+
+    -- Divergence location
+    divergence_contract BLOB,
+    divergence_function_selector BLOB,
+    divergence_function_selectors_json TEXT,
+    divergence_pc INTEGER,
+    divergence_call_depth INTEGER,
+    divergence_opcode INTEGER,
+    divergence_opcode_name TEXT,
+    divergence_code_hash BLOB,
+
+    -- OOG analysis
+    oog_occurred BOOLEAN,
+    oog_opcode INTEGER,
+    oog_opcode_name TEXT,
+    oog_pc INTEGER,
+    oog_contract BLOB,
+    oog_call_depth INTEGER,
+    oog_gas_remaining INTEGER,
+    oog_pattern TEXT,
+    oog_gas_trajectory_json TEXT,
+    oog_precompile BLOB,
+
+    -- Selector of the deepest frame that started the experimental revert
+    reverting_selector BLOB,
+
+    -- Whether the gas costs or the experimental state drove the divergence
+    divergence_cause TEXT,
+
+    -- Multiplier at which each divergence type first appeared in a sweep
+    threshold_map_json TEXT,
+
+    -- EXTCODESIZE/EXTCODECOPY/EXTCODEHASH counts
+    normal_extcode_count INTEGER,
+    exp_extcode_count INTEGER,
+
+    -- TLOAD/TSTORE counts
+    normal_tload_count INTEGER,
+    exp_tload_count INTEGER,
+    normal_tstore_count INTEGER,
+    exp_tstore_count INTEGER,
+
+    -- MCOPY counts
+    normal_mcopy_count INTEGER,
+    exp_mcopy_count INTEGER,
+
+    -- Whether call frame or event log data was dropped to stay under the size cap
+    truncated BOOLEAN,
+
+    -- Whether the executions matched with little gas left in experimental execution
+    near_miss BOOLEAN,
+
+    -- Executions the divergence was found between (normal_a, normal_b or a_b)
+    comparison TEXT,
+
+    -- Contract created by the transaction and the hashes of the runtime code each execution
+    -- deployed, if they differ
+    created_address BLOB,
+    normal_created_code_hash BLOB,
+    exp_created_code_hash BLOB,
+
+    -- Signature of the function at the divergence location, resolved from a signature database
+    function_name TEXT,
+
+    -- Size and zero/non-zero byte split of the transaction's calldata
+    calldata_len INTEGER,
+    calldata_zero_bytes INTEGER,
+    calldata_nonzero_bytes INTEGER,
+
+    -- Accounts and storage slots only one execution accessed (detailed traces)
+    access_list_diff_json TEXT,
+
+    -- Addresses the code at the divergence and OOG was loaded from, differing from the
+    -- contract in DELEGATECALL and CALLCODE frames
+    divergence_code_address BLOB,
+    oog_code_address BLOB,
+
+    -- Child call that reverted after consuming at least 63/64 of its gas (not an OOG)
+    griefing_json TEXT,
+
+    created_at INTEGER DEFAULT (strftime('%s', 'now'))
+)";
+
+/// Call trees table
+const CALL_TREES_TABLE: &str = "CREATE TABLE IF NOT EXISTS call_trees (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    divergence_id INTEGER NOT NULL,
+    is_experimental BOOLEAN NOT NULL,
+    call_index INTEGER NOT NULL,
+    depth INTEGER NOT NULL,
+    from_addr BLOB NOT NULL,
+    to_addr BLOB,
+    call_type TEXT NOT NULL,
+    gas_provided INTEGER,
+    gas_used INTEGER,
+    success BOOLEAN,
+    out_of_gas BOOLEAN,
+    input_len INTEGER,
+    output_len INTEGER,
+    input BLOB,
+    output BLOB,
+
+    -- Per-frame gas from both executions, matched by call_index
+    normal_gas_used INTEGER,
+    simulated_gas_used INTEGER,
+    FOREIGN KEY (divergence_id) REFERENCES divergences(id) ON DELETE CASCADE
+)";
+
+/// Event logs table
+const EVENT_LOGS_TABLE: &str = "CREATE TABLE IF NOT EXISTS event_logs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    divergence_id INTEGER NOT NULL,
+    is_experimental BOOLEAN NOT NULL,
+    log_index INTEGER NOT NULL,
+    contract_address BLOB NOT NULL,
+    topic0 BLOB,
+    topic1 BLOB,
+    topic2 BLOB,
+    topic3 BLOB,
+    data BLOB,
+    FOREIGN KEY (divergence_id) REFERENCES divergences(id) ON DELETE CASCADE
+)";
+
+/// CREATE2 deployments table
+const CREATE2_DEPLOYMENTS_TABLE: &str = "CREATE TABLE IF NOT EXISTS create2_deployments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    divergence_id INTEGER NOT NULL,
+    is_experimental BOOLEAN NOT NULL,
+    caller BLOB NOT NULL,
+    salt BLOB NOT NULL,
+    init_code_hash BLOB NOT NULL,
+    predicted_address BLOB NOT NULL,
+    actual_address BLOB,
+    FOREIGN KEY (divergence_id) REFERENCES divergences(id) ON DELETE CASCADE
+)";
+
+/// Gas observations table, one row per value a GAS opcode returned in experimental execution
+const GAS_OBSERVATIONS_TABLE: &str = "CREATE TABLE IF NOT EXISTS gas_observations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    divergence_id INTEGER NOT NULL,
+    contract_address BLOB NOT NULL,
+    pc INTEGER NOT NULL,
+    gas_remaining INTEGER NOT NULL,
+    FOREIGN KEY (divergence_id) REFERENCES divergences(id) ON DELETE CASCADE
+)";
+
+/// Gas loops table
+const GAS_LOOPS_TABLE: &str = "CREATE TABLE IF NOT EXISTS gas_loops (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    block_number INTEGER NOT NULL,
+    tx_hash BLOB NOT NULL,
+    contract_address BLOB NOT NULL,
+    function_selector BLOB,
+    first_seen_block INTEGER NOT NULL,
+    gas_threshold INTEGER,
+    loop_pattern TEXT,
+    created_at INTEGER DEFAULT (strftime('%s', 'now'))
+)";
+
+/// Block summaries table, one row per analyzed block
+const BLOCK_SUMMARIES_TABLE: &str = "CREATE TABLE IF NOT EXISTS block_summaries (
+    block_number INTEGER PRIMARY KEY,
+    analyzed_tx_count INTEGER NOT NULL,
+    header_gas_used INTEGER,
+    normal_gas_used INTEGER,
+    created_at INTEGER DEFAULT (strftime('%s', 'now'))
+)";
+
+/// Checkpoint table, holding a single row for the last finished block
+const CHECKPOINT_TABLE: &str = "CREATE TABLE IF NOT EXISTS checkpoint (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    block_number INTEGER NOT NULL,
+    config_hash BLOB NOT NULL,
+    created_at INTEGER DEFAULT (strftime('%s', 'now'))
+)";
+
+/// Runs table, appended to by every run so results can be traced back to their settings
+const RUNS_TABLE: &str = "CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    config_hash BLOB NOT NULL,
+    settings TEXT NOT NULL,
+    start_block INTEGER NOT NULL,
+    end_block INTEGER,
+    started_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+    ended_at INTEGER,
+    blocks_processed INTEGER,
+    divergences_found INTEGER,
+    divergences_recorded INTEGER
+)";
+
+/// Statements creating the database schema, each table followed by its indexes.
+pub const SCHEMA: &[&str] = &[
+    DIVERGENCES_TABLE,
+    "CREATE INDEX IF NOT EXISTS idx_divergences_block ON divergences(block_number)",
+    "CREATE UNIQUE INDEX IF NOT EXISTS idx_divergences_uid ON divergences(divergence_uid)",
+    "CREATE INDEX IF NOT EXISTS idx_divergences_types ON divergences(divergence_types)",
+    "CREATE INDEX IF NOT EXISTS idx_divergences_code_hash ON divergences(divergence_code_hash)",
+    "CREATE INDEX IF NOT EXISTS idx_divergences_oog_opcode ON divergences(oog_opcode)",
+    CALL_TREES_TABLE,
+    "CREATE INDEX IF NOT EXISTS idx_call_trees_divergence ON call_trees(divergence_id)",
+    EVENT_LOGS_TABLE,
+    "CREATE INDEX IF NOT EXISTS idx_event_logs_divergence ON event_logs(divergence_id)",
+    CREATE2_DEPLOYMENTS_TABLE,
+    "CREATE INDEX IF NOT EXISTS idx_create2_deployments_divergence ON create2_deployments(divergence_id)",
+    GAS_OBSERVATIONS_TABLE,
+    "CREATE INDEX IF NOT EXISTS idx_gas_observations_divergence ON gas_observations(divergence_id)",
+    GAS_LOOPS_TABLE,
+    "CREATE INDEX IF NOT EXISTS idx_gas_loops_contract ON gas_loops(contract_address)",
+    BLOCK_SUMMARIES_TABLE,
+    CHECKPOINT_TABLE,
+    RUNS_TABLE,
+];
+
+/// The full schema as an SQL script that sets the schema version and runs every statement in
+/// [`SCHEMA`].
+pub fn schema_sql() -> String {
+    let mut sql = format!("PRAGMA user_version = {DATABASE_SCHEMA_VERSION};\n");
+    for statement in SCHEMA {
+        sql.push('\n');
+        sql.push_str(statement);
+        sql.push_str(";\n");
+    }
+    sql
+}
+
+/// Migrate the tables of a database written with schema `version` to
+/// [`DATABASE_SCHEMA_VERSION`], by adding the columns added since.
+///
+/// New tables and indexes are created by [`SCHEMA`] afterwards. Columns that already exist, in a
+/// database written by a build between two versions, are skipped.
+fn migrate_schema(conn: &Connection, version: u32) -> Result<(), DatabaseError> {
+    if version < 1 {
+        add_missing_columns(conn, DIVERGENCES_COLUMNS_V1)?;
+    }
+    if version < 2 {
+        add_missing_columns(conn, DIVERGENCES_COLUMNS_V2)?;
+    }
+    Ok(())
+}
+
+/// Add the `columns` the divergences table does not have yet.
+fn add_missing_columns(conn: &Connection, columns: &[(&str, &str)]) -> Result<(), DatabaseError> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('divergences')")?;
+    let existing =
+        stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<HashSet<_>, _>>()?;
+    for (name, column_type) in columns {
+        if !existing.contains(*name) {
+            let sql = format!("ALTER TABLE divergences ADD COLUMN {name} {column_type}");
+            conn.execute(&sql, [])?;
+        }
+    }
+    Ok(())
+}
+
+/// Read the schema version of a database, refusing versions this build does not know.
+///
+/// Returns `None` for a database without tables, which is initialized from scratch. One with
+/// tables but no version predates schema versioning and is at version 0.
+fn schema_version(conn: &Connection) -> Result<Option<u32>, DatabaseError> {
+    let version: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let has_tables: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'divergences')",
+        [],
+        |row| row.get(0),
+    )?;
+    if version > DATABASE_SCHEMA_VERSION {
+        return Err(DatabaseError::UnsupportedSchemaVersion(version))
+    }
+    Ok(has_tables.then_some(version))
 }
 
 /// Database for storing divergence data.
@@ -36,13 +795,38 @@ pub struct DivergenceDatabase {
 
 impl DivergenceDatabase {
     /// Open or create a database at the given path.
+    ///
+    /// The database is switched to WAL mode so readers (e.g. the `watch` command) can follow it
+    /// while the node is writing. A quick consistency check is run first and corruption is logged
+    /// as a warning, see [`Self::integrity_check`] for the full check.
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
         let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "busy_timeout", BUSY_TIMEOUT.as_millis() as u64)?;
         let db = Self { conn: Arc::new(Mutex::new(conn)) };
+        db.check("quick_check")?;
         db.initialize_schema()?;
         Ok(db)
     }
 
+    /// Open an existing database at the given path for reading only.
+    ///
+    /// Used by tools following a database the node is writing, which must not create, migrate
+    /// or otherwise modify it. A database at an older schema version is refused, as it cannot
+    /// be migrated.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        conn.pragma_update(None, "busy_timeout", BUSY_TIMEOUT.as_millis() as u64)?;
+        let version = schema_version(&conn)?;
+        if version != Some(DATABASE_SCHEMA_VERSION) {
+            return Err(DatabaseError::UnsupportedSchemaVersion(version.unwrap_or_default()))
+        }
+        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+    }
+
     /// Create an in-memory database (for testing).
     #[cfg(test)]
     pub fn in_memory() -> Result<Self, DatabaseError> {
@@ -52,160 +836,70 @@ impl DivergenceDatabase {
         Ok(db)
     }
 
-    /// Initialize the database schema.
+    /// Initialize the database schema, migrating a database written with an older schema
+    /// version in the same transaction.
     fn initialize_schema(&self) -> Result<(), DatabaseError> {
-        let conn = self.conn.lock().unwrap();
-
-        // Main divergences table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS divergences (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                block_number INTEGER NOT NULL,
-                tx_index INTEGER NOT NULL,
-                tx_hash BLOB NOT NULL,
-                timestamp INTEGER NOT NULL,
-
-                -- Divergence classification (comma-separated types)
-                divergence_types TEXT NOT NULL,
-
-                -- Gas analysis
-                normal_gas_used INTEGER NOT NULL,
-                experimental_gas_used INTEGER NOT NULL,
-                gas_efficiency_ratio REAL NOT NULL,
-
-                -- Operation counts (normal)
-                normal_sload_count INTEGER,
-                normal_sstore_count INTEGER,
-                normal_call_count INTEGER,
-                normal_log_count INTEGER,
-                normal_total_ops INTEGER,
-                normal_memory_words INTEGER,
-                normal_create_count INTEGER,
-
-                -- Operation counts (experimental)
-                exp_sload_count INTEGER,
-                exp_sstore_count INTEGER,
-                exp_call_count INTEGER,
-                exp_log_count INTEGER,
-                exp_total_ops INTEGER,
-                exp_memory_words INTEGER,
-                exp_create_count INTEGER,
-
-                -- Divergence location
-                divergence_contract BLOB,
-                divergence_function_selector BLOB,
-                divergence_function_selectors_json TEXT,
-                divergence_pc INTEGER,
-                divergence_call_depth INTEGER,
-                divergence_opcode INTEGER,
-                divergence_opcode_name TEXT,
-
-                -- OOG analysis
-                oog_occurred BOOLEAN,
-                oog_opcode INTEGER,
-                oog_opcode_name TEXT,
-                oog_pc INTEGER,
-                oog_contract BLOB,
-                oog_call_depth INTEGER,
-                oog_gas_remaining INTEGER,
-                oog_pattern TEXT,
-
-                created_at INTEGER DEFAULT (strftime('%s', 'now'))
-            )",
-            [],
-        )?;
-
-        // Indexes
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_divergences_block ON divergences(block_number)",
-            [],
-        )?;
-
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_divergences_types ON divergences(divergence_types)",
-            [],
-        )?;
-
-        // Call trees table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS call_trees (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                divergence_id INTEGER NOT NULL,
-                is_experimental BOOLEAN NOT NULL,
-                call_index INTEGER NOT NULL,
-                depth INTEGER NOT NULL,
-                from_addr BLOB NOT NULL,
-                to_addr BLOB,
-                call_type TEXT NOT NULL,
-                gas_provided INTEGER,
-                gas_used INTEGER,
-                success BOOLEAN,
-                input BLOB,
-                output BLOB,
-                FOREIGN KEY (divergence_id) REFERENCES divergences(id) ON DELETE CASCADE
-            )",
-            [],
-        )?;
-
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_call_trees_divergence ON call_trees(divergence_id)",
-            [],
-        )?;
-
-        // Event logs table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS event_logs (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                divergence_id INTEGER NOT NULL,
-                is_experimental BOOLEAN NOT NULL,
-                log_index INTEGER NOT NULL,
-                contract_address BLOB NOT NULL,
-                topic0 BLOB,
-                topic1 BLOB,
-                topic2 BLOB,
-                topic3 BLOB,
-                data BLOB,
-                FOREIGN KEY (divergence_id) REFERENCES divergences(id) ON DELETE CASCADE
-            )",
-            [],
-        )?;
-
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_event_logs_divergence ON event_logs(divergence_id)",
-            [],
-        )?;
-
-        // Gas loops table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS gas_loops (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                block_number INTEGER NOT NULL,
-                tx_hash BLOB NOT NULL,
-                contract_address BLOB NOT NULL,
-                function_selector BLOB,
-                first_seen_block INTEGER NOT NULL,
-                gas_threshold INTEGER,
-                loop_pattern TEXT,
-                created_at INTEGER DEFAULT (strftime('%s', 'now'))
-            )",
-            [],
-        )?;
+        let mut conn = self.conn.lock().unwrap();
+        let conn = conn.transaction()?;
+        if let Some(version) = schema_version(&conn)? {
+            migrate_schema(&conn, version)?;
+        }
+        for statement in SCHEMA {
+            conn.execute(statement, [])?;
+        }
+        conn.pragma_update(None, "user_version", DATABASE_SCHEMA_VERSION)?;
+        conn.commit()?;
+        Ok(())
+    }
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_gas_loops_contract ON gas_loops(contract_address)",
-            [],
-        )?;
+    /// Run SQLite's `PRAGMA integrity_check` and return whether the database is intact.
+    ///
+    /// The check reads the whole file, so it takes a while on large databases. Each problem
+    /// found is logged as a warning.
+    pub fn integrity_check(&self) -> Result<bool, DatabaseError> {
+        self.check("integrity_check")
+    }
 
-        Ok(())
+    /// Run the consistency check `pragma` and return whether it reported no problems, logging
+    /// the problems otherwise.
+    fn check(&self, pragma: &str) -> Result<bool, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("PRAGMA {pragma}"))?;
+        let problems =
+            stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+        let intact = problems == ["ok"];
+        if !intact {
+            for problem in &problems {
+                warn!(
+                    target: "reth::research",
+                    check = pragma,
+                    %problem,
+                    "Divergence database is corrupt"
+                );
+            }
+        }
+        Ok(intact)
     }
 
     /// Record a divergence.
+    ///
+    /// A divergence already in the database, matched by [`Divergence::uid`], is not recorded
+    /// again and the row id of the existing record is returned.
     pub fn record_divergence(&self, divergence: &Divergence) -> Result<i64, DatabaseError> {
-        let conn = self.conn.lock().unwrap();
+        retry_busy("record_divergence", || self.insert_divergence(divergence))
+    }
 
-        // Format divergence types as comma-separated string
+    /// Insert a divergence and its related rows in a single transaction, so a failed attempt
+    /// leaves nothing behind and can be retried.
+    fn insert_divergence(&self, divergence: &Divergence) -> Result<i64, DatabaseError> {
+        let mut conn = self.conn.lock().unwrap();
+        let conn = conn.transaction()?;
+        let uid = divergence.uid();
+
+        // Format divergence types as comma-separated string, sorted and deduplicated so the
+        // stored string is canonical
         let types_str =
-            divergence.divergence_types.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(",");
+            divergence.sorted_types().iter().map(|t| t.to_string()).collect::<Vec<_>>().join(",");
 
         // Prepare function selector fields
         let deepest_selector = divergence
@@ -213,12 +907,15 @@ impl DivergenceDatabase {
             .as_ref()
             .and_then(|l| l.function_selectors.last().and_then(|s| *s));
 
-        let selectors_json = divergence
-            .divergence_location
-            .as_ref()
-            .map(|l| serde_json::to_string(&l.function_selectors).unwrap_or_default());
+        let selectors_json =
+            json_column(divergence.divergence_location.as_ref().map(|l| &l.function_selectors))?;
+        let trajectory_json = json_column(divergence.oog_info.as_ref().map(|o| &o.gas_trajectory))?;
+        let threshold_map_json = json_column(divergence.threshold_map.as_ref())?;
+        let access_list_diff_json = json_column(divergence.access_list_diff.as_ref())?;
+        let griefing_json = json_column(divergence.griefing.as_ref())?;
+        let oog_precompile = divergence.oog_info.as_ref().and_then(|o| o.precompile);
 
-        let _divergence_id = conn.execute(
+        let inserted = conn.execute(
             "INSERT INTO divergences (
                 block_number, tx_index, tx_hash, timestamp,
                 divergence_types,
@@ -230,12 +927,23 @@ impl DivergenceDatabase {
                 divergence_contract, divergence_function_selector, divergence_function_selectors_json, divergence_pc,
                 divergence_call_depth, divergence_opcode, divergence_opcode_name,
                 oog_occurred, oog_opcode, oog_opcode_name, oog_pc,
-                oog_contract, oog_call_depth, oog_gas_remaining, oog_pattern
+                oog_contract, oog_call_depth, oog_gas_remaining, oog_pattern,
+                normal_extcode_count, exp_extcode_count, oog_gas_trajectory_json, threshold_map_json,
+                normal_tload_count, exp_tload_count, normal_tstore_count, exp_tstore_count,
+                normal_mcopy_count, exp_mcopy_count, divergence_code_hash,
+                normal_gas_charged, exp_gas_charged, reverting_selector, divergence_cause,
+                truncated, oog_precompile, divergence_uid, near_miss, comparison,
+                created_address, normal_created_code_hash, exp_created_code_hash, function_name,
+                calldata_len, calldata_zero_bytes, calldata_nonzero_bytes, access_list_diff_json,
+                divergence_code_address, oog_code_address, griefing_json
             ) VALUES (
                 ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15,
                 ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28,
-                ?29, ?30, ?31, ?32, ?33, ?34, ?35, ?36, ?37
-            )",
+                ?29, ?30, ?31, ?32, ?33, ?34, ?35, ?36, ?37, ?38, ?39, ?40, ?41,
+                ?42, ?43, ?44, ?45, ?46, ?47, ?48, ?49, ?50, ?51, ?52, ?53, ?54,
+                ?55, ?56, ?57, ?58, ?59, ?60, ?61, ?62, ?63, ?64, ?65,
+                ?66, ?67, ?68
+            ) ON CONFLICT(divergence_uid) DO NOTHING",
             params![
                 divergence.block_number,
                 divergence.tx_index,
@@ -274,18 +982,64 @@ impl DivergenceDatabase {
                 divergence.oog_info.as_ref().map(|o| o.call_depth as i64),
                 divergence.oog_info.as_ref().map(|o| o.gas_remaining as i64),
                 divergence.oog_info.as_ref().map(|o| o.pattern.to_string()),
+                divergence.normal_ops.extcode_count,
+                divergence.experimental_ops.extcode_count,
+                trajectory_json,
+                threshold_map_json,
+                divergence.normal_ops.tload_count,
+                divergence.experimental_ops.tload_count,
+                divergence.normal_ops.tstore_count,
+                divergence.experimental_ops.tstore_count,
+                divergence.normal_ops.mcopy_count,
+                divergence.experimental_ops.mcopy_count,
+                divergence.divergence_location.as_ref().map(|l| l.code_hash.as_slice()),
+                divergence.gas_analysis.normal_gas_charged,
+                divergence.gas_analysis.experimental_gas_charged,
+                divergence.reverting_selector.as_ref().map(|s| s.as_slice()),
+                divergence.cause.map(|cause| cause.to_string()),
+                divergence.truncated,
+                oog_precompile.as_ref().map(|a| a.as_slice()),
+                uid.as_slice(),
+                divergence.near_miss,
+                divergence.comparison.to_string(),
+                divergence.created_code.as_ref().map(|c| c.address.as_slice()),
+                divergence.created_code.as_ref().map(|c| c.normal_code_hash.as_slice()),
+                divergence.created_code.as_ref().map(|c| c.experimental_code_hash.as_slice()),
+                divergence.function_name,
+                divergence.calldata.map(|c| c.len),
+                divergence.calldata.map(|c| c.zero_bytes),
+                divergence.calldata.map(|c| c.nonzero_bytes),
+                access_list_diff_json,
+                divergence.divergence_location.as_ref().map(|l| l.code_address.as_slice()),
+                divergence.oog_info.as_ref().map(|o| o.code_address.as_slice()),
+                griefing_json,
             ],
         )?;
+        if inserted == 0 {
+            let id = id_by_uid(&conn, uid)?.ok_or(DatabaseError::DuplicateNotFound(uid))?;
+            debug!(target: "reth::research", id, %uid, "Divergence already recorded");
+            return Ok(id);
+        }
 
         let divergence_id = conn.last_insert_rowid();
 
         // Store call trees if present
         if let Some(ref call_trees) = divergence.call_trees {
+            let normal_gas = frame_gas_by_index(&call_trees.normal);
+            let simulated_gas = frame_gas_by_index(&call_trees.experimental);
+
             for (is_experimental, frames) in
                 [(false, &call_trees.normal), (true, &call_trees.experimental)]
             {
                 for frame in frames {
-                    self.insert_call_frame(&conn, divergence_id, is_experimental, frame)?;
+                    self.insert_call_frame(
+                        &conn,
+                        divergence_id,
+                        is_experimental,
+                        frame,
+                        normal_gas.get(&frame.call_index).copied(),
+                        simulated_gas.get(&frame.call_index).copied(),
+                    )?;
                 }
             }
         }
@@ -301,6 +1055,42 @@ impl DivergenceDatabase {
             }
         }
 
+        // Store CREATE2 deployments if present
+        if let Some(ref deployments) = divergence.create2_deployments {
+            for (is_experimental, deployments) in
+                [(false, &deployments.normal), (true, &deployments.experimental)]
+            {
+                for deployment in deployments {
+                    self.insert_create2_deployment(
+                        &conn,
+                        divergence_id,
+                        is_experimental,
+                        deployment,
+                    )?;
+                }
+            }
+        }
+
+        // Store gas observations if present
+        if let Some(ref observations) = divergence.gas_observations {
+            for observation in observations {
+                for &gas_remaining in &observation.gas_remaining {
+                    conn.execute(
+                        "INSERT INTO gas_observations (
+                            divergence_id, contract_address, pc, gas_remaining
+                        ) VALUES (?1, ?2, ?3, ?4)",
+                        params![
+                            divergence_id,
+                            observation.contract.as_slice(),
+                            observation.pc,
+                            gas_remaining,
+                        ],
+                    )?;
+                }
+            }
+        }
+
+        conn.commit()?;
         Ok(divergence_id)
     }
 
@@ -311,13 +1101,16 @@ impl DivergenceDatabase {
         divergence_id: i64,
         is_experimental: bool,
         frame: &CallFrame,
+        normal_gas_used: Option<u64>,
+        simulated_gas_used: Option<u64>,
     ) -> Result<(), DatabaseError> {
         conn.execute(
             "INSERT INTO call_trees (
                 divergence_id, is_experimental, call_index, depth,
                 from_addr, to_addr, call_type, gas_provided,
-                gas_used, success, input, output
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                gas_used, success, out_of_gas, input_len, output_len, input, output,
+                normal_gas_used, simulated_gas_used
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
             params![
                 divergence_id,
                 is_experimental,
@@ -329,8 +1122,13 @@ impl DivergenceDatabase {
                 frame.gas_provided,
                 frame.gas_used,
                 frame.success,
+                frame.out_of_gas,
+                frame.input_len,
+                frame.output_len,
                 frame.input.as_ref().map(|b| b.as_ref()),
                 frame.output.as_ref().map(|b| b.as_ref()),
+                normal_gas_used,
+                simulated_gas_used,
             ],
         )?;
 
@@ -366,69 +1164,1595 @@ impl DivergenceDatabase {
         Ok(())
     }
 
-    /// Get divergence count by block range.
+    /// Insert a CREATE2 deployment.
+    fn insert_create2_deployment(
+        &self,
+        conn: &Connection,
+        divergence_id: i64,
+        is_experimental: bool,
+        deployment: &Create2Deployment,
+    ) -> Result<(), DatabaseError> {
+        conn.execute(
+            "INSERT INTO create2_deployments (
+                divergence_id, is_experimental, caller, salt,
+                init_code_hash, predicted_address, actual_address
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                divergence_id,
+                is_experimental,
+                deployment.caller.as_slice(),
+                deployment.salt.as_slice(),
+                deployment.init_code_hash.as_slice(),
+                deployment.predicted_address.as_slice(),
+                deployment.actual_address.as_ref().map(|a| a.as_slice()),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Get divergence count by block range, leaving out rows that are not
+    /// [counted](Divergence::is_counted).
     pub fn count_divergences(&self, from_block: u64, to_block: u64) -> Result<u64, DatabaseError> {
         let conn = self.conn.lock().unwrap();
         let count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM divergences WHERE block_number >= ?1 AND block_number <= ?2",
+            "SELECT COUNT(*) FROM divergences
+            WHERE block_number >= ?1 AND block_number <= ?2 AND
+                NOT COALESCE(near_miss, 0) AND COALESCE(comparison, '') != 'a_b'",
             params![from_block, to_block],
             |row| row.get(0),
         )?;
         Ok(count as u64)
     }
 
-    /// Get divergence count by type.
-    pub fn count_by_type(&self, dtype: DivergenceType) -> Result<u64, DatabaseError> {
+    /// Get the row id of the divergence with the given [`Divergence::uid`], if it is recorded.
+    pub fn divergence_id_by_uid(&self, uid: B256) -> Result<Option<i64>, DatabaseError> {
         let conn = self.conn.lock().unwrap();
-        let pattern = format!("%{}%", dtype);
-        let count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM divergences WHERE divergence_types LIKE ?1",
-            params![pattern],
-            |row| row.get(0),
+        Ok(id_by_uid(&conn, uid)?)
+    }
+
+    /// Get the divergence with row id `divergence_id`, if it is recorded.
+    pub fn divergence(
+        &self,
+        divergence_id: i64,
+    ) -> Result<Option<DivergenceRecord>, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        let record = conn
+            .query_row(
+                &format!("SELECT {DIVERGENCE_COLUMNS} FROM divergences WHERE id = ?1"),
+                params![divergence_id],
+                DivergenceRecord::from_row,
+            )
+            .optional()?;
+        Ok(record)
+    }
+
+    /// Get the highest divergence row id, or 0 if the database is empty.
+    pub fn max_id(&self) -> Result<i64, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        let max: Option<i64> =
+            conn.query_row("SELECT MAX(id) FROM divergences", [], |row| row.get(0))?;
+        Ok(max.unwrap_or(0))
+    }
+
+    /// Get up to `limit` divergences with a row id greater than `last_id`, in insertion order.
+    pub fn since_id(
+        &self,
+        last_id: i64,
+        limit: usize,
+    ) -> Result<Vec<DivergenceRecord>, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {DIVERGENCE_COLUMNS} FROM divergences WHERE id > ?1 ORDER BY id LIMIT ?2"
+        ))?;
+        let records = stmt
+            .query_map(params![last_id, limit as i64], DivergenceRecord::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(records)
+    }
+
+    /// Get the side-by-side per-frame gas of a divergence's call trees, ordered by call index.
+    pub fn frame_gas(&self, divergence_id: i64) -> Result<Vec<FrameGas>, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT call_index, normal_gas_used, simulated_gas_used
+             FROM call_trees WHERE divergence_id = ?1 ORDER BY call_index",
         )?;
-        Ok(count as u64)
+        let frames = stmt
+            .query_map(params![divergence_id], |row| {
+                Ok(FrameGas {
+                    call_index: row.get::<_, i64>(0)? as usize,
+                    normal_gas_used: row.get::<_, Option<i64>>(1)?.map(|g| g as u64),
+                    simulated_gas_used: row.get::<_, Option<i64>>(2)?.map(|g| g as u64),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(frames)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::divergence::{GasAnalysis, OperationCounts};
-    use alloy_primitives::B256;
+    /// Get the call trees stored for a divergence, with each execution's frames ordered by call
+    /// index. Returns None if the divergence was recorded without call trees.
+    pub fn call_trees(&self, divergence_id: i64) -> Result<Option<CallTrees>, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT is_experimental, call_index, depth, from_addr, to_addr, call_type,
+                    gas_provided, gas_used, success, out_of_gas, input_len, output_len,
+                    input, output
+             FROM call_trees WHERE divergence_id = ?1 ORDER BY is_experimental, call_index",
+        )?;
+        let rows = stmt
+            .query_map(params![divergence_id], |row| {
+                let from: Vec<u8> = row.get(3)?;
+                let call_type: String = row.get(5)?;
+                let frame = CallFrame {
+                    call_index: row.get::<_, i64>(1)? as usize,
+                    depth: row.get::<_, i64>(2)? as usize,
+                    from: Address::from_slice(&from),
+                    to: row.get::<_, Option<Vec<u8>>>(4)?.map(|to| Address::from_slice(&to)),
+                    call_type: parse_column(5, &call_type)?,
+                    gas_provided: row.get::<_, Option<i64>>(6)?.unwrap_or_default() as u64,
+                    gas_used: row.get::<_, Option<i64>>(7)?.unwrap_or_default() as u64,
+                    success: row.get::<_, Option<bool>>(8)?.unwrap_or_default(),
+                    out_of_gas: row.get::<_, Option<bool>>(9)?.unwrap_or_default(),
+                    input_len: row.get::<_, Option<i64>>(10)?.unwrap_or_default() as usize,
+                    output_len: row.get::<_, Option<i64>>(11)?.unwrap_or_default() as usize,
+                    input: row.get::<_, Option<Vec<u8>>>(12)?.map(Bytes::from),
+                    output: row.get::<_, Option<Vec<u8>>>(13)?.map(Bytes::from),
+                };
+                Ok((row.get::<_, bool>(0)?, frame))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
 
-    #[test]
-    fn test_database_creation() {
-        let db = DivergenceDatabase::in_memory().unwrap();
-        assert!(db.count_divergences(0, 1000).unwrap() == 0);
+        if rows.is_empty() {
+            return Ok(None)
+        }
+        let (experimental, normal): (Vec<_>, Vec<_>) =
+            rows.into_iter().partition(|(is_experimental, _)| *is_experimental);
+        Ok(Some(CallTrees {
+            normal: normal.into_iter().map(|(_, frame)| frame).collect(),
+            experimental: experimental.into_iter().map(|(_, frame)| frame).collect(),
+        }))
     }
 
-    #[test]
-    fn test_record_divergence() {
-        let db = DivergenceDatabase::in_memory().unwrap();
+    /// Get the gas observations stored for a divergence, grouped by contract and PC in the order
+    /// each location was first observed. Returns None if the divergence was recorded without
+    /// gas observations.
+    pub fn gas_observations(
+        &self,
+        divergence_id: i64,
+    ) -> Result<Option<Vec<GasObservation>>, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT contract_address, pc, gas_remaining
+             FROM gas_observations WHERE divergence_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt
+            .query_map(params![divergence_id], |row| {
+                let contract: Vec<u8> = row.get(0)?;
+                Ok((
+                    Address::from_slice(&contract),
+                    row.get::<_, i64>(1)? as usize,
+                    row.get::<_, i64>(2)? as u64,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        if rows.is_empty() {
+            return Ok(None)
+        }
 
-        let divergence = Divergence {
-            block_number: 100,
-            tx_index: 5,
-            tx_hash: B256::ZERO,
-            timestamp: 1234567890,
-            divergence_types: vec![DivergenceType::StateRoot],
-            gas_analysis: GasAnalysis {
-                normal_gas_used: 21000,
-                experimental_gas_used: 2688000,
-                gas_efficiency_ratio: 1.0,
-            },
-            normal_ops: OperationCounts::default(),
-            experimental_ops: OperationCounts::default(),
-            divergence_location: None,
-            oog_info: None,
-            call_trees: None,
-            event_logs: None,
-        };
+        let mut observations: Vec<GasObservation> = Vec::new();
+        for (contract, pc, gas_remaining) in rows {
+            match observations.iter_mut().find(|seen| seen.contract == contract && seen.pc == pc) {
+                Some(observation) => observation.gas_remaining.push(gas_remaining),
+                None => observations.push(GasObservation {
+                    contract,
+                    pc,
+                    gas_remaining: vec![gas_remaining],
+                }),
+            }
+        }
+        Ok(Some(observations))
+    }
 
-        let id = db.record_divergence(&divergence).unwrap();
-        assert!(id > 0);
+    /// Stream the gas efficiency ratio of every divergent transaction in a block range as CSV.
+    ///
+    /// Writes a `block_number,tx_hash,gas_efficiency_ratio,ratio_valid` header followed by one
+    /// row per transaction, ordered by block and transaction index. `ratio_valid` is false when
+    /// the normal execution used no gas, in which case the ratio is a placeholder.
+    ///
+    /// Only transactions stored in the `divergences` table are included. Ratios of transactions
+    /// that did not diverge are never recorded, so the exported distribution is biased towards
+    /// divergences and is not the ratio distribution of the analyzed range.
+    ///
+    /// Returns the number of rows written, excluding the header.
+    pub fn export_ratio_csv<W: Write>(
+        &self,
+        mut writer: W,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<u64, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT block_number, tx_hash, gas_efficiency_ratio, normal_gas_used
+             FROM divergences WHERE block_number >= ?1 AND block_number <= ?2
+             ORDER BY block_number, tx_index",
+        )?;
+        let mut rows = stmt.query(params![from_block, to_block])?;
 
-        assert_eq!(db.count_divergences(0, 1000).unwrap(), 1);
-        assert_eq!(db.count_by_type(DivergenceType::StateRoot).unwrap(), 1);
+        writeln!(writer, "block_number,tx_hash,gas_efficiency_ratio,ratio_valid")?;
+
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            let block_number: i64 = row.get(0)?;
+            let tx_hash: Vec<u8> = row.get(1)?;
+            let ratio: f64 = row.get(2)?;
+            let ratio_valid = row.get::<_, i64>(3)? > 0;
+            writeln!(
+                writer,
+                "{block_number},{},{ratio},{ratio_valid}",
+                B256::from_slice(&tx_hash)
+            )?;
+            count += 1;
+        }
+
+        writer.flush()?;
+        Ok(count)
+    }
+
+    /// Group divergences by the hash of the bytecode they were located in, most divergent first.
+    ///
+    /// Divergences without a recorded location are not included.
+    pub fn divergences_by_code_hash(&self) -> Result<Vec<CodeHashGroup>, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT divergence_code_hash, COUNT(*), COUNT(DISTINCT divergence_contract)
+             FROM divergences WHERE divergence_code_hash IS NOT NULL
+             GROUP BY divergence_code_hash ORDER BY COUNT(*) DESC, divergence_code_hash",
+        )?;
+        let groups = stmt
+            .query_map([], |row| {
+                Ok(CodeHashGroup {
+                    code_hash: B256::from_slice(&row.get::<_, Vec<u8>>(0)?),
+                    divergence_count: row.get::<_, i64>(1)? as u64,
+                    contract_count: row.get::<_, i64>(2)? as u64,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(groups)
+    }
+
+    /// Get the number of experimental OOGs by the opcode that ran out of gas.
+    pub fn oog_by_opcode(&self) -> Result<HashMap<u8, u64>, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT oog_opcode, COUNT(*) FROM divergences WHERE oog_opcode IS NOT NULL
+             GROUP BY oog_opcode",
+        )?;
+        let counts = stmt
+            .query_map([], |row| Ok((row.get::<_, i64>(0)? as u8, row.get::<_, i64>(1)? as u64)))?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        Ok(counts)
+    }
+
+    /// Get the number of experimental OOGs by opcode mnemonic, most frequent first.
+    pub fn oog_by_mnemonic(&self) -> Result<Vec<(&'static str, u64)>, DatabaseError> {
+        let mut counts: Vec<_> = self
+            .oog_by_opcode()?
+            .into_iter()
+            .map(|(opcode, count)| (opcode_mnemonic(opcode), count))
+            .collect();
+        counts.sort_by(|(a_name, a_count), (b_name, b_count)| {
+            b_count.cmp(a_count).then_with(|| a_name.cmp(b_name))
+        });
+        Ok(counts)
+    }
+
+    /// Write every divergence in a block range as JSONL, one [`Divergence::to_json`] record per
+    /// line, ordered by block and transaction index.
+    ///
+    /// Call trees, event logs and CREATE2 deployments are not loaded from the database and so are
+    /// not exported.
+    ///
+    /// Returns the number of records written.
+    pub fn export_jsonl<W: Write>(
+        &self,
+        mut writer: W,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<u64, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {DIVERGENCE_COLUMNS} FROM divergences
+             WHERE block_number >= ?1 AND block_number <= ?2
+             ORDER BY block_number, tx_index"
+        ))?;
+        let mut rows = stmt.query(params![from_block, to_block])?;
+
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            let record = DivergenceRecord::from_row(row)?;
+            writeln!(writer, "{}", record.divergence.to_json()?)?;
+            count += 1;
+        }
+
+        writer.flush()?;
+        Ok(count)
+    }
+
+    /// Record every divergence in a JSONL stream produced by [`Self::export_jsonl`].
+    ///
+    /// Blank lines are skipped, as are divergences already in the database, so merging the same
+    /// export twice is a no-op. Records with an unsupported schema version are rejected.
+    ///
+    /// Returns the number of records imported.
+    pub fn import_jsonl<R: BufRead>(&self, reader: R) -> Result<u64, DatabaseError> {
+        let mut count = 0;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let divergence = Divergence::from_json(&line)?;
+            if self.divergence_id_by_uid(divergence.uid())?.is_some() {
+                continue;
+            }
+            self.record_divergence(&divergence)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Export a block range to a JSONL file with [`Self::export_jsonl`], compressing it with
+    /// `compression`.
+    ///
+    /// Use [`Compression::from_path`] to pick the compression from the file extension.
+    pub fn export_jsonl_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        from_block: u64,
+        to_block: u64,
+        compression: Compression,
+    ) -> Result<u64, DatabaseError> {
+        let file = BufWriter::new(File::create(path)?);
+        match compression {
+            Compression::None => self.export_jsonl(file, from_block, to_block),
+            #[cfg(feature = "compression")]
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(file, flate2::Compression::default());
+                let count = self.export_jsonl(&mut encoder, from_block, to_block)?;
+                encoder.finish()?.flush()?;
+                Ok(count)
+            }
+            #[cfg(feature = "compression")]
+            Compression::Zstd => {
+                let mut encoder = zstd::Encoder::new(file, 0)?;
+                let count = self.export_jsonl(&mut encoder, from_block, to_block)?;
+                encoder.finish()?.flush()?;
+                Ok(count)
+            }
+            #[cfg(not(feature = "compression"))]
+            compression => Err(DatabaseError::UnsupportedCompression(compression)),
+        }
+    }
+
+    /// Import a JSONL file with [`Self::import_jsonl`], decompressing it with `compression`.
+    ///
+    /// Use [`Compression::from_path`] to pick the compression from the file extension.
+    pub fn import_jsonl_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        compression: Compression,
+    ) -> Result<u64, DatabaseError> {
+        let file = BufReader::new(File::open(path)?);
+        match compression {
+            Compression::None => self.import_jsonl(file),
+            #[cfg(feature = "compression")]
+            Compression::Gzip => {
+                self.import_jsonl(BufReader::new(flate2::bufread::MultiGzDecoder::new(file)))
+            }
+            #[cfg(feature = "compression")]
+            Compression::Zstd => {
+                self.import_jsonl(BufReader::new(zstd::Decoder::with_buffer(file)?))
+            }
+            #[cfg(not(feature = "compression"))]
+            compression => Err(DatabaseError::UnsupportedCompression(compression)),
+        }
+    }
+
+    /// Get divergence count by type.
+    pub fn count_by_type(&self, dtype: DivergenceType) -> Result<u64, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        // Types are stored comma-separated; match whole entries so `status` does not also count
+        // `status_improved`
+        let pattern = format!("%,{},%", dtype);
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM divergences WHERE ',' || divergence_types || ',' LIKE ?1",
+            params![pattern],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+
+    /// Record the number of transactions analyzed and the gas used in a block, replacing any
+    /// earlier summary of the same block.
+    pub fn record_block_summary(&self, summary: &BlockSummary) -> Result<(), DatabaseError> {
+        retry_busy("record_block_summary", || {
+            let conn = self.conn.lock().unwrap();
+            conn.execute(
+                "INSERT OR REPLACE INTO block_summaries (
+                    block_number, analyzed_tx_count, header_gas_used, normal_gas_used
+                ) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    summary.block_number,
+                    summary.analyzed_tx_count,
+                    summary.header_gas_used,
+                    summary.normal_gas_used
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Record the last block a run finished analyzing, replacing the previous checkpoint.
+    pub fn record_checkpoint(&self, checkpoint: &Checkpoint) -> Result<(), DatabaseError> {
+        retry_busy("record_checkpoint", || {
+            let conn = self.conn.lock().unwrap();
+            conn.execute(
+                "INSERT OR REPLACE INTO checkpoint (id, block_number, config_hash)
+                VALUES (0, ?1, ?2)",
+                params![checkpoint.block_number, checkpoint.config_hash.as_slice()],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Get the last checkpoint recorded, if any.
+    pub fn last_checkpoint(&self) -> Result<Option<Checkpoint>, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        let checkpoint = conn
+            .query_row("SELECT block_number, config_hash FROM checkpoint WHERE id = 0", [], |row| {
+                let config_hash: Vec<u8> = row.get(1)?;
+                Ok(Checkpoint {
+                    block_number: row.get::<_, i64>(0)? as u64,
+                    config_hash: B256::from_slice(&config_hash),
+                })
+            })
+            .optional()?;
+        Ok(checkpoint)
+    }
+
+    /// Record the start of a run with `config`, returning its id for [`Self::record_run_end`].
+    pub fn record_run_start(&self, config: &ResearchConfig) -> Result<i64, DatabaseError> {
+        retry_busy("record_run_start", || {
+            let conn = self.conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO runs (config_hash, settings, start_block, end_block)
+                VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    config.config_hash().as_slice(),
+                    config.settings(),
+                    config.start_block,
+                    config.end_block
+                ],
+            )?;
+            Ok(conn.last_insert_rowid())
+        })
+    }
+
+    /// Record the end of the run `run_id` with its final `stats`.
+    ///
+    /// Runs that already ended are left as they are, so the log is never rewritten.
+    pub fn record_run_end(&self, run_id: i64, stats: &ResearchStats) -> Result<(), DatabaseError> {
+        retry_busy("record_run_end", || {
+            let conn = self.conn.lock().unwrap();
+            conn.execute(
+                "UPDATE runs SET
+                    ended_at = strftime('%s', 'now'),
+                    blocks_processed = ?2,
+                    divergences_found = ?3,
+                    divergences_recorded = ?4
+                WHERE id = ?1 AND ended_at IS NULL",
+                params![
+                    run_id,
+                    stats.blocks_processed,
+                    stats.divergences_found,
+                    stats.divergences_recorded
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Get every recorded run, oldest first.
+    pub fn runs(&self) -> Result<Vec<RunRecord>, DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, config_hash, settings, start_block, end_block, started_at, ended_at,
+                blocks_processed, divergences_found, divergences_recorded
+            FROM runs ORDER BY id",
+        )?;
+        let runs = stmt
+            .query_map([], |row| {
+                let config_hash: Vec<u8> = row.get(1)?;
+                let ended_at = row.get::<_, Option<i64>>(6)?.map(|at| at as u64);
+                let stats = match ended_at {
+                    Some(_) => Some(ResearchStats {
+                        blocks_processed: row.get::<_, i64>(7)? as u64,
+                        divergences_found: row.get::<_, i64>(8)? as u64,
+                        divergences_recorded: row.get::<_, i64>(9)? as u64,
+                    }),
+                    None => None,
+                };
+                Ok(RunRecord {
+                    id: row.get(0)?,
+                    config_hash: B256::from_slice(&config_hash),
+                    settings: row.get(2)?,
+                    start_block: row.get::<_, i64>(3)? as u64,
+                    end_block: row.get::<_, Option<i64>>(4)?.map(|block| block as u64),
+                    started_at: row.get::<_, i64>(5)? as u64,
+                    ended_at,
+                    stats,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(runs)
+    }
+
+    /// Get the divergence rate per `window` blocks, as
+    /// `(window_start_block, analyzed_tx_count, divergence_count)` in block order.
+    ///
+    /// Windows are aligned to multiples of `window`. Only windows with an analyzed block or a
+    /// divergence are returned. Rows that are not [counted](Divergence::is_counted) are left out.
+    pub fn divergence_rate_series(
+        &self,
+        window: u64,
+    ) -> Result<Vec<(u64, u64, u64)>, DatabaseError> {
+        if window == 0 {
+            return Err(DatabaseError::InvalidQuery("window must be > 0 blocks"));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "WITH analyzed AS (
+                SELECT block_number / ?1 AS bucket, SUM(analyzed_tx_count) AS count
+                FROM block_summaries GROUP BY bucket
+            ),
+            diverged AS (
+                SELECT block_number / ?1 AS bucket, COUNT(*) AS count
+                FROM divergences
+                WHERE NOT COALESCE(near_miss, 0) AND COALESCE(comparison, '') != 'a_b'
+                GROUP BY bucket
+            )
+            SELECT bucket * ?1, COALESCE(analyzed.count, 0), COALESCE(diverged.count, 0)
+            FROM (SELECT bucket FROM analyzed UNION SELECT bucket FROM diverged)
+            LEFT JOIN analyzed USING (bucket)
+            LEFT JOIN diverged USING (bucket)
+            ORDER BY bucket",
+        )?;
+        let series = stmt
+            .query_map(params![window], |row| {
+                Ok((
+                    row.get::<_, i64>(0)? as u64,
+                    row.get::<_, i64>(1)? as u64,
+                    row.get::<_, i64>(2)? as u64,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(series)
+    }
+
+    /// Move all WAL contents into the main database file.
+    pub fn checkpoint(&self) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        Ok(())
+    }
+}
+
+/// A row queued on a [`DivergenceWriter`].
+#[derive(Debug)]
+enum WriterMessage {
+    Divergence(Divergence),
+    BlockSummary(BlockSummary),
+    Checkpoint(Checkpoint),
+}
+
+/// Records divergences, block summaries and checkpoints to a [`DivergenceStore`] on a dedicated
+/// thread.
+///
+/// Rows are written in the order they are sent. Dropping the writer, or calling
+/// [`DivergenceWriter::shutdown`], closes the queue and blocks until every queued row has been
+/// written and the store flushed, so nothing queued is lost when the node exits.
+#[derive(Debug)]
+pub struct DivergenceWriter {
+    /// Queue of rows to write, taken on shutdown
+    tx: Option<mpsc::Sender<WriterMessage>>,
+
+    /// Writer thread, returning the number of divergences written
+    handle: Option<JoinHandle<u64>>,
+}
+
+impl DivergenceWriter {
+    /// Spawn a writer thread recording to `store`.
+    pub fn spawn(store: Arc<dyn DivergenceStore>) -> Result<Self, DatabaseError> {
+        let (tx, rx) = mpsc::channel::<WriterMessage>();
+        let handle =
+            thread::Builder::new().name("research-db-writer".to_string()).spawn(move || {
+                let mut write_count = 0u64;
+                for message in rx {
+                    let divergence = match message {
+                        WriterMessage::Divergence(divergence) => divergence,
+                        WriterMessage::BlockSummary(summary) => {
+                            if let Err(e) = store.record_block_summary(&summary) {
+                                warn!(
+                                    target: "reth::research::db_writer",
+                                    block = summary.block_number,
+                                    error = %e,
+                                    "Failed to record block summary to database"
+                                );
+                            }
+                            continue
+                        }
+                        WriterMessage::Checkpoint(checkpoint) => {
+                            if let Err(e) = store.record_checkpoint(&checkpoint) {
+                                warn!(
+                                    target: "reth::research::db_writer",
+                                    block = checkpoint.block_number,
+                                    error = %e,
+                                    "Failed to record checkpoint to database"
+                                );
+                            }
+                            continue
+                        }
+                    };
+                    match store.record_divergence(&divergence) {
+                        Ok(_id) => {
+                            write_count += 1;
+                            if write_count % 100 == 0 {
+                                debug!(
+                                    target: "reth::research::db_writer",
+                                    total_writes = write_count,
+                                    "Database writer progress"
+                                );
+                            }
+                        }
+                        Err(e) => {
+                            warn!(
+                                target: "reth::research::db_writer",
+                                block = divergence.block_number,
+                                tx_idx = divergence.tx_index,
+                                error = %e,
+                                "Failed to record divergence to database"
+                            );
+                        }
+                    }
+                }
+
+                if let Err(e) = store.flush() {
+                    warn!(
+                        target: "reth::research::db_writer",
+                        error = %e,
+                        "Failed to flush divergence store"
+                    );
+                }
+                info!(
+                    target: "reth::research::db_writer",
+                    total_writes = write_count,
+                    "Database writer exiting"
+                );
+                write_count
+            })?;
+
+        Ok(Self { tx: Some(tx), handle: Some(handle) })
+    }
+
+    /// Queue a divergence for writing.
+    pub fn send(&self, divergence: Divergence) -> Result<(), DatabaseError> {
+        self.queue(WriterMessage::Divergence(divergence))
+    }
+
+    /// Queue a block summary for writing.
+    pub fn send_block_summary(&self, summary: BlockSummary) -> Result<(), DatabaseError> {
+        self.queue(WriterMessage::BlockSummary(summary))
+    }
+
+    /// Queue a checkpoint for writing, after every row sent before it.
+    pub fn send_checkpoint(&self, checkpoint: Checkpoint) -> Result<(), DatabaseError> {
+        self.queue(WriterMessage::Checkpoint(checkpoint))
+    }
+
+    /// Queue a row, failing if the writer thread has already exited.
+    fn queue(&self, message: WriterMessage) -> Result<(), DatabaseError> {
+        let tx = self.tx.as_ref().ok_or(DatabaseError::WriterClosed)?;
+        tx.send(message).map_err(|_| DatabaseError::WriterClosed)
+    }
+
+    /// Write all queued rows and stop the writer thread.
+    ///
+    /// Returns the number of divergences written.
+    pub fn shutdown(mut self) -> u64 {
+        self.finish()
+    }
+
+    /// Close the queue and wait for the writer thread to drain it.
+    fn finish(&mut self) -> u64 {
+        self.tx.take();
+        let Some(handle) = self.handle.take() else { return 0 };
+        handle.join().unwrap_or_else(|_| {
+            warn!(target: "reth::research::db_writer", "Database writer thread panicked");
+            0
+        })
+    }
+}
+
+impl Drop for DivergenceWriter {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Run the write `operation`, retrying it with exponential backoff while it fails because the
+/// database is busy or locked by another connection, e.g. a reader checkpointing the WAL.
+///
+/// Other errors, and the last busy error once the retries are exhausted, are returned as they are.
+fn retry_busy<T>(
+    operation: &'static str,
+    mut write: impl FnMut() -> Result<T, DatabaseError>,
+) -> Result<T, DatabaseError> {
+    let mut attempt = 0;
+    loop {
+        match write() {
+            Err(err) if is_busy(&err) && attempt < WRITE_RETRIES => {
+                let backoff = RETRY_BACKOFF * 2u32.pow(attempt);
+                attempt += 1;
+                debug!(
+                    target: "reth::research",
+                    operation,
+                    attempt,
+                    ?backoff,
+                    %err,
+                    "Database busy, retrying write"
+                );
+                metrics::record_database_retry();
+                thread::sleep(backoff);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether `err` is a transient `SQLITE_BUSY` or `SQLITE_LOCKED` failure.
+fn is_busy(err: &DatabaseError) -> bool {
+    matches!(
+        err,
+        DatabaseError::Sqlite(rusqlite::Error::SqliteFailure(failure, _))
+            if matches!(failure.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+/// Row id of the divergence with the given uid.
+fn id_by_uid(conn: &Connection, uid: B256) -> rusqlite::Result<Option<i64>> {
+    conn.query_row(
+        "SELECT id FROM divergences WHERE divergence_uid = ?1",
+        [uid.as_slice()],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Serialize an optional value into a JSON text column.
+fn json_column<T: Serialize>(value: Option<&T>) -> Result<Option<String>, DatabaseError> {
+    Ok(value.map(serde_json::to_string).transpose()?)
+}
+
+/// Map each frame's call index to its gas used.
+fn frame_gas_by_index(frames: &[CallFrame]) -> HashMap<usize, u64> {
+    frames.iter().map(|frame| (frame.call_index, frame.gas_used)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::ResearchConfig,
+        divergence::{AccessListDiff, AccessSet, CallType, Comparison, EventLogs},
+        inspector::GasResearchInspector,
+        test_utils::{self, counter_value, DivergenceBuilder, TestEnv, CONTRACT},
+        tracking_inspector::TrackingInspector,
+    };
+    use metrics_util::debugging::DebuggingRecorder;
+
+    fn summary(block_number: u64, analyzed_tx_count: u64) -> BlockSummary {
+        BlockSummary { block_number, analyzed_tx_count, header_gas_used: 0, normal_gas_used: None }
+    }
+
+    #[test]
+    fn test_json_column_propagates_serialization_errors() {
+        struct Unserializable;
+
+        impl Serialize for Unserializable {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("unserializable"))
+            }
+        }
+
+        assert!(matches!(json_column(Some(&Unserializable)), Err(DatabaseError::Serialization(_))));
+        assert_eq!(json_column(Some(&vec![1u8])).unwrap().as_deref(), Some("[1]"));
+        assert_eq!(json_column::<Unserializable>(None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_divergences_by_code_hash() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let implementation = B256::repeat_byte(0x01);
+        let other = B256::repeat_byte(0x02);
+        let located = |block_number, contract: u8, code_hash| {
+            let mut divergence = DivergenceBuilder::new(block_number).build();
+            divergence.divergence_location = Some(DivergenceLocation {
+                contract: Address::repeat_byte(contract),
+                code_address: Address::repeat_byte(contract),
+                function_selectors: vec![],
+                pc: 0,
+                call_depth: 1,
+                opcode: 0x54,
+                opcode_name: "SLOAD".to_string(),
+                code_hash,
+            });
+            divergence
+        };
+
+        // Two clones sharing an implementation, one unrelated contract and one unlocated
+        db.record_divergence(&located(1, 0xaa, implementation)).unwrap();
+        db.record_divergence(&located(2, 0xbb, implementation)).unwrap();
+        db.record_divergence(&located(3, 0xbb, implementation)).unwrap();
+        db.record_divergence(&located(4, 0xcc, other)).unwrap();
+        db.record_divergence(&DivergenceBuilder::new(5).build()).unwrap();
+
+        let groups = db.divergences_by_code_hash().unwrap();
+        assert_eq!(
+            groups,
+            vec![
+                CodeHashGroup { code_hash: implementation, divergence_count: 3, contract_count: 2 },
+                CodeHashGroup { code_hash: other, divergence_count: 1, contract_count: 1 },
+            ]
+        );
+
+        let record = db.since_id(0, 10).unwrap().remove(0);
+        assert_eq!(record.divergence.divergence_location.unwrap().code_hash, implementation);
+    }
+
+    #[test]
+    fn test_oog_by_opcode() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let oog = |block_number, opcode: u8| {
+            let mut divergence = DivergenceBuilder::new(block_number).build();
+            divergence.oog_info = Some(OutOfGasInfo {
+                opcode,
+                opcode_name: opcode_mnemonic(opcode).to_string(),
+                pc: 0,
+                contract: CONTRACT,
+                code_address: CONTRACT,
+                call_depth: 1,
+                gas_remaining: 0,
+                pattern: crate::divergence::OogPattern::Unknown,
+                gas_trajectory: vec![],
+                precompile: None,
+            });
+            divergence
+        };
+
+        // Three SSTOREs, two SLOADs, one CALL and a divergence without an OOG
+        for (block_number, opcode) in
+            [(1, 0x55), (2, 0x54), (3, 0x55), (4, 0xf1), (5, 0x54), (6, 0x55)]
+        {
+            db.record_divergence(&oog(block_number, opcode)).unwrap();
+        }
+        db.record_divergence(&DivergenceBuilder::new(7).build()).unwrap();
+
+        assert_eq!(db.oog_by_opcode().unwrap(), HashMap::from([(0x55, 3), (0x54, 2), (0xf1, 1)]));
+        assert_eq!(db.oog_by_mnemonic().unwrap(), vec![("SSTORE", 3), ("SLOAD", 2), ("CALL", 1)]);
+    }
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        for block_number in [100, 101, 102] {
+            db.record_divergence(&DivergenceBuilder::new(block_number).build()).unwrap();
+        }
+
+        let mut out = Vec::new();
+        assert_eq!(db.export_jsonl(&mut out, 100, 101).unwrap(), 2);
+        assert_eq!(String::from_utf8(out.clone()).unwrap().lines().count(), 2);
+
+        let imported = DivergenceDatabase::in_memory().unwrap();
+        assert_eq!(imported.import_jsonl(&out[..]).unwrap(), 2);
+        let records = imported.since_id(0, 10).unwrap();
+        assert_eq!(
+            records[0].divergence.to_json().unwrap(),
+            DivergenceBuilder::new(100).build().to_json().unwrap()
+        );
+        assert_eq!(
+            records[1].divergence.to_json().unwrap(),
+            DivergenceBuilder::new(101).build().to_json().unwrap()
+        );
+
+        assert!(imported.import_jsonl(&b"{}\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_divergence_uid_across_databases() {
+        let stored_uid = |db: &DivergenceDatabase, id: i64| -> Vec<u8> {
+            let conn = db.conn.lock().unwrap();
+            conn.query_row("SELECT divergence_uid FROM divergences WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+        let divergence = DivergenceBuilder::new(2).build();
+
+        let a = DivergenceDatabase::in_memory().unwrap();
+        a.record_divergence(&DivergenceBuilder::new(1).build()).unwrap();
+        let id_a = a.record_divergence(&divergence).unwrap();
+        let b = DivergenceDatabase::in_memory().unwrap();
+        let id_b = b.record_divergence(&divergence).unwrap();
+
+        // Different row ids, same uid
+        assert_ne!(id_a, id_b);
+        assert_eq!(stored_uid(&a, id_a), divergence.uid().to_vec());
+        assert_eq!(stored_uid(&b, id_b), divergence.uid().to_vec());
+        assert_eq!(a.divergence_id_by_uid(divergence.uid()).unwrap(), Some(id_a));
+        assert_eq!(b.divergence_id_by_uid(divergence.uid()).unwrap(), Some(id_b));
+
+        // Recording it again returns the existing row
+        assert_eq!(b.record_divergence(&divergence).unwrap(), id_b);
+        assert_eq!(b.count_divergences(0, 10).unwrap(), 1);
+
+        // Merging `a` into `b` only adds the divergence `b` is missing
+        let mut out = Vec::new();
+        a.export_jsonl(&mut out, 0, 10).unwrap();
+        assert_eq!(b.import_jsonl(&out[..]).unwrap(), 1);
+        assert_eq!(b.import_jsonl(&out[..]).unwrap(), 0);
+        assert_eq!(b.count_divergences(0, 10).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_compression_from_path() {
+        assert_eq!(Compression::from_path("out.jsonl"), Compression::None);
+        assert_eq!(Compression::from_path("out.jsonl.gz"), Compression::Gzip);
+        assert_eq!(Compression::from_path("out.jsonl.zst"), Compression::Zstd);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_jsonl_round_trip() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        for block_number in 0..50 {
+            db.record_divergence(&DivergenceBuilder::new(block_number).build()).unwrap();
+        }
+        let expected: Vec<_> = (0..50)
+            .map(|block_number| DivergenceBuilder::new(block_number).build().to_json().unwrap())
+            .collect();
+
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["divergences.jsonl.gz", "divergences.jsonl.zst"] {
+            let path = dir.path().join(name);
+            let compression = Compression::from_path(&path);
+            assert_eq!(db.export_jsonl_file(&path, 0, 49, compression).unwrap(), 50);
+
+            let imported = DivergenceDatabase::in_memory().unwrap();
+            assert_eq!(imported.import_jsonl_file(&path, compression).unwrap(), 50);
+            let records = imported.since_id(0, 100).unwrap();
+            let actual: Vec<_> =
+                records.iter().map(|r| r.divergence.to_json().unwrap()).collect();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_dropping_writer_flushes_queued_divergences() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let writer = DivergenceWriter::spawn(Arc::new(db.clone())).unwrap();
+        for block_number in 0..250 {
+            writer.send(DivergenceBuilder::new(block_number).build()).unwrap();
+        }
+
+        drop(writer);
+        assert_eq!(db.count_divergences(0, 1000).unwrap(), 250);
+
+        let writer = DivergenceWriter::spawn(Arc::new(db.clone())).unwrap();
+        writer.send(DivergenceBuilder::new(1000).build()).unwrap();
+        assert_eq!(writer.shutdown(), 1);
+        assert_eq!(db.count_divergences(0, 1000).unwrap(), 251);
+    }
+
+    #[test]
+    fn test_divergence_rate_series() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        for block_number in [5, 999, 1000, 3500, 3999] {
+            db.record_divergence(&DivergenceBuilder::new(block_number).build()).unwrap();
+        }
+        let summaries = [(5, 10), (999, 20), (1000, 30), (1500, 40), (2500, 50), (3999, 60)];
+        for (block_number, analyzed_tx_count) in summaries {
+            db.record_block_summary(&summary(block_number, analyzed_tx_count)).unwrap();
+        }
+        // A re-analyzed block replaces its summary
+        db.record_block_summary(&summary(5, 15)).unwrap();
+
+        assert_eq!(
+            db.divergence_rate_series(1000).unwrap(),
+            vec![(0, 35, 2), (1000, 70, 1), (2000, 50, 0), (3000, 60, 2)]
+        );
+        assert_eq!(db.divergence_rate_series(4000).unwrap(), vec![(0, 215, 5)]);
+        assert!(db.divergence_rate_series(0).is_err());
+    }
+
+    #[test]
+    fn test_near_misses_not_counted() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let mut near_miss = DivergenceBuilder::new(10).build();
+        near_miss.near_miss = true;
+        db.record_divergence(&near_miss).unwrap();
+        let mut ab = DivergenceBuilder::new(10).build();
+        ab.comparison = Comparison::AB;
+        db.record_divergence(&ab).unwrap();
+        db.record_block_summary(&summary(10, 2)).unwrap();
+
+        assert_eq!(db.count_divergences(0, 100).unwrap(), 0);
+        assert_eq!(db.divergence_rate_series(100).unwrap(), vec![(0, 2, 0)]);
+    }
+
+    #[test]
+    fn test_writer_records_block_summaries() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let writer = DivergenceWriter::spawn(Arc::new(db.clone())).unwrap();
+        writer.send(DivergenceBuilder::new(10).build()).unwrap();
+        writer.send_block_summary(summary(10, 4)).unwrap();
+
+        assert_eq!(writer.shutdown(), 1);
+        assert_eq!(db.divergence_rate_series(100).unwrap(), vec![(0, 4, 1)]);
+    }
+
+    #[test]
+    fn test_writer_records_checkpoints() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        assert_eq!(db.last_checkpoint().unwrap(), None);
+
+        let writer = DivergenceWriter::spawn(Arc::new(db.clone())).unwrap();
+        for block_number in [10, 11] {
+            let checkpoint = Checkpoint { block_number, config_hash: B256::repeat_byte(0x01) };
+            writer.send_checkpoint(checkpoint).unwrap();
+        }
+        writer.shutdown();
+
+        assert_eq!(
+            db.last_checkpoint().unwrap(),
+            Some(Checkpoint { block_number: 11, config_hash: B256::repeat_byte(0x01) })
+        );
+    }
+
+    #[test]
+    fn test_schema_sql_covers_database() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let conn = db.conn.lock().unwrap();
+        let version: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+        assert_eq!(version, DATABASE_SCHEMA_VERSION);
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT name FROM sqlite_master
+                 WHERE type IN ('table', 'index') AND name NOT LIKE 'sqlite_%'",
+            )
+            .unwrap();
+        let names: Vec<String> =
+            stmt.query_map([], |row| row.get(0)).unwrap().collect::<Result<_, _>>().unwrap();
+        assert!(names.contains(&"runs".to_string()));
+
+        let sql = schema_sql();
+        assert!(sql.starts_with(&format!("PRAGMA user_version = {DATABASE_SCHEMA_VERSION};")));
+        for name in names {
+            assert!(sql.contains(&format!("IF NOT EXISTS {name} ")), "{name} missing from schema");
+        }
+
+        // The script builds the same schema on a fresh database
+        let fresh = Connection::open_in_memory().unwrap();
+        fresh.execute_batch(&sql).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_schema_from_v1() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let old = db.record_divergence(&DivergenceBuilder::new(1).build()).unwrap();
+
+        // Take the database back to version 1
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute_batch("DROP INDEX idx_divergences_uid; DROP TABLE gas_observations;")
+                .unwrap();
+            for (name, _) in DIVERGENCES_COLUMNS_V2 {
+                conn.execute(&format!("ALTER TABLE divergences DROP COLUMN {name}"), []).unwrap();
+            }
+            conn.pragma_update(None, "user_version", 1).unwrap();
+        }
+
+        db.initialize_schema().unwrap();
+        let version: u32 = db
+            .conn
+            .lock()
+            .unwrap()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, DATABASE_SCHEMA_VERSION);
+
+        // Old rows read back with the new columns empty, new rows are deduplicated again
+        let divergence = DivergenceBuilder::new(2).build();
+        let new = db.record_divergence(&divergence).unwrap();
+        assert_eq!(db.record_divergence(&divergence).unwrap(), new);
+        let records = db.since_id(0, 10).unwrap();
+        assert_eq!(records.iter().map(|record| record.id).collect::<Vec<_>>(), vec![old, new]);
+        assert!(!records[0].divergence.near_miss);
+
+        // Migrating an up to date database changes nothing
+        db.initialize_schema().unwrap();
+    }
+
+    #[test]
+    fn test_migrate_schema_from_v0() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let old = db.record_divergence(&DivergenceBuilder::new(1).build()).unwrap();
+
+        // Take the database back to the tables it had before schema versioning
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute_batch(
+                "DROP INDEX idx_divergences_uid; DROP INDEX idx_divergences_code_hash;
+                DROP TABLE gas_observations; DROP TABLE create2_deployments;
+                DROP TABLE block_summaries; DROP TABLE checkpoint; DROP TABLE runs;",
+            )
+            .unwrap();
+            for (name, _) in DIVERGENCES_COLUMNS_V1.iter().chain(DIVERGENCES_COLUMNS_V2) {
+                conn.execute(&format!("ALTER TABLE divergences DROP COLUMN {name}"), []).unwrap();
+            }
+            conn.pragma_update(None, "user_version", 0).unwrap();
+        }
+
+        db.initialize_schema().unwrap();
+        let version: u32 = db
+            .conn
+            .lock()
+            .unwrap()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, DATABASE_SCHEMA_VERSION);
+
+        let new = db.record_divergence(&DivergenceBuilder::new(2).build()).unwrap();
+        let records = db.since_id(0, 10).unwrap();
+        assert_eq!(records.iter().map(|record| record.id).collect::<Vec<_>>(), vec![old, new]);
+        // Old rows read back with the added columns empty
+        assert_eq!(records[0].divergence.gas_analysis.normal_gas_charged, 0);
+        assert_eq!(records[1].divergence.gas_analysis.normal_gas_charged, 21_000);
+    }
+
+    #[test]
+    fn test_unsupported_schema_version() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let version = DATABASE_SCHEMA_VERSION + 1;
+        db.conn.lock().unwrap().pragma_update(None, "user_version", version).unwrap();
+        let err = db.initialize_schema().unwrap_err();
+        assert!(matches!(err, DatabaseError::UnsupportedSchemaVersion(v) if v == version));
+    }
+
+    #[test]
+    fn test_undecodable_row_is_an_error() {
+        for corruption in [
+            "UPDATE divergences SET threshold_map_json = 'not json'",
+            "UPDATE divergences SET comparison = 'c_d'",
+        ] {
+            let db = DivergenceDatabase::in_memory().unwrap();
+            let id = db.record_divergence(&DivergenceBuilder::new(1).build()).unwrap();
+            db.conn.lock().unwrap().execute(corruption, []).unwrap();
+
+            let err = db.divergence(id).unwrap_err();
+            assert!(matches!(
+                err,
+                DatabaseError::Sqlite(rusqlite::Error::FromSqlConversionFailure(..))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_truncated_divergence() {
+        let frame = |call_index, input: &[u8]| CallFrame {
+            call_index,
+            depth: 0,
+            from: Address::ZERO,
+            to: Some(CONTRACT),
+            call_type: CallType::Call,
+            gas_provided: 100_000,
+            gas_used: 21_000,
+            success: true,
+            out_of_gas: false,
+            input_len: input.len(),
+            output_len: 0,
+            input: Some(Bytes::copy_from_slice(input)),
+            output: Some(Bytes::new()),
+        };
+        let mut divergence = DivergenceBuilder::new(1).build();
+        divergence.call_trees = Some(CallTrees {
+            normal: vec![frame(0, &[0xaa; 64])],
+            experimental: vec![frame(0, &[0xaa; 64]), frame(1, &[0xbb; 8])],
+        });
+        divergence.event_logs = Some(EventLogs {
+            normal: vec![EventLog {
+                log_index: 0,
+                address: CONTRACT,
+                topics: vec![],
+                data: Bytes::from_static(&[0xcc; 4]),
+            }],
+            experimental: vec![],
+        });
+
+        // Within the cap nothing is dropped
+        let mut within = divergence.clone();
+        within.limit_stored_bytes(140);
+        assert!(!within.truncated);
+
+        // The second 64-byte input overflows the cap, so it and everything after are dropped
+        divergence.limit_stored_bytes(100);
+        assert!(divergence.truncated);
+        let call_trees = divergence.call_trees.as_ref().unwrap();
+        assert!(call_trees.normal[0].input.is_some());
+        assert!(call_trees.experimental.iter().all(|frame| frame.input.is_none()));
+        assert_eq!(call_trees.experimental[0].input_len, 64);
+        assert!(divergence.event_logs.as_ref().unwrap().normal[0].data.is_empty());
+
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let id = db.record_divergence(&divergence).unwrap();
+        db.record_divergence(&DivergenceBuilder::new(2).build()).unwrap();
+        let records = db.since_id(0, 10).unwrap();
+        assert_eq!(records[0].id, id);
+        assert!(records[0].divergence.truncated);
+        assert!(!records[1].divergence.truncated);
+        let stored = db.call_trees(id).unwrap().unwrap();
+        assert_eq!(stored.experimental[0].input, None);
+        assert_eq!(stored.experimental[0].input_len, 64);
+    }
+
+    #[test]
+    fn test_run_log() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        assert!(db.runs().unwrap().is_empty());
+
+        let first = ResearchConfig::default();
+        let second = ResearchConfig { gas_multiplier: 64, end_block: Some(500), ..first.clone() };
+
+        let first_id = db.record_run_start(&first).unwrap();
+        let stats =
+            ResearchStats { blocks_processed: 10, divergences_found: 3, divergences_recorded: 2 };
+        db.record_run_end(first_id, &stats).unwrap();
+        db.record_run_start(&second).unwrap();
+
+        // Ended runs are not rewritten
+        let later =
+            ResearchStats { blocks_processed: 99, divergences_found: 0, divergences_recorded: 0 };
+        db.record_run_end(first_id, &later).unwrap();
+
+        let runs = db.runs().unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].config_hash, first.config_hash());
+        assert_eq!(runs[1].config_hash, second.config_hash());
+        assert_ne!(runs[0].config_hash, runs[1].config_hash);
+        assert_eq!(runs[0].settings, first.settings());
+        assert!(runs[1].settings.contains("gas_multiplier=64"));
+        assert_eq!(runs[1].end_block, Some(500));
+
+        assert!(runs[0].ended_at.is_some_and(|ended| ended >= runs[0].started_at));
+        let recorded = runs[0].stats.unwrap();
+        assert_eq!(recorded.blocks_processed, 10);
+        assert_eq!(recorded.divergences_recorded, 2);
+        assert_eq!(runs[1].ended_at, None);
+        assert!(runs[1].stats.is_none());
+    }
+
+    #[test]
+    fn test_database_creation() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        assert!(db.count_divergences(0, 1000).unwrap() == 0);
+    }
+
+    #[test]
+    fn test_integrity_check() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        assert!(db.integrity_check().unwrap());
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = DivergenceDatabase::open(dir.path().join("divergences.db")).unwrap();
+        db.record_divergence(&DivergenceBuilder::new(1).build()).unwrap();
+        assert!(db.integrity_check().unwrap());
+    }
+
+    #[test]
+    fn test_open_read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("divergences.db");
+        assert!(DivergenceDatabase::open_read_only(&path).is_err());
+
+        let db = DivergenceDatabase::open(&path).unwrap();
+        db.record_divergence(&DivergenceBuilder::new(1).build()).unwrap();
+
+        let reader = DivergenceDatabase::open_read_only(&path).unwrap();
+        assert_eq!(reader.since_id(0, 10).unwrap().len(), 1);
+        assert!(reader.insert_divergence(&DivergenceBuilder::new(2).build()).is_err());
+
+        // An older schema cannot be migrated without writing
+        db.conn.lock().unwrap().pragma_update(None, "user_version", 1).unwrap();
+        assert!(matches!(
+            DivergenceDatabase::open_read_only(&path),
+            Err(DatabaseError::UnsupportedSchemaVersion(1))
+        ));
+    }
+
+    #[test]
+    fn test_write_retried_while_database_busy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("divergences.db");
+        let db = DivergenceDatabase::open(&path).unwrap();
+        // Fail on a held lock right away instead of waiting in SQLite, so the retries kick in
+        db.conn.lock().unwrap().busy_timeout(Duration::ZERO).unwrap();
+
+        let locker = Connection::open(&path).unwrap();
+        locker.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        let err = db.insert_divergence(&DivergenceBuilder::new(1).build()).unwrap_err();
+        assert!(is_busy(&err));
+
+        // Release the lock while the write is backing off
+        let release = thread::spawn(move || {
+            thread::sleep(RETRY_BACKOFF * 3);
+            locker.execute_batch("COMMIT").unwrap();
+        });
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        ::metrics::with_local_recorder(&recorder, || {
+            db.record_divergence(&DivergenceBuilder::new(1).build()).unwrap();
+        });
+        release.join().unwrap();
+        assert_eq!(db.count_divergences(0, 10).unwrap(), 1);
+
+        assert!(counter_value(&snapshotter, "reth_research_database_retries_total", None) > 0);
+
+        // Other errors are returned without retrying
+        let mut attempts = 0;
+        let result = retry_busy("test", || -> Result<(), _> {
+            attempts += 1;
+            Err(DatabaseError::NotInitialized)
+        });
+        assert!(matches!(result, Err(DatabaseError::NotInitialized)));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn test_record_divergence() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+
+        let divergence = DivergenceBuilder::new(100).with_tx_index(5).build();
+
+        let id = db.record_divergence(&divergence).unwrap();
+        assert!(id > 0);
+
+        assert_eq!(db.count_divergences(0, 1000).unwrap(), 1);
+        assert_eq!(db.count_by_type(DivergenceType::StateRoot).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_duplicate_types_stored_once() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let mut divergence = DivergenceBuilder::new(1).build();
+        divergence.divergence_types =
+            vec![DivergenceType::GasPattern, DivergenceType::Status, DivergenceType::GasPattern];
+        db.record_divergence(&divergence).unwrap();
+
+        let types: String = db
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT divergence_types FROM divergences", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(types, "status,gas_pattern");
+        assert_eq!(db.count_by_type(DivergenceType::GasPattern).unwrap(), 1);
+        assert_eq!(
+            db.since_id(0, 10).unwrap()[0].divergence.divergence_types,
+            vec![DivergenceType::Status, DivergenceType::GasPattern]
+        );
+    }
+
+    #[test]
+    fn test_export_ratio_csv() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        for block_number in [100, 101, 102] {
+            db.record_divergence(&DivergenceBuilder::new(block_number).build()).unwrap();
+        }
+        let mut no_gas = DivergenceBuilder::new(101).build();
+        no_gas.tx_index = 1;
+        no_gas.gas_analysis.normal_gas_used = 0;
+        db.record_divergence(&no_gas).unwrap();
+
+        let mut out = Vec::new();
+        let rows = db.export_ratio_csv(&mut out, 100, 101).unwrap();
+        assert_eq!(rows, 3);
+
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "block_number,tx_hash,gas_efficiency_ratio,ratio_valid");
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1], format!("100,{},1,true", B256::ZERO));
+        assert!(lines[3].starts_with("101,") && lines[3].ends_with(",false"));
+    }
+
+    #[test]
+    fn test_since_id_returns_only_newer_rows() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        assert_eq!(db.max_id().unwrap(), 0);
+
+        for block_number in 1..=3 {
+            db.record_divergence(&DivergenceBuilder::new(block_number).build()).unwrap();
+        }
+        let last_id = db.max_id().unwrap();
+
+        for block_number in 4..=5 {
+            db.record_divergence(&DivergenceBuilder::new(block_number).build()).unwrap();
+        }
+
+        let records = db.since_id(last_id, 100).unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.id > last_id));
+        assert_eq!(records[0].divergence.block_number, 4);
+        assert_eq!(records[1].divergence.block_number, 5);
+        assert_eq!(records[0].divergence.divergence_types, vec![DivergenceType::StateRoot]);
+
+        assert!(db.since_id(db.max_id().unwrap(), 100).unwrap().is_empty());
+
+        assert_eq!(db.divergence(last_id).unwrap().unwrap().divergence.block_number, 3);
+        assert!(db.divergence(db.max_id().unwrap() + 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_gas_observations_round_trip() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let mut divergence = DivergenceBuilder::new(1).build();
+        divergence.gas_observations = Some(vec![
+            GasObservation {
+                contract: Address::repeat_byte(0x01),
+                pc: 4,
+                gas_remaining: vec![5_000, 4_912, 4_824],
+            },
+            GasObservation { contract: Address::repeat_byte(0x02), pc: 4, gas_remaining: vec![900] },
+        ]);
+        let id = db.record_divergence(&divergence).unwrap();
+
+        assert_eq!(db.gas_observations(id).unwrap(), divergence.gas_observations);
+        let without = db.record_divergence(&DivergenceBuilder::new(2).build()).unwrap();
+        assert_eq!(db.gas_observations(without).unwrap(), None);
+    }
+
+    #[test]
+    fn test_created_code_round_trip() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let mut divergence = DivergenceBuilder::new(1).build();
+        divergence.divergence_types = vec![DivergenceType::Created];
+        divergence.created_code = Some(CreatedCode {
+            address: Address::repeat_byte(0x01),
+            normal_code_hash: B256::repeat_byte(0x02),
+            experimental_code_hash: B256::repeat_byte(0x03),
+        });
+        db.record_divergence(&divergence).unwrap();
+        db.record_divergence(&DivergenceBuilder::new(2).build()).unwrap();
+
+        let records = db.since_id(0, 10).unwrap();
+        assert_eq!(records[0].divergence.created_code, divergence.created_code);
+        assert_eq!(records[1].divergence.created_code, None);
+        assert_eq!(db.count_by_type(DivergenceType::Created).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_access_list_diff_round_trip() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let mut divergence = DivergenceBuilder::new(1).build();
+        divergence.divergence_types = vec![DivergenceType::AccessList];
+        divergence.access_list_diff = Some(AccessListDiff {
+            normal_only: AccessSet::from([(Address::repeat_byte(0x01), [B256::ZERO].into())]),
+            experimental_only: AccessSet::from([(Address::repeat_byte(0x02), Default::default())]),
+        });
+        db.record_divergence(&divergence).unwrap();
+        db.record_divergence(&DivergenceBuilder::new(2).build()).unwrap();
+
+        let records = db.since_id(0, 10).unwrap();
+        assert_eq!(records[0].divergence.access_list_diff, divergence.access_list_diff);
+        assert_eq!(records[1].divergence.access_list_diff, None);
+    }
+
+    #[test]
+    fn test_griefing_is_not_an_oog() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let mut divergence = DivergenceBuilder::new(1).build();
+        divergence.griefing = Some(OutOfGasInfo {
+            opcode: 0xFD,
+            opcode_name: opcode_mnemonic(0xFD).to_string(),
+            pc: 12,
+            contract: CONTRACT,
+            code_address: CONTRACT,
+            call_depth: 2,
+            gas_remaining: 50,
+            pattern: crate::divergence::OogPattern::Griefing,
+            gas_trajectory: vec![],
+            precompile: None,
+        });
+        db.record_divergence(&divergence).unwrap();
+
+        let records = db.since_id(0, 10).unwrap();
+        assert_eq!(records[0].divergence.griefing, divergence.griefing);
+        assert!(records[0].divergence.oog_info.is_none());
+        assert!(db.oog_by_opcode().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_frame_gas_matches_inspectors() {
+        let callee = Address::repeat_byte(0xbb);
+        let env = TestEnv::new()
+            .with_contract(CONTRACT, test_utils::call_code(callee))
+            .with_contract(callee, vec![0x00]);
+        let tx = TestEnv::call_tx(CONTRACT, vec![], 1_000_000);
+
+        let mut normal_inspector = TrackingInspector::new();
+        env.transact(tx.clone(), &mut normal_inspector).unwrap();
+
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let mut experimental_inspector = GasResearchInspector::new(config, 1_000_000);
+        env.transact(tx, &mut experimental_inspector).unwrap();
+
+        let normal_frames = normal_inspector.call_frames().to_vec();
+        let experimental_frames = experimental_inspector.call_frames().to_vec();
+        assert_eq!(normal_frames.len(), 2);
+        assert_eq!(experimental_frames.len(), 2);
+
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let mut divergence = DivergenceBuilder::new(1).build();
+        divergence.call_trees = Some(CallTrees {
+            normal: normal_frames.clone(),
+            experimental: experimental_frames.clone(),
+        });
+        let id = db.record_divergence(&divergence).unwrap();
+
+        let call_trees = db.call_trees(id).unwrap().unwrap();
+        for (stored, recorded) in
+            [(&call_trees.normal, &normal_frames), (&call_trees.experimental, &experimental_frames)]
+        {
+            let mut recorded = recorded.clone();
+            recorded.sort_by_key(|frame| frame.call_index);
+            assert_eq!(stored.len(), recorded.len());
+            for (stored, recorded) in stored.iter().zip(&recorded) {
+                assert_eq!(stored.call_index, recorded.call_index);
+                assert_eq!(stored.depth, recorded.depth);
+                assert_eq!(stored.to, recorded.to);
+                assert_eq!(stored.call_type, recorded.call_type);
+                assert_eq!(stored.gas_used, recorded.gas_used);
+                assert_eq!(stored.input_len, recorded.input_len);
+                assert_eq!(stored.output_len, recorded.output_len);
+            }
+        }
+        assert!(db
+            .call_trees(db.record_divergence(&DivergenceBuilder::new(2).build()).unwrap())
+            .unwrap()
+            .is_none());
+
+        let frame_gas = db.frame_gas(id).unwrap();
+        assert_eq!(frame_gas.len(), 2);
+        for gas in frame_gas {
+            let normal = normal_frames.iter().find(|f| f.call_index == gas.call_index).unwrap();
+            let experimental =
+                experimental_frames.iter().find(|f| f.call_index == gas.call_index).unwrap();
+            assert_eq!(gas.normal_gas_used, Some(normal.gas_used));
+            assert_eq!(gas.simulated_gas_used, Some(experimental.gas_used));
+        }
     }
 }
@@ -1,7 +1,31 @@
 //! Types for representing execution divergences.
 
-use alloy_primitives::{Address, Bytes, B256};
+use crate::inspector::opcode_mnemonic;
+use alloy_primitives::{hex, keccak256, Address, Bytes, B256};
 use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+/// Version of the exported [`Divergence`] JSON format.
+///
+/// Bump this whenever a change to [`Divergence`] would make older readers mis-parse new records.
+///
+/// - 1: initial format.
+/// - 2: [`DivergenceLocation::opcode_name`] holds mnemonics rather than hex, and records carry
+///   `griefing`, `reverting_selector`, `cause`, `created_code`, `gas_observations`, `truncated`,
+///   `near_miss`, `comparison`, `function_name`, `calldata` and `access_list_diff`.
+///   [`DivergenceLocation`] gains `code_address` and `code_hash`, and [`OutOfGasInfo`] gains
+///   `code_address` and `precompile`.
+pub const DIVERGENCE_SCHEMA_VERSION: u32 = 2;
+
+/// Envelope that tags an exported divergence with the schema version it was written with.
+#[derive(Serialize, Deserialize)]
+struct VersionedDivergence<T> {
+    schema_version: u32,
+    divergence: T,
+}
 
 /// A detected divergence between normal and experimental execution.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,15 +60,385 @@ pub struct Divergence {
     /// Out-of-gas information (if applicable)
     pub oog_info: Option<OutOfGasInfo>,
 
+    /// First child call in experimental execution that reverted after consuming at least 63/64
+    /// of its gas, griefing its parent (if any)
+    #[serde(default)]
+    pub griefing: Option<OutOfGasInfo>,
+
+    /// Selector of the deepest frame that started the experimental revert, if the transaction
+    /// reverted
+    #[serde(default)]
+    pub reverting_selector: Option<[u8; 4]>,
+
+    /// Whether the gas costs or the experimental state drove the divergence (only if detailed
+    /// tracing is enabled)
+    #[serde(default)]
+    pub cause: Option<DivergenceCause>,
+
     /// Call trees (only if detailed tracing is enabled)
     pub call_trees: Option<CallTrees>,
 
     /// Event logs (only if detailed tracing is enabled)
     pub event_logs: Option<EventLogs>,
+
+    /// CREATE2 deployments (only if detailed tracing is enabled)
+    pub create2_deployments: Option<Create2Deployments>,
+
+    /// Runtime code hashes of the contract created by the transaction, if they differ (only if
+    /// detailed tracing is enabled)
+    #[serde(default)]
+    pub created_code: Option<CreatedCode>,
+
+    /// Values returned by GAS opcodes in experimental execution, for inferring the `gasleft()`
+    /// thresholds contracts branch on (only if detailed tracing is enabled)
+    #[serde(default)]
+    pub gas_observations: Option<Vec<GasObservation>>,
+
+    /// Multiplier at which each divergence type first appeared (only with sweep multipliers set)
+    #[serde(default)]
+    pub threshold_map: Option<BTreeMap<u64, Vec<DivergenceType>>>,
+
+    /// Whether call frame or event log data was dropped to stay under the configured size cap,
+    /// see [`Self::limit_stored_bytes`]
+    #[serde(default)]
+    pub truncated: bool,
+
+    /// Whether the executions matched but experimental execution finished with less gas left
+    /// than the [near miss threshold], so a slightly higher multiplier could break the
+    /// transaction
+    ///
+    /// [near miss threshold]: crate::config::ResearchConfig::near_miss_gas_threshold
+    #[serde(default)]
+    pub near_miss: bool,
+
+    /// Executions the divergence was found between. For [`Comparison::AB`] the normal fields
+    /// describe the execution under schedule A and the experimental fields the one under B
+    #[serde(default)]
+    pub comparison: Comparison,
+
+    /// Signature of the function the divergence is located in (e.g.
+    /// `transfer(address,uint256)`), if a signature database resolves its selector
+    #[serde(default)]
+    pub function_name: Option<String>,
+
+    /// Size and zero/non-zero byte split of the transaction's calldata
+    #[serde(default)]
+    pub calldata: Option<CalldataStats>,
+
+    /// Accounts and storage slots only one execution accessed (only if detailed tracing is
+    /// enabled and access lists are compared)
+    #[serde(default)]
+    pub access_list_diff: Option<AccessListDiff>,
+}
+
+impl Divergence {
+    /// The highest severity among the detected divergence types.
+    pub fn severity(&self) -> Severity {
+        self.divergence_types.iter().map(|t| t.severity()).max().unwrap_or(Severity::Low)
+    }
+
+    /// The detected divergence types in canonical order, each once.
+    pub fn sorted_types(&self) -> Vec<DivergenceType> {
+        let mut divergence_types = self.divergence_types.clone();
+        divergence_types.sort_unstable();
+        divergence_types.dedup();
+        divergence_types
+    }
+
+    /// Whether the divergence counts towards divergence totals and rates: near misses matched
+    /// and [`Comparison::AB`] rows compare two experimental schedules, so neither does.
+    pub fn is_counted(&self) -> bool {
+        !self.near_miss && self.comparison != Comparison::AB
+    }
+
+    /// One-line human summary, e.g.
+    /// `block 18000000 tx 5 [state_root,status] ratio=0.720 oog=storage_heavy@SLOAD`.
+    ///
+    /// Types are listed in canonical order and the out-of-gas part is left out when
+    /// experimental execution did not run out of gas. Near misses end in `near_miss`.
+    pub fn summary_line(&self) -> String {
+        let types = self.sorted_types().iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        let mut line = format!(
+            "block {} tx {} [{}] ratio={:.3}",
+            self.block_number,
+            self.tx_index,
+            types.join(","),
+            self.gas_analysis.gas_efficiency_ratio,
+        );
+        if let Some(oog) = &self.oog_info {
+            line.push_str(&format!(" oog={}@{}", oog.pattern, opcode_mnemonic(oog.opcode)));
+        }
+        if self.near_miss {
+            line.push_str(" near_miss");
+        }
+        line
+    }
+
+    /// Human-readable Markdown report for sharing, e.g. in an issue: a header describing the
+    /// transaction and where it diverged, the gas and operation counts of both executions, the
+    /// out-of-gas details and, if recorded, both call trees.
+    ///
+    /// Only operation counts that are non-zero in either execution are listed.
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        self.write_markdown(&mut md).expect("writing to a string is infallible");
+        md
+    }
+
+    fn write_markdown(&self, md: &mut String) -> std::fmt::Result {
+        let types = self.sorted_types().iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        writeln!(md, "## Divergence in block {} tx {}", self.block_number, self.tx_index)?;
+        writeln!(md)?;
+        writeln!(md, "| | |")?;
+        writeln!(md, "|---|---|")?;
+        writeln!(md, "| Transaction | `{}` |", self.tx_hash)?;
+        writeln!(md, "| Timestamp | {} |", self.timestamp)?;
+        writeln!(md, "| Types | {} |", types.join(", "))?;
+        writeln!(md, "| Severity | {:?} |", self.severity())?;
+        writeln!(md, "| Comparison | {} |", self.comparison)?;
+        if let Some(cause) = self.cause {
+            writeln!(md, "| Cause | {cause} |")?;
+        }
+        if let Some(location) = &self.divergence_location {
+            writeln!(
+                md,
+                "| Location | `{}` pc {} `{}` (depth {}) |",
+                location.contract,
+                location.pc,
+                opcode_mnemonic(location.opcode),
+                location.call_depth
+            )?;
+            if location.code_address != location.contract {
+                writeln!(md, "| Code address | `{}` |", location.code_address)?;
+            }
+            writeln!(md, "| Code hash | `{}` |", location.code_hash)?;
+            if let Some(selector) = location.function_selectors.last().copied().flatten() {
+                writeln!(md, "| Selector | `{}` |", hex::encode_prefixed(selector))?;
+            }
+        }
+        if let Some(function_name) = &self.function_name {
+            writeln!(md, "| Function | `{function_name}` |")?;
+        }
+        if let Some(selector) = self.reverting_selector {
+            writeln!(md, "| Reverting selector | `{}` |", hex::encode_prefixed(selector))?;
+        }
+        if let Some(calldata) = self.calldata {
+            writeln!(
+                md,
+                "| Calldata | {} bytes ({} zero, {} non-zero) |",
+                calldata.len, calldata.zero_bytes, calldata.nonzero_bytes
+            )?;
+        }
+        if self.near_miss {
+            writeln!(md, "| Near miss | yes |")?;
+        }
+        if self.truncated {
+            writeln!(md, "| Truncated | yes |")?;
+        }
+
+        let gas = &self.gas_analysis;
+        writeln!(md, "\n### Gas\n")?;
+        writeln!(md, "| | Normal | Experimental |")?;
+        writeln!(md, "|---|---:|---:|")?;
+        writeln!(md, "| Gas used | {} | {} |", gas.normal_gas_used, gas.experimental_gas_used)?;
+        writeln!(
+            md,
+            "| Gas charged | {} | {} |",
+            gas.normal_gas_charged, gas.experimental_gas_charged
+        )?;
+        writeln!(md, "\nGas efficiency ratio: **{:.3}**", gas.gas_efficiency_ratio)?;
+
+        writeln!(md, "\n### Operation counts\n")?;
+        writeln!(md, "| Operation | Normal | Experimental | Diff |")?;
+        writeln!(md, "|---|---:|---:|---:|")?;
+        let experimental_counts = self.experimental_ops.named_counts();
+        for ((name, normal), (_, experimental)) in
+            self.normal_ops.named_counts().into_iter().zip(experimental_counts)
+        {
+            if normal != 0 || experimental != 0 {
+                let diff = experimental as i128 - normal as i128;
+                writeln!(md, "| {name} | {normal} | {experimental} | {diff:+} |")?;
+            }
+        }
+
+        if let Some(oog) = &self.oog_info {
+            writeln!(md, "\n### Out of gas\n")?;
+            writeln!(md, "- Opcode: `{}` at pc {}", opcode_mnemonic(oog.opcode), oog.pc)?;
+            writeln!(md, "- Contract: `{}` (depth {})", oog.contract, oog.call_depth)?;
+            if oog.code_address != oog.contract {
+                writeln!(md, "- Code address: `{}`", oog.code_address)?;
+            }
+            writeln!(md, "- Pattern: {}", oog.pattern)?;
+            writeln!(md, "- Gas remaining: {}", oog.gas_remaining)?;
+            if let Some(precompile) = oog.precompile {
+                writeln!(md, "- Precompile: `{precompile}`")?;
+            }
+        }
+
+        if let Some(griefing) = &self.griefing {
+            writeln!(md, "\n### Griefing revert\n")?;
+            writeln!(md, "- Contract: `{}` (depth {})", griefing.contract, griefing.call_depth)?;
+            writeln!(md, "- Gas remaining: {}", griefing.gas_remaining)?;
+        }
+
+        if let Some(call_trees) = &self.call_trees {
+            writeln!(md, "\n### Call trees")?;
+            for (label, frames) in
+                [("Normal", &call_trees.normal), ("Experimental", &call_trees.experimental)]
+            {
+                writeln!(md, "\n{label}:\n\n```text")?;
+                write_call_tree(md, frames)?;
+                writeln!(md, "```")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The semantic identity of this divergence, ignoring volatile details like call trees.
+    pub fn identity(&self) -> DivergenceIdentity {
+        DivergenceIdentity {
+            block_number: self.block_number,
+            tx_index: self.tx_index,
+            tx_hash: self.tx_hash,
+            divergence_types: self.sorted_types(),
+            comparison: self.comparison,
+        }
+    }
+
+    /// Content-addressed id of this divergence, see [`DivergenceIdentity::uid`].
+    pub fn uid(&self) -> B256 {
+        self.identity().uid()
+    }
+
+    /// The contract the divergence is attributed to: the storage context where execution first
+    /// diverged, or otherwise where experimental execution ran out of gas.
+    pub fn contract(&self) -> Option<Address> {
+        self.divergence_location
+            .as_ref()
+            .map(|location| location.contract)
+            .or_else(|| self.oog_info.as_ref().map(|oog| oog.contract))
+    }
+
+    /// Drop call frame input and output data and event log data once their combined size would
+    /// exceed `max_bytes`, and mark the divergence as truncated if anything was dropped.
+    ///
+    /// Data is kept in order: normal then experimental call frames, then normal then
+    /// experimental event logs. Frame data lengths are kept.
+    pub fn limit_stored_bytes(&mut self, max_bytes: usize) {
+        let mut stored = 0usize;
+        let mut truncated = false;
+        let mut keep = |len: usize| {
+            if !truncated && stored.saturating_add(len) <= max_bytes {
+                stored += len;
+            } else {
+                truncated = true;
+            }
+            !truncated
+        };
+
+        if let Some(call_trees) = &mut self.call_trees {
+            for frame in call_trees.normal.iter_mut().chain(&mut call_trees.experimental) {
+                for data in [&mut frame.input, &mut frame.output] {
+                    if data.as_ref().is_some_and(|bytes| !keep(bytes.len())) {
+                        *data = None;
+                    }
+                }
+            }
+        }
+        if let Some(event_logs) = &mut self.event_logs {
+            for log in event_logs.normal.iter_mut().chain(&mut event_logs.experimental) {
+                if !keep(log.data.len()) {
+                    log.data = Bytes::new();
+                }
+            }
+        }
+
+        self.truncated |= truncated;
+    }
+
+    /// Serialize this divergence to JSON, wrapped in an envelope carrying
+    /// [`DIVERGENCE_SCHEMA_VERSION`].
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        let versioned =
+            VersionedDivergence { schema_version: DIVERGENCE_SCHEMA_VERSION, divergence: self };
+        serde_json::to_string(&versioned)
+    }
+
+    /// Deserialize a divergence from JSON produced by [`Divergence::to_json`].
+    ///
+    /// Records written with a schema version other than [`DIVERGENCE_SCHEMA_VERSION`] are
+    /// rejected rather than parsed on a best-effort basis.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let versioned: VersionedDivergence<serde_json::Value> = serde_json::from_str(json)?;
+        if versioned.schema_version != DIVERGENCE_SCHEMA_VERSION {
+            return Err(serde::de::Error::custom(format!(
+                "unsupported divergence schema version {}, expected {}",
+                versioned.schema_version, DIVERGENCE_SCHEMA_VERSION
+            )));
+        }
+        serde_json::from_value(versioned.divergence)
+    }
+}
+
+/// Divergences are equal if they have the same [`DivergenceIdentity`].
+impl PartialEq for Divergence {
+    fn eq(&self, other: &Self) -> bool {
+        self.identity() == other.identity()
+    }
+}
+
+impl Eq for Divergence {}
+
+impl std::hash::Hash for Divergence {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.identity().hash(state);
+    }
+}
+
+/// Identifies a divergence by the transaction it occurred in and what diverged.
+///
+/// Usable as a map key for deduplicating and diffing divergences across runs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DivergenceIdentity {
+    /// Block number
+    pub block_number: u64,
+
+    /// Transaction index within the block
+    pub tx_index: u64,
+
+    /// Transaction hash
+    pub tx_hash: B256,
+
+    /// Types of divergence detected, sorted and deduplicated
+    pub divergence_types: Vec<DivergenceType>,
+
+    /// Executions the divergence was found between
+    #[serde(default)]
+    pub comparison: Comparison,
+}
+
+impl DivergenceIdentity {
+    /// Hash of the block number, transaction index, transaction hash and divergence types, and
+    /// of the comparison unless it is the default normal against schedule A.
+    ///
+    /// Unlike a database row id it is the same for the same divergence in every database, so it
+    /// correlates records across databases.
+    pub fn uid(&self) -> B256 {
+        let types = self.divergence_types.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        let mut preimage = Vec::with_capacity(48);
+        preimage.extend_from_slice(&self.block_number.to_be_bytes());
+        preimage.extend_from_slice(&self.tx_index.to_be_bytes());
+        preimage.extend_from_slice(self.tx_hash.as_slice());
+        preimage.extend_from_slice(types.join(",").as_bytes());
+        if self.comparison != Comparison::NormalA {
+            preimage.extend_from_slice(format!(";{}", self.comparison).as_bytes());
+        }
+        keccak256(preimage)
+    }
 }
 
 /// Type of divergence detected.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum DivergenceType {
     /// Post-transaction state root differs
@@ -56,6 +450,9 @@ pub enum DivergenceType {
     /// Success/revert status differs
     Status,
 
+    /// Transaction reverted normally but succeeded under the experimental costs
+    StatusImproved,
+
     /// Event logs differ
     EventLogs,
 
@@ -64,6 +461,63 @@ pub enum DivergenceType {
 
     /// Gas usage pattern significantly differs (structural difference)
     GasPattern,
+
+    /// CREATE2 deployed to an unexpected address or its success differs
+    Create2Address,
+
+    /// Transaction can no longer afford the adjusted experimental base fee
+    BaseFee,
+
+    /// Peak memory differs beyond the configured tolerance
+    Memory,
+
+    /// Contract creation succeeded in both executions but deployed different runtime code
+    Created,
+
+    /// The executions accessed different accounts or storage slots
+    AccessList,
+}
+
+impl DivergenceType {
+    /// How concerning this divergence type is on its own.
+    pub const fn severity(self) -> Severity {
+        match self {
+            Self::GasPattern => Severity::Low,
+            Self::ExecutionTrace |
+            Self::EventLogs |
+            Self::CallTree |
+            Self::StateRoot |
+            Self::Memory |
+            Self::AccessList => Severity::Medium,
+            Self::Status |
+            Self::StatusImproved |
+            Self::Create2Address |
+            Self::BaseFee |
+            Self::Created => Severity::High,
+        }
+    }
+}
+
+impl std::str::FromStr for DivergenceType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "state_root" => Ok(Self::StateRoot),
+            "execution_trace" => Ok(Self::ExecutionTrace),
+            "status" => Ok(Self::Status),
+            "status_improved" => Ok(Self::StatusImproved),
+            "event_logs" => Ok(Self::EventLogs),
+            "call_tree" => Ok(Self::CallTree),
+            "gas_pattern" => Ok(Self::GasPattern),
+            "create2_address" => Ok(Self::Create2Address),
+            "base_fee" => Ok(Self::BaseFee),
+            "memory" => Ok(Self::Memory),
+            "created" => Ok(Self::Created),
+            "access_list" => Ok(Self::AccessList),
+            _ => Err(format!("Invalid divergence type: {}", s)),
+        }
+    }
 }
 
 impl std::fmt::Display for DivergenceType {
@@ -72,9 +526,42 @@ impl std::fmt::Display for DivergenceType {
             Self::StateRoot => write!(f, "state_root"),
             Self::ExecutionTrace => write!(f, "execution_trace"),
             Self::Status => write!(f, "status"),
+            Self::StatusImproved => write!(f, "status_improved"),
             Self::EventLogs => write!(f, "event_logs"),
             Self::CallTree => write!(f, "call_tree"),
             Self::GasPattern => write!(f, "gas_pattern"),
+            Self::Create2Address => write!(f, "create2_address"),
+            Self::BaseFee => write!(f, "base_fee"),
+            Self::Memory => write!(f, "memory"),
+            Self::Created => write!(f, "created"),
+            Self::AccessList => write!(f, "access_list"),
+        }
+    }
+}
+
+/// Severity of a divergence, ordered from least to most concerning.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Only the gas usage pattern changed
+    Low,
+
+    /// Execution took a different path without changing the outcome
+    Medium,
+
+    /// The outcome of the transaction changed
+    High,
+}
+
+impl std::str::FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            _ => Err(format!("Invalid severity: {}", s)),
         }
     }
 }
@@ -92,6 +579,14 @@ pub struct GasAnalysis {
     /// Values near 1.0 indicate same execution path, just more expensive
     /// Values != 1.0 indicate different execution path taken
     pub gas_efficiency_ratio: f64,
+
+    /// Gas charged to the sender in normal execution, after the refund
+    #[serde(default)]
+    pub normal_gas_charged: u64,
+
+    /// Gas charged to the sender in experimental execution, after the scaled refund
+    #[serde(default)]
+    pub experimental_gas_charged: u64,
 }
 
 impl GasAnalysis {
@@ -109,10 +604,71 @@ impl GasAnalysis {
     pub fn is_structural_divergence(&self) -> bool {
         (self.gas_efficiency_ratio - 1.0).abs() > 0.05
     }
+
+    /// Calculate the gas charged to the sender with the refund scaled by `refund_multiplier`.
+    ///
+    /// `gas_used` and `gas_refunded` are as reported by the EVM: the refund is already capped at
+    /// the gross gas (`gas_used + gas_refunded`) divided by `max_refund_quotient` (5 since London,
+    /// 2 before), and the scaled refund is capped again at the same limit.
+    pub fn gas_charged(
+        gas_used: u64,
+        gas_refunded: u64,
+        refund_multiplier: f64,
+        max_refund_quotient: u64,
+    ) -> u64 {
+        let gross = gas_used + gas_refunded;
+        let refund = (gas_refunded as f64 * refund_multiplier) as u64;
+        gross - refund.min(gross / max_refund_quotient)
+    }
+
+    /// Ratio of experimental to normal gas charged: how much more the sender would pay.
+    pub fn gas_charged_ratio(&self) -> f64 {
+        if self.normal_gas_charged == 0 {
+            return 1.0;
+        }
+        self.experimental_gas_charged as f64 / self.normal_gas_charged as f64
+    }
+}
+
+/// Intrinsic gas per zero byte of calldata (EIP-2028).
+const CALLDATA_ZERO_BYTE_GAS: u64 = 4;
+
+/// Intrinsic gas per non-zero byte of calldata (EIP-2028).
+const CALLDATA_NONZERO_BYTE_GAS: u64 = 16;
+
+/// Size of a transaction's calldata (the init code for contract creations), split into the zero
+/// and non-zero bytes its intrinsic gas is charged for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CalldataStats {
+    /// Length in bytes
+    pub len: u64,
+
+    /// Number of zero bytes
+    pub zero_bytes: u64,
+
+    /// Number of non-zero bytes
+    pub nonzero_bytes: u64,
+}
+
+impl CalldataStats {
+    /// Count the bytes of `calldata`.
+    pub fn new(calldata: &[u8]) -> Self {
+        let zero_bytes = calldata.iter().filter(|byte| **byte == 0).count() as u64;
+        let len = calldata.len() as u64;
+        Self { len, zero_bytes, nonzero_bytes: len - zero_bytes }
+    }
+
+    /// Intrinsic gas charged for the calldata at the EIP-2028 costs, multiplied by
+    /// `multiplier`.
+    pub const fn intrinsic_gas(&self, multiplier: u64) -> u64 {
+        let gas = self.zero_bytes * CALLDATA_ZERO_BYTE_GAS +
+            self.nonzero_bytes * CALLDATA_NONZERO_BYTE_GAS;
+        gas.saturating_mul(multiplier)
+    }
 }
 
 /// Counts of various operations executed.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct OperationCounts {
     /// Number of SLOAD operations
     pub sload_count: u64,
@@ -134,14 +690,54 @@ pub struct OperationCounts {
 
     /// Number of CREATE/CREATE2 operations
     pub create_count: u64,
+
+    /// Number of EXTCODESIZE/EXTCODECOPY/EXTCODEHASH operations
+    #[serde(default)]
+    pub extcode_count: u64,
+
+    /// Number of TLOAD operations
+    #[serde(default)]
+    pub tload_count: u64,
+
+    /// Number of TSTORE operations
+    #[serde(default)]
+    pub tstore_count: u64,
+
+    /// Number of MCOPY operations
+    #[serde(default)]
+    pub mcopy_count: u64,
+}
+
+impl OperationCounts {
+    /// Each count with the name of the operations it counts, in field order.
+    fn named_counts(&self) -> [(&'static str, u64); 11] {
+        [
+            ("SLOAD", self.sload_count),
+            ("SSTORE", self.sstore_count),
+            ("CALL", self.call_count),
+            ("LOG", self.log_count),
+            ("Total opcodes", self.total_ops),
+            ("Memory words", self.memory_words_allocated),
+            ("CREATE", self.create_count),
+            ("EXTCODE*", self.extcode_count),
+            ("TLOAD", self.tload_count),
+            ("TSTORE", self.tstore_count),
+            ("MCOPY", self.mcopy_count),
+        ]
+    }
 }
 
 /// Location where divergence first occurred.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DivergenceLocation {
-    /// Contract address where divergence occurred
+    /// Contract address where divergence occurred, i.e. whose state the diverging frame acts on
     pub contract: Address,
 
+    /// Address the code executing at the divergence was loaded from, which `pc` and `opcode`
+    /// refer to; differs from `contract` in DELEGATECALL and CALLCODE frames
+    #[serde(default)]
+    pub code_address: Address,
+
     /// Function selector call stack (first 4 bytes of calldata for each call in the stack)
     /// Ordered from root (index 0) to deepest call (last index)
     /// None entries represent calls without selectors (e.g., value transfers, creates)
@@ -158,10 +754,15 @@ pub struct DivergenceLocation {
 
     /// Opcode name (for readability)
     pub opcode_name: String,
+
+    /// Hash of the bytecode executing at the divergence, shared by every contract (or proxy
+    /// implementation) running the same code
+    #[serde(default)]
+    pub code_hash: B256,
 }
 
 /// Information about out-of-gas occurrence in experimental execution.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct OutOfGasInfo {
     /// Opcode that caused OOG
     pub opcode: u8,
@@ -172,9 +773,14 @@ pub struct OutOfGasInfo {
     /// Program counter where OOG occurred
     pub pc: usize,
 
-    /// Contract address
+    /// Contract address, i.e. whose state the frame that ran out of gas acts on
     pub contract: Address,
 
+    /// Address the code that ran out of gas was loaded from, which `pc` and `opcode` refer to;
+    /// differs from `contract` in DELEGATECALL and CALLCODE frames
+    #[serde(default)]
+    pub code_address: Address,
+
     /// Call depth
     pub call_depth: usize,
 
@@ -183,6 +789,39 @@ pub struct OutOfGasInfo {
 
     /// Detected pattern (heuristic)
     pub pattern: OogPattern,
+
+    /// Most recent `(pc, gas_remaining)` samples leading up to the OOG, oldest first
+    /// A steep final drop points to a single expensive operation, a steady decline to a loop
+    #[serde(default)]
+    pub gas_trajectory: Vec<GasSample>,
+
+    /// Precompile whose call ran out of gas, for [`OogPattern::Precompile`]
+    #[serde(default)]
+    pub precompile: Option<Address>,
+}
+
+/// Gas remaining before an opcode executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GasSample {
+    /// Program counter of the opcode
+    pub pc: usize,
+
+    /// Gas remaining before the opcode executed
+    pub gas_remaining: u64,
+}
+
+/// Values returned by the GAS opcode at one location, in execution order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GasObservation {
+    /// Address of the code executing the opcode, the library rather than its caller in a
+    /// DELEGATECALL frame
+    pub contract: Address,
+
+    /// Program counter of the opcode
+    pub pc: usize,
+
+    /// Gas remaining after the opcode's own cost, i.e. the value `gasleft()` returned
+    pub gas_remaining: Vec<u64>,
 }
 
 /// Pattern that caused out-of-gas.
@@ -195,28 +834,178 @@ pub enum OogPattern {
     /// Memory expansion
     MemoryExpansion,
 
+    /// Memory expansion dominated by the quadratic term of the memory cost
+    Quadratic,
+
     /// Chain of external calls
     CallChain,
 
     /// Heavy storage operations
     StorageHeavy,
 
+    /// Child call that consumed at least 63/64 of the gas provided to it and then reverted,
+    /// griefing its parent (only recorded in [`Divergence::griefing`], it is not an OOG)
+    Griefing,
+
+    /// Call to a precompile that ran out of gas. Precompile costs are not multiplied, so this is
+    /// always the repriced caller forwarding less gas than the precompile needs
+    Precompile,
+
     /// Unknown/other pattern
     Unknown,
 }
 
+impl std::str::FromStr for OogPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "loop" => Ok(Self::Loop),
+            "memory_expansion" => Ok(Self::MemoryExpansion),
+            "quadratic" => Ok(Self::Quadratic),
+            "call_chain" => Ok(Self::CallChain),
+            "storage_heavy" => Ok(Self::StorageHeavy),
+            "griefing" => Ok(Self::Griefing),
+            "precompile" => Ok(Self::Precompile),
+            "unknown" => Ok(Self::Unknown),
+            _ => Err(format!("Invalid OOG pattern: {}", s)),
+        }
+    }
+}
+
 impl std::fmt::Display for OogPattern {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Loop => write!(f, "loop"),
             Self::MemoryExpansion => write!(f, "memory_expansion"),
+            Self::Quadratic => write!(f, "quadratic"),
             Self::CallChain => write!(f, "call_chain"),
             Self::StorageHeavy => write!(f, "storage_heavy"),
+            Self::Griefing => write!(f, "griefing"),
+            Self::Precompile => write!(f, "precompile"),
             Self::Unknown => write!(f, "unknown"),
         }
     }
 }
 
+/// What drove a divergence, found by replaying the transaction at normal gas costs on the state
+/// the experimental execution started from.
+///
+/// Only attributed with [`ResearchConfig::sequential_state`], the one mode in which that state
+/// can differ from the state normal execution started from.
+///
+/// [`ResearchConfig::sequential_state`]: crate::config::ResearchConfig::sequential_state
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergenceCause {
+    /// The replay matches normal execution, so the gas costs changed the outcome directly
+    GasDirect,
+
+    /// The replay differs from normal execution, so the experimental state changed the outcome
+    StateCascade,
+
+    /// The replay fails just like experimental execution did, so the failure is not down to the
+    /// gas costs
+    NonGas,
+
+    /// The replay could not be run
+    Unknown,
+}
+
+impl std::str::FromStr for DivergenceCause {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gas_direct" => Ok(Self::GasDirect),
+            "state_cascade" => Ok(Self::StateCascade),
+            "non_gas" => Ok(Self::NonGas),
+            "unknown" => Ok(Self::Unknown),
+            _ => Err(format!("Invalid divergence cause: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for DivergenceCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GasDirect => write!(f, "gas_direct"),
+            Self::StateCascade => write!(f, "state_cascade"),
+            Self::NonGas => write!(f, "non_gas"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Pair of executions a divergence was found between.
+///
+/// Only runs with a second gas schedule, see
+/// [`ResearchConfig::schedule_b`](crate::config::ResearchConfig::schedule_b), compare anything
+/// but normal execution against schedule A.
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    /// Normal execution against the primary experimental schedule (A)
+    #[default]
+    NormalA,
+
+    /// Normal execution against the second experimental schedule (B)
+    NormalB,
+
+    /// The primary experimental schedule (A) against the second one (B)
+    AB,
+}
+
+impl std::str::FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal_a" => Ok(Self::NormalA),
+            "normal_b" => Ok(Self::NormalB),
+            "a_b" => Ok(Self::AB),
+            _ => Err(format!("Invalid comparison: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NormalA => write!(f, "normal_a"),
+            Self::NormalB => write!(f, "normal_b"),
+            Self::AB => write!(f, "a_b"),
+        }
+    }
+}
+
+/// Accounts an execution accessed, each with the storage slots accessed in it: the content of
+/// an EIP-2930 access list generated from the execution.
+pub type AccessSet = BTreeMap<Address, BTreeSet<B256>>;
+
+/// Accesses made by only one of two executions, see
+/// [`compare::access_list_divergence`](crate::compare::access_list_divergence).
+///
+/// An account only one execution accessed is listed with all the slots accessed in it, an
+/// account both accessed with the slots only one of them accessed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessListDiff {
+    /// Accessed by normal execution only
+    pub normal_only: AccessSet,
+
+    /// Accessed by experimental execution only
+    pub experimental_only: AccessSet,
+}
+
+impl AccessListDiff {
+    /// Whether both executions accessed the same accounts and slots.
+    pub fn is_empty(&self) -> bool {
+        self.normal_only.is_empty() && self.experimental_only.is_empty()
+    }
+}
+
 /// Call trees from both executions.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CallTrees {
@@ -228,7 +1017,7 @@ pub struct CallTrees {
 }
 
 /// A single call frame in the call tree.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CallFrame {
     /// Index in the call tree
     pub call_index: usize,
@@ -254,13 +1043,50 @@ pub struct CallFrame {
     /// Whether the call succeeded
     pub success: bool,
 
-    /// Input data
+    /// Whether the call ran out of gas
+    #[serde(default)]
+    pub out_of_gas: bool,
+
+    /// Length of the input data, recorded even when the data itself is not
+    #[serde(default)]
+    pub input_len: usize,
+
+    /// Length of the output/return data, recorded even when the data itself is not
+    #[serde(default)]
+    pub output_len: usize,
+
+    /// Input data (only captured for detailed traces)
     pub input: Option<Bytes>,
 
-    /// Output/return data
+    /// Output/return data (only captured for detailed traces)
     pub output: Option<Bytes>,
 }
 
+/// Write `frames` as an indented tree, one line per call with its gas used out of the gas
+/// provided and whether it failed.
+fn write_call_tree(md: &mut String, frames: &[CallFrame]) -> std::fmt::Result {
+    let mut frames = frames.iter().collect::<Vec<_>>();
+    frames.sort_by_key(|frame| frame.call_index);
+    let root_depth = frames.iter().map(|frame| frame.depth).min().unwrap_or_default();
+    for frame in frames {
+        let indent = "  ".repeat(frame.depth - root_depth);
+        let target = frame.to.map_or_else(|| "(new contract)".to_string(), |to| to.to_string());
+        let status = if frame.out_of_gas {
+            " out of gas"
+        } else if frame.success {
+            ""
+        } else {
+            " reverted"
+        };
+        writeln!(
+            md,
+            "{indent}{} {target} {}/{} gas{status}",
+            frame.call_type, frame.gas_used, frame.gas_provided
+        )?;
+    }
+    Ok(())
+}
+
 /// Type of call.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -292,6 +1118,88 @@ impl std::fmt::Display for CallType {
     }
 }
 
+impl std::str::FromStr for CallType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "CALL" => Ok(Self::Call),
+            "DELEGATECALL" => Ok(Self::DelegateCall),
+            "STATICCALL" => Ok(Self::StaticCall),
+            "CALLCODE" => Ok(Self::CallCode),
+            "CREATE" => Ok(Self::Create),
+            "CREATE2" => Ok(Self::Create2),
+            _ => Err(format!("Invalid call type: {}", s)),
+        }
+    }
+}
+
+/// CREATE2 deployments from both executions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Create2Deployments {
+    /// Deployments from normal execution
+    pub normal: Vec<Create2Deployment>,
+
+    /// Deployments from experimental execution
+    pub experimental: Vec<Create2Deployment>,
+}
+
+/// A single CREATE2 deployment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Create2Deployment {
+    /// Deployer address
+    pub caller: Address,
+
+    /// Salt provided to CREATE2
+    pub salt: B256,
+
+    /// Keccak256 hash of the init code
+    pub init_code_hash: B256,
+
+    /// Address predicted from caller, salt and init code hash
+    pub predicted_address: Address,
+
+    /// Address returned by the deployment (None if it failed)
+    pub actual_address: Option<Address>,
+}
+
+impl Create2Deployment {
+    /// Create a pending deployment, predicting its address from the inputs.
+    pub fn new(caller: Address, salt: B256, init_code: &[u8]) -> Self {
+        let init_code_hash = keccak256(init_code);
+        Self {
+            caller,
+            salt,
+            init_code_hash,
+            predicted_address: caller.create2(salt, init_code_hash),
+            actual_address: None,
+        }
+    }
+
+    /// Whether the deployment succeeded.
+    pub const fn succeeded(&self) -> bool {
+        self.actual_address.is_some()
+    }
+
+    /// Whether the deployment succeeded at an address other than the predicted one.
+    pub fn address_mismatch(&self) -> bool {
+        self.actual_address.is_some_and(|actual| actual != self.predicted_address)
+    }
+}
+
+/// Runtime code deployed by a contract-creation transaction in both executions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreatedCode {
+    /// Address of the created contract
+    pub address: Address,
+
+    /// Keccak256 hash of the runtime code deployed in normal execution
+    pub normal_code_hash: B256,
+
+    /// Keccak256 hash of the runtime code deployed in experimental execution
+    pub experimental_code_hash: B256,
+}
+
 /// Event logs from both executions.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventLogs {
@@ -321,6 +1229,7 @@ pub struct EventLog {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::DivergenceBuilder;
 
     #[test]
     fn test_gas_efficiency_ratio() {
@@ -331,6 +1240,8 @@ mod tests {
             normal_gas_used: 1000,
             experimental_gas_used: 128_000,
             gas_efficiency_ratio: ratio,
+            normal_gas_charged: 1000,
+            experimental_gas_charged: 128_000,
         }
         .is_structural_divergence());
 
@@ -341,6 +1252,8 @@ mod tests {
             normal_gas_used: 1000,
             experimental_gas_used: 100_000,
             gas_efficiency_ratio: ratio,
+            normal_gas_charged: 1000,
+            experimental_gas_charged: 100_000,
         }
         .is_structural_divergence());
 
@@ -351,6 +1264,8 @@ mod tests {
             normal_gas_used: 1000,
             experimental_gas_used: 150_000,
             gas_efficiency_ratio: ratio,
+            normal_gas_charged: 1000,
+            experimental_gas_charged: 150_000,
         }
         .is_structural_divergence());
     }
@@ -361,6 +1276,443 @@ mod tests {
         assert_eq!(DivergenceType::CallTree.to_string(), "call_tree");
     }
 
+    #[test]
+    fn test_divergence_type_parsing_round_trips() {
+        for dtype in [
+            DivergenceType::StateRoot,
+            DivergenceType::ExecutionTrace,
+            DivergenceType::Status,
+            DivergenceType::StatusImproved,
+            DivergenceType::EventLogs,
+            DivergenceType::CallTree,
+            DivergenceType::GasPattern,
+            DivergenceType::Create2Address,
+            DivergenceType::BaseFee,
+            DivergenceType::Memory,
+            DivergenceType::Created,
+            DivergenceType::AccessList,
+        ] {
+            assert_eq!(dtype.to_string().parse::<DivergenceType>().unwrap(), dtype);
+        }
+        assert!("invalid".parse::<DivergenceType>().is_err());
+        assert!(Severity::High > Severity::Medium && Severity::Medium > Severity::Low);
+    }
+
+    #[test]
+    fn test_create2_prediction() {
+        let caller = Address::repeat_byte(0x11);
+        let mut deployment = Create2Deployment::new(caller, B256::ZERO, &[0x60, 0x00]);
+        assert!(!deployment.succeeded());
+        assert!(!deployment.address_mismatch());
+
+        deployment.actual_address = Some(deployment.predicted_address);
+        assert!(deployment.succeeded());
+        assert!(!deployment.address_mismatch());
+
+        deployment.actual_address = Some(Address::repeat_byte(0x22));
+        assert!(deployment.address_mismatch());
+    }
+
+    fn test_divergence(divergence_types: Vec<DivergenceType>) -> Divergence {
+        DivergenceBuilder::new(100)
+            .with_tx_index(5)
+            .with_tx_hash(B256::repeat_byte(0xab))
+            .with_types(divergence_types)
+            .build()
+    }
+
+    #[test]
+    fn test_summary_line() {
+        let mut divergence =
+            test_divergence(vec![DivergenceType::StateRoot, DivergenceType::Status]);
+        divergence.block_number = 18_000_000;
+        divergence.gas_analysis.gas_efficiency_ratio = 0.72;
+        assert_eq!(
+            divergence.summary_line(),
+            "block 18000000 tx 5 [state_root,status] ratio=0.720"
+        );
+
+        divergence.oog_info = Some(OutOfGasInfo {
+            opcode: 0x54,
+            opcode_name: "SLOAD".to_string(),
+            pc: 42,
+            contract: Address::repeat_byte(0xcc),
+            code_address: Address::repeat_byte(0xcc),
+            call_depth: 1,
+            gas_remaining: 100,
+            pattern: OogPattern::StorageHeavy,
+            gas_trajectory: vec![],
+            precompile: None,
+        });
+        assert_eq!(
+            divergence.summary_line(),
+            "block 18000000 tx 5 [state_root,status] ratio=0.720 oog=storage_heavy@SLOAD"
+        );
+
+        let mut near_miss = test_divergence(vec![]);
+        near_miss.near_miss = true;
+        assert_eq!(near_miss.summary_line(), "block 100 tx 5 [] ratio=1.000 near_miss");
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let mut divergence =
+            test_divergence(vec![DivergenceType::Status, DivergenceType::StateRoot]);
+        divergence.gas_analysis.gas_efficiency_ratio = 0.72;
+        divergence.normal_ops.sload_count = 3;
+        divergence.experimental_ops.sload_count = 1;
+        divergence.divergence_location = Some(DivergenceLocation {
+            contract: Address::repeat_byte(0xcc),
+            code_address: Address::repeat_byte(0xcc),
+            function_selectors: vec![Some([0xa9, 0x05, 0x9c, 0xbb])],
+            pc: 42,
+            call_depth: 1,
+            opcode: 0x54,
+            opcode_name: "SLOAD".to_string(),
+            code_hash: B256::ZERO,
+        });
+        divergence.function_name = Some("transfer(address,uint256)".to_string());
+        divergence.oog_info = Some(OutOfGasInfo {
+            opcode: 0x54,
+            opcode_name: "SLOAD".to_string(),
+            pc: 42,
+            contract: Address::repeat_byte(0xcc),
+            code_address: Address::repeat_byte(0xcc),
+            call_depth: 1,
+            gas_remaining: 100,
+            pattern: OogPattern::StorageHeavy,
+            gas_trajectory: vec![],
+            precompile: None,
+        });
+        let frame = |call_index, depth, success, out_of_gas| CallFrame {
+            call_index,
+            depth,
+            from: Address::repeat_byte(0xaa),
+            to: Some(Address::repeat_byte(0xcc)),
+            call_type: CallType::Call,
+            gas_provided: 5000,
+            gas_used: 1000,
+            success,
+            out_of_gas,
+            input_len: 0,
+            output_len: 0,
+            input: None,
+            output: None,
+        };
+        divergence.call_trees = Some(CallTrees {
+            normal: vec![frame(0, 0, true, false), frame(1, 1, true, false)],
+            experimental: vec![frame(0, 0, false, false), frame(1, 1, false, true)],
+        });
+
+        let md = divergence.to_markdown();
+        let contract = Address::repeat_byte(0xcc);
+        let tx_line = format!("| Transaction | `{}` |", B256::repeat_byte(0xab));
+        let location_line = format!("| Location | `{contract}` pc 42 `SLOAD` (depth 1) |");
+        let frame_line = format!("  CALL {contract} 1000/5000 gas out of gas");
+        for expected in [
+            "## Divergence in block 100 tx 5",
+            tx_line.as_str(),
+            "| Types | state_root, status |",
+            "| Severity | High |",
+            location_line.as_str(),
+            "| Selector | `0xa9059cbb` |",
+            "| Function | `transfer(address,uint256)` |",
+            "| Gas used | 21000 | 2688000 |",
+            "Gas efficiency ratio: **0.720**",
+            "| SLOAD | 3 | 1 | -2 |",
+            "- Pattern: storage_heavy",
+            "- Gas remaining: 100",
+            frame_line.as_str(),
+        ] {
+            assert!(md.contains(expected), "missing {expected:?} in:\n{md}");
+        }
+
+        // Counts that are zero in both executions are left out
+        assert!(!md.contains("| SSTORE |"));
+    }
+
+    #[test]
+    fn test_gas_charged() {
+        // 50,000 gross gas with a 4,800 refund, capped at 10,000 since London
+        assert_eq!(GasAnalysis::gas_charged(45_200, 4_800, 1.0, 5), 45_200);
+        assert_eq!(GasAnalysis::gas_charged(45_200, 4_800, 0.5, 5), 47_600);
+        assert_eq!(GasAnalysis::gas_charged(45_200, 4_800, 3.0, 5), 40_000);
+        assert_eq!(GasAnalysis::gas_charged(45_200, 4_800, 3.0, 2), 45_200 + 4_800 - 14_400);
+        assert_eq!(GasAnalysis::gas_charged(21_000, 0, 2.0, 5), 21_000);
+
+        let gas = GasAnalysis {
+            normal_gas_used: 40_000,
+            experimental_gas_used: 80_000,
+            gas_efficiency_ratio: 1.0,
+            normal_gas_charged: 40_000,
+            experimental_gas_charged: 60_000,
+        };
+        assert_eq!(gas.gas_charged_ratio(), 1.5);
+    }
+
+    #[test]
+    fn test_json_round_trip_with_all_fields() {
+        let frame = |call_type| CallFrame {
+            call_index: 1,
+            depth: 1,
+            from: Address::repeat_byte(0x11),
+            to: Some(Address::repeat_byte(0x22)),
+            call_type,
+            gas_provided: 100_000,
+            gas_used: 21_000,
+            success: false,
+            out_of_gas: true,
+            input_len: 4,
+            output_len: 0,
+            input: Some(Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef])),
+            output: None,
+        };
+        let log = EventLog {
+            log_index: 0,
+            address: Address::repeat_byte(0x33),
+            topics: vec![B256::repeat_byte(0x44)],
+            data: Bytes::from_static(&[0x01]),
+        };
+        let mut deployment = Create2Deployment::new(Address::repeat_byte(0x55), B256::ZERO, &[0]);
+        deployment.actual_address = Some(deployment.predicted_address);
+
+        let mut divergence =
+            test_divergence(vec![DivergenceType::Status, DivergenceType::Create2Address]);
+        divergence.gas_analysis.gas_efficiency_ratio = 0.75;
+        divergence.experimental_ops.sload_count = 3;
+        divergence.divergence_location = Some(DivergenceLocation {
+            contract: Address::repeat_byte(0x66),
+            code_address: Address::repeat_byte(0x66),
+            function_selectors: vec![Some([0xa9, 0x05, 0x9c, 0xbb]), None],
+            pc: 42,
+            call_depth: 2,
+            opcode: 0x54,
+            opcode_name: "SLOAD".to_string(),
+            code_hash: B256::repeat_byte(0x88),
+        });
+        divergence.oog_info = Some(OutOfGasInfo {
+            opcode: 0x55,
+            opcode_name: "SSTORE".to_string(),
+            pc: 7,
+            contract: Address::repeat_byte(0x77),
+            code_address: Address::repeat_byte(0x77),
+            call_depth: 1,
+            gas_remaining: 100,
+            pattern: OogPattern::StorageHeavy,
+            gas_trajectory: vec![GasSample { pc: 5, gas_remaining: 30_000 }],
+            precompile: None,
+        });
+        divergence.call_trees = Some(CallTrees {
+            normal: vec![frame(CallType::DelegateCall)],
+            experimental: vec![frame(CallType::Create2)],
+        });
+        divergence.event_logs = Some(EventLogs { normal: vec![log], experimental: vec![] });
+        divergence.create2_deployments =
+            Some(Create2Deployments { normal: vec![deployment.clone()], experimental: vec![] });
+
+        let json = divergence.to_json().unwrap();
+        let decoded = Divergence::from_json(&json).unwrap();
+        assert_eq!(decoded.to_json().unwrap(), json);
+        assert_eq!(decoded.identity(), divergence.identity());
+        assert_eq!(decoded.gas_analysis.gas_efficiency_ratio, 0.75);
+        assert_eq!(decoded.experimental_ops, divergence.experimental_ops);
+        let oog_info = decoded.oog_info.unwrap();
+        assert_eq!(oog_info.pattern, OogPattern::StorageHeavy);
+        assert_eq!(oog_info.gas_trajectory, vec![GasSample { pc: 5, gas_remaining: 30_000 }]);
+        assert_eq!(
+            decoded.divergence_location.unwrap().function_selectors,
+            vec![Some([0xa9, 0x05, 0x9c, 0xbb]), None]
+        );
+        assert_eq!(decoded.call_trees.unwrap().experimental[0].call_type, CallType::Create2);
+        assert_eq!(decoded.event_logs.unwrap().normal[0].topics, vec![B256::repeat_byte(0x44)]);
+        assert_eq!(decoded.create2_deployments.unwrap().normal, vec![deployment]);
+
+        assert!(Divergence::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn test_json_schema_version() {
+        let divergence = test_divergence(vec![DivergenceType::Status]);
+        let json = divergence.to_json().unwrap();
+
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["schema_version"], DIVERGENCE_SCHEMA_VERSION);
+        assert_eq!(value["divergence"]["block_number"], divergence.block_number);
+
+        // Bare records without an envelope are rejected
+        let bare = value["divergence"].to_string();
+        assert!(Divergence::from_json(&bare).is_err());
+
+        value["schema_version"] = (DIVERGENCE_SCHEMA_VERSION + 1).into();
+        let err = Divergence::from_json(&value.to_string()).unwrap_err();
+        assert!(err.to_string().contains("unsupported divergence schema version"));
+    }
+
+    #[test]
+    fn test_json_schema_fields() {
+        // Changing the exported fields changes the schema: bump DIVERGENCE_SCHEMA_VERSION along
+        // with these lists
+        let mut divergence = test_divergence(vec![DivergenceType::Status]);
+        divergence.divergence_location = Some(DivergenceLocation {
+            contract: Address::repeat_byte(0x66),
+            code_address: Address::repeat_byte(0x67),
+            function_selectors: vec![None],
+            pc: 42,
+            call_depth: 2,
+            opcode: 0x54,
+            opcode_name: "SLOAD".to_string(),
+            code_hash: B256::repeat_byte(0x88),
+        });
+        divergence.oog_info = Some(OutOfGasInfo {
+            opcode: 0x55,
+            opcode_name: "SSTORE".to_string(),
+            pc: 7,
+            contract: Address::repeat_byte(0x77),
+            code_address: Address::repeat_byte(0x78),
+            call_depth: 1,
+            gas_remaining: 100,
+            pattern: OogPattern::StorageHeavy,
+            gas_trajectory: vec![],
+            precompile: None,
+        });
+        let json = divergence.to_json().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        fn keys(value: &serde_json::Value) -> BTreeSet<&str> {
+            value.as_object().unwrap().keys().map(String::as_str).collect()
+        }
+        assert_eq!(DIVERGENCE_SCHEMA_VERSION, 2);
+        assert_eq!(
+            keys(&value["divergence"]),
+            BTreeSet::from([
+                "access_list_diff",
+                "block_number",
+                "call_trees",
+                "calldata",
+                "cause",
+                "comparison",
+                "create2_deployments",
+                "created_code",
+                "divergence_location",
+                "divergence_types",
+                "event_logs",
+                "experimental_ops",
+                "function_name",
+                "gas_analysis",
+                "gas_observations",
+                "griefing",
+                "near_miss",
+                "normal_ops",
+                "oog_info",
+                "reverting_selector",
+                "threshold_map",
+                "timestamp",
+                "truncated",
+                "tx_hash",
+                "tx_index",
+            ])
+        );
+
+        assert_eq!(
+            keys(&value["divergence"]["divergence_location"]),
+            BTreeSet::from([
+                "call_depth",
+                "code_address",
+                "code_hash",
+                "contract",
+                "function_selectors",
+                "opcode",
+                "opcode_name",
+                "pc",
+            ])
+        );
+        assert_eq!(
+            keys(&value["divergence"]["oog_info"]),
+            BTreeSet::from([
+                "call_depth",
+                "code_address",
+                "contract",
+                "gas_remaining",
+                "gas_trajectory",
+                "opcode",
+                "opcode_name",
+                "pattern",
+                "pc",
+                "precompile",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_enum_wire_format() {
+        fn json<T: Serialize>(value: &T) -> String {
+            serde_json::to_string(value).unwrap()
+        }
+
+        assert_eq!(json(&DivergenceType::StateRoot), r#""state_root""#);
+        assert_eq!(json(&DivergenceType::ExecutionTrace), r#""execution_trace""#);
+        assert_eq!(json(&DivergenceType::Status), r#""status""#);
+        assert_eq!(json(&DivergenceType::StatusImproved), r#""status_improved""#);
+        assert_eq!(json(&DivergenceType::EventLogs), r#""event_logs""#);
+        assert_eq!(json(&DivergenceType::CallTree), r#""call_tree""#);
+        assert_eq!(json(&DivergenceType::GasPattern), r#""gas_pattern""#);
+        assert_eq!(json(&DivergenceType::Create2Address), r#""create2_address""#);
+        assert_eq!(json(&DivergenceType::BaseFee), r#""base_fee""#);
+        assert_eq!(json(&DivergenceType::Memory), r#""memory""#);
+        assert_eq!(json(&DivergenceType::Created), r#""created""#);
+        assert_eq!(json(&DivergenceType::AccessList), r#""access_list""#);
+
+        assert_eq!(json(&Severity::Low), r#""low""#);
+        assert_eq!(json(&Severity::Medium), r#""medium""#);
+        assert_eq!(json(&Severity::High), r#""high""#);
+
+        assert_eq!(json(&OogPattern::Loop), r#""loop""#);
+        assert_eq!(json(&OogPattern::MemoryExpansion), r#""memory_expansion""#);
+        assert_eq!(json(&OogPattern::Quadratic), r#""quadratic""#);
+        assert_eq!(json(&OogPattern::CallChain), r#""call_chain""#);
+        assert_eq!(json(&OogPattern::StorageHeavy), r#""storage_heavy""#);
+        assert_eq!(json(&OogPattern::Unknown), r#""unknown""#);
+
+        assert_eq!(json(&CallType::Call), r#""CALL""#);
+        assert_eq!(json(&CallType::DelegateCall), r#""DELEGATE_CALL""#);
+        assert_eq!(json(&CallType::StaticCall), r#""STATIC_CALL""#);
+        assert_eq!(json(&CallType::CallCode), r#""CALL_CODE""#);
+        assert_eq!(json(&CallType::Create), r#""CREATE""#);
+        assert_eq!(json(&CallType::Create2), r#""CREATE2""#);
+    }
+
+    #[test]
+    fn test_identity_ignores_call_trees() {
+        let a = test_divergence(vec![DivergenceType::Status, DivergenceType::GasPattern]);
+        let mut b = test_divergence(vec![DivergenceType::GasPattern, DivergenceType::Status]);
+        b.call_trees = Some(CallTrees {
+            normal: vec![CallFrame {
+                call_index: 0,
+                depth: 0,
+                from: Address::repeat_byte(0x11),
+                to: Some(Address::repeat_byte(0x22)),
+                call_type: CallType::Call,
+                gas_provided: 100_000,
+                gas_used: 21_000,
+                success: true,
+                out_of_gas: false,
+                input_len: 0,
+                output_len: 0,
+                input: None,
+                output: None,
+            }],
+            experimental: vec![],
+        });
+
+        assert_eq!(a.identity(), b.identity());
+        assert_eq!(a.uid(), b.uid());
+        assert_eq!(a, b);
+        assert_eq!(std::collections::HashSet::from([a.clone(), b]).len(), 1);
+
+        let c = test_divergence(vec![DivergenceType::Status]);
+        assert_ne!(a.identity(), c.identity());
+        assert_ne!(a.uid(), c.uid());
+    }
+
     #[test]
     fn test_operation_counts_default() {
         let ops = OperationCounts::default();
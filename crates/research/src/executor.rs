@@ -14,6 +14,7 @@ use reth_execution_types::BlockExecutionResult;
 use reth_primitives_traits::{
     AlloyBlockHeader, BlockBody, NodePrimitives, RecoveredBlock, SignedTransaction,
 };
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use thiserror::Error;
 use tracing::{debug, info, warn};
 
@@ -54,8 +55,7 @@ pub struct ResearchExecutor<E> {
     divergence_db: Option<DivergenceDatabase>,
 
     /// Statistics
-    blocks_processed: u64,
-    divergences_found: u64,
+    stats: ResearchCounters,
 }
 
 impl<E> ResearchExecutor<E> {
@@ -78,15 +78,12 @@ impl<E> ResearchExecutor<E> {
         // Register metrics
         metrics::register_metrics();
 
-        Ok(Self { inner, config, divergence_db, blocks_processed: 0, divergences_found: 0 })
+        Ok(Self { inner, config, divergence_db, stats: ResearchCounters::default() })
     }
 
     /// Get statistics.
     pub fn stats(&self) -> ResearchStats {
-        ResearchStats {
-            blocks_processed: self.blocks_processed,
-            divergences_found: self.divergences_found,
-        }
+        self.stats.snapshot()
     }
 
     /// Check if research mode is enabled for the given block.
@@ -115,29 +112,8 @@ impl<E> ResearchExecutor<E> {
         let tx_count = block.body().transactions().len();
 
         for tx_idx in 0..tx_count {
-            let mut divergence_types = Vec::new();
-
-            // Check if OOG occurred in simulation
-            if inspector.oog_occurred() {
-                divergence_types.push(DivergenceType::Status);
-
-                // If experimental ran out of gas but normal didn't, that's a status divergence
-                if let Some(receipt) = result.receipts.get(tx_idx) {
-                    if receipt.status() {
-                        // Normal succeeded but experimental would have failed
-                        divergence_types.push(DivergenceType::ExecutionTrace);
-                    }
-                }
-            }
-
-            // Check if we have gas loop patterns (potential divergence source)
-            if inspector.has_gas_loop_pattern() {
-                divergence_types.push(DivergenceType::GasPattern);
-            }
-
             // Get gas metrics
-            let normal_gas =
-                result.receipts.get(tx_idx).map(|r| r.cumulative_gas_used()).unwrap_or(0);
+            let normal_gas = tx_gas_used(&result.receipts, tx_idx);
 
             let simulated_gas = inspector.simulated_gas_used();
 
@@ -148,26 +124,32 @@ impl<E> ResearchExecutor<E> {
                 normal_gas_used: normal_gas,
                 experimental_gas_used: simulated_gas,
                 gas_efficiency_ratio: gas_ratio,
+                // Refunds are not simulated here
+                normal_gas_charged: normal_gas,
+                experimental_gas_charged: simulated_gas,
             };
 
-            // If gas pattern is structurally different, that indicates divergence
-            if gas_analysis.is_structural_divergence() {
-                divergence_types.push(DivergenceType::GasPattern);
-            }
+            let normal_success =
+                result.receipts.get(tx_idx).is_some_and(|receipt| receipt.status());
+            let divergence_types =
+                simulated_divergence_types(inspector.oog_occurred(), normal_success, &gas_analysis);
+            // Detection is done, the ratio is only reported from here on
+            let gas_ratio = self.config.quantize_ratio(gas_ratio);
+            let gas_analysis = GasAnalysis { gas_efficiency_ratio: gas_ratio, ..gas_analysis };
 
             // Get operation counts from inspector
             let ops = inspector.operation_counts().clone();
 
             // If any divergences detected, record it
-            if !divergence_types.is_empty() || inspector.oog_occurred() {
+            if (!divergence_types.is_empty() || inspector.oog_occurred()) &&
+                self.config.records_types(&divergence_types)
+            {
                 // For a RecoveredBlock, transactions should already be recovered
                 // We'll just compute the hash from the transaction itself
-                let tx_hash = block
-                    .body()
-                    .transactions()
-                    .get(tx_idx)
-                    .map(|tx| *tx.tx_hash())
-                    .unwrap_or(B256::ZERO);
+                let tx = block.body().transactions().get(tx_idx);
+                let tx_hash = tx.map(|tx| *tx.tx_hash()).unwrap_or(B256::ZERO);
+                let trace_detail =
+                    self.config.trace_detail_for(tx.and_then(crate::analysis::tx_selector));
 
                 // Note: In simulation mode, we track operations from normal execution
                 // and simulate gas costs. The experimental_ops are the same unless
@@ -186,7 +168,7 @@ impl<E> ResearchExecutor<E> {
                 };
 
                 // Extract call trees if detailed tracing is enabled
-                let call_trees = if self.config.trace_detail.include_call_trees() {
+                let call_trees = if trace_detail.include_call_trees() {
                     let frames = inspector.call_frames().to_vec();
                     Some(crate::divergence::CallTrees {
                         normal: frames.clone(),
@@ -197,7 +179,7 @@ impl<E> ResearchExecutor<E> {
                 };
 
                 // Extract event logs if detailed tracing is enabled
-                let event_logs = if self.config.trace_detail.include_event_logs() {
+                let event_logs = if trace_detail.include_event_logs() {
                     if let Some(receipt) = result.receipts.get(tx_idx) {
                         let logs: Vec<crate::divergence::EventLog> = receipt
                             .logs()
@@ -221,7 +203,7 @@ impl<E> ResearchExecutor<E> {
                     None
                 };
 
-                let divergence = Divergence {
+                let mut divergence = Divergence {
                     block_number: block.number(),
                     tx_index: tx_idx as u64,
                     tx_hash,
@@ -232,41 +214,50 @@ impl<E> ResearchExecutor<E> {
                     experimental_ops,
                     divergence_location: inspector.divergence_location().cloned(),
                     oog_info: inspector.oog_info().cloned(),
+                    griefing: inspector.griefing_info().cloned(),
+                    reverting_selector: inspector.reverting_selector(),
+                    cause: None,
                     call_trees,
                     event_logs,
+                    create2_deployments: None,
+                    created_code: None,
+                    gas_observations: None,
+                    threshold_map: None,
+                    truncated: false,
+                    near_miss: false,
+                    comparison: Default::default(),
+                    function_name: None,
+                    calldata: None,
+                    access_list_diff: None,
                 };
-
-                // Record to database if available
-                if let Some(ref db) = self.divergence_db {
-                    match db.record_divergence(&divergence) {
-                        Ok(id) => {
-                            debug!(
-                                target: "reth::research",
-                                divergence_id = id,
-                                tx_hash = ?divergence.tx_hash,
-                                types = ?divergence.divergence_types,
-                                "Recorded divergence"
-                            );
-                        }
-                        Err(e) => {
-                            warn!(
-                                target: "reth::research",
-                                error = %e,
-                                "Failed to record divergence"
-                            );
-                        }
-                    }
+                if let Some(max_bytes) = self.config.max_bytes_per_divergence {
+                    divergence.limit_stored_bytes(max_bytes);
                 }
 
-                // Record metrics
-                metrics::record_divergence(&divergence_types, gas_ratio);
-                if inspector.oog_occurred() {
-                    if let Some(ref oog) = inspector.oog_info() {
-                        metrics::record_oog(oog.pattern);
+                // Store the divergence unless the total limit was reached
+                if self.stats.record_found(&divergence, self.config.max_divergences_total) {
+                    if let Some(ref db) = self.divergence_db {
+                        match db.record_divergence(&divergence) {
+                            Ok(id) => {
+                                debug!(
+                                    target: "reth::research",
+                                    divergence_id = id,
+                                    tx_hash = ?divergence.tx_hash,
+                                    types = ?divergence.divergence_types,
+                                    "Recorded divergence"
+                                );
+                            }
+                            Err(e) => {
+                                warn!(
+                                    target: "reth::research",
+                                    error = %e,
+                                    "Failed to record divergence"
+                                );
+                            }
+                        }
                     }
                 }
 
-                self.divergences_found += 1;
                 divergences.push(divergence);
             }
 
@@ -291,6 +282,41 @@ impl<E> ResearchExecutor<E> {
     }
 }
 
+/// Divergence types of a transaction simulated by the inspector, each at most once.
+///
+/// Running out of gas is a status divergence, and also a trace divergence if the transaction
+/// succeeded normally. [`DivergenceType::GasPattern`] is strictly the ratio-based signal of a
+/// structurally different path: gas loops only explain an out of gas, which is reported through
+/// the status and its [`OogPattern`](crate::divergence::OogPattern).
+fn simulated_divergence_types(
+    oog_occurred: bool,
+    normal_success: bool,
+    gas_analysis: &GasAnalysis,
+) -> Vec<DivergenceType> {
+    let mut divergence_types = Vec::new();
+    if oog_occurred {
+        divergence_types.push(DivergenceType::Status);
+        // Normal succeeded but experimental would have failed
+        if normal_success {
+            divergence_types.push(DivergenceType::ExecutionTrace);
+        }
+    }
+    if gas_analysis.is_structural_divergence() {
+        divergence_types.push(DivergenceType::GasPattern);
+    }
+    divergence_types
+}
+
+/// Gas used by the transaction at `tx_idx`, derived from the difference between its receipt's
+/// cumulative gas and that of the preceding receipt.
+fn tx_gas_used<R: TxReceipt>(receipts: &[R], tx_idx: usize) -> u64 {
+    let Some(receipt) = receipts.get(tx_idx) else {
+        return 0;
+    };
+    let previous = tx_idx.checked_sub(1).map_or(0, |idx| receipts[idx].cumulative_gas_used());
+    receipt.cumulative_gas_used().saturating_sub(previous)
+}
+
 impl<E, DB> Executor<DB> for ResearchExecutor<E>
 where
     E: Executor<DB>,
@@ -341,10 +367,15 @@ where
             "Block executed in research mode (analysis not yet implemented)"
         );
 
-        self.blocks_processed += 1;
+        self.stats.record_block();
 
         let duration = start.elapsed().as_secs_f64();
-        metrics::record_block_processed(block_number, block.body().transactions().len(), duration);
+        metrics::record_block_processed(
+            block_number,
+            block.body().transactions().len(),
+            0,
+            duration,
+        );
 
         Ok(result)
     }
@@ -378,20 +409,237 @@ pub struct ResearchStats {
     pub blocks_processed: u64,
     /// Number of divergences found
     pub divergences_found: u64,
+    /// Number of divergences recorded, at most the configured total limit
+    pub divergences_recorded: u64,
+}
+
+/// Statistics counters that can be updated from multiple threads.
+#[derive(Debug, Default)]
+pub struct ResearchCounters {
+    /// Number of blocks processed
+    blocks_processed: AtomicU64,
+    /// Number of divergences found
+    divergences_found: AtomicU64,
+    /// Number of divergences recorded
+    divergences_recorded: AtomicU64,
+    /// Whether recording stopped after reaching the total limit
+    recording_stopped: AtomicBool,
+}
+
+impl ResearchCounters {
+    /// Count a processed block.
+    pub fn record_block(&self) {
+        self.blocks_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a divergence, returning the total number of divergences found including it.
+    pub fn record_divergence(&self) -> u64 {
+        self.divergences_found.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Count a found divergence and, unless `limit` divergences were already recorded, record its
+    /// metrics.
+    ///
+    /// Returns whether the divergence should be stored.
+    pub fn record_found(&self, divergence: &Divergence, limit: Option<u64>) -> bool {
+        self.record_divergence();
+        if !self.try_record(limit) {
+            return false
+        }
+
+        metrics::record_divergence(
+            &divergence.divergence_types,
+            divergence.gas_analysis.gas_efficiency_ratio,
+        );
+        if let Some(ref oog) = divergence.oog_info {
+            metrics::record_oog(oog.pattern);
+        }
+        true
+    }
+
+    /// Claim one of `limit` slots for recording a divergence.
+    ///
+    /// Returns `false` once `limit` divergences have been recorded. The first refusal logs a
+    /// message and sets the `reth_research_recording_stopped` gauge.
+    pub fn try_record(&self, limit: Option<u64>) -> bool {
+        let Some(limit) = limit else {
+            self.divergences_recorded.fetch_add(1, Ordering::Relaxed);
+            return true
+        };
+
+        let claimed = self
+            .divergences_recorded
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| (n < limit).then_some(n + 1))
+            .is_ok();
+        if !claimed && !self.recording_stopped.swap(true, Ordering::Relaxed) {
+            warn!(
+                target: "reth::research",
+                limit,
+                "Reached max total divergences, no further divergences will be recorded"
+            );
+            metrics::record_recording_stopped();
+        }
+        claimed
+    }
+
+    /// Whether recording stopped after reaching the total limit.
+    pub fn recording_stopped(&self) -> bool {
+        self.recording_stopped.load(Ordering::Relaxed)
+    }
+
+    /// Take a snapshot of the current statistics.
+    pub fn snapshot(&self) -> ResearchStats {
+        ResearchStats {
+            blocks_processed: self.blocks_processed.load(Ordering::Relaxed),
+            divergences_found: self.divergences_found.load(Ordering::Relaxed),
+            divergences_recorded: self.divergences_recorded.load(Ordering::Relaxed),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::TraceDetail;
+    use crate::{
+        config::TraceDetail,
+        database::DivergenceWriter,
+        test_utils::{counter_value, DivergenceBuilder},
+    };
+    use metrics_util::debugging::DebuggingRecorder;
+    use std::sync::Arc;
 
     #[test]
     fn test_research_stats_default() {
-        let stats = ResearchStats { blocks_processed: 0, divergences_found: 0 };
+        let stats =
+            ResearchStats { blocks_processed: 0, divergences_found: 0, divergences_recorded: 0 };
         assert_eq!(stats.blocks_processed, 0);
         assert_eq!(stats.divergences_found, 0);
     }
 
+    #[test]
+    fn test_research_counters_concurrent_updates() {
+        let counters = ResearchCounters::default();
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..1000 {
+                        counters.record_block();
+                        counters.record_divergence();
+                        counters.record_divergence();
+                    }
+                });
+            }
+        });
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.blocks_processed, 8_000);
+        assert_eq!(stats.divergences_found, 16_000);
+        assert_eq!(counters.record_divergence(), 16_001);
+    }
+
+    #[test]
+    fn test_max_divergences_total() {
+        let db = DivergenceDatabase::in_memory().unwrap();
+        let writer = DivergenceWriter::spawn(Arc::new(db.clone())).unwrap();
+        let counters = ResearchCounters::default();
+
+        // Workers racing for the last slots never record more than the limit
+        std::thread::scope(|scope| {
+            for block_number in 0..4 {
+                let (writer, counters) = (&writer, &counters);
+                scope.spawn(move || {
+                    for tx_index in 0..10 {
+                        let divergence =
+                            DivergenceBuilder::new(block_number).with_tx_index(tx_index).build();
+                        if counters.record_found(&divergence, Some(5)) {
+                            writer.send(divergence).unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        assert!(counters.recording_stopped());
+        assert!(!counters.try_record(Some(5)));
+        drop(writer);
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.divergences_found, 40);
+        assert_eq!(stats.divergences_recorded, 5);
+        assert_eq!(db.count_divergences(0, 1000).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_no_metrics_past_total_limit() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let counters = ResearchCounters::default();
+
+        let recorded = ::metrics::with_local_recorder(&recorder, || {
+            (0..3)
+                .filter(|&block_number| {
+                    counters.record_found(&DivergenceBuilder::new(block_number).build(), Some(2))
+                })
+                .count()
+        });
+        assert_eq!(recorded, 2);
+        assert_eq!(counters.snapshot().divergences_found, 3);
+
+        assert_eq!(counter_value(&snapshotter, "reth_research_divergences_total", None), 2);
+    }
+
+    #[test]
+    fn test_unlimited_recording() {
+        let counters = ResearchCounters::default();
+        assert!((0..100).all(|_| counters.try_record(None)));
+        assert!(!counters.recording_stopped());
+        assert_eq!(counters.snapshot().divergences_recorded, 100);
+    }
+
+    #[test]
+    fn test_tx_gas_used_from_cumulative() {
+        let receipt = |cumulative_gas_used| reth_ethereum_primitives::Receipt {
+            success: true,
+            cumulative_gas_used,
+            ..Default::default()
+        };
+        let receipts: Vec<reth_ethereum_primitives::Receipt> =
+            vec![receipt(21_000), receipt(71_000), receipt(92_000)];
+
+        assert_eq!(tx_gas_used(&receipts, 0), 21_000);
+        assert_eq!(tx_gas_used(&receipts, 1), 50_000);
+        assert_eq!(tx_gas_used(&receipts, 2), 21_000);
+        assert_eq!(tx_gas_used(&receipts, 3), 0);
+    }
+
+    #[test]
+    fn test_simulated_divergence_types_unique() {
+        let gas_analysis = |experimental_gas_used| GasAnalysis {
+            normal_gas_used: 100_000,
+            experimental_gas_used,
+            gas_efficiency_ratio: GasAnalysis::calculate_ratio(100_000, experimental_gas_used, 2),
+            normal_gas_charged: 100_000,
+            experimental_gas_charged: experimental_gas_used,
+        };
+
+        // Out of gas after a structurally different path: every signal, once each
+        let types = simulated_divergence_types(true, true, &gas_analysis(500_000));
+        assert_eq!(
+            types,
+            vec![DivergenceType::Status, DivergenceType::ExecutionTrace, DivergenceType::GasPattern]
+        );
+        for (index, divergence_type) in types.iter().enumerate() {
+            assert!(!types[index + 1..].contains(divergence_type));
+        }
+
+        // Out of gas with the expected gas ratio is not a gas pattern divergence
+        assert_eq!(
+            simulated_divergence_types(true, false, &gas_analysis(200_000)),
+            vec![DivergenceType::Status]
+        );
+        assert!(simulated_divergence_types(false, true, &gas_analysis(200_000)).is_empty());
+    }
+
     #[test]
     fn test_research_error_display() {
         let err: ResearchError<String> = ResearchError::NotEnabled(100);
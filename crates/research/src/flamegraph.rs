@@ -0,0 +1,112 @@
+//! Gas profiles of call trees in the folded-stack format read by flamegraph tools.
+//!
+//! Each line of the output is a call stack of `;`-separated callee addresses followed by the gas
+//! spent in the innermost frame itself, excluding the gas its subcalls used, e.g.
+//!
+//! ```text
+//! 0xC0c0…;0xBbBb… 4200
+//! ```
+//!
+//! Pipe it into `inferno-flamegraph` or `flamegraph.pl` to render where the gas of an execution
+//! went. Frames are stored flat, so the nesting is rebuilt from their call order and depth.
+
+use crate::divergence::CallFrame;
+use std::collections::BTreeMap;
+
+/// Fold `frames` into stacks of callee addresses with the gas spent in each stack's innermost
+/// frame, sorted by stack.
+///
+/// Contract creations are labelled by their call type. Calls reaching the same stack are merged,
+/// and stacks whose innermost frame spent no gas itself are left out.
+pub fn folded_stacks(frames: &[CallFrame]) -> Vec<String> {
+    let mut frames: Vec<_> = frames.iter().collect();
+    frames.sort_by_key(|frame| frame.call_index);
+
+    // Ancestors of the current frame as (depth, stack, index into `self_gas`)
+    let mut ancestors: Vec<(usize, String, usize)> = Vec::new();
+    let mut self_gas: Vec<(String, u64)> = Vec::with_capacity(frames.len());
+
+    for frame in frames {
+        while ancestors.last().is_some_and(|(depth, ..)| *depth >= frame.depth) {
+            ancestors.pop();
+        }
+
+        let label = frame.to.map_or_else(|| frame.call_type.to_string(), |to| to.to_string());
+        let stack = match ancestors.last() {
+            Some((_, parent_stack, parent)) => {
+                let parent_gas = &mut self_gas[*parent].1;
+                *parent_gas = parent_gas.saturating_sub(frame.gas_used);
+                format!("{parent_stack};{label}")
+            }
+            None => label,
+        };
+
+        ancestors.push((frame.depth, stack.clone(), self_gas.len()));
+        self_gas.push((stack, frame.gas_used));
+    }
+
+    let mut folded = BTreeMap::<String, u64>::new();
+    for (stack, gas) in self_gas {
+        *folded.entry(stack).or_default() += gas;
+    }
+    folded
+        .into_iter()
+        .filter(|(_, gas)| *gas > 0)
+        .map(|(stack, gas)| format!("{stack} {gas}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::divergence::CallType;
+    use alloy_primitives::Address;
+
+    fn frame(call_index: usize, depth: usize, to: Option<Address>, gas_used: u64) -> CallFrame {
+        CallFrame {
+            call_index,
+            depth,
+            from: Address::ZERO,
+            to,
+            call_type: if to.is_some() { CallType::Call } else { CallType::Create },
+            gas_provided: 1_000_000,
+            gas_used,
+            success: true,
+            out_of_gas: false,
+            input_len: 0,
+            output_len: 0,
+            input: None,
+            output: None,
+        }
+    }
+
+    #[test]
+    fn test_folded_stacks() {
+        let (a, b, c) =
+            (Address::repeat_byte(0x11), Address::repeat_byte(0x22), Address::repeat_byte(0x33));
+
+        // A calls B (which calls C), B again and creates a contract. Frames are recorded as
+        // they finish, so children come before their parents.
+        let frames = [
+            frame(2, 2, Some(c), 100),
+            frame(1, 1, Some(b), 600),
+            frame(3, 1, Some(b), 200),
+            frame(4, 1, None, 50),
+            frame(0, 0, Some(a), 1_000),
+            // A second top-level tree with no gas of its own below its child
+            frame(5, 0, Some(c), 30),
+            frame(6, 1, Some(a), 30),
+        ];
+
+        assert_eq!(
+            folded_stacks(&frames),
+            vec![
+                format!("{a} 150"),
+                format!("{a};{b} 700"),
+                format!("{a};{b};{c} 100"),
+                format!("{a};CREATE 50"),
+                format!("{c};{a} 30"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Bounded-memory tracking of the most frequent items in a stream.
+//!
+//! [`FrequentItems`] implements the Misra–Gries summary: with `capacity` counters it finds every
+//! item occurring more than `total / (capacity + 1)` times, and undercounts each reported item
+//! by at most that amount. The ExEx uses it to keep a live ranking of the contracts causing the
+//! most divergences without querying the database.
+
+use std::{collections::HashMap, hash::Hash};
+
+/// Misra–Gries frequent-items summary holding at most `capacity` counters.
+#[derive(Debug, Clone)]
+pub struct FrequentItems<T> {
+    /// Maximum number of counters kept
+    capacity: usize,
+
+    /// Estimated count per tracked item
+    counters: HashMap<T, u64>,
+
+    /// Number of items recorded since the last reset
+    total: u64,
+}
+
+impl<T: Eq + Hash + Clone> FrequentItems<T> {
+    /// Create an empty summary with `capacity` counters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "frequent items capacity must be non-zero");
+        Self { capacity, counters: HashMap::with_capacity(capacity), total: 0 }
+    }
+
+    /// Record one occurrence of `item`.
+    pub fn record(&mut self, item: T) {
+        self.total += 1;
+
+        if let Some(count) = self.counters.get_mut(&item) {
+            *count += 1;
+        } else if self.counters.len() < self.capacity {
+            self.counters.insert(item, 1);
+        } else {
+            // No free counter: the new occurrence and one occurrence of every tracked item
+            // cancel out
+            self.counters.retain(|_, count| {
+                *count -= 1;
+                *count > 0
+            });
+        }
+    }
+
+    /// The `n` items with the highest estimated counts, most frequent first.
+    ///
+    /// Each estimate is at most the true count and at least the true count minus
+    /// [`FrequentItems::max_error`].
+    pub fn top(&self, n: usize) -> Vec<(T, u64)> {
+        let mut items: Vec<_> =
+            self.counters.iter().map(|(item, count)| (item.clone(), *count)).collect();
+        items.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+        items.truncate(n);
+        items
+    }
+
+    /// Upper bound on how much any estimated count undercounts the true count.
+    pub const fn max_error(&self) -> u64 {
+        self.total / (self.capacity as u64 + 1)
+    }
+
+    /// Number of items recorded since the last reset.
+    pub const fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Clear all counters, e.g. at the start of a new run.
+    pub fn reset(&mut self) {
+        self.counters.clear();
+        self.total = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_heavy_hitters_within_error_bound() {
+        let mut summary = FrequentItems::new(9);
+
+        // Three heavy hitters interleaved with a long tail of distinct items, each above the
+        // `total / (capacity + 1)` threshold so all of them are guaranteed to be reported
+        let true_counts = [(1u32, 300u64), (2, 200), (3, 100)];
+        let mut tail = 1_000u32;
+        for round in 0..300u64 {
+            for &(item, count) in &true_counts {
+                if round < count {
+                    summary.record(item);
+                }
+            }
+            summary.record(tail);
+            tail += 1;
+        }
+
+        assert_eq!(summary.total(), 900);
+        let max_error = summary.max_error();
+        assert_eq!(max_error, 90);
+
+        let top = summary.top(3);
+        assert_eq!(top.iter().map(|(item, _)| *item).collect::<Vec<_>>(), vec![1, 2, 3]);
+        for ((_, estimate), (_, true_count)) in top.iter().zip(true_counts) {
+            assert!(*estimate <= true_count);
+            assert!(*estimate + max_error >= true_count);
+        }
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut summary = FrequentItems::new(2);
+        summary.record("a");
+        summary.record("b");
+        summary.reset();
+
+        assert_eq!(summary.total(), 0);
+        assert!(summary.top(2).is_empty());
+
+        summary.record("c");
+        assert_eq!(summary.top(2), vec![("c", 1)]);
+    }
+}
@@ -3,18 +3,43 @@
 use crate::{
     config::ResearchConfig,
     divergence::{
-        CallFrame, CallType, DivergenceLocation, OogPattern, OperationCounts, OutOfGasInfo,
+        CallFrame, CallType, Create2Deployment, DivergenceLocation, GasObservation, GasSample,
+        OogPattern, OperationCounts, OutOfGasInfo,
     },
+    oog::OogContext,
 };
-use alloy_primitives::Address;
+use alloy_primitives::{Address, Bytes, B256};
 use revm::{
-    context_interface::ContextTr,
-    interpreter::{CallInputs, CallOutcome, CreateInputs, CreateOutcome, Interpreter},
+    bytecode::opcode::OpCode,
+    context_interface::{Cfg, ContextTr},
+    interpreter::{
+        CallInputs, CallOutcome, CreateInputs, CreateOutcome, Gas, InstructionResult, Interpreter,
+        InterpreterResult,
+    },
+    primitives::hardfork::SpecId,
     Inspector,
 };
-use revm_interpreter::interpreter_types::Jumps;
+use revm_interpreter::interpreter_types::{Jumps, LoopControl};
 use std::collections::VecDeque;
 
+/// Number of recent gas samples kept for the OOG gas trajectory.
+const GAS_TRAJECTORY_LEN: usize = 16;
+
+/// Gas charged by the GAS opcode before it pushes the gas remaining.
+const GAS_OPCODE_COST: u64 = 2;
+
+/// Gas charged per 32-byte word of init code by CREATE and CREATE2 (EIP-3860).
+pub const INITCODE_WORD_COST: u64 = 2;
+
+/// Maximum init code size in bytes (EIP-3860).
+pub const MAX_INITCODE_SIZE: usize = 2 * 24_576;
+
+/// Divisor of the gross gas that caps the refund since London (EIP-3529).
+const MAX_REFUND_QUOTIENT_LONDON: u64 = 5;
+
+/// Divisor of the gross gas that caps the refund before London.
+const MAX_REFUND_QUOTIENT_FRONTIER: u64 = 2;
+
 /// Inspector that multiplies gas costs and tracks execution details.
 ///
 /// This inspector ACTUALLY modifies gas costs during execution by intercepting
@@ -27,9 +52,15 @@ pub struct GasResearchInspector {
     /// Gas remaining before the current step (for calculating cost)
     gas_before_step: Option<u64>,
 
+    /// Opcode executed by the current step
+    current_opcode: u8,
+
     /// Total gas used (with multiplier applied)
     simulated_gas_used: u64,
 
+    /// Gas left in the top-level frame when it returned
+    gas_remaining: Option<u64>,
+
     /// Simulated gas limit (inflated)
     #[allow(dead_code)]
     simulated_gas_limit: u64,
@@ -46,62 +77,231 @@ pub struct GasResearchInspector {
     /// Recorded call frames
     call_frames: Vec<CallFrame>,
 
+    /// Completed CREATE2 deployments
+    create2_deployments: Vec<Create2Deployment>,
+
+    /// Call indices of frames that ran out of gas while their parent completed successfully
+    child_frame_oogs: Vec<usize>,
+
+    /// Index assigned to the next call frame (in call order)
+    next_call_index: usize,
+
     /// Location of first divergence (if any)
     first_divergence_location: Option<DivergenceLocation>,
 
     /// Out-of-gas information
     oog_info: Option<OutOfGasInfo>,
 
+    /// First child frame that reverted after consuming at least 63/64 of its gas
+    griefing_info: Option<OutOfGasInfo>,
+
+    /// Selector of the deepest frame that started the top-level revert
+    reverting_selector: Option<[u8; 4]>,
+
     /// Gas opcode usage tracking (for loop detection)
     gas_opcode_usage: VecDeque<GasOpcodeEvent>,
 
     /// Maximum entries to track for gas loop detection
     max_gas_events: usize,
+
+    /// Most recent gas samples, captured at every step
+    gas_trajectory: VecDeque<GasSample>,
+
+    /// Accounts loaded before the top-level frame runs so they start warm
+    warm_addresses: Vec<Address>,
+
+    /// Whether call frames keep copies of their input and output data, not just the lengths
+    capture_call_data: bool,
+
+    /// Whether GAS opcodes are tracked for [`Self::gas_observations`] even without gas loop
+    /// detection
+    capture_gas_observations: bool,
+
+    /// Divisor of the gross gas that caps the refund under the active spec
+    max_refund_quotient: u64,
+
+    /// Whether the interpreter charges the modified gas costs itself, see [`Self::with_real_gas`]
+    real_gas: bool,
 }
 
 /// Entry in the call stack.
 #[derive(Debug, Clone)]
 struct CallStackEntry {
+    call_index: usize,
     depth: usize,
-    contract: Address,
+    /// Address the frame's code was loaded from, which PCs and opcodes refer to
+    code_address: Address,
+    /// Address whose storage and balance the frame acts on; differs from the code address in
+    /// DELEGATECALL and CALLCODE frames, e.g. a proxy running a library's code
+    storage_address: Address,
     call_type: CallType,
     gas_at_start: u64,
     function_selector: Option<[u8; 4]>,
+    create2: Option<Create2Deployment>,
+    /// Whether this frame ran out of gas under the simulated costs
+    oog: bool,
+    /// Call indices of direct children that ran out of gas
+    oog_children: Vec<usize>,
+    /// Selector of the frame that started the revert of the most recent direct child, if that
+    /// child reverted (the inner `None` is a frame without a selector)
+    child_revert_origin: Option<Option<[u8; 4]>>,
+}
+
+/// Coarse kind of a gas-dependent loop, see [`GasResearchInspector::gas_loop_kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GasLoopKind {
+    /// GAS read at the same PC with strictly decreasing values, like a loop running until the
+    /// gas left drops below a threshold
+    Monotonic,
+
+    /// GAS read at the same PC without strictly decreasing values, like the same code entered
+    /// repeatedly in frames of their own
+    Repeated,
+}
+
+impl std::fmt::Display for GasLoopKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Monotonic => write!(f, "monotonic"),
+            Self::Repeated => write!(f, "repeated"),
+        }
+    }
+}
+
+/// Results of an experimental execution, cloned out of a [`GasResearchInspector`] by
+/// [`GasResearchInspector::snapshot`].
+///
+/// The snapshot owns its data and is `Send + Sync`, so the inspector can be dropped as soon as
+/// the transaction ran and the results handed to another thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InspectorSnapshot {
+    /// Operation counts
+    pub operation_counts: OperationCounts,
+
+    /// Recorded call frames
+    pub call_frames: Vec<CallFrame>,
+
+    /// Completed CREATE2 deployments
+    pub create2_deployments: Vec<Create2Deployment>,
+
+    /// Call indices of frames that ran out of gas while their parent completed successfully
+    pub child_frame_oogs: Vec<usize>,
+
+    /// Location of the first divergence, if any
+    pub divergence_location: Option<DivergenceLocation>,
+
+    /// Out-of-gas information, if the execution ran out of gas
+    pub oog_info: Option<OutOfGasInfo>,
+
+    /// First child frame that reverted after consuming at least 63/64 of its gas, if any
+    pub griefing_info: Option<OutOfGasInfo>,
+
+    /// Selector of the deepest frame that started the top-level revert
+    pub reverting_selector: Option<[u8; 4]>,
+
+    /// Whether out-of-gas occurred
+    pub oog_occurred: bool,
+
+    /// Simulated gas used
+    pub simulated_gas_used: u64,
+
+    /// Gas left in the top-level frame when it returned
+    pub gas_remaining: Option<u64>,
+
+    /// Values returned by the tracked GAS opcodes
+    pub gas_observations: Vec<GasObservation>,
+
+    /// Kind of the gas-dependent loop detected, if any
+    pub gas_loop_kind: Option<GasLoopKind>,
+
+    /// Divisor of the gross gas that caps the refund under the active spec
+    pub max_refund_quotient: u64,
 }
 
 /// Gas opcode usage event for loop detection.
 #[derive(Debug, Clone)]
 struct GasOpcodeEvent {
     pc: usize,
-    #[allow(dead_code)]
     gas_remaining: u64,
-    #[allow(dead_code)]
     contract: Address,
 }
 
 impl GasResearchInspector {
     /// Create a new inspector.
     pub fn new(config: ResearchConfig, gas_limit: u64) -> Self {
-        let simulated_gas_limit = gas_limit
-            .saturating_mul(config.effective_gas_limit_multiplier())
-            .saturating_sub(21000 * (config.gas_multiplier - 1)); // Adjust for intrinsic gas
+        let simulated_gas_limit = config.simulated_gas_limit(gas_limit);
 
         Self {
             config,
             gas_before_step: None,
+            current_opcode: 0,
             simulated_gas_used: 0,
+            gas_remaining: None,
             simulated_gas_limit,
             oog_occurred: false,
             op_counts: OperationCounts::default(),
             call_stack: Vec::new(),
             call_frames: Vec::new(),
+            create2_deployments: Vec::new(),
+            child_frame_oogs: Vec::new(),
+            next_call_index: 0,
             first_divergence_location: None,
             oog_info: None,
+            griefing_info: None,
+            reverting_selector: None,
             gas_opcode_usage: VecDeque::new(),
             max_gas_events: 1000,
+            gas_trajectory: VecDeque::with_capacity(GAS_TRAJECTORY_LEN),
+            warm_addresses: Vec::new(),
+            capture_call_data: false,
+            capture_gas_observations: false,
+            max_refund_quotient: MAX_REFUND_QUOTIENT_LONDON,
+            real_gas: false,
         }
     }
 
+    /// Warm `addresses` before the top-level frame runs.
+    pub fn with_warm_addresses(mut self, addresses: Vec<Address>) -> Self {
+        self.warm_addresses = addresses;
+        self
+    }
+
+    /// Keep copies of each call frame's input and output data, which is only needed for
+    /// detailed traces. Lengths are always recorded.
+    pub const fn with_call_data(mut self, capture: bool) -> Self {
+        self.capture_call_data = capture;
+        self
+    }
+
+    /// Track the values GAS opcodes return, which is only needed for detailed traces.
+    pub const fn with_gas_observations(mut self, capture: bool) -> Self {
+        self.capture_gas_observations = capture;
+        self
+    }
+
+    /// Observe an execution whose interpreter charges the modified gas costs itself, like the
+    /// [`real_gas`](crate::config::ResearchConfig::real_gas) execution: no additional gas is
+    /// charged, and running out of gas is recorded where the interpreter halts.
+    pub const fn with_real_gas(mut self, real_gas: bool) -> Self {
+        self.real_gas = real_gas;
+        self
+    }
+
+    /// Record that the current opcode ran out of gas in the current frame.
+    fn record_out_of_gas(&mut self, interp: &Interpreter) {
+        self.oog_occurred = true;
+        if let Some(entry) = self.call_stack.last_mut() {
+            entry.oog = true;
+        }
+
+        // The interpreter has already advanced past the opcode that ran out of gas
+        let opcode_byte = self.current_opcode;
+        let opcode_name = opcode_mnemonic(opcode_byte);
+
+        self.record_oog(interp, opcode_byte, opcode_name.to_string());
+        self.record_divergence_location(interp, opcode_byte, opcode_name.to_string());
+    }
+
     /// Extract function selector (first 4 bytes) from call input
     fn extract_function_selector(input: &revm::interpreter::CallInput) -> Option<[u8; 4]> {
         match input {
@@ -112,6 +312,11 @@ impl GasResearchInspector {
         }
     }
 
+    /// Get the gas limit simulated costs are measured against.
+    pub const fn simulated_gas_limit(&self) -> u64 {
+        self.simulated_gas_limit
+    }
+
     /// Get the operation counts.
     pub fn operation_counts(&self) -> &OperationCounts {
         &self.op_counts
@@ -122,6 +327,20 @@ impl GasResearchInspector {
         &self.call_frames
     }
 
+    /// Get the completed CREATE2 deployments.
+    pub fn create2_deployments(&self) -> &[Create2Deployment] {
+        &self.create2_deployments
+    }
+
+    /// Get the call indices of frames that would run out of gas under the simulated costs while
+    /// their parent frame goes on to complete successfully.
+    ///
+    /// These are outcome changes that do not surface as a top-level status change, e.g. a
+    /// try/catch swallowing a child revert.
+    pub fn child_frame_oogs(&self) -> &[usize] {
+        &self.child_frame_oogs
+    }
+
     /// Get the divergence location.
     pub fn divergence_location(&self) -> Option<&DivergenceLocation> {
         self.first_divergence_location.as_ref()
@@ -132,6 +351,18 @@ impl GasResearchInspector {
         self.oog_info.as_ref()
     }
 
+    /// Get the first griefing revert: a child frame that reverted after consuming at least 63/64
+    /// of its gas. Not an out-of-gas, so it is never reported by [`Self::oog_info`].
+    pub fn griefing_info(&self) -> Option<&OutOfGasInfo> {
+        self.griefing_info.as_ref()
+    }
+
+    /// Selector of the deepest frame that reverted before the revert bubbled up to the top-level
+    /// frame, if the transaction reverted.
+    pub fn reverting_selector(&self) -> Option<[u8; 4]> {
+        self.reverting_selector
+    }
+
     /// Check if OOG occurred.
     pub fn oog_occurred(&self) -> bool {
         self.oog_occurred
@@ -142,26 +373,103 @@ impl GasResearchInspector {
         self.simulated_gas_used
     }
 
+    /// Gas left in the top-level frame when it returned, after the simulated charges and before
+    /// refunds. `None` until the transaction finished.
+    pub fn gas_remaining(&self) -> Option<u64> {
+        self.gas_remaining
+    }
+
+    /// Get the values the tracked GAS opcodes returned, grouped by contract and PC in the order
+    /// each location first ran. Only the most recent GAS opcodes are tracked.
+    pub fn gas_observations(&self) -> Vec<GasObservation> {
+        let mut observations: Vec<GasObservation> = Vec::new();
+        for event in &self.gas_opcode_usage {
+            let gas = event.gas_remaining.saturating_sub(GAS_OPCODE_COST);
+            let location = observations
+                .iter_mut()
+                .find(|seen| seen.contract == event.contract && seen.pc == event.pc);
+            match location {
+                Some(observation) => observation.gas_remaining.push(gas),
+                None => observations.push(GasObservation {
+                    contract: event.contract,
+                    pc: event.pc,
+                    gas_remaining: vec![gas],
+                }),
+            }
+        }
+        observations
+    }
+
+    /// Divisor of the gross gas that caps the refund under the spec the transaction ran with.
+    pub fn max_refund_quotient(&self) -> u64 {
+        self.max_refund_quotient
+    }
+
+    /// Clone the execution results out of the inspector, leaving its internal tracking state
+    /// behind.
+    pub fn snapshot(&self) -> InspectorSnapshot {
+        InspectorSnapshot {
+            operation_counts: self.op_counts.clone(),
+            call_frames: self.call_frames.clone(),
+            create2_deployments: self.create2_deployments.clone(),
+            child_frame_oogs: self.child_frame_oogs.clone(),
+            divergence_location: self.first_divergence_location.clone(),
+            oog_info: self.oog_info.clone(),
+            griefing_info: self.griefing_info.clone(),
+            reverting_selector: self.reverting_selector,
+            oog_occurred: self.oog_occurred,
+            simulated_gas_used: self.simulated_gas_used,
+            gas_remaining: self.gas_remaining,
+            gas_observations: self.gas_observations(),
+            gas_loop_kind: self.gas_loop_kind(),
+            max_refund_quotient: self.max_refund_quotient,
+        }
+    }
+
+    /// Prepare for a transaction as its top-level frame starts: warm the configured accounts and
+    /// record the refund cap of the active spec.
+    fn start_transaction<CTX: ContextTr>(&mut self, context: &mut CTX) {
+        warm_accounts(context, &self.warm_addresses);
+
+        let spec: SpecId = context.cfg().spec().into();
+        self.max_refund_quotient = if spec.is_enabled_in(SpecId::LONDON) {
+            MAX_REFUND_QUOTIENT_LONDON
+        } else {
+            MAX_REFUND_QUOTIENT_FRONTIER
+        };
+    }
+
     /// Check if a potential gas-dependent loop is detected.
     pub fn has_gas_loop_pattern(&self) -> bool {
+        self.gas_loop_kind().is_some()
+    }
+
+    /// Kind of the gas-dependent loop detected, if any. A monotonic loop at any PC takes
+    /// precedence over repeated reads at another.
+    pub fn gas_loop_kind(&self) -> Option<GasLoopKind> {
         // Look for repeated GAS opcode usage at same PC with decreasing gas
-        if self.gas_opcode_usage.len() < 3 {
-            return false;
+        if !self.config.detect_gas_loops || self.gas_opcode_usage.len() < 3 {
+            return None;
         }
 
-        // Simple heuristic: same PC accessed multiple times with decreasing gas
-        let mut pc_counts = std::collections::HashMap::new();
+        // Simple heuristic: same PC accessed multiple times, with decreasing gas for a loop
+        let mut gas_by_pc: std::collections::HashMap<_, Vec<_>> = Default::default();
         for event in &self.gas_opcode_usage {
-            *pc_counts.entry(event.pc).or_insert(0) += 1;
+            gas_by_pc.entry(event.pc).or_default().push(event.gas_remaining);
         }
 
-        pc_counts.values().any(|&count| count >= 3)
+        let loops: Vec<_> = gas_by_pc.into_values().filter(|gas| gas.len() >= 3).collect();
+        if loops.is_empty() {
+            return None;
+        }
+        let monotonic = loops.iter().any(|gas| gas.windows(2).all(|pair| pair[1] < pair[0]));
+        Some(if monotonic { GasLoopKind::Monotonic } else { GasLoopKind::Repeated })
     }
 
     /// Calculate the gas cost for an operation with the multiplier applied.
-    #[allow(dead_code)]
-    fn calculate_gas_cost(&self, base_cost: u64) -> u64 {
-        base_cost.saturating_mul(self.config.gas_multiplier)
+    /// Opcodes in `excluded_opcodes` are returned at their base cost.
+    fn calculate_gas_cost(&self, opcode: u8, base_cost: u64) -> u64 {
+        base_cost.saturating_mul(self.config.opcode_multiplier(opcode))
     }
 
     /// Record a divergence location if not already recorded.
@@ -172,23 +480,19 @@ impl GasResearchInspector {
         opcode_name: String,
     ) {
         if self.first_divergence_location.is_none() {
-            let contract = if let Some(entry) = self.call_stack.last() {
-                entry.contract
-            } else {
-                Address::ZERO
-            };
-
             // Collect function selectors from the entire call stack
             let function_selectors: Vec<Option<[u8; 4]>> =
                 self.call_stack.iter().map(|entry| entry.function_selector).collect();
 
             self.first_divergence_location = Some(DivergenceLocation {
-                contract,
+                contract: self.storage_address(),
+                code_address: self.code_address(),
                 function_selectors,
                 pc: interp.bytecode.pc(),
                 call_depth: self.call_stack.len(),
                 opcode,
                 opcode_name,
+                code_hash: interp.bytecode.hash_slow(),
             });
         }
     }
@@ -196,56 +500,123 @@ impl GasResearchInspector {
     /// Record OOG information.
     fn record_oog(&mut self, interp: &Interpreter, opcode: u8, opcode_name: String) {
         if self.oog_info.is_none() {
-            let contract = if let Some(entry) = self.call_stack.last() {
-                entry.contract
-            } else {
-                Address::ZERO
-            };
-
             // Determine pattern heuristically
-            let pattern = self.infer_oog_pattern(opcode);
+            let gas_trajectory: Vec<_> = self.gas_trajectory.iter().copied().collect();
+            let pattern = self.config.oog_classifier.classify(&OogContext {
+                opcode,
+                gas_trajectory: &gas_trajectory,
+                gas_loop_detected: self.has_gas_loop_pattern(),
+                memory_words: interp.memory.len().div_ceil(32),
+            });
 
             self.oog_info = Some(OutOfGasInfo {
                 opcode,
                 opcode_name,
                 pc: interp.bytecode.pc(),
-                contract,
+                contract: self.storage_address(),
+                code_address: self.code_address(),
                 call_depth: self.call_stack.len(),
                 gas_remaining: interp.gas.remaining(),
                 pattern,
+                gas_trajectory,
+                precompile: None,
             });
         }
     }
 
-    /// Infer the OOG pattern based on context.
-    fn infer_oog_pattern(&self, opcode: u8) -> OogPattern {
-        match opcode {
-            // Storage operations
-            0x54 | 0x55 => OogPattern::StorageHeavy, // SLOAD, SSTORE
+    /// Record a REVERT in a child frame that consumed at least 63/64 of the gas provided to it,
+    /// unless one was already recorded.
+    fn record_griefing(&mut self, interp: &Interpreter) {
+        if self.griefing_info.is_some() {
+            return;
+        }
 
-            // Call operations
-            0xF1 | 0xF2 | 0xF4 | 0xFA => OogPattern::CallChain, /* CALL, CALLCODE, DELEGATECALL,
-                                                                  * STATICCALL */
+        self.griefing_info = Some(OutOfGasInfo {
+            opcode: 0xFD,
+            opcode_name: opcode_mnemonic(0xFD).to_string(),
+            pc: interp.bytecode.pc(),
+            contract: self.storage_address(),
+            code_address: self.code_address(),
+            call_depth: self.call_stack.len(),
+            gas_remaining: interp.gas.remaining(),
+            pattern: OogPattern::Griefing,
+            gas_trajectory: self.gas_trajectory.iter().copied().collect(),
+            precompile: None,
+        });
+    }
 
-            // Memory operations
-            0x51 | 0x52 | 0x53 => OogPattern::MemoryExpansion, // MLOAD, MSTORE, MSTORE8
+    /// Record a call from the current frame to `precompile` that ran out of gas.
+    ///
+    /// Precompile costs are never multiplied, so the call only fails because the repriced caller
+    /// had less gas left to forward. Precompiles run no steps, so the calling opcode and its PC
+    /// are the last ones sampled.
+    fn record_precompile_oog(&mut self, precompile: Address, gas_remaining: u64) {
+        self.oog_occurred = true;
+        if self.oog_info.is_some() {
+            return;
+        }
 
-            // If we detected a gas loop pattern, assume it's a loop
-            _ if self.has_gas_loop_pattern() => OogPattern::Loop,
+        self.oog_info = Some(OutOfGasInfo {
+            opcode: self.current_opcode,
+            opcode_name: opcode_mnemonic(self.current_opcode).to_string(),
+            pc: self.gas_trajectory.back().map_or(0, |sample| sample.pc),
+            contract: self.storage_address(),
+            code_address: self.code_address(),
+            call_depth: self.call_stack.len(),
+            gas_remaining,
+            pattern: OogPattern::Precompile,
+            gas_trajectory: self.gas_trajectory.iter().copied().collect(),
+            precompile: Some(precompile),
+        });
+    }
 
-            _ => OogPattern::Unknown,
+    /// Address the current frame's code was loaded from.
+    fn code_address(&self) -> Address {
+        self.call_stack.last().map_or(Address::ZERO, |entry| entry.code_address)
+    }
+
+    /// Address whose state the current frame acts on.
+    fn storage_address(&self) -> Address {
+        self.call_stack.last().map_or(Address::ZERO, |entry| entry.storage_address)
+    }
+
+    /// Pop the current frame, propagating an OOG and the origin of a revert to its parent.
+    ///
+    /// Returns the popped entry and whether the frame ran out of gas.
+    fn end_frame(&mut self, result: InstructionResult) -> Option<(CallStackEntry, bool)> {
+        let entry = self.call_stack.pop()?;
+        let out_of_gas = entry.oog || is_out_of_gas(result);
+
+        // A frame that completed swallowed any OOGs in its children
+        if result.is_ok() && !entry.oog {
+            self.child_frame_oogs.extend_from_slice(&entry.oog_children);
+        }
+        if out_of_gas {
+            if let Some(parent) = self.call_stack.last_mut() {
+                parent.oog_children.push(entry.call_index);
+            }
+        }
+
+        // A revert starts in a frame whose last child did not revert, and is carried up through
+        // each parent that reverts in turn
+        let revert_origin = result
+            .is_revert()
+            .then(|| entry.child_revert_origin.unwrap_or(entry.function_selector));
+        match self.call_stack.last_mut() {
+            Some(parent) => parent.child_revert_origin = revert_origin,
+            None => self.reverting_selector = revert_origin.flatten(),
         }
+
+        Some((entry, out_of_gas))
     }
 
     /// Track a GAS opcode usage.
     fn track_gas_opcode(&mut self, interp: &Interpreter) {
-        let contract =
-            if let Some(entry) = self.call_stack.last() { entry.contract } else { Address::ZERO };
-
+        // Keyed by PC, so by the code the PC is in
         self.gas_opcode_usage.push_back(GasOpcodeEvent {
             pc: interp.bytecode.pc(),
             gas_remaining: interp.gas.remaining(),
-            contract,
+            contract: self.code_address(),
         });
 
         // Keep only recent events
@@ -259,6 +630,21 @@ impl<CTX> Inspector<CTX, revm::interpreter::interpreter::EthInterpreter> for Gas
 where
     CTX: ContextTr,
 {
+    fn initialize_interp(
+        &mut self,
+        interp: &mut Interpreter<revm::interpreter::interpreter::EthInterpreter>,
+        _context: &mut CTX,
+    ) {
+        // The address a CREATE frame deploys to is only known once its init code starts, so
+        // divergences in init code are attributed to the created address
+        if let Some(entry) = self.call_stack.last_mut() {
+            if matches!(entry.call_type, CallType::Create | CallType::Create2) {
+                entry.code_address = interp.input.target_address;
+                entry.storage_address = interp.input.target_address;
+            }
+        }
+    }
+
     fn step(
         &mut self,
         interp: &mut Interpreter<revm::interpreter::interpreter::EthInterpreter>,
@@ -269,6 +655,16 @@ where
 
         // Get the current opcode
         let opcode_byte = interp.bytecode.opcode();
+        self.current_opcode = opcode_byte;
+
+        // Sample gas for the OOG trajectory
+        if self.gas_trajectory.len() == GAS_TRAJECTORY_LEN {
+            self.gas_trajectory.pop_front();
+        }
+        self.gas_trajectory.push_back(GasSample {
+            pc: interp.bytecode.pc(),
+            gas_remaining: interp.gas.remaining(),
+        });
 
         // Track total operations
         self.op_counts.total_ops += 1;
@@ -277,6 +673,9 @@ where
         match opcode_byte {
             0x54 => self.op_counts.sload_count += 1,  // SLOAD
             0x55 => self.op_counts.sstore_count += 1, // SSTORE
+            0x5C => self.op_counts.tload_count += 1,  // TLOAD
+            0x5D => self.op_counts.tstore_count += 1, // TSTORE
+            0x5E => self.op_counts.mcopy_count += 1,  // MCOPY
             0xA0 | 0xA1 | 0xA2 | 0xA3 | 0xA4 => {
                 // LOG0-LOG4
                 self.op_counts.log_count += 1
@@ -286,9 +685,13 @@ where
                 self.op_counts.call_count += 1
             }
             0xF0 | 0xF5 => self.op_counts.create_count += 1, // CREATE, CREATE2
+            0x3B | 0x3C | 0x3F => {
+                // EXTCODESIZE, EXTCODECOPY, EXTCODEHASH
+                self.op_counts.extcode_count += 1
+            }
             0x5A => {
                 // GAS
-                if self.config.detect_gas_loops {
+                if self.config.detect_gas_loops || self.capture_gas_observations {
                     self.track_gas_opcode(interp);
                 }
             }
@@ -296,11 +699,9 @@ where
         }
 
         // Track memory usage
-        let memory_size = interp.memory.len();
-        let memory_words = (memory_size + 31) / 32;
-        let memory_words_u64 = memory_words as u64;
-        if memory_words_u64 > self.op_counts.memory_words_allocated {
-            self.op_counts.memory_words_allocated = memory_words_u64;
+        let memory_words = interp.memory.len().div_ceil(32) as u64;
+        if memory_words > self.op_counts.memory_words_allocated {
+            self.op_counts.memory_words_allocated = memory_words;
         }
     }
 
@@ -309,8 +710,8 @@ where
         interp: &mut Interpreter<revm::interpreter::interpreter::EthInterpreter>,
         _context: &mut CTX,
     ) {
-        // Skip if we already hit OOG
-        if self.oog_occurred {
+        // Skip while inside a frame that already hit OOG
+        if self.call_stack.iter().any(|entry| entry.oog) {
             return;
         }
 
@@ -322,31 +723,55 @@ where
 
         let actual_gas_cost = gas_before.saturating_sub(gas_after_step);
 
-        // Calculate additional gas to charge (multiplier - 1) * actual_cost
-        // If multiplier is 100, we charge 99x additional gas
-        let additional_gas = actual_gas_cost.saturating_mul(self.config.gas_multiplier - 1);
-
-        // Track total gas used
-        self.simulated_gas_used =
-            self.simulated_gas_used.saturating_add(actual_gas_cost).saturating_add(additional_gas);
-
-        // Try to charge the additional gas
-        if additional_gas > 0 {
-            if !interp.gas.record_cost(additional_gas) {
-                // OUT OF GAS! The execution actually failed due to repricing
-                // When record_cost returns false, revm will automatically halt execution
-                self.oog_occurred = true;
-
-                let opcode_byte = interp.bytecode.opcode();
-                let opcode_name = format!("0x{:02x}", opcode_byte);
-
-                self.record_oog(interp, opcode_byte, opcode_name.clone());
-                self.record_divergence_location(interp, opcode_byte, opcode_name);
+        if self.real_gas {
+            // The interpreter already charged the modified cost and halts where it runs out
+            self.simulated_gas_used = self.simulated_gas_used.saturating_add(actual_gas_cost);
+            if matches!(
+                interp.bytecode.instruction_result(),
+                Some(InstructionResult::OutOfGas | InstructionResult::MemoryOOG)
+            ) {
+                self.record_out_of_gas(interp);
+            }
+        } else {
+            // Calculate additional gas to charge (multiplier - 1) * actual_cost
+            // If multiplier is 100, we charge 99x additional gas
+            let simulated_cost = self.calculate_gas_cost(self.current_opcode, actual_gas_cost);
+            let additional_gas = simulated_cost.saturating_sub(actual_gas_cost);
+
+            // Track total gas used
+            self.simulated_gas_used = self.simulated_gas_used.saturating_add(simulated_cost);
+
+            // Try to charge the additional gas
+            if additional_gas > 0 && !interp.gas.record_cost(additional_gas) {
+                // OUT OF GAS! The frame would have failed due to repricing
+                self.record_out_of_gas(interp);
+
+                // A child frame fails as it would under the new costs: its state changes are
+                // reverted and its parent sees the failed call. The top-level frame runs on
+                // without further charges so the rest of the transaction is still observed.
+                if self.call_stack.len() > 1 {
+                    interp.gas.spend_all();
+                    interp.halt(InstructionResult::OutOfGas);
+                }
             }
         }
+
+        // A child that reverts after burning nearly all of its gas leaves its parent only the
+        // 1/64 of the gas it held back
+        if self.current_opcode == 0xFD &&
+            self.call_stack.len() > 1 &&
+            !self.call_stack.last().is_some_and(|entry| entry.oog) &&
+            interp.gas.spent().saturating_mul(64) >= interp.gas.limit().saturating_mul(63)
+        {
+            self.record_griefing(interp);
+        }
     }
 
-    fn call(&mut self, _context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+    fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        if self.call_stack.is_empty() {
+            self.start_transaction(context);
+        }
+
         // Track call depth
         let call_type = match inputs.scheme {
             revm::interpreter::CallScheme::Call => CallType::Call,
@@ -357,31 +782,52 @@ where
 
         let function_selector = Self::extract_function_selector(&inputs.input);
 
+        let call_index = self.next_call_index;
+        self.next_call_index += 1;
+
         self.call_stack.push(CallStackEntry {
+            call_index,
             depth: self.call_stack.len(),
-            contract: inputs.bytecode_address,
+            code_address: inputs.bytecode_address,
+            storage_address: inputs.target_address,
             call_type,
             gas_at_start: self.simulated_gas_used,
             function_selector,
+            create2: None,
+            oog: false,
+            oog_children: Vec::new(),
+            child_revert_origin: None,
         });
 
         None // Let execution continue normally
     }
 
-    fn call_end(&mut self, _context: &mut CTX, inputs: &CallInputs, outcome: &mut CallOutcome) {
+    fn call_end(&mut self, context: &mut CTX, inputs: &CallInputs, outcome: &mut CallOutcome) {
         // Record the call frame
-        if let Some(entry) = self.call_stack.pop() {
+        if let Some((entry, out_of_gas)) = self.end_frame(outcome.result.result) {
+            if self.call_stack.is_empty() {
+                self.gas_remaining = Some(outcome.result.gas.remaining());
+            }
+            if out_of_gas &&
+                !self.call_stack.is_empty() &&
+                context.journal().precompile_addresses().contains(&inputs.bytecode_address)
+            {
+                self.record_precompile_oog(inputs.bytecode_address, outcome.result.gas.remaining());
+            }
+
             let gas_used = self.simulated_gas_used.saturating_sub(entry.gas_at_start);
 
             // Extract input bytes based on CallInput enum
-            let input_bytes = match &inputs.input {
-                revm::interpreter::CallInput::Bytes(bytes) => Some(bytes.clone()),
-                revm::interpreter::CallInput::SharedBuffer(_) => None, /* Can't safely access
-                                                                        * without context */
+            let (input_len, input_bytes) = match &inputs.input {
+                revm::interpreter::CallInput::Bytes(bytes) => {
+                    (bytes.len(), self.capture_call_data.then(|| bytes.clone()))
+                }
+                // Can't safely access the shared buffer without context
+                revm::interpreter::CallInput::SharedBuffer(range) => (range.len(), None),
             };
 
             self.call_frames.push(CallFrame {
-                call_index: self.call_frames.len(),
+                call_index: entry.call_index,
                 depth: entry.depth,
                 from: inputs.caller,
                 to: Some(inputs.bytecode_address),
@@ -389,16 +835,39 @@ where
                 gas_provided: inputs.gas_limit,
                 gas_used,
                 success: outcome.result.result.is_ok(),
+                out_of_gas,
+                input_len,
+                output_len: outcome.result.output.len(),
                 input: input_bytes,
-                output: Some(outcome.result.output.clone()),
+                output: self.capture_call_data.then(|| outcome.result.output.clone()),
             });
         }
     }
 
-    fn create(&mut self, _context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+    fn create(&mut self, context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        if self.call_stack.is_empty() {
+            self.start_transaction(context);
+        }
+
+        let spec: SpecId = context.cfg().spec().into();
+        let shanghai = spec.is_enabled_in(SpecId::SHANGHAI);
+        let init_code_len = inputs.init_code.len();
+        let create2 = match inputs.scheme {
+            revm::context_interface::CreateScheme::Create2 { salt } => {
+                Some(Create2Deployment::new(inputs.caller, B256::from(salt), &inputs.init_code))
+            }
+            _ => None,
+        };
+
+        let call_index = self.next_call_index;
+        self.next_call_index += 1;
+
         self.call_stack.push(CallStackEntry {
+            call_index,
             depth: self.call_stack.len(),
-            contract: Address::ZERO, // Will be filled in create_end
+            // Both filled in initialize_interp
+            code_address: Address::ZERO,
+            storage_address: Address::ZERO,
             call_type: match inputs.scheme {
                 revm::context_interface::CreateScheme::Create => CallType::Create,
                 revm::context_interface::CreateScheme::Create2 { .. } |
@@ -406,8 +875,35 @@ where
             },
             gas_at_start: self.simulated_gas_used,
             function_selector: None, // CREATE operations don't have function selectors
+            create2,
+            oog: false,
+            oog_children: Vec::new(),
+            child_revert_origin: None,
         });
 
+        if shanghai {
+            if init_code_len > MAX_INITCODE_SIZE {
+                return Some(CreateOutcome::new(
+                    InterpreterResult::new(
+                        InstructionResult::CreateInitCodeSizeLimit,
+                        Bytes::new(),
+                        Gas::new_spent(inputs.gas_limit),
+                    ),
+                    None,
+                ));
+            }
+
+            // A nested CREATE's init code cost is part of the opcode's cost measured in
+            // step_end, but for a creation transaction it is intrinsic gas no step sees
+            if self.call_stack.len() == 1 {
+                let words = init_code_len.div_ceil(32) as u64;
+                // Priced like the CREATE opcode
+                let init_code_cost =
+                    self.calculate_gas_cost(0xF0, words.saturating_mul(INITCODE_WORD_COST));
+                self.simulated_gas_used = self.simulated_gas_used.saturating_add(init_code_cost);
+            }
+        }
+
         None
     }
 
@@ -417,12 +913,21 @@ where
         inputs: &CreateInputs,
         outcome: &mut CreateOutcome,
     ) {
-        if let Some(entry) = self.call_stack.pop() {
+        if let Some((entry, out_of_gas)) = self.end_frame(outcome.result.result) {
+            if self.call_stack.is_empty() {
+                self.gas_remaining = Some(outcome.result.gas.remaining());
+            }
             let gas_used = self.simulated_gas_used.saturating_sub(entry.gas_at_start);
             let created_address = outcome.address.unwrap_or(Address::ZERO);
 
+            if let Some(mut deployment) = entry.create2 {
+                deployment.actual_address =
+                    outcome.result.result.is_ok().then_some(outcome.address).flatten();
+                self.create2_deployments.push(deployment);
+            }
+
             self.call_frames.push(CallFrame {
-                call_index: self.call_frames.len(),
+                call_index: entry.call_index,
                 depth: entry.depth,
                 from: inputs.caller,
                 to: Some(created_address),
@@ -430,66 +935,71 @@ where
                 gas_provided: inputs.gas_limit,
                 gas_used,
                 success: outcome.result.result.is_ok(),
-                input: Some(inputs.init_code.clone()),
-                output: Some(outcome.result.output.clone()),
+                out_of_gas,
+                input_len: inputs.init_code.len(),
+                output_len: outcome.result.output.len(),
+                input: self.capture_call_data.then(|| inputs.init_code.clone()),
+                output: self.capture_call_data.then(|| outcome.result.output.clone()),
             });
         }
     }
 }
 
-/// Estimate base gas cost for an opcode.
-/// This is a simplified estimation - real costs depend on context (memory, storage, etc.)
-#[allow(dead_code)]
-fn estimate_opcode_gas_cost(opcode: u8) -> u64 {
-    match opcode {
-        // Medium: 5-10 gas
-        0x0A => 10, // EXP - Base cost, can be much higher
-
-        // Very cheap: 2-3 gas (arithmetic, stack, etc.)
-        0x01..=0x09 | 0x0B | // ADD through SIGNEXTEND (except EXP)
-        0x10..=0x1D | // LT through BYTE (includes SHL, SHR, SAR)
-        0x50 | // POP
-        0x51 | 0x52 | 0x53 | // MLOAD, MSTORE, MSTORE8
-        0x5F..=0x7F | // PUSH0-PUSH32
-        0x80..=0x8F | // DUP1-DUP16
-        0x90..=0x9F => 3, // SWAP1-SWAP16
-        0x20 => 30, // SHA3 - Base cost
-        0x35 | 0x36 | 0x37 => 3, // CALLDATALOAD, CALLDATASIZE, CALLDATACOPY
-        0x38 | 0x39 => 3, // CODESIZE, CODECOPY
-        0x3D | 0x3E => 3, // RETURNDATASIZE, RETURNDATACOPY
-
-        // Expensive: Storage operations
-        0x54 => 800, // SLOAD - Warm access, can be 2100 for cold
-        0x55 => 2900, // SSTORE - Can be 20000 for cold or creation
-
-        // Very expensive: External calls and creates
-        0xF1 | 0xF2 => 700, // CALL, CALLCODE - Base cost, can be much higher
-        0xF4 | 0xFA => 700, // DELEGATECALL, STATICCALL
-        0xF0 => 32000, // CREATE
-        0xF5 => 32000, // CREATE2
-
-        // Logs
-        0xA0 => 375, // LOG0
-        0xA1 => 375, // LOG1
-        0xA2 => 375, // LOG2
-        0xA3 => 375, // LOG3
-        0xA4 => 375, // LOG4
-
-        // Other operations
-        0x57 => 10, // JUMPI
-        0x56 => 8,  // JUMP
-        0x58 => 2,  // PC
-        0x59 => 2,  // MSIZE
-        0x5A => 2,  // GAS
-
-        // Default
-        _ => 3,
+/// Load `addresses` into the journal so accessing them is charged at the warm cost.
+///
+/// Called before the top-level frame runs, see [`BlockContext::with_warm_recipients`].
+///
+/// [`BlockContext::with_warm_recipients`]: crate::analysis::BlockContext::with_warm_recipients
+pub(crate) fn warm_accounts<CTX: ContextTr>(context: &mut CTX, addresses: &[Address]) {
+    for address in addresses {
+        // A database error resurfaces if execution accesses the account
+        let _ = context.journal_mut().load_account(*address);
+    }
+}
+
+/// Whether a frame result is an out-of-gas halt.
+pub(crate) const fn is_out_of_gas(result: InstructionResult) -> bool {
+    matches!(
+        result,
+        InstructionResult::OutOfGas |
+            InstructionResult::MemoryOOG |
+            InstructionResult::MemoryLimitOOG |
+            InstructionResult::PrecompileOOG |
+            InstructionResult::InvalidOperandOOG
+    )
+}
+
+/// Hex names of all byte values, used for undefined opcodes.
+static OPCODE_HEX_NAMES: [[u8; 4]; 256] = {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut names = [[0; 4]; 256];
+    let mut i = 0;
+    while i < 256 {
+        names[i] = [b'0', b'x', DIGITS[i >> 4], DIGITS[i & 0xf]];
+        i += 1;
+    }
+    names
+};
+
+/// Mnemonic of an opcode (e.g. `SLOAD`), or its hex value (e.g. `0x0c`) if it is undefined.
+pub fn opcode_mnemonic(opcode: u8) -> &'static str {
+    match OpCode::new(opcode) {
+        Some(op) => op.as_str(),
+        None => {
+            std::str::from_utf8(&OPCODE_HEX_NAMES[opcode as usize]).expect("hex names are ASCII")
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        test_utils::{self, TestEnv, CONTRACT},
+        tracking_inspector::TrackingInspector,
+    };
+    use revm::inspector::NoOpInspector;
+    use std::collections::HashSet;
 
     #[test]
     fn test_gas_calculation() {
@@ -498,15 +1008,565 @@ mod tests {
         let inspector = GasResearchInspector::new(config, 100_000);
 
         // Base cost of 100 should become 12,800
-        assert_eq!(inspector.calculate_gas_cost(100), 12_800);
+        assert_eq!(inspector.calculate_gas_cost(0x01, 100), 12_800);
+    }
+
+    #[test]
+    fn test_excluded_opcode_gas_calculation() {
+        let config = ResearchConfig {
+            gas_multiplier: 128,
+            excluded_opcodes: HashSet::from([0x5b, 0x60]),
+            ..Default::default()
+        };
+        let inspector = GasResearchInspector::new(config, 100_000);
+
+        // Excluded opcodes stay at 1x
+        assert_eq!(inspector.calculate_gas_cost(0x5b, 1), 1); // JUMPDEST
+        assert_eq!(inspector.calculate_gas_cost(0x60, 3), 3); // PUSH1
+
+        // Everything else is multiplied
+        assert_eq!(inspector.calculate_gas_cost(0x01, 3), 384); // ADD
+    }
+
+    #[test]
+    fn test_excluded_opcode_not_multiplied_in_execution() {
+        // PUSH1 0 PUSH1 0 ADD POP STOP
+        let code = vec![0x60, 0x00, 0x60, 0x00, 0x01, 0x50, 0x00];
+        let env = TestEnv::new().with_contract(CONTRACT, code);
+        let tx = || TestEnv::call_tx(CONTRACT, vec![], 1_000_000);
+
+        let normal = env.transact(tx(), NoOpInspector).unwrap().result.gas_used();
+
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let inspector = GasResearchInspector::new(config, 1_000_000);
+        let multiplied = env.transact(tx(), inspector).unwrap().result.gas_used();
+        // Two PUSH1 (3), ADD (3) and POP (2) are doubled
+        assert_eq!(multiplied, normal + 11);
+
+        // Exclusion wins over the global multiplier
+        let config = ResearchConfig {
+            gas_multiplier: 2,
+            excluded_opcodes: HashSet::from([0x60]),
+            ..Default::default()
+        };
+        let inspector = GasResearchInspector::new(config, 1_000_000);
+        let excluded = env.transact(tx(), inspector).unwrap().result.gas_used();
+        assert_eq!(excluded, normal + 5);
+    }
+
+    #[test]
+    fn test_child_frame_oog_swallowed_by_parent() {
+        let child = Address::repeat_byte(0xcc);
+        // PUSH1 1 PUSH1 0 SSTORE STOP: a 22,100 gas SSTORE fits in 30k only at 1x
+        let env = TestEnv::new()
+            .with_contract(CONTRACT, test_utils::call_code_with_gas(child, 30_000))
+            .with_contract(child, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00]);
+        let tx = || TestEnv::call_tx(CONTRACT, vec![], 100_000);
+
+        let mut normal = TrackingInspector::new();
+        assert!(env.transact(tx(), &mut normal).unwrap().result.is_success());
+        assert!(normal.call_frames().iter().all(|frame| frame.success && !frame.out_of_gas));
+
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let mut experimental = GasResearchInspector::new(config, 100_000);
+        // The parent ignores the call's outcome, so the transaction still succeeds
+        assert!(env.transact(tx(), &mut experimental).unwrap().result.is_success());
+
+        assert!(experimental.oog_occurred());
+        assert_eq!(experimental.oog_info().unwrap().call_depth, 2);
+        assert_eq!(experimental.child_frame_oogs(), &[1]);
+
+        let child_frame =
+            experimental.call_frames().iter().find(|frame| frame.call_index == 1).unwrap();
+        assert_eq!(child_frame.to, Some(child));
+        assert!(child_frame.out_of_gas);
+        let parent_frame =
+            experimental.call_frames().iter().find(|frame| frame.call_index == 0).unwrap();
+        assert!(!parent_frame.out_of_gas);
+    }
+
+    #[test]
+    fn test_child_frame_oog_fails_call_in_parent() {
+        let child = Address::repeat_byte(0xcc);
+        // CALL the child with 30,000 gas and revert if the call failed
+        let mut parent_code = vec![
+            0x60, 0x00, // PUSH1 0 (retSize)
+            0x60, 0x00, // PUSH1 0 (retOffset)
+            0x60, 0x00, // PUSH1 0 (argsSize)
+            0x60, 0x00, // PUSH1 0 (argsOffset)
+            0x60, 0x00, // PUSH1 0 (value)
+            0x73, // PUSH20 child
+        ];
+        parent_code.extend_from_slice(child.as_slice());
+        parent_code.extend_from_slice(&[
+            0x61, 0x75, 0x30, // PUSH2 30000
+            0xf1, // CALL
+            0x60, 0x2a, 0x57, // PUSH1 42 JUMPI
+            0x60, 0x00, 0x80, 0xfd, // PUSH1 0 DUP1 REVERT
+            0x5b, 0x00, // JUMPDEST STOP
+        ]);
+        assert_eq!(parent_code[42], 0x5b);
+
+        // PUSH1 1 PUSH1 0 SSTORE STOP: a 22,100 gas SSTORE fits in 30k only at 1x
+        let env = TestEnv::new()
+            .with_contract(CONTRACT, parent_code)
+            .with_contract(child, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00]);
+        let tx = || TestEnv::call_tx(CONTRACT, vec![], 100_000);
+
+        let normal = env.transact(tx(), NoOpInspector).unwrap();
+        assert!(normal.result.is_success());
+        assert!(!normal.state[&child].storage.is_empty());
+
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let mut experimental = GasResearchInspector::new(config, 100_000);
+        let result = env.transact(tx(), &mut experimental).unwrap();
+
+        // The parent sees the call fail and takes the revert branch
+        assert!(!result.result.is_success());
+        assert_eq!(experimental.oog_info().unwrap().call_depth, 2);
+        let child_frame =
+            experimental.call_frames().iter().find(|frame| frame.call_index == 1).unwrap();
+        assert!(child_frame.out_of_gas);
+        assert!(!child_frame.success);
+    }
+
+    #[test]
+    fn test_reverting_selector_from_deepest_frame() {
+        let inner = Address::repeat_byte(0xcc);
+        let inner_selector = [0x12, 0x34, 0x56, 0x78];
+        let outer_selector = [0xaa, 0xbb, 0xcc, 0xdd];
+
+        // Call `inner` with its selector as calldata and revert if the call failed
+        let mut outer_code = vec![0x63]; // PUSH4 selector
+        outer_code.extend_from_slice(&inner_selector);
+        outer_code.extend_from_slice(&[
+            0x60, 0xe0, 0x1b, // PUSH1 224 SHL
+            0x60, 0x00, 0x52, // PUSH1 0 MSTORE
+            0x60, 0x00, // PUSH1 0 (retSize)
+            0x60, 0x00, // PUSH1 0 (retOffset)
+            0x60, 0x04, // PUSH1 4 (argsSize)
+            0x60, 0x00, // PUSH1 0 (argsOffset)
+            0x60, 0x00, // PUSH1 0 (value)
+            0x73, // PUSH20 inner
+        ]);
+        outer_code.extend_from_slice(inner.as_slice());
+        outer_code.extend_from_slice(&[
+            0x5a, 0xf1, // GAS CALL
+            0x15, 0x60, 0x31, 0x57, // ISZERO PUSH1 49 JUMPI
+            0x00, // STOP
+            0x5b, 0x60, 0x00, 0x60, 0x00, 0xfd, // JUMPDEST PUSH1 0 PUSH1 0 REVERT
+        ]);
+        assert_eq!(outer_code[49], 0x5b);
+
+        let env = TestEnv::new()
+            .with_contract(CONTRACT, outer_code)
+            // PUSH1 0 PUSH1 0 REVERT
+            .with_contract(inner, vec![0x60, 0x00, 0x60, 0x00, 0xfd]);
+        let tx = TestEnv::call_tx(CONTRACT, outer_selector.to_vec(), 100_000);
+
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let mut inspector = GasResearchInspector::new(config, 100_000);
+        assert!(!env.transact(tx, &mut inspector).unwrap().result.is_success());
+
+        // The outer frame only bubbled the revert up
+        assert_eq!(inspector.reverting_selector(), Some(inner_selector));
+    }
+
+    #[test]
+    fn test_init_code_cost_scales_with_size() {
+        let env = TestEnv::new();
+        // Init code that stops immediately, padded to `words` words
+        let simulated_cost = |words: usize| {
+            let config = ResearchConfig { gas_multiplier: 4, ..Default::default() };
+            let mut inspector = GasResearchInspector::new(config, 1_000_000);
+            let tx = TestEnv::create_tx(vec![0x00; words * 32], 1_000_000);
+            assert!(env.transact(tx, &mut inspector).unwrap().result.is_success());
+            inspector.simulated_gas_used()
+        };
+
+        assert_eq!(simulated_cost(1), 4 * INITCODE_WORD_COST);
+        assert_eq!(simulated_cost(32), 4 * 32 * INITCODE_WORD_COST);
+        assert_eq!(simulated_cost(MAX_INITCODE_SIZE / 32), 4 * 1536 * INITCODE_WORD_COST);
+    }
+
+    #[test]
+    fn test_warm_addresses() {
+        let recipient = Address::repeat_byte(0x42);
+        // PUSH20 recipient BALANCE POP STOP
+        let mut code = vec![0x73];
+        code.extend_from_slice(recipient.as_slice());
+        code.extend_from_slice(&[0x31, 0x50, 0x00]);
+        let env = TestEnv::new().with_contract(CONTRACT, code);
+        let tx = || TestEnv::call_tx(CONTRACT, vec![], 100_000);
+
+        // The first BALANCE costs 2,600 cold and 100 warm
+        let cold = env.transact(tx(), TrackingInspector::new()).unwrap().result.gas_used();
+        let inspector = TrackingInspector::new().with_warm_addresses(vec![recipient]);
+        let warm = env.transact(tx(), inspector).unwrap().result.gas_used();
+        assert_eq!(cold - warm, 2_500);
+
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let inspector = GasResearchInspector::new(config.clone(), 100_000);
+        let cold = env.transact(tx(), inspector).unwrap().result.gas_used();
+        let inspector =
+            GasResearchInspector::new(config, 100_000).with_warm_addresses(vec![recipient]);
+        let warm = env.transact(tx(), inspector).unwrap().result.gas_used();
+        assert_eq!(cold - warm, 2 * 2_500);
+    }
+
+    #[test]
+    fn test_intrinsic_gas_adjustment_toggle() {
+        let config = ResearchConfig { gas_multiplier: 4, ..Default::default() };
+        let adjusted = GasResearchInspector::new(config.clone(), 100_000);
+        assert_eq!(adjusted.simulated_gas_limit(), 400_000 - 3 * 21_000);
+
+        let config = ResearchConfig { adjust_intrinsic_gas: false, ..config };
+        let raw = GasResearchInspector::new(config.clone(), 100_000);
+        assert_eq!(raw.simulated_gas_limit(), 400_000);
+
+        // With an unscaled limit the adjustment saturates to zero, the raw limit does not
+        let config = ResearchConfig { gas_limit_multiplier: Some(1), ..config };
+        assert_eq!(GasResearchInspector::new(config.clone(), 50_000).simulated_gas_limit(), 50_000);
+        let config = ResearchConfig { adjust_intrinsic_gas: true, ..config };
+        assert_eq!(GasResearchInspector::new(config, 50_000).simulated_gas_limit(), 0);
+    }
+
+    #[test]
+    fn test_oog_gas_trajectory() {
+        // PUSH1 1 PUSH1 0 SSTORE STOP: the 22,100 gas SSTORE fits in 29k only at 1x
+        let env = TestEnv::new().with_contract(CONTRACT, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00]);
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let mut inspector = GasResearchInspector::new(config, 50_000);
+        env.transact(TestEnv::call_tx(CONTRACT, vec![], 50_000), &mut inspector).unwrap();
+
+        let location = inspector.divergence_location().unwrap();
+        assert_eq!(
+            location.code_hash,
+            alloy_primitives::keccak256([0x60, 0x01, 0x60, 0x00, 0x55, 0x00])
+        );
+        assert_eq!(location.opcode_name, "SSTORE");
+
+        // Each PUSH1 burns 3 gas plus 3 more for the multiplier before the SSTORE spike
+        let oog = inspector.oog_info().unwrap();
+        assert_eq!(
+            oog.gas_trajectory,
+            vec![
+                GasSample { pc: 0, gas_remaining: 29_000 },
+                GasSample { pc: 2, gas_remaining: 28_994 },
+                GasSample { pc: 4, gas_remaining: 28_988 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quadratic_memory_oog() {
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+
+        // PUSH1 1 PUSH3 0x010000 MSTORE STOP: expands memory to 2049 words (~14k gas)
+        let code = vec![0x60, 0x01, 0x62, 0x01, 0x00, 0x00, 0x52, 0x00];
+        let env = TestEnv::new().with_contract(CONTRACT, code);
+        let mut inspector = GasResearchInspector::new(config.clone(), 40_000);
+        env.transact(TestEnv::call_tx(CONTRACT, vec![], 40_000), &mut inspector).unwrap();
+        let oog = inspector.oog_info().unwrap();
+        assert_eq!(oog.opcode, 0x52);
+        assert_eq!(oog.pattern, OogPattern::Quadratic);
+
+        // PUSH1 1 PUSH1 0 MSTORE STOP: a single word is a modest expansion
+        let code = vec![0x60, 0x01, 0x60, 0x00, 0x52, 0x00];
+        let env = TestEnv::new().with_contract(CONTRACT, code);
+        let mut inspector = GasResearchInspector::new(config, 21_020);
+        env.transact(TestEnv::call_tx(CONTRACT, vec![], 21_020), &mut inspector).unwrap();
+        let oog = inspector.oog_info().unwrap();
+        assert_eq!(oog.opcode, 0x52);
+        assert_eq!(oog.pattern, OogPattern::MemoryExpansion);
+    }
+
+    #[test]
+    fn test_delegatecall_attributes_storage_context() {
+        let library = Address::repeat_byte(0x1b);
+        // PUSH1 0 (x4) PUSH20 library GAS DELEGATECALL STOP
+        let mut code = [0x60, 0x00].repeat(4);
+        code.push(0x73);
+        code.extend_from_slice(library.as_slice());
+        code.extend([0x5a, 0xf4, 0x00]);
+        // PUSH1 1 PUSH1 0 SSTORE STOP: the library writes to the proxy's storage
+        let env = TestEnv::new()
+            .with_contract(CONTRACT, code)
+            .with_contract(library, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00]);
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let mut inspector = GasResearchInspector::new(config, 50_000);
+        env.transact(TestEnv::call_tx(CONTRACT, vec![], 50_000), &mut inspector).unwrap();
+
+        // State is attributed to the proxy, the PC and opcode to the library code
+        let location = inspector.divergence_location().unwrap();
+        assert_eq!(location.contract, CONTRACT);
+        assert_eq!(location.code_address, library);
+        assert_eq!(location.opcode_name, "SSTORE");
+        assert_eq!(location.call_depth, 2);
+
+        let oog = inspector.oog_info().unwrap();
+        assert_eq!(oog.contract, CONTRACT);
+        assert_eq!(oog.code_address, library);
+    }
+
+    #[test]
+    fn test_griefing_child_revert() {
+        let child = Address::repeat_byte(0xcc);
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let tx = || TestEnv::call_tx(CONTRACT, vec![], 100_000);
+
+        // JUMPDEST PUSH2 100 GAS GT PUSH1 0 JUMPI PUSH1 0 DUP1 REVERT: loop until at most 100 gas
+        // is left, then revert
+        let burner =
+            vec![0x5b, 0x61, 0x00, 0x64, 0x5a, 0x11, 0x60, 0x00, 0x57, 0x60, 0x00, 0x80, 0xfd];
+        let env = TestEnv::new()
+            .with_contract(CONTRACT, test_utils::call_code_with_gas(child, 30_000))
+            .with_contract(child, burner);
+        let mut inspector = GasResearchInspector::new(config.clone(), 100_000);
+        assert!(env.transact(tx(), &mut inspector).unwrap().result.is_success());
+        assert!(!inspector.oog_occurred());
+        assert!(inspector.oog_info().is_none());
+        let griefing = inspector.griefing_info().unwrap();
+        assert_eq!(griefing.pattern, OogPattern::Griefing);
+        assert_eq!(griefing.opcode, 0xFD);
+        assert_eq!(griefing.contract, child);
+        assert_eq!(griefing.call_depth, 2);
+        assert!(griefing.gas_remaining <= 100);
+
+        // PUSH1 0 DUP1 REVERT: a child reverting straight away is not griefing
+        let env = TestEnv::new()
+            .with_contract(CONTRACT, test_utils::call_code_with_gas(child, 30_000))
+            .with_contract(child, vec![0x60, 0x00, 0x80, 0xfd]);
+        let mut inspector = GasResearchInspector::new(config, 100_000);
+        assert!(env.transact(tx(), &mut inspector).unwrap().result.is_success());
+        assert!(inspector.griefing_info().is_none());
+    }
+
+    #[test]
+    fn test_precompile_oog() {
+        let ecrecover = Address::with_last_byte(0x01);
+        // 200 x (PUSH1 0 POP) burns 1,000 gas, then ECRECOVER (3,000 gas) is called with all
+        // remaining gas. CALL is excluded so only the gas left to forward changes.
+        let mut code = [0x60, 0x00, 0x50].repeat(200);
+        code.extend(test_utils::call_code(ecrecover));
+        let env = TestEnv::new().with_contract(CONTRACT, code);
+        let tx = || TestEnv::call_tx(CONTRACT, vec![], 27_000);
+
+        let mut normal = TrackingInspector::new();
+        assert!(env.transact(tx(), &mut normal).unwrap().result.is_success());
+        assert!(normal.call_frames().iter().all(|frame| frame.success));
+
+        let config = ResearchConfig {
+            gas_multiplier: 4,
+            excluded_opcodes: HashSet::from([0xf1]),
+            ..Default::default()
+        };
+        let mut experimental = GasResearchInspector::new(config, 27_000);
+        // The parent ignores the call's outcome
+        assert!(env.transact(tx(), &mut experimental).unwrap().result.is_success());
+
+        assert!(experimental.oog_occurred());
+        let oog = experimental.oog_info().unwrap();
+        assert_eq!(oog.pattern, OogPattern::Precompile);
+        assert_eq!(oog.precompile, Some(ecrecover));
+        assert_eq!(oog.opcode_name, "CALL");
+        assert_eq!(oog.contract, CONTRACT);
+        assert_eq!(oog.call_depth, 1);
+        let precompile_frame =
+            experimental.call_frames().iter().find(|frame| frame.call_index == 1).unwrap();
+        assert!(precompile_frame.out_of_gas);
+    }
+
+    #[test]
+    fn test_extcode_operations_counted() {
+        // PUSH20 CONTRACT EXTCODESIZE POP PUSH20 CONTRACT EXTCODEHASH POP STOP
+        let mut code = Vec::new();
+        for opcode in [0x3B, 0x3F] {
+            code.push(0x73);
+            code.extend_from_slice(CONTRACT.as_slice());
+            code.extend_from_slice(&[opcode, 0x50]);
+        }
+        code.push(0x00);
+        let env = TestEnv::new().with_contract(CONTRACT, code);
+        let tx = || TestEnv::call_tx(CONTRACT, vec![], 100_000);
+
+        let mut normal = TrackingInspector::new();
+        assert!(env.transact(tx(), &mut normal).unwrap().result.is_success());
+        assert_eq!(normal.operation_counts().extcode_count, 2);
+
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let mut experimental = GasResearchInspector::new(config, 100_000);
+        assert!(env.transact(tx(), &mut experimental).unwrap().result.is_success());
+        assert_eq!(experimental.operation_counts().extcode_count, 2);
+    }
+
+    #[test]
+    fn test_transient_storage_operations_counted() {
+        // PUSH1 1 PUSH1 0 TSTORE PUSH1 0 TLOAD PUSH1 1 TLOAD POP POP STOP
+        let code = vec![
+            0x60, 0x01, 0x60, 0x00, 0x5D, 0x60, 0x00, 0x5C, 0x60, 0x01, 0x5C, 0x50, 0x50, 0x00,
+        ];
+        let env = TestEnv::new().with_contract(CONTRACT, code);
+        let tx = || TestEnv::call_tx(CONTRACT, vec![], 100_000);
+
+        let mut normal = TrackingInspector::new();
+        assert!(env.transact(tx(), &mut normal).unwrap().result.is_success());
+        assert_eq!(normal.operation_counts().tstore_count, 1);
+        assert_eq!(normal.operation_counts().tload_count, 2);
+
+        let config = ResearchConfig { gas_multiplier: 2, ..Default::default() };
+        let mut experimental = GasResearchInspector::new(config, 100_000);
+        assert!(env.transact(tx(), &mut experimental).unwrap().result.is_success());
+        assert_eq!(experimental.operation_counts(), normal.operation_counts());
+    }
+
+    #[test]
+    fn test_mcopy_cost_scaled() {
+        // PUSH2 0x0400 PUSH1 0 PUSH1 0 MCOPY STOP
+        let code = vec![0x61, 0x04, 0x00, 0x60, 0x00, 0x60, 0x00, 0x5E, 0x00];
+        let env = TestEnv::new().with_contract(CONTRACT, code);
+        let tx = || TestEnv::call_tx(CONTRACT, vec![], 100_000);
+
+        // Three pushes, then MCOPY over 32 words: 3 + 3 * 32 plus expansion of 3 * 32 + 32^2 / 512
+        let execution_gas = 3 * 3 + (3 + 3 * 32) + (3 * 32 + 2);
+
+        let mut normal = TrackingInspector::new();
+        let result = env.transact(tx(), &mut normal).unwrap().result;
+        assert_eq!(result.gas_used(), 21_000 + execution_gas);
+        assert_eq!(normal.operation_counts().mcopy_count, 1);
+
+        let config = ResearchConfig { gas_multiplier: 4, ..Default::default() };
+        let mut experimental = GasResearchInspector::new(config, 100_000);
+        assert!(env.transact(tx(), &mut experimental).unwrap().result.is_success());
+        assert_eq!(experimental.operation_counts().mcopy_count, 1);
+        assert_eq!(experimental.simulated_gas_used(), 4 * execution_gas);
+    }
+
+    #[test]
+    fn test_call_data_only_captured_when_requested() {
+        // PUSH1 0x20 PUSH1 0 RETURN: return one zeroed word
+        let env = TestEnv::new().with_contract(CONTRACT, vec![0x60, 0x20, 0x60, 0x00, 0xF3]);
+        let tx = || TestEnv::call_tx(CONTRACT, vec![0xde, 0xad, 0xbe, 0xef], 100_000);
+
+        let mut normal = TrackingInspector::new();
+        env.transact(tx(), &mut normal).unwrap();
+        let mut experimental = GasResearchInspector::new(ResearchConfig::default(), 100_000);
+        env.transact(tx(), &mut experimental).unwrap();
+        for frame in [&normal.call_frames()[0], &experimental.call_frames()[0]] {
+            assert_eq!((frame.input_len, frame.output_len), (4, 32));
+            assert!(frame.input.is_none());
+            assert!(frame.output.is_none());
+        }
+
+        let mut normal = TrackingInspector::new().with_call_data(true);
+        env.transact(tx(), &mut normal).unwrap();
+        let mut experimental =
+            GasResearchInspector::new(ResearchConfig::default(), 100_000).with_call_data(true);
+        env.transact(tx(), &mut experimental).unwrap();
+        for frame in [&normal.call_frames()[0], &experimental.call_frames()[0]] {
+            assert_eq!((frame.input_len, frame.output_len), (4, 32));
+            assert_eq!(frame.input.as_deref(), Some(&[0xde, 0xad, 0xbe, 0xef][..]));
+            assert_eq!(frame.output.as_deref(), Some(&[0u8; 32][..]));
+        }
+    }
+
+    #[test]
+    fn test_gas_observations() {
+        // JUMPDEST PUSH2 60000 GAS GT PUSH1 0 JUMPI STOP: loop while more than 60,000 gas is left
+        let code = vec![0x5b, 0x61, 0xea, 0x60, 0x5a, 0x11, 0x60, 0x00, 0x57, 0x00];
+        let env = TestEnv::new().with_contract(CONTRACT, code);
+        let tx = || TestEnv::call_tx(CONTRACT, vec![], 150_000);
+        let config =
+            ResearchConfig { gas_multiplier: 4, detect_gas_loops: false, ..Default::default() };
+
+        let mut inspector = GasResearchInspector::new(config.clone(), 150_000);
+        env.transact(tx(), &mut inspector).unwrap();
+        assert!(inspector.gas_observations().is_empty());
+
+        let mut inspector = GasResearchInspector::new(config, 150_000).with_gas_observations(true);
+        env.transact(tx(), &mut inspector).unwrap();
+        let observations = inspector.gas_observations();
+        assert_eq!(observations.len(), 1);
+        let observation = &observations[0];
+        assert_eq!((observation.contract, observation.pc), (CONTRACT, 4));
+
+        // Each iteration costs 4 * 22 gas, and the loop exits at the first value at or below
+        // the threshold
+        let (last, looped) = observation.gas_remaining.split_last().unwrap();
+        assert!(looped.len() > 3);
+        assert!(looped.windows(2).all(|pair| pair[0] - pair[1] == 4 * 22));
+        assert!(looped.iter().all(|&gas| gas > 60_000));
+        assert!(*last <= 60_000);
+        assert_eq!(looped.last().unwrap() - last, 4 * 22);
+    }
+
+    #[test]
+    fn test_gas_loop_kind() {
+        // The gas-guarded loop of `test_gas_observations` reads GAS with decreasing values
+        let code = vec![0x5b, 0x61, 0xea, 0x60, 0x5a, 0x11, 0x60, 0x00, 0x57, 0x00];
+        let env = TestEnv::new().with_contract(CONTRACT, code);
+        let tx = || TestEnv::call_tx(CONTRACT, vec![], 150_000);
+        let config = ResearchConfig { gas_multiplier: 4, ..Default::default() };
+
+        let mut inspector = GasResearchInspector::new(config.clone(), 150_000);
+        env.transact(tx(), &mut inspector).unwrap();
+        assert_eq!(inspector.gas_loop_kind(), Some(GasLoopKind::Monotonic));
+        assert!(inspector.has_gas_loop_pattern());
+
+        let config = ResearchConfig { detect_gas_loops: false, ..config };
+        let mut inspector = GasResearchInspector::new(config, 150_000);
+        env.transact(tx(), &mut inspector).unwrap();
+        assert_eq!(inspector.gas_loop_kind(), None);
+    }
+
+    #[test]
+    fn test_snapshot_outlives_inspector() {
+        fn assert_send_sync<T: Send + Sync + 'static>(_: &T) {}
+
+        // The gas-guarded loop of `test_gas_observations`
+        let code = vec![0x5b, 0x61, 0xea, 0x60, 0x5a, 0x11, 0x60, 0x00, 0x57, 0x00];
+        let env = TestEnv::new().with_contract(CONTRACT, code);
+        let config = ResearchConfig { gas_multiplier: 4, ..Default::default() };
+        let mut inspector = GasResearchInspector::new(config, 150_000)
+            .with_call_data(true)
+            .with_gas_observations(true);
+        env.transact(TestEnv::call_tx(CONTRACT, vec![], 150_000), &mut inspector).unwrap();
+
+        let snapshot = inspector.snapshot();
+        let expected = InspectorSnapshot {
+            operation_counts: inspector.operation_counts().clone(),
+            call_frames: inspector.call_frames().to_vec(),
+            create2_deployments: inspector.create2_deployments().to_vec(),
+            child_frame_oogs: inspector.child_frame_oogs().to_vec(),
+            divergence_location: inspector.divergence_location().cloned(),
+            oog_info: inspector.oog_info().cloned(),
+            griefing_info: inspector.griefing_info().cloned(),
+            reverting_selector: inspector.reverting_selector(),
+            oog_occurred: inspector.oog_occurred(),
+            simulated_gas_used: inspector.simulated_gas_used(),
+            gas_remaining: inspector.gas_remaining(),
+            gas_observations: inspector.gas_observations(),
+            gas_loop_kind: inspector.gas_loop_kind(),
+            max_refund_quotient: inspector.max_refund_quotient(),
+        };
+        drop(inspector);
+
+        assert_send_sync(&snapshot);
+        assert_eq!(snapshot, expected);
+        assert_eq!(snapshot.call_frames.len(), 1);
+        assert!(snapshot.gas_remaining.is_some());
+        assert!(!snapshot.gas_observations.is_empty());
+        assert_eq!(snapshot.gas_loop_kind, Some(GasLoopKind::Monotonic));
     }
 
     #[test]
-    fn test_opcode_gas_estimation() {
-        assert_eq!(estimate_opcode_gas_cost(0x01), 3); // ADD
-        assert_eq!(estimate_opcode_gas_cost(0x54), 800); // SLOAD
-        assert_eq!(estimate_opcode_gas_cost(0x55), 2900); // SSTORE
-        assert_eq!(estimate_opcode_gas_cost(0xF1), 700); // CALL
+    fn test_opcode_mnemonic() {
+        assert_eq!(opcode_mnemonic(0x54), "SLOAD");
+        assert_eq!(opcode_mnemonic(0x01), "ADD");
+        assert_eq!(opcode_mnemonic(0x5E), "MCOPY");
+        // Undefined opcodes fall back to hex
+        assert_eq!(opcode_mnemonic(0x0c), "0x0c");
+        assert_eq!(opcode_mnemonic(0xef), "0xef");
     }
 
     #[test]
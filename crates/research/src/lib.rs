@@ -10,7 +10,18 @@
 //! - [`ResearchExecutor`]: Wraps an executor to perform dual execution
 //! - [`GasResearchInspector`]: Tracks execution details and simulates high gas costs
 //! - [`DivergenceDatabase`]: Stores detected divergences for later analysis
+//! - [`DivergenceStore`]: Storage backend trait, implemented by [`DivergenceDatabase`] and, with
+//!   feature `postgres`, by `postgres::PostgresStore`
 //! - [`ResearchConfig`]: Configuration for research mode
+//! - [`analysis::analyze_transaction`]: Dual execution and comparison of a single transaction
+//! - [`analysis::analyze_schedules`]: A/B comparison of two experimental gas schedules
+//! - [`analysis::SequentialState`]: Experimental state carried between a block's transactions
+//! - [`code_cache::CodeCache`]: Contract bytecode shared across transactions and blocks
+//! - [`offline::OfflineAnalyzer`]: Analyzes exported RLP blocks without a running node
+//! - [`dashboard::DashboardState`]: Live aggregates of a divergence database for dashboards
+//! - [`signatures::SignatureDatabase`]: Offline function names for selectors
+//! - `real_gas` (feature `real-gas`): Experimental execution with the modified gas costs charged
+//!   by the interpreter, halting where they run out
 //!
 //! # Example Usage
 //!
@@ -29,19 +40,44 @@
 #![cfg_attr(not(test), warn(unused_crate_dependencies))]
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+pub mod analysis;
+pub mod code_cache;
+pub mod compare;
 pub mod config;
+pub mod dashboard;
 pub mod database;
 pub mod divergence;
 pub mod executor;
+pub mod flamegraph;
+pub mod frequent;
 pub mod inspector;
 pub mod metrics;
+pub mod offline;
+pub mod oog;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod progress;
+pub mod provider;
+#[cfg(feature = "real-gas")]
+pub mod real_gas;
+pub mod sampling;
+pub mod signatures;
+pub mod store;
+pub mod system;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub mod timing;
 pub mod tracking_inspector;
 
-pub use config::{ResearchConfig, TraceDetail};
-pub use database::DivergenceDatabase;
-pub use divergence::{CallTrees, Divergence, DivergenceType, EventLog, EventLogs, OperationCounts};
-pub use executor::ResearchExecutor;
-pub use inspector::GasResearchInspector;
+pub use config::{GasSchedule, ResearchConfig, ResearchConfigBuilder, TraceDetail};
+pub use database::{Compression, DivergenceDatabase, DivergenceRecord, DivergenceWriter};
+pub use divergence::{
+    CallTrees, Divergence, DivergenceIdentity, DivergenceType, EventLog, EventLogs, OperationCounts,
+    Severity, DIVERGENCE_SCHEMA_VERSION,
+};
+pub use executor::{ResearchCounters, ResearchExecutor, ResearchStats};
+pub use inspector::{GasResearchInspector, InspectorSnapshot};
+pub use store::DivergenceStore;
 pub use tracking_inspector::{EventLogEntry, TrackingInspector};
 
 /// Re-export error types
@@ -1,6 +1,7 @@
 //! Metrics for research mode.
 
-use metrics::{counter, describe_counter, describe_histogram, histogram};
+use crate::inspector::GasLoopKind;
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
 
 /// Register all research metrics.
 pub fn register_metrics() {
@@ -14,6 +15,11 @@ pub fn register_metrics() {
         "Total number of transactions processed in research mode"
     );
 
+    describe_counter!(
+        "reth_research_transactions_analyzed_total",
+        "Total number of transactions dual-executed in research mode"
+    );
+
     describe_counter!("reth_research_divergences_total", "Total number of divergences detected");
 
     describe_counter!(
@@ -26,13 +32,109 @@ pub fn register_metrics() {
         "Total number of out-of-gas events in experimental execution"
     );
 
+    describe_counter!(
+        "reth_research_near_misses_total",
+        "Matching transactions whose experimental execution finished with little gas left"
+    );
+
+    describe_counter!(
+        "reth_research_experimental_panic_total",
+        "Transactions skipped because experimental execution panicked"
+    );
+
+    describe_counter!(
+        "reth_research_invalid_tx_total",
+        "Transactions skipped because they were invalid in experimental execution only"
+    );
+
+    describe_counter!(
+        "reth_research_unknown_error_total",
+        "Experimental executions that failed with an error that is neither an invalid transaction nor an execution result"
+    );
+
+    describe_counter!(
+        "reth_research_oog_fast_skip_total",
+        "Transactions whose experimental execution was skipped because they cannot run out of gas"
+    );
+
+    describe_counter!(
+        "reth_research_gas_loops_total",
+        "Experimental executions with a gas-dependent loop detected, by kind"
+    );
+
+    describe_counter!(
+        "reth_research_non_divergent_skip_total",
+        "Transactions skipped because a call of the same shape was analyzed without diverging"
+    );
+
+    describe_counter!(
+        "reth_research_non_gas_failure_total",
+        "Experimental-only failures that also fail when replayed at normal gas costs"
+    );
+
+    describe_counter!(
+        "reth_research_gas_reconciliation_mismatch_total",
+        "Blocks whose transactions' summed normal gas differs from the gas used in the header beyond the tolerance"
+    );
+
+    describe_counter!(
+        "reth_research_unexplained_opcount_diff_total",
+        "Transactions whose operation counts differ between executions without a flagged divergence"
+    );
+
+    describe_counter!(
+        "reth_research_database_retries_total",
+        "Divergence database writes retried because the database was busy or locked"
+    );
+
+    describe_counter!(
+        "reth_research_state_unavailable_total",
+        "Transactions skipped because their pre-block state was pruned or unavailable"
+    );
+
+    describe_gauge!(
+        "reth_research_state_unavailable_last_block",
+        "Most recent block whose pre-block state was unavailable"
+    );
+
+    describe_gauge!(
+        "reth_research_recording_stopped",
+        "Whether divergence recording stopped after reaching the configured total limit"
+    );
+
+    describe_gauge!(
+        "reth_research_expected_oog_mode",
+        "Whether gas limits are inflated less than gas costs, so experimental OOGs are expected"
+    );
+
+    describe_counter!(
+        "reth_research_expected_oog_total",
+        "Experimental OOGs of transactions whose normal gas times the multiplier exceeds their \
+         gas limit"
+    );
+
+    describe_gauge!(
+        "reth_research_top_contract_divergences",
+        "Estimated divergence count of the most frequently diverging contracts, by rank"
+    );
+
     describe_histogram!(
         "reth_research_block_execution_seconds",
         "Time to execute a block in research mode (both executions)"
     );
 
+    describe_histogram!(
+        "reth_research_transaction_analysis_seconds",
+        "Average time to dual-execute a transaction, per block with analyzed transactions"
+    );
+
     describe_histogram!("reth_research_gas_efficiency_ratio", "Gas efficiency ratio distribution");
 
+    describe_histogram!(
+        "reth_research_gas_charged_ratio",
+        "Ratio of experimental to normal gas charged after refunds, per transaction"
+    );
+
     describe_histogram!(
         "reth_research_divergence_detection_seconds",
         "Time spent detecting divergences"
@@ -40,15 +142,30 @@ pub fn register_metrics() {
 }
 
 /// Record a block being processed.
-pub fn record_block_processed(block_number: u64, tx_count: usize, duration_secs: f64) {
+///
+/// `analyzed` of the block's `tx_count` transactions were dual-executed in `duration_secs`.
+/// Blocks without analyzed transactions, e.g. empty ones, are counted but add no
+/// per-transaction time.
+pub fn record_block_processed(
+    block_number: u64,
+    tx_count: usize,
+    analyzed: u64,
+    duration_secs: f64,
+) {
     counter!("reth_research_blocks_processed_total").increment(1);
     counter!("reth_research_transactions_processed_total").increment(tx_count as u64);
+    counter!("reth_research_transactions_analyzed_total").increment(analyzed);
     histogram!("reth_research_block_execution_seconds").record(duration_secs);
+    if analyzed > 0 {
+        histogram!("reth_research_transaction_analysis_seconds")
+            .record(duration_secs / analyzed as f64);
+    }
 
     tracing::debug!(
         target: "reth::research",
         block = block_number,
         tx_count = tx_count,
+        analyzed,
         duration_ms = duration_secs * 1000.0,
         "Block processed in research mode"
     );
@@ -68,13 +185,163 @@ pub fn record_divergence(
     histogram!("reth_research_gas_efficiency_ratio").record(gas_efficiency_ratio);
 }
 
+/// Record a near miss: a transaction that matched but only just fit in the gas limit. Near misses
+/// are not divergences and are not counted by [`record_divergence`].
+pub fn record_near_miss() {
+    counter!("reth_research_near_misses_total").increment(1);
+}
+
+/// Record how much more gas a transaction is charged in experimental execution.
+pub fn record_gas_charged_ratio(gas_charged_ratio: f64) {
+    histogram!("reth_research_gas_charged_ratio").record(gas_charged_ratio);
+}
+
 /// Record an out-of-gas event.
 pub fn record_oog(pattern: crate::divergence::OogPattern) {
     counter!("reth_research_oog_total").increment(1);
     counter!("reth_research_oog_by_pattern", "pattern" => pattern.to_string()).increment(1);
 }
 
+/// Record a transaction skipped because experimental execution panicked.
+pub fn record_experimental_panic() {
+    counter!("reth_research_experimental_panic_total").increment(1);
+}
+
+/// Record a transaction that was invalid in experimental execution only.
+pub fn record_invalid_tx() {
+    counter!("reth_research_invalid_tx_total").increment(1);
+}
+
+/// Record an experimental execution that failed with an unknown error.
+pub fn record_unknown_error() {
+    counter!("reth_research_unknown_error_total").increment(1);
+}
+
+/// Record a transaction whose experimental execution was skipped because it cannot run out of
+/// gas.
+pub fn record_oog_fast_skip() {
+    counter!("reth_research_oog_fast_skip_total").increment(1);
+}
+
+/// Record an experimental execution with a gas-dependent loop of `kind`.
+pub fn record_gas_loop(kind: GasLoopKind) {
+    counter!("reth_research_gas_loops_total", "kind" => kind.to_string()).increment(1);
+}
+
+/// Record a transaction skipped because a call of the same shape did not diverge before.
+pub fn record_non_divergent_skip() {
+    counter!("reth_research_non_divergent_skip_total").increment(1);
+}
+
+/// Record an experimental-only failure that is not down to the gas costs.
+pub fn record_non_gas_failure() {
+    counter!("reth_research_non_gas_failure_total").increment(1);
+}
+
+/// Record a block whose summed normal gas does not match the gas used in its header.
+pub fn record_gas_reconciliation_mismatch() {
+    counter!("reth_research_gas_reconciliation_mismatch_total").increment(1);
+}
+
+/// Record operation counts that differ without any flagged divergence.
+pub fn record_unexplained_opcount_diff() {
+    counter!("reth_research_unexplained_opcount_diff_total").increment(1);
+}
+
+/// Record a transaction skipped because its pre-block state is unavailable.
+pub fn record_state_unavailable(block_number: u64) {
+    counter!("reth_research_state_unavailable_total").increment(1);
+    gauge!("reth_research_state_unavailable_last_block").set(block_number as f64);
+}
+
+/// Record a divergence database write retried because the database was busy or locked.
+pub fn record_database_retry() {
+    counter!("reth_research_database_retries_total").increment(1);
+}
+
 /// Record divergence detection time.
 pub fn record_divergence_detection_time(duration_secs: f64) {
     histogram!("reth_research_divergence_detection_seconds").record(duration_secs);
 }
+
+/// Record that divergence recording stopped after reaching the configured total limit.
+pub fn record_recording_stopped() {
+    gauge!("reth_research_recording_stopped").set(1.0);
+}
+
+/// Record whether experimental OOGs are expected by configuration.
+pub fn record_expected_oog_mode(expected: bool) {
+    gauge!("reth_research_expected_oog_mode").set(if expected { 1.0 } else { 0.0 });
+}
+
+/// Record an experimental OOG that was expected because the multiplied normal gas exceeds the
+/// transaction's gas limit.
+pub fn record_expected_oog() {
+    counter!("reth_research_expected_oog_total").increment(1);
+}
+
+/// Record the estimated divergence counts of the top `ranks` contracts, most frequent first.
+///
+/// Labelled by rank rather than address so the number of series stays bounded; the addresses
+/// themselves are available from the ExEx. Ranks without a contract in `counts` are reset to
+/// zero, so contracts that dropped out of the summary do not keep their last count.
+pub fn record_top_contracts(counts: &[u64], ranks: usize) {
+    for rank in 0..ranks {
+        let count = counts.get(rank).copied().unwrap_or_default();
+        gauge!("reth_research_top_contract_divergences", "rank" => (rank + 1).to_string())
+            .set(count as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::counter_value;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    #[test]
+    fn test_record_gas_loop() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        ::metrics::with_local_recorder(&recorder, || {
+            register_metrics();
+            record_gas_loop(GasLoopKind::Monotonic);
+            record_gas_loop(GasLoopKind::Monotonic);
+            record_gas_loop(GasLoopKind::Repeated);
+        });
+
+        let loops = |kind| {
+            counter_value(&snapshotter, "reth_research_gas_loops_total", Some(("kind", kind)))
+        };
+        assert_eq!(loops("monotonic"), 2);
+        assert_eq!(loops("repeated"), 1);
+    }
+
+    #[test]
+    fn test_record_top_contracts_resets_stale_ranks() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        ::metrics::with_local_recorder(&recorder, || {
+            record_top_contracts(&[5, 3, 2], 3);
+            record_top_contracts(&[7], 3);
+        });
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        let rank = |rank: &str| {
+            snapshot.iter().find_map(|(key, _, _, value)| {
+                let key = key.key();
+                let matches = key.name() == "reth_research_top_contract_divergences" &&
+                    key.labels().any(|label| label.key() == "rank" && label.value() == rank);
+                match value {
+                    DebugValue::Gauge(value) if matches => Some(value.into_inner()),
+                    _ => None,
+                }
+            })
+        };
+        assert_eq!(rank("1"), Some(7.0));
+        assert_eq!(rank("2"), Some(0.0));
+        assert_eq!(rank("3"), Some(0.0));
+    }
+}
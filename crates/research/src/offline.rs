@@ -0,0 +1,604 @@
+//! Offline analysis of exported blocks without a running node.
+//!
+//! Blocks are read from a file containing concatenated RLP-encoded blocks, the same format
+//! `reth import` consumes. The state the first block executes against is read from a JSON
+//! file mapping addresses to genesis-style accounts:
+//!
+//! ```json
+//! {
+//!   "0x00000000000000000000000000000000000000ca": { "balance": "0xde0b6b3a7640000" },
+//!   "0x00000000000000000000000000000000000000c0": {
+//!     "balance": "0x0",
+//!     "code": "0x600160005500",
+//!     "storage": { "0x00": "0x01" }
+//!   }
+//! }
+//! ```
+//!
+//! Blocks must be given in order. Each transaction is analyzed against the state left by the
+//! normal executions of the previous ones, or with [`ResearchConfig::sequential_state`] its
+//! experimental execution against the state left by the experimental executions before it in
+//! the block. Withdrawals are credited after each block. Block rewards and system calls
+//! (e.g. beacon root updates) are not applied.
+
+use crate::{
+    analysis::{self, BlockContext, TxContext},
+    config::ResearchConfig,
+    database::BlockSummary,
+    divergence::Divergence,
+    metrics,
+    timing::AnalysisTimings,
+};
+use alloy_genesis::GenesisAccount;
+use alloy_primitives::{Address, U256};
+use alloy_rlp::Decodable;
+use reth_chainspec::ChainSpec;
+use reth_ethereum_primitives::Block;
+use reth_evm_ethereum::EthEvmConfig;
+use reth_primitives_traits::{Block as _, RecoveredBlock};
+use revm::{
+    context_interface::result::ResultAndState,
+    database::{CacheDB, EmptyDB},
+    state::{AccountInfo, Bytecode},
+    DatabaseCommit,
+};
+use std::{collections::BTreeMap, path::Path, sync::Arc, time::Instant};
+use thiserror::Error;
+use tracing::warn;
+
+/// Errors that can occur during offline analysis.
+#[derive(Debug, Error)]
+pub enum OfflineError {
+    /// I/O error
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Invalid state file
+    #[error("Invalid state file: {0}")]
+    State(#[from] serde_json::Error),
+
+    /// A block could not be decoded
+    #[error("Failed to decode block #{index} in file: {source}")]
+    Decode {
+        /// Position of the block in the file
+        index: usize,
+        /// Decoding error
+        source: alloy_rlp::Error,
+    },
+
+    /// Transaction senders could not be recovered
+    #[error("Failed to recover senders for block {0}")]
+    SenderRecovery(u64),
+
+    /// The EVM environment could not be built for a block
+    #[error("Failed to build EVM environment for block {block}: {message}")]
+    EvmEnv {
+        /// Block number
+        block: u64,
+        /// Error message
+        message: String,
+    },
+}
+
+/// Decode a buffer of concatenated RLP-encoded blocks.
+///
+/// Returns one result per block so that a malformed block can be reported and skipped. If a
+/// block's RLP header itself is invalid the remaining data cannot be split into blocks, so
+/// decoding stops after reporting the error.
+pub fn decode_blocks(mut buf: &[u8]) -> Vec<Result<Block, OfflineError>> {
+    let mut blocks = Vec::new();
+
+    while !buf.is_empty() {
+        let index = blocks.len();
+        let start = buf;
+
+        let header = match alloy_rlp::Header::decode(&mut buf) {
+            Ok(header) if header.payload_length <= buf.len() => header,
+            Ok(_) => {
+                blocks.push(Err(OfflineError::Decode {
+                    index,
+                    source: alloy_rlp::Error::InputTooShort,
+                }));
+                break
+            }
+            Err(source) => {
+                blocks.push(Err(OfflineError::Decode { index, source }));
+                break
+            }
+        };
+
+        let item_len = start.len() - buf.len() + header.payload_length;
+        let mut item = &start[..item_len];
+        blocks.push(
+            Block::decode(&mut item).map_err(|source| OfflineError::Decode { index, source }),
+        );
+
+        buf = &buf[header.payload_length..];
+    }
+
+    blocks
+}
+
+/// Read and decode a file of concatenated RLP-encoded blocks.
+pub fn read_blocks(
+    path: impl AsRef<Path>,
+) -> Result<Vec<Result<Block, OfflineError>>, OfflineError> {
+    Ok(decode_blocks(&std::fs::read(path)?))
+}
+
+/// Build an in-memory state from genesis-style accounts.
+pub fn state_from_accounts(accounts: BTreeMap<Address, GenesisAccount>) -> CacheDB<EmptyDB> {
+    let mut state = CacheDB::new(EmptyDB::default());
+
+    for (address, account) in accounts {
+        let code = account.code.filter(|code| !code.is_empty()).map(Bytecode::new_raw);
+        state.insert_account_info(
+            address,
+            AccountInfo {
+                balance: account.balance,
+                nonce: account.nonce.unwrap_or_default(),
+                code_hash: code.as_ref().map(Bytecode::hash_slow).unwrap_or_default(),
+                code,
+            },
+        );
+
+        for (slot, value) in account.storage.unwrap_or_default() {
+            let Ok(()) = state.insert_account_storage(address, slot.into(), value.into());
+        }
+    }
+
+    state
+}
+
+/// Read the pre-state from a JSON file of genesis-style accounts.
+pub fn read_state(path: impl AsRef<Path>) -> Result<CacheDB<EmptyDB>, OfflineError> {
+    let accounts = serde_json::from_slice(&std::fs::read(path)?)?;
+    Ok(state_from_accounts(accounts))
+}
+
+/// Outcome of analyzing a block.
+#[derive(Debug)]
+pub struct BlockAnalysis {
+    /// Number of transactions analyzed and gas used in the block
+    pub summary: BlockSummary,
+
+    /// Divergences found in the block
+    pub divergences: Vec<Divergence>,
+}
+
+/// EVM configuration of the offline analyzer, whose EVMs can run
+/// [`real_gas`](ResearchConfig::real_gas) execution with the `real-gas` feature.
+#[cfg(feature = "real-gas")]
+type OfflineEvmConfig = EthEvmConfig<ChainSpec, crate::real_gas::RealGasEvmFactory>;
+#[cfg(not(feature = "real-gas"))]
+type OfflineEvmConfig = EthEvmConfig;
+
+/// Runs the dual execution analysis over blocks against an in-memory state.
+#[derive(Debug)]
+pub struct OfflineAnalyzer {
+    /// EVM configuration for the chain the blocks belong to
+    evm_config: OfflineEvmConfig,
+
+    /// Research configuration
+    config: ResearchConfig,
+
+    /// State the next transaction executes against
+    state: CacheDB<EmptyDB>,
+
+    /// Counters the analysis time of each transaction is added to, if set
+    timings: Option<Arc<AnalysisTimings>>,
+}
+
+impl OfflineAnalyzer {
+    /// Create an analyzer starting from `state`.
+    pub fn new(
+        chain_spec: Arc<ChainSpec>,
+        config: ResearchConfig,
+        state: CacheDB<EmptyDB>,
+    ) -> Self {
+        #[cfg(feature = "real-gas")]
+        let evm_config =
+            EthEvmConfig::new_with_evm_factory(chain_spec, crate::real_gas::RealGasEvmFactory);
+        #[cfg(not(feature = "real-gas"))]
+        let evm_config = EthEvmConfig::new(chain_spec);
+        Self { evm_config, config, state, timings: None }
+    }
+
+    /// Add the time spent in each phase of analyzing transactions to `timings`.
+    pub fn with_timings(mut self, timings: Arc<AnalysisTimings>) -> Self {
+        self.timings = Some(timings);
+        self
+    }
+
+    /// Analyze every transaction in a block and advance the state past it.
+    pub fn analyze_block(&mut self, block: Block) -> Result<BlockAnalysis, OfflineError> {
+        let block_start = Instant::now();
+        let block_number = block.header.number;
+        let block = RecoveredBlock::try_recover(block.seal_slow())
+            .map_err(|_| OfflineError::SenderRecovery(block_number))?;
+
+        // Empty blocks have nothing to execute and only credit withdrawals
+        let tx_count = block.body().transactions.len();
+        let (divergences, normal_gas_used) =
+            if tx_count == 0 { (Vec::new(), 0) } else { self.analyze_transactions(&block)? };
+
+        for withdrawal in block.body().withdrawals.iter().flatten() {
+            let Ok(account) = self.state.load_account(withdrawal.address);
+            account.info.balance = account.info.balance.saturating_add(withdrawal.amount_wei());
+        }
+
+        let summary = BlockSummary {
+            block_number,
+            analyzed_tx_count: tx_count as u64,
+            header_gas_used: block.header().gas_used,
+            normal_gas_used: Some(normal_gas_used),
+        };
+        if summary.gas_mismatch(self.config.gas_reconciliation_tolerance) {
+            metrics::record_gas_reconciliation_mismatch();
+            warn!(
+                target: "reth::research",
+                block = block_number,
+                header_gas_used = summary.header_gas_used,
+                normal_gas_used,
+                "Summed normal gas of the block's transactions does not match its header"
+            );
+        }
+        metrics::record_block_processed(
+            block_number,
+            tx_count,
+            summary.analyzed_tx_count,
+            block_start.elapsed().as_secs_f64(),
+        );
+
+        Ok(BlockAnalysis { summary, divergences })
+    }
+
+    /// Analyze each transaction in `block`, applying its normal execution to the state.
+    ///
+    /// Returns the divergences found and the gas used by the normal executions.
+    fn analyze_transactions(
+        &mut self,
+        block: &RecoveredBlock<Block>,
+    ) -> Result<(Vec<Divergence>, u64), OfflineError> {
+        let block_number = block.header().number;
+        let mut block_ctx = BlockContext::new(&self.evm_config, &self.config, block.header())
+            .map_err(|e| OfflineError::EvmEnv { block: block_number, message: e.to_string() })?
+            .with_warm_recipients(&self.config, &block.body().transactions);
+        if let Some(timings) = &self.timings {
+            block_ctx = block_ctx.with_timings(timings.clone());
+        }
+
+        // With sequential state the experimental executions advance their own copy of the state
+        let mut experimental_state = self.config.sequential_state.then(|| self.state.clone());
+
+        let (mut divergences, mut normal_gas_used) = (Vec::new(), 0u64);
+        for (index, tx) in block.transactions_recovered().enumerate() {
+            let tx_ctx = TxContext::new(&self.evm_config, &block_ctx, index, tx);
+
+            let (tx_divergences, normal) = analysis::analyze_schedules_with_normal(
+                &self.evm_config,
+                &self.config,
+                &block_ctx,
+                &tx_ctx,
+                &self.state,
+                experimental_state.as_ref().unwrap_or(&self.state),
+            );
+            divergences.extend(tx_divergences);
+
+            // Apply the normal execution so the next transaction sees its effects, executing it
+            // again only if the analysis did not
+            let result = match normal {
+                Some(ResultAndState { result, state }) => {
+                    self.state.commit(state);
+                    Some(result)
+                }
+                None => {
+                    analysis::commit_normal(&self.evm_config, &block_ctx, &tx_ctx, &mut self.state)
+                }
+            };
+            if let Some(result) = result {
+                normal_gas_used = normal_gas_used.saturating_add(result.gas_used());
+            }
+            if let Some(state) = &mut experimental_state {
+                analysis::commit_experimental(
+                    &self.evm_config,
+                    &self.config,
+                    &block_ctx,
+                    &tx_ctx,
+                    state,
+                );
+            }
+        }
+
+        Ok((divergences, normal_gas_used))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::counter_value;
+    use alloy_consensus::{BlockBody, Header, TxLegacy};
+    use alloy_eips::eip4895::Withdrawal;
+    use alloy_primitives::{Bytes, TxKind};
+    use alloy_rlp::Encodable;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use reth_chainspec::ChainSpecBuilder;
+    use reth_ethereum_primitives::{Transaction, TransactionSigned};
+    use reth_primitives_traits::crypto::secp256k1::public_key_to_address;
+    use reth_testing_utils::generators::{self, sign_tx_with_key_pair};
+    use revm::database::DatabaseRef;
+    use std::collections::HashMap;
+
+    const CONTRACT: Address = Address::repeat_byte(0xc0);
+
+    /// PUSH1 1 PUSH1 0 SSTORE STOP: setting the slot runs out of gas at 2x, rewriting it once set
+    /// does not
+    const SSTORE_CODE: &[u8] = &[0x60, 0x01, 0x60, 0x00, 0x55, 0x00];
+
+    fn block(number: u64, transactions: Vec<TransactionSigned>) -> Block {
+        Block {
+            header: Header {
+                number,
+                timestamp: number * 12,
+                gas_limit: 30_000_000,
+                base_fee_per_gas: Some(1_000_000_000),
+                beneficiary: Address::repeat_byte(0xbe),
+                ..Default::default()
+            },
+            body: BlockBody { transactions, ommers: vec![], withdrawals: None },
+        }
+    }
+
+    fn chain_spec() -> Arc<ChainSpec> {
+        Arc::new(ChainSpecBuilder::mainnet().shanghai_activated().build())
+    }
+
+    /// Genesis accounts of a funded sender and of `contracts` with their code, and a signer of
+    /// legacy calls from that sender, taking the nonce, recipient and gas limit.
+    fn fixture(
+        contracts: impl IntoIterator<Item = (Address, Bytes)>,
+    ) -> (BTreeMap<Address, GenesisAccount>, impl Fn(u64, Address, u64) -> TransactionSigned) {
+        let key_pair = generators::generate_key(&mut generators::rng());
+        let sender = public_key_to_address(key_pair.public_key());
+
+        let mut accounts = BTreeMap::from([(
+            sender,
+            GenesisAccount { balance: U256::from(10u128.pow(18)), ..Default::default() },
+        )]);
+        accounts.extend(contracts.into_iter().map(|(address, code)| {
+            (address, GenesisAccount { code: Some(code), ..Default::default() })
+        }));
+
+        let tx = move |nonce, to, gas_limit| {
+            sign_tx_with_key_pair(
+                key_pair,
+                Transaction::Legacy(TxLegacy {
+                    chain_id: Some(1),
+                    nonce,
+                    gas_price: 2_000_000_000,
+                    gas_limit,
+                    to: TxKind::Call(to),
+                    ..Default::default()
+                }),
+            )
+        };
+        (accounts, tx)
+    }
+
+    #[test]
+    fn test_analyze_encoded_blocks() {
+        // Setting the slot fits the gas limit only at normal cost
+        let (accounts, tx) = fixture([(CONTRACT, Bytes::from_static(SSTORE_CODE))]);
+
+        let mut file = Vec::new();
+        block(1, vec![tx(0, CONTRACT, 50_000)]).encode(&mut file);
+        block(2, vec![tx(1, CONTRACT, 50_000)]).encode(&mut file);
+        // Trailing garbage that is not a valid block
+        file.extend_from_slice(&[0xc1, 0x80]);
+
+        let blocks = decode_blocks(&file);
+        assert_eq!(blocks.len(), 3);
+        assert!(matches!(blocks[2], Err(OfflineError::Decode { index: 2, .. })));
+
+        let config = ResearchConfig::builder().with_gas_multiplier(2).build().unwrap();
+        let timings = Arc::new(AnalysisTimings::default());
+        let mut analyzer = OfflineAnalyzer::new(chain_spec(), config, state_from_accounts(accounts))
+            .with_timings(timings.clone());
+
+        let mut divergences = Vec::new();
+        let start = std::time::Instant::now();
+        for block in blocks.into_iter().flatten() {
+            divergences.extend(analyzer.analyze_block(block).unwrap().divergences);
+        }
+        let elapsed = start.elapsed();
+
+        // Both transactions diverge: the second one only executes if the first block's nonce
+        // increment was applied
+        assert_eq!(divergences.len(), 2);
+        assert_eq!(divergences[0].block_number, 1);
+        assert_eq!(divergences[1].block_number, 2);
+        // The first SSTORE runs out of gas at 2x; the second only rewrites the now-set slot
+        assert!(divergences[0].oog_info.is_some());
+        assert!(divergences[1].oog_info.is_none());
+
+        // The phases account for all of the analysis time, which is part of the wall time
+        let breakdown = timings.snapshot();
+        assert_eq!(breakdown.transactions, 2);
+        let phases =
+            breakdown.normal_execution + breakdown.experimental_execution + breakdown.comparison;
+        assert!(phases.abs_diff(breakdown.total) < std::time::Duration::from_micros(1));
+        assert!(breakdown.total <= elapsed);
+    }
+
+    #[test]
+    fn test_sequential_state() {
+        let (accounts, tx) = fixture([(CONTRACT, Bytes::from_static(SSTORE_CODE))]);
+
+        let chain_spec = chain_spec();
+        let analyze = |sequential| {
+            let config = ResearchConfig::builder()
+                .with_gas_multiplier(2)
+                .with_sequential_state(sequential)
+                .build()
+                .unwrap();
+            let mut analyzer = OfflineAnalyzer::new(
+                chain_spec.clone(),
+                config,
+                state_from_accounts(accounts.clone()),
+            );
+            let transactions = vec![tx(0, CONTRACT, 50_000), tx(1, CONTRACT, 50_000)];
+            analyzer.analyze_block(block(1, transactions)).unwrap().divergences
+        };
+        let second_oogs = |divergences: &[Divergence]| {
+            divergences
+                .iter()
+                .any(|divergence| divergence.tx_index == 1 && divergence.oog_info.is_some())
+        };
+
+        // In isolation the second transaction finds the slot set by the first
+        let independent = analyze(false);
+        assert!(independent[0].oog_info.is_some());
+        assert!(!second_oogs(&independent));
+
+        // The first transaction never set the slot in experimental execution, so the second
+        // runs out of gas setting it as well
+        let sequential = analyze(true);
+        assert!(sequential[0].oog_info.is_some());
+        assert!(second_oogs(&sequential));
+    }
+
+    #[test]
+    fn test_self_test_block_has_no_divergences() {
+        // The SSTORE called directly and through a proxy forwarding all gas
+        let proxy = Address::repeat_byte(0xc1);
+        let (accounts, tx) = fixture([
+            (CONTRACT, Bytes::from_static(SSTORE_CODE)),
+            (proxy, crate::test_utils::call_code(CONTRACT).into()),
+        ]);
+        let test_block = block(1, vec![tx(0, CONTRACT, 50_000), tx(1, proxy, 100_000)]);
+
+        let chain_spec = chain_spec();
+        let analyze = |config: ResearchConfig| {
+            let state = state_from_accounts(accounts.clone());
+            let mut analyzer = OfflineAnalyzer::new(chain_spec.clone(), config, state);
+            analyzer.analyze_block(test_block.clone()).unwrap().divergences
+        };
+
+        // The block diverges under a real schedule
+        let config = ResearchConfig::builder().with_gas_multiplier(2).build().unwrap();
+        assert!(!analyze(config.clone()).is_empty());
+
+        // At 1x with the inspector only, nothing may diverge
+        let divergences = analyze(config.self_test_config());
+        assert!(divergences.is_empty(), "spurious divergences: {divergences:?}");
+    }
+
+    #[cfg(feature = "real-gas")]
+    #[test]
+    fn test_real_gas_execution() {
+        let (accounts, tx) = fixture([(CONTRACT, Bytes::from_static(SSTORE_CODE))]);
+        let analyze = |real_gas| {
+            let config = ResearchConfig::builder()
+                .with_gas_multiplier(2)
+                .with_real_gas(real_gas)
+                .build()
+                .unwrap();
+            let mut analyzer =
+                OfflineAnalyzer::new(chain_spec(), config, state_from_accounts(accounts.clone()));
+            analyzer.analyze_block(block(1, vec![tx(0, CONTRACT, 50_000)])).unwrap().divergences
+        };
+
+        // Setting the slot runs out of gas at 2x whether the interpreter charges the costs or the
+        // inspector simulates them, at the same opcode
+        let simulated = analyze(false);
+        let real = analyze(true);
+        assert_eq!(real.len(), 1);
+        let (real_oog, simulated_oog) =
+            (real[0].oog_info.as_ref().unwrap(), simulated[0].oog_info.as_ref().unwrap());
+        assert_eq!(real_oog.opcode, simulated_oog.opcode);
+        // The interpreter halts at the OOG, burning the whole gas limit
+        assert_eq!(real[0].gas_analysis.experimental_gas_used, 50_000);
+    }
+
+    #[test]
+    fn test_gas_reconciliation_mismatch() {
+        let (accounts, tx) = fixture([]);
+        let transfer = |nonce| tx(nonce, Address::repeat_byte(0xaa), 21_000);
+
+        let mut analyzer = OfflineAnalyzer::new(
+            chain_spec(),
+            ResearchConfig::default(),
+            state_from_accounts(accounts),
+        );
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        ::metrics::with_local_recorder(&recorder, || {
+            let mut matching = block(1, vec![transfer(0)]);
+            matching.header.gas_used = 21_000;
+            let summary = analyzer.analyze_block(matching).unwrap().summary;
+            assert_eq!(summary.normal_gas_used, Some(21_000));
+            assert!(!summary.gas_mismatch(0));
+
+            // A header claiming more gas than the transactions used, unless within tolerance
+            let mut mismatched = block(2, vec![transfer(1)]);
+            mismatched.header.gas_used = 42_000;
+            let summary = analyzer.analyze_block(mismatched).unwrap().summary;
+            assert!(summary.gas_mismatch(0));
+            assert!(!summary.gas_mismatch(21_000));
+        });
+
+        let mismatches =
+            counter_value(&snapshotter, "reth_research_gas_reconciliation_mismatch_total", None);
+        assert_eq!(mismatches, 1);
+    }
+
+    #[test]
+    fn test_analyze_empty_block() {
+        let recipient = Address::repeat_byte(0xaa);
+        let mut empty = block(7, vec![]);
+        empty.body.withdrawals = Some(
+            vec![Withdrawal { index: 0, validator_index: 0, address: recipient, amount: 1 }].into(),
+        );
+
+        let timings = Arc::new(AnalysisTimings::default());
+        let mut analyzer =
+            OfflineAnalyzer::new(chain_spec(), ResearchConfig::default(), CacheDB::default())
+                .with_timings(timings.clone());
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let analysis =
+            ::metrics::with_local_recorder(&recorder, || analyzer.analyze_block(empty)).unwrap();
+
+        assert_eq!(
+            analysis.summary,
+            BlockSummary {
+                block_number: 7,
+                analyzed_tx_count: 0,
+                header_gas_used: 0,
+                normal_gas_used: Some(0)
+            }
+        );
+        assert!(analysis.divergences.is_empty());
+        assert_eq!(timings.snapshot().transactions, 0);
+        // Withdrawals are still credited, in gwei
+        assert_eq!(
+            analyzer.state.basic_ref(recipient).unwrap().unwrap().balance,
+            U256::from(1_000_000_000)
+        );
+
+        // The block is counted, without any per-transaction time
+        let metrics: HashMap<_, _> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| (key.key().name().to_string(), value))
+            .collect();
+        assert_eq!(metrics["reth_research_blocks_processed_total"], DebugValue::Counter(1));
+        assert_eq!(metrics["reth_research_transactions_processed_total"], DebugValue::Counter(0));
+        assert_eq!(metrics["reth_research_transactions_analyzed_total"], DebugValue::Counter(0));
+        assert!(!metrics.contains_key("reth_research_transaction_analysis_seconds"));
+    }
+}
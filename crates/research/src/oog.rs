@@ -0,0 +1,105 @@
+//! Classification of out-of-gas events in experimental execution.
+//!
+//! The inspector hands an [`OogContext`] describing the failing step to the configured
+//! [`OogClassifier`]. [`DefaultOogClassifier`] is used unless another classifier is set with
+//! [`ResearchConfigBuilder::with_oog_classifier`](crate::ResearchConfigBuilder::with_oog_classifier).
+
+use crate::divergence::{GasSample, OogPattern};
+use std::fmt::Debug;
+
+/// Memory size in words above which the quadratic term of the memory cost (`words² / 512`)
+/// exceeds the linear term (`3 * words`).
+pub const QUADRATIC_MEMORY_WORDS: usize = 3 * 512;
+
+/// What the inspector knows about the step that ran out of gas.
+#[derive(Debug, Clone, Copy)]
+pub struct OogContext<'a> {
+    /// Opcode that ran out of gas
+    pub opcode: u8,
+
+    /// Most recent gas samples leading up to the OOG, oldest first
+    pub gas_trajectory: &'a [GasSample],
+
+    /// Whether repeated GAS opcode usage at the same PC suggests a gas-dependent loop
+    pub gas_loop_detected: bool,
+
+    /// Size of the frame's memory in words after the opcode expanded it
+    pub memory_words: usize,
+}
+
+/// Assigns an [`OogPattern`] to an out-of-gas event.
+pub trait OogClassifier: Debug + Send + Sync {
+    /// Classify the OOG described by `ctx`.
+    fn classify(&self, ctx: &OogContext<'_>) -> OogPattern;
+}
+
+/// Classifies by the failing opcode, falling back to loop evidence.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultOogClassifier;
+
+impl OogClassifier for DefaultOogClassifier {
+    fn classify(&self, ctx: &OogContext<'_>) -> OogPattern {
+        match ctx.opcode {
+            // Storage operations: SLOAD, SSTORE
+            0x54 | 0x55 => OogPattern::StorageHeavy,
+
+            // Call operations: CALL, CALLCODE, DELEGATECALL, STATICCALL
+            0xF1 | 0xF2 | 0xF4 | 0xFA => OogPattern::CallChain,
+
+            // Memory operations: MLOAD, MSTORE, MSTORE8
+            0x51..=0x53 if ctx.memory_words > QUADRATIC_MEMORY_WORDS => OogPattern::Quadratic,
+            0x51..=0x53 => OogPattern::MemoryExpansion,
+
+            // If we detected a gas loop pattern, assume it's a loop
+            _ if ctx.gas_loop_detected => OogPattern::Loop,
+
+            _ => OogPattern::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(opcode: u8, gas_loop_detected: bool, memory_words: usize) -> OogPattern {
+        DefaultOogClassifier.classify(&OogContext {
+            opcode,
+            gas_trajectory: &[],
+            gas_loop_detected,
+            memory_words,
+        })
+    }
+
+    #[test]
+    fn test_default_classifier_storage() {
+        assert_eq!(classify(0x54, false, 0), OogPattern::StorageHeavy);
+        assert_eq!(classify(0x55, true, 0), OogPattern::StorageHeavy);
+    }
+
+    #[test]
+    fn test_default_classifier_calls() {
+        for opcode in [0xF1, 0xF2, 0xF4, 0xFA] {
+            assert_eq!(classify(opcode, false, 0), OogPattern::CallChain);
+        }
+    }
+
+    #[test]
+    fn test_default_classifier_memory() {
+        for opcode in [0x51, 0x52, 0x53] {
+            assert_eq!(classify(opcode, false, 1), OogPattern::MemoryExpansion);
+            assert_eq!(
+                classify(opcode, false, QUADRATIC_MEMORY_WORDS),
+                OogPattern::MemoryExpansion
+            );
+            assert_eq!(classify(opcode, false, QUADRATIC_MEMORY_WORDS + 1), OogPattern::Quadratic);
+        }
+    }
+
+    #[test]
+    fn test_default_classifier_loop_and_unknown() {
+        // ADD
+        assert_eq!(classify(0x01, true, 0), OogPattern::Loop);
+        assert_eq!(classify(0x01, false, 0), OogPattern::Unknown);
+    }
+}
@@ -0,0 +1,331 @@
+//! PostgreSQL [`DivergenceStore`] for aggregating research results from many nodes.
+//!
+//! Divergences are stored as their JSON encoding next to the columns the store queries by.
+//! Call trees, event logs and CREATE2 deployments are kept inside the JSON rather than in
+//! tables of their own.
+
+use crate::{
+    config::ResearchConfig,
+    database::{BlockSummary, Checkpoint, DatabaseError, DivergenceRecord},
+    divergence::{Divergence, DivergenceType},
+    executor::ResearchStats,
+    store::DivergenceStore,
+};
+use alloy_primitives::B256;
+use std::future::Future;
+use tokio::runtime::{Handle, Runtime};
+use tokio_postgres::{Client, NoTls};
+use tracing::warn;
+
+/// Main divergences table
+const DIVERGENCES_TABLE: &str = "CREATE TABLE IF NOT EXISTS divergences (
+    id BIGSERIAL PRIMARY KEY,
+    divergence_uid BYTEA NOT NULL UNIQUE,
+    block_number BIGINT NOT NULL,
+    tx_index BIGINT NOT NULL,
+    tx_hash BYTEA NOT NULL,
+    divergence_types TEXT[] NOT NULL,
+    divergence_json JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+/// Block summaries table, one row per analyzed block
+const BLOCK_SUMMARIES_TABLE: &str = "CREATE TABLE IF NOT EXISTS block_summaries (
+    block_number BIGINT PRIMARY KEY,
+    analyzed_tx_count BIGINT NOT NULL,
+    header_gas_used BIGINT,
+    normal_gas_used BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+/// Checkpoint table, holding a single row for the last finished block
+const CHECKPOINT_TABLE: &str = "CREATE TABLE IF NOT EXISTS checkpoint (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    block_number BIGINT NOT NULL,
+    config_hash BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+/// Runs table, appended to by every run so results can be traced back to their settings
+const RUNS_TABLE: &str = "CREATE TABLE IF NOT EXISTS runs (
+    id BIGSERIAL PRIMARY KEY,
+    config_hash BYTEA NOT NULL,
+    settings TEXT NOT NULL,
+    start_block BIGINT NOT NULL,
+    end_block BIGINT,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ended_at TIMESTAMPTZ,
+    blocks_processed BIGINT,
+    divergences_found BIGINT,
+    divergences_recorded BIGINT
+)";
+
+/// Statements creating the PostgreSQL schema, each table followed by its indexes.
+pub const SCHEMA: &[&str] = &[
+    DIVERGENCES_TABLE,
+    "CREATE INDEX IF NOT EXISTS idx_divergences_block ON divergences(block_number)",
+    "CREATE INDEX IF NOT EXISTS idx_divergences_types ON divergences USING GIN (divergence_types)",
+    BLOCK_SUMMARIES_TABLE,
+    CHECKPOINT_TABLE,
+    RUNS_TABLE,
+];
+
+/// Divergence store backed by a PostgreSQL database.
+///
+/// The store drives its connection on a runtime of its own, so its blocking methods can be
+/// called from any thread. Inside the node's multi-threaded runtime they block in place.
+#[derive(Debug)]
+pub struct PostgresStore {
+    /// Runtime driving the connection, taken when the store is dropped
+    runtime: Option<Runtime>,
+
+    /// Database client
+    client: Client,
+}
+
+impl PostgresStore {
+    /// Connect to the database at `url` (e.g. `postgres://user@host/research`) and create the
+    /// schema if it does not exist yet.
+    pub fn connect(url: &str) -> Result<Self, DatabaseError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("research-postgres")
+            .enable_all()
+            .build()?;
+
+        let client = block_on(&runtime, async {
+            let (client, connection) = tokio_postgres::connect(url, NoTls).await?;
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    warn!(target: "reth::research", error = %e, "PostgreSQL connection failed");
+                }
+            });
+            Ok::<_, DatabaseError>(client)
+        })?;
+
+        let store = Self { runtime: Some(runtime), client };
+        store.block_on(async {
+            for statement in SCHEMA {
+                store.client.batch_execute(statement).await?;
+            }
+            Ok::<_, DatabaseError>(())
+        })?;
+        Ok(store)
+    }
+
+    /// Run `future` to completion on the store's runtime.
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        block_on(self.runtime.as_ref().expect("runtime is only taken on drop"), future)
+    }
+}
+
+impl DivergenceStore for PostgresStore {
+    fn record_divergence(&self, divergence: &Divergence) -> Result<i64, DatabaseError> {
+        let uid = divergence.uid();
+        let types: Vec<String> = divergence.sorted_types().iter().map(|t| t.to_string()).collect();
+        let json = divergence.to_json()?;
+        self.block_on(async {
+            let inserted = self
+                .client
+                .query_opt(
+                    "INSERT INTO divergences (
+                        divergence_uid, block_number, tx_index, tx_hash, divergence_types,
+                        divergence_json
+                    ) VALUES ($1, $2, $3, $4, $5, $6::text::jsonb)
+                    ON CONFLICT (divergence_uid) DO NOTHING
+                    RETURNING id",
+                    &[
+                        &uid.as_slice(),
+                        &(divergence.block_number as i64),
+                        &(divergence.tx_index as i64),
+                        &divergence.tx_hash.as_slice(),
+                        &types,
+                        &json,
+                    ],
+                )
+                .await?;
+            let row = match inserted {
+                Some(row) => row,
+                None => {
+                    self.client
+                        .query_one(
+                            "SELECT id FROM divergences WHERE divergence_uid = $1",
+                            &[&uid.as_slice()],
+                        )
+                        .await?
+                }
+            };
+            Ok(row.get(0))
+        })
+    }
+
+    fn record_block_summary(&self, summary: &BlockSummary) -> Result<(), DatabaseError> {
+        self.block_on(async {
+            self.client
+                .execute(
+                    "INSERT INTO block_summaries (
+                        block_number, analyzed_tx_count, header_gas_used, normal_gas_used
+                    ) VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (block_number) DO UPDATE SET
+                        analyzed_tx_count = EXCLUDED.analyzed_tx_count,
+                        header_gas_used = EXCLUDED.header_gas_used,
+                        normal_gas_used = EXCLUDED.normal_gas_used",
+                    &[
+                        &(summary.block_number as i64),
+                        &(summary.analyzed_tx_count as i64),
+                        &(summary.header_gas_used as i64),
+                        &summary.normal_gas_used.map(|gas| gas as i64),
+                    ],
+                )
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn record_checkpoint(&self, checkpoint: &Checkpoint) -> Result<(), DatabaseError> {
+        self.block_on(async {
+            self.client
+                .execute(
+                    "INSERT INTO checkpoint (id, block_number, config_hash) VALUES (0, $1, $2)
+                    ON CONFLICT (id) DO UPDATE SET
+                        block_number = EXCLUDED.block_number,
+                        config_hash = EXCLUDED.config_hash,
+                        created_at = now()",
+                    &[&(checkpoint.block_number as i64), &checkpoint.config_hash.as_slice()],
+                )
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn last_checkpoint(&self) -> Result<Option<Checkpoint>, DatabaseError> {
+        self.block_on(async {
+            let row = self
+                .client
+                .query_opt("SELECT block_number, config_hash FROM checkpoint WHERE id = 0", &[])
+                .await?;
+            Ok(row.map(|row| Checkpoint {
+                block_number: row.get::<_, i64>(0) as u64,
+                config_hash: B256::from_slice(row.get(1)),
+            }))
+        })
+    }
+
+    fn record_run_start(&self, config: &ResearchConfig) -> Result<i64, DatabaseError> {
+        self.block_on(async {
+            let row = self
+                .client
+                .query_one(
+                    "INSERT INTO runs (config_hash, settings, start_block, end_block)
+                    VALUES ($1, $2, $3, $4)
+                    RETURNING id",
+                    &[
+                        &config.config_hash().as_slice(),
+                        &config.settings(),
+                        &(config.start_block as i64),
+                        &config.end_block.map(|block| block as i64),
+                    ],
+                )
+                .await?;
+            Ok(row.get(0))
+        })
+    }
+
+    fn record_run_end(&self, run_id: i64, stats: &ResearchStats) -> Result<(), DatabaseError> {
+        self.block_on(async {
+            self.client
+                .execute(
+                    "UPDATE runs SET
+                        ended_at = now(),
+                        blocks_processed = $2,
+                        divergences_found = $3,
+                        divergences_recorded = $4
+                    WHERE id = $1 AND ended_at IS NULL",
+                    &[
+                        &run_id,
+                        &(stats.blocks_processed as i64),
+                        &(stats.divergences_found as i64),
+                        &(stats.divergences_recorded as i64),
+                    ],
+                )
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn count_divergences(&self, from_block: u64, to_block: u64) -> Result<u64, DatabaseError> {
+        self.block_on(async {
+            let row = self
+                .client
+                .query_one(
+                    "SELECT COUNT(*) FROM divergences
+                    WHERE block_number BETWEEN $1 AND $2 AND
+                        NOT COALESCE((divergence_json->>'near_miss')::boolean, false) AND
+                        COALESCE(divergence_json->>'comparison', '') <> 'a_b'",
+                    &[&(from_block as i64), &(to_block as i64)],
+                )
+                .await?;
+            Ok(row.get::<_, i64>(0) as u64)
+        })
+    }
+
+    fn count_by_type(&self, dtype: DivergenceType) -> Result<u64, DatabaseError> {
+        self.block_on(async {
+            let row = self
+                .client
+                .query_one(
+                    "SELECT COUNT(*) FROM divergences WHERE $1 = ANY(divergence_types)",
+                    &[&dtype.to_string()],
+                )
+                .await?;
+            Ok(row.get::<_, i64>(0) as u64)
+        })
+    }
+
+    fn max_id(&self) -> Result<i64, DatabaseError> {
+        self.block_on(async {
+            let row =
+                self.client.query_one("SELECT COALESCE(MAX(id), 0) FROM divergences", &[]).await?;
+            Ok(row.get(0))
+        })
+    }
+
+    fn since_id(&self, last_id: i64, limit: usize) -> Result<Vec<DivergenceRecord>, DatabaseError> {
+        self.block_on(async {
+            let rows = self
+                .client
+                .query(
+                    "SELECT id, divergence_json::text FROM divergences
+                    WHERE id > $1 ORDER BY id LIMIT $2",
+                    &[&last_id, &(limit as i64)],
+                )
+                .await?;
+            rows.iter()
+                .map(|row| {
+                    Ok(DivergenceRecord {
+                        id: row.get(0),
+                        divergence: Divergence::from_json(row.get(1))?,
+                    })
+                })
+                .collect()
+        })
+    }
+}
+
+impl Drop for PostgresStore {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks, which is not allowed inside the node's runtime
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Run `future` to completion on `runtime`, blocking in place if called from a worker of
+/// another multi-threaded runtime.
+fn block_on<F: Future>(runtime: &Runtime, future: F) -> F::Output {
+    match Handle::try_current() {
+        Ok(_) => tokio::task::block_in_place(|| runtime.block_on(future)),
+        Err(_) => runtime.block_on(future),
+    }
+}
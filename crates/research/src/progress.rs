@@ -0,0 +1,174 @@
+//! Periodic progress reporting for long research runs.
+//!
+//! [`RateWindow`] estimates how fast a running total grows over a rolling window of timed
+//! samples. [`ProgressReporter`] uses one to turn the blocks processed so far into a progress
+//! report once every [`ResearchConfig::progress_interval`], rather than on every block.
+//!
+//! [`ResearchConfig::progress_interval`]: crate::config::ResearchConfig::progress_interval
+
+use crate::executor::ResearchStats;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Number of report intervals the block rate of a [`ProgressReporter`] is averaged over.
+pub const PROGRESS_WINDOW_INTERVALS: u32 = 5;
+
+/// Rate at which a running total grows, estimated over a rolling window of samples.
+#[derive(Debug, Clone)]
+pub struct RateWindow {
+    /// Time span the rate is estimated over
+    window: Duration,
+    /// Samples of the running total, oldest first
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RateWindow {
+    /// Create an estimator over the samples of the last `window`.
+    pub const fn new(window: Duration) -> Self {
+        Self { window, samples: VecDeque::new() }
+    }
+
+    /// Record that the running total was `total` at `at`.
+    ///
+    /// Samples that fell out of the window are dropped, except the newest of them so the
+    /// estimate spans the whole window.
+    pub fn record(&mut self, at: Instant, total: u64) {
+        self.samples.push_back((at, total));
+        while self
+            .samples
+            .get(1)
+            .is_some_and(|(time, _)| at.saturating_duration_since(*time) >= self.window)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Growth of the total per second across the window, or `None` until samples span some
+    /// time.
+    pub fn per_second(&self) -> Option<f64> {
+        let ((start, first), (end, last)) = (self.samples.front()?, self.samples.back()?);
+        let elapsed = end.saturating_duration_since(*start).as_secs_f64();
+        (elapsed > 0.0).then(|| last.saturating_sub(*first) as f64 / elapsed)
+    }
+}
+
+/// Aggregate progress of a research run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Last block processed
+    pub block_number: u64,
+    /// Blocks processed so far
+    pub blocks_processed: u64,
+    /// Divergences found so far
+    pub divergences_found: u64,
+    /// Blocks processed per second over the recent window
+    pub blocks_per_second: Option<f64>,
+    /// Estimated time until the end block is reached, if one is set
+    pub eta: Option<Duration>,
+}
+
+/// Produces a [`Progress`] report at most once per interval.
+#[derive(Debug, Clone)]
+pub struct ProgressReporter {
+    /// Minimum time between reports
+    interval: Duration,
+    /// Last block of the run, if it has one
+    end_block: Option<u64>,
+    /// Time of the last report, or of the start
+    last_report: Instant,
+    /// Rate at which blocks are processed
+    blocks: RateWindow,
+}
+
+impl ProgressReporter {
+    /// Create a reporter for a run started at `now` that reports every `interval`, towards
+    /// `end_block` if set.
+    pub fn new(interval: Duration, end_block: Option<u64>, now: Instant) -> Self {
+        let mut blocks = RateWindow::new(interval * PROGRESS_WINDOW_INTERVALS);
+        blocks.record(now, 0);
+        Self { interval, end_block, last_report: now, blocks }
+    }
+
+    /// Record the run's statistics after processing `block_number` at `now`, and return a
+    /// report if one is due.
+    pub fn record(
+        &mut self,
+        now: Instant,
+        block_number: u64,
+        stats: &ResearchStats,
+    ) -> Option<Progress> {
+        self.blocks.record(now, stats.blocks_processed);
+        if now.saturating_duration_since(self.last_report) < self.interval {
+            return None
+        }
+        self.last_report = now;
+
+        let blocks_per_second = self.blocks.per_second();
+        let eta = self.end_block.zip(blocks_per_second).and_then(|(end_block, rate)| {
+            let remaining = end_block.saturating_sub(block_number);
+            Duration::try_from_secs_f64(remaining as f64 / rate).ok()
+        });
+        Some(Progress {
+            block_number,
+            blocks_processed: stats.blocks_processed,
+            divergences_found: stats.divergences_found,
+            blocks_per_second,
+            eta,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_window() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut rate = RateWindow::new(Duration::from_secs(10));
+        assert_eq!(rate.per_second(), None);
+
+        // A single sample spans no time
+        rate.record(at(0), 0);
+        assert_eq!(rate.per_second(), None);
+
+        // 10 blocks per second
+        for secs in 1..=10 {
+            rate.record(at(secs), secs * 10);
+        }
+        assert_eq!(rate.per_second(), Some(10.0));
+
+        // After slowing to 2 blocks per second for a full window the old rate is forgotten
+        for secs in 11..=20 {
+            rate.record(at(secs), 100 + (secs - 10) * 2);
+        }
+        assert_eq!(rate.per_second(), Some(2.0));
+    }
+
+    #[test]
+    fn test_progress_reporter() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let stats = |blocks_processed| ResearchStats {
+            blocks_processed,
+            divergences_found: 3,
+            divergences_recorded: 3,
+        };
+        let mut reporter = ProgressReporter::new(Duration::from_secs(10), Some(1_000), start);
+
+        // Nothing is reported before the interval has passed
+        assert_eq!(reporter.record(at(5), 50, &stats(25)), None);
+
+        // 5 blocks per second with 900 blocks to go
+        let progress = reporter.record(at(10), 100, &stats(50)).unwrap();
+        assert_eq!(progress.blocks_per_second, Some(5.0));
+        assert_eq!(progress.eta, Some(Duration::from_secs(180)));
+        assert_eq!(progress.divergences_found, 3);
+
+        // The next report waits for another interval
+        assert_eq!(reporter.record(at(15), 125, &stats(75)), None);
+    }
+}
@@ -0,0 +1,214 @@
+//! Helpers for fetching historical state for research replays and for tracking which blocks
+//! were replayed.
+
+use crate::{config::ResearchConfig, metrics};
+use reth_storage_errors::provider::{ProviderError, ProviderResult};
+use thiserror::Error;
+use tracing::debug;
+
+/// The configured block range is not fully available on this node.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BlockRangeError {
+    /// The start block precedes the earliest block the node still has
+    #[error("start block {start} precedes the earliest available block {earliest}")]
+    StartBeforeEarliest {
+        /// Configured start block
+        start: u64,
+        /// Earliest block available on the node
+        earliest: u64,
+    },
+
+    /// The end block is beyond the node's current tip
+    #[error("end block {end} is beyond the current tip {tip}")]
+    EndAfterTip {
+        /// Configured end block
+        end: u64,
+        /// Current tip of the node
+        tip: u64,
+    },
+}
+
+/// Check the configured block range against the blocks available on the node.
+///
+/// A start block before `earliest` means its state was pruned, so those blocks would be
+/// skipped. An end block beyond `tip` is only reached once the node syncs that far.
+pub const fn check_block_range(
+    earliest: u64,
+    tip: u64,
+    config: &ResearchConfig,
+) -> Result<(), BlockRangeError> {
+    if config.start_block < earliest {
+        return Err(BlockRangeError::StartBeforeEarliest { start: config.start_block, earliest })
+    }
+    if let Some(end) = config.end_block {
+        if end > tip {
+            return Err(BlockRangeError::EndAfterTip { end, tip })
+        }
+    }
+    Ok(())
+}
+
+/// Whether a provider error means the requested state is not available on this node (e.g. it
+/// was pruned), as opposed to a genuine provider failure.
+pub const fn is_state_unavailable(error: &ProviderError) -> bool {
+    matches!(
+        error,
+        ProviderError::StateAtBlockPruned(_) |
+            ProviderError::StateForNumberNotFound(_) |
+            ProviderError::StateForHashNotFound(_)
+    )
+}
+
+/// Resolve a per-transaction state lookup, skipping the transaction if the state is unavailable.
+///
+/// Returns `Ok(None)` and records `reth_research_state_unavailable_total` when the state is
+/// pruned or missing so the caller can continue with the next transaction. Genuine provider
+/// errors are returned unchanged.
+pub fn state_or_skip<S>(
+    block_number: u64,
+    tx_index: usize,
+    result: ProviderResult<S>,
+) -> ProviderResult<Option<S>> {
+    match result {
+        Ok(state) => Ok(Some(state)),
+        Err(error) if is_state_unavailable(&error) => {
+            debug!(
+                target: "reth::research",
+                block = block_number,
+                tx_idx = tx_index,
+                %error,
+                "State unavailable, skipping transaction"
+            );
+            metrics::record_state_unavailable(block_number);
+            Ok(None)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Height of the last block analyzed in this process, guarding against analyzing a block twice
+/// when chain notifications overlap, e.g. after resuming from a checkpoint.
+///
+/// This is kept in memory only, independent of the database checkpoint, so it also avoids the
+/// duplicate work for runs without a database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AnalyzedBlocks {
+    /// Last block analyzed, if any
+    last: Option<u64>,
+}
+
+impl AnalyzedBlocks {
+    /// Create a guard that has seen no block yet.
+    pub const fn new() -> Self {
+        Self { last: None }
+    }
+
+    /// The last block analyzed, if any.
+    pub const fn last(&self) -> Option<u64> {
+        self.last
+    }
+
+    /// Whether `block_number` is above the last block analyzed, in which case it becomes the last
+    /// block analyzed.
+    pub fn claim(&mut self, block_number: u64) -> bool {
+        if self.last.is_some_and(|last| block_number <= last) {
+            return false
+        }
+        self.last = Some(block_number);
+        true
+    }
+
+    /// Forget the blocks from `first_reverted` on, so the blocks replacing them after a reorg or
+    /// revert are analyzed again.
+    pub fn revert(&mut self, first_reverted: u64) {
+        if self.last.is_some_and(|last| last >= first_reverted) {
+            self.last = first_reverted.checked_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unavailable_state_skips_only_affected_transactions() {
+        // Provider that fails for the second transaction's lookup only
+        let lookups = [Ok(0), Err(ProviderError::StateAtBlockPruned(9)), Ok(2)];
+
+        let mut analyzed = Vec::new();
+        for (tx_index, lookup) in lookups.into_iter().enumerate() {
+            let Some(state) = state_or_skip(10, tx_index, lookup).unwrap() else { continue };
+            analyzed.push(state);
+        }
+
+        assert_eq!(analyzed, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_block_range_within_available_blocks() {
+        let config =
+            ResearchConfig { start_block: 100, end_block: Some(200), ..Default::default() };
+        assert_eq!(check_block_range(100, 200, &config), Ok(()));
+
+        // An open-ended range is never beyond the tip
+        let config = ResearchConfig { start_block: 100, ..Default::default() };
+        assert_eq!(check_block_range(50, 60, &config), Ok(()));
+    }
+
+    #[test]
+    fn test_block_range_start_pruned() {
+        let config = ResearchConfig { start_block: 10, end_block: Some(200), ..Default::default() };
+        assert_eq!(
+            check_block_range(50, 300, &config),
+            Err(BlockRangeError::StartBeforeEarliest { start: 10, earliest: 50 })
+        );
+    }
+
+    #[test]
+    fn test_block_range_end_beyond_tip() {
+        let config =
+            ResearchConfig { start_block: 100, end_block: Some(500), ..Default::default() };
+        assert_eq!(
+            check_block_range(0, 300, &config),
+            Err(BlockRangeError::EndAfterTip { end: 500, tip: 300 })
+        );
+    }
+
+    #[test]
+    fn test_genuine_provider_error_propagates() {
+        let result = state_or_skip::<()>(10, 0, Err(ProviderError::BestBlockNotFound));
+        assert!(matches!(result, Err(ProviderError::BestBlockNotFound)));
+        assert!(!is_state_unavailable(&ProviderError::BestBlockNotFound));
+        assert!(is_state_unavailable(&ProviderError::StateForNumberNotFound(9)));
+    }
+
+    #[test]
+    fn test_overlapping_notifications_analyze_blocks_once() {
+        let mut guard = AnalyzedBlocks::new();
+        let mut analyzed = Vec::new();
+
+        // The second notification repeats block 3
+        for notification in [1..=3, 3..=5] {
+            for block_number in notification {
+                if guard.claim(block_number) {
+                    analyzed.push(block_number);
+                }
+            }
+        }
+        assert_eq!(analyzed, vec![1, 2, 3, 4, 5]);
+        assert_eq!(guard.last(), Some(5));
+
+        // Blocks replacing reverted ones are analyzed again
+        guard.revert(4);
+        assert_eq!(guard.last(), Some(3));
+        assert!(!guard.claim(3));
+        assert!(guard.claim(4));
+
+        // Reverting blocks that were never analyzed changes nothing
+        guard.revert(10);
+        assert_eq!(guard.last(), Some(4));
+        guard.revert(0);
+        assert_eq!(guard.last(), None);
+    }
+}
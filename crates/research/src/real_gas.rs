@@ -0,0 +1,290 @@
+//! Experimental execution with the modified gas costs charged by the interpreter itself.
+//!
+//! [`GasResearchInspector`](crate::inspector::GasResearchInspector) charges the extra gas of an
+//! opcode only after the opcode ran, and lets the top-level frame carry on past an OOG so the
+//! rest of the transaction is still observed. Here the instruction table is replaced instead:
+//! static gas is multiplied before an opcode runs and dynamic gas right after it, so execution
+//! halts at the point where the modified costs run out, exactly as a repriced chain would.
+//!
+//! The dynamic gas of the call and create opcodes is left unscaled, since it includes the gas
+//! forwarded to the new frame, which the frame's own opcodes are charged against.
+//!
+//! Analysis runs experimental execution this way with
+//! [`ResearchConfig::real_gas`](crate::config::ResearchConfig::real_gas) set. Its EVMs then
+//! have to come from a [`RealGasEvmFactory`], as those of the
+//! [`OfflineAnalyzer`](crate::offline::OfflineAnalyzer) do: they charge mainnet costs, except
+//! inside [`with_schedule`].
+
+use crate::config::ResearchConfig;
+use reth_evm::{
+    eth::EthEvmContext, precompiles::PrecompilesMap, Database as EvmDatabase, EthEvm,
+    EthEvmFactory, EvmEnv, EvmFactory,
+};
+use revm::{
+    context::{BlockEnv, TxEnv},
+    context_interface::result::{EVMError, HaltReason, ResultAndState},
+    handler::{instructions::EthInstructions, MainnetContext},
+    inspector::NoOpInspector,
+    interpreter::{
+        instruction_table as mainnet_instruction_table, interpreter::EthInterpreter, Host,
+        Instruction, InstructionContext, InstructionResult, InstructionTable,
+    },
+    primitives::hardfork::SpecId,
+    Database, InspectEvm, Inspector, MainBuilder,
+};
+use revm_interpreter::interpreter_types::{Jumps, LegacyBytecode};
+use std::cell::Cell;
+
+/// Opcodes whose dynamic gas includes the gas forwarded to a new frame.
+const FRAME_OPCODES: [u8; 6] = [0xF0, 0xF1, 0xF2, 0xF4, 0xF5, 0xFA];
+
+thread_local! {
+    /// Multipliers of each opcode's gas, set for the duration of [`transact`] and
+    /// [`with_schedule`].
+    static MULTIPLIERS: [Cell<u64>; 256] = const { [const { Cell::new(1) }; 256] };
+
+    /// Whether a [`RealGasEvmFactory`] created an EVM since [`with_schedule`] was entered.
+    static SCHEDULED_EVM_CREATED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Gas multiplier applied to each opcode in real-gas execution.
+pub trait OpcodeMultipliers {
+    /// Multiplier applied to the gas charged by `opcode`.
+    fn multiplier(&self, opcode: u8) -> u64;
+}
+
+impl OpcodeMultipliers for ResearchConfig {
+    fn multiplier(&self, opcode: u8) -> u64 {
+        self.opcode_multiplier(opcode)
+    }
+}
+
+/// The multipliers set on the current thread.
+struct ThreadMultipliers;
+
+impl OpcodeMultipliers for ThreadMultipliers {
+    fn multiplier(&self, opcode: u8) -> u64 {
+        MULTIPLIERS.with(|multipliers| multipliers[opcode as usize].get())
+    }
+}
+
+/// [`EvmFactory`] of mainnet EVMs whose instruction table charges the gas costs set by
+/// [`with_schedule`] on the thread creating them, and mainnet costs anywhere else.
+///
+/// Plugged into an [`EthEvmConfig`](reth_evm_ethereum::EthEvmConfig) with
+/// `EthEvmConfig::new_with_evm_factory`, the same config serves normal and real-gas execution.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealGasEvmFactory;
+
+impl RealGasEvmFactory {
+    /// Build a mainnet EVM, with the instruction table of the current thread's multipliers.
+    fn build<DB, I>(
+        db: DB,
+        input: EvmEnv,
+        inspector: I,
+        inspect: bool,
+    ) -> EthEvm<DB, I, PrecompilesMap>
+    where
+        DB: EvmDatabase,
+        I: Inspector<EthEvmContext<DB>>,
+    {
+        SCHEDULED_EVM_CREATED.with(|created| created.set(true));
+        let mut evm = EthEvmFactory.create_evm_with_inspector(db, input, inspector).into_inner();
+        evm.instruction = EthInstructions::new(instruction_table(&ThreadMultipliers));
+        EthEvm::new(evm, inspect)
+    }
+}
+
+impl EvmFactory for RealGasEvmFactory {
+    type Evm<DB: EvmDatabase, I: Inspector<EthEvmContext<DB>>> = EthEvm<DB, I, PrecompilesMap>;
+    type Context<DB: EvmDatabase> = EthEvmContext<DB>;
+    type Tx = TxEnv;
+    type Error<DBError: core::error::Error + Send + Sync + 'static> = EVMError<DBError>;
+    type HaltReason = HaltReason;
+    type Spec = SpecId;
+    type BlockEnv = BlockEnv;
+    type Precompiles = PrecompilesMap;
+
+    fn create_evm<DB: EvmDatabase>(&self, db: DB, input: EvmEnv) -> Self::Evm<DB, NoOpInspector> {
+        Self::build(db, input, NoOpInspector {}, false)
+    }
+
+    fn create_evm_with_inspector<DB: EvmDatabase, I: Inspector<Self::Context<DB>>>(
+        &self,
+        db: DB,
+        input: EvmEnv,
+        inspector: I,
+    ) -> Self::Evm<DB, I> {
+        Self::build(db, input, inspector, true)
+    }
+}
+
+/// Run `f` with the EVMs a [`RealGasEvmFactory`] creates on the current thread charging the gas
+/// costs of `schedule`.
+///
+/// Returns `None` if `f` created no such EVM: its EVM config is not built on a
+/// [`RealGasEvmFactory`], so it executed at mainnet costs.
+pub fn with_schedule<R>(schedule: &impl OpcodeMultipliers, f: impl FnOnce() -> R) -> Option<R> {
+    let _multipliers = Multipliers::set(schedule);
+    let outer = SCHEDULED_EVM_CREATED.with(|created| created.replace(false));
+    let result = f();
+    let created = SCHEDULED_EVM_CREATED.with(|created| created.replace(outer));
+    created.then_some(result)
+}
+
+/// Build a mainnet instruction table charging the gas costs of `schedule`.
+///
+/// Static gas is multiplied in the table. Dynamic gas is only scaled while [`transact`] or
+/// [`with_schedule`] runs on the same thread.
+pub fn instruction_table<H: Host>(
+    schedule: &impl OpcodeMultipliers,
+) -> InstructionTable<EthInterpreter, H> {
+    let base = mainnet_instruction_table::<EthInterpreter, H>();
+    std::array::from_fn(|opcode| {
+        let static_gas =
+            base[opcode].static_gas().saturating_mul(schedule.multiplier(opcode as u8));
+        Instruction::new(scaled_instruction::<H>, static_gas)
+    })
+}
+
+/// Execute `tx` in `context` with `inspector` attached, charging the gas costs of `schedule`.
+pub fn transact<DB, I>(
+    schedule: &impl OpcodeMultipliers,
+    context: MainnetContext<DB>,
+    tx: TxEnv,
+    inspector: I,
+) -> Result<ResultAndState, EVMError<DB::Error>>
+where
+    DB: Database,
+    I: Inspector<MainnetContext<DB>>,
+{
+    let mut evm = context.build_mainnet_with_inspector(inspector);
+    evm.instruction = EthInstructions::new(instruction_table(schedule));
+
+    let _multipliers = Multipliers::set(schedule);
+    evm.inspect_tx(tx)
+}
+
+/// Run the mainnet instruction of the current opcode, then charge its dynamic gas again for
+/// every multiple beyond the first.
+fn scaled_instruction<H: Host>(context: InstructionContext<'_, H, EthInterpreter>) {
+    let InstructionContext { interpreter, host } = context;
+
+    // The interpreter has already stepped past the opcode
+    let opcode = interpreter.bytecode.bytecode_slice()[interpreter.bytecode.pc() - 1];
+    let gas_before = interpreter.gas.remaining();
+
+    let base = const { &mainnet_instruction_table::<EthInterpreter, H>() };
+    base[opcode as usize].execute(InstructionContext { interpreter: &mut *interpreter, host });
+
+    if FRAME_OPCODES.contains(&opcode) {
+        return
+    }
+    let multiplier = ThreadMultipliers.multiplier(opcode);
+    let dynamic_gas = gas_before.saturating_sub(interpreter.gas.remaining());
+    let additional_gas = dynamic_gas.saturating_mul(multiplier.saturating_sub(1));
+    if additional_gas > 0 && !interpreter.gas.record_cost(additional_gas) {
+        interpreter.gas.spend_all();
+        interpreter.halt(InstructionResult::OutOfGas);
+    }
+}
+
+/// Gas multipliers of the current thread, restored to their previous values on drop.
+struct Multipliers {
+    previous: [u64; 256],
+}
+
+impl Multipliers {
+    /// Set the current thread's multipliers to those of `schedule`.
+    fn set(schedule: &impl OpcodeMultipliers) -> Self {
+        let previous = MULTIPLIERS.with(|multipliers| {
+            std::array::from_fn(|opcode| {
+                multipliers[opcode].replace(schedule.multiplier(opcode as u8))
+            })
+        });
+        Self { previous }
+    }
+}
+
+impl Drop for Multipliers {
+    fn drop(&mut self) {
+        MULTIPLIERS.with(|multipliers| {
+            for (multiplier, previous) in multipliers.iter().zip(self.previous) {
+                multiplier.set(previous);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        inspector::GasResearchInspector,
+        test_utils::{TestEnv, CONTRACT},
+    };
+    use revm::{
+        context_interface::result::{ExecutionResult, HaltReason},
+        interpreter::Interpreter,
+    };
+
+    /// Records the program counter of the last step started.
+    #[derive(Debug, Default)]
+    struct LastStep {
+        pc: Option<usize>,
+    }
+
+    impl<CTX> Inspector<CTX, EthInterpreter> for LastStep {
+        fn step(&mut self, interp: &mut Interpreter<EthInterpreter>, _context: &mut CTX) {
+            self.pc = Some(interp.bytecode.pc());
+        }
+    }
+
+    #[test]
+    fn test_static_gas_scaled() {
+        let config = ResearchConfig {
+            gas_multiplier: 4,
+            excluded_opcodes: [0x5b].into(),
+            ..Default::default()
+        };
+        let table = instruction_table::<MainnetContext<revm::database::EmptyDB>>(&config);
+        assert_eq!(table[0x01].static_gas(), 4 * 3); // ADD
+        assert_eq!(table[0x5b].static_gas(), 1); // JUMPDEST, excluded
+    }
+
+    #[test]
+    fn test_real_gas_oog_matches_simulation() {
+        // 100 JUMPDESTs at 1 gas each, then an MSTORE expanding memory by one word
+        let mut code = vec![0x5b; 100];
+        code.extend_from_slice(&[0x60, 0x01, 0x60, 0x00, 0x52, 0x00]);
+        let env = TestEnv::new().with_contract(CONTRACT, code);
+        let config = ResearchConfig { gas_multiplier: 4, ..Default::default() };
+
+        // 4 gas per JUMPDEST, so the 51st runs out of gas
+        let tx = || TestEnv::call_tx(CONTRACT, vec![], 21_000 + 200 + 3);
+
+        let mut simulated = GasResearchInspector::new(config.clone(), 21_203);
+        env.transact(tx(), &mut simulated).unwrap();
+        let oog = simulated.oog_info().unwrap();
+        let simulated_pc = oog.gas_trajectory.last().unwrap().pc;
+        assert_eq!((oog.opcode, simulated_pc), (0x5b, 50));
+
+        let mut last_step = LastStep::default();
+        let result = transact(&config, env.context(), tx(), &mut last_step).unwrap().result;
+        assert!(matches!(result, ExecutionResult::Halt { reason: HaltReason::OutOfGas(_), .. }));
+        assert_eq!(last_step.pc, Some(simulated_pc));
+
+        // With gas to spare the dynamic memory cost is scaled too: 4 * (3 + 3 + 3 + 3) for the
+        // pushes, MSTORE and its expansion, on top of the JUMPDESTs
+        let result = transact(
+            &config,
+            env.context(),
+            TestEnv::call_tx(CONTRACT, vec![], 100_000),
+            LastStep::default(),
+        )
+        .unwrap()
+        .result;
+        assert!(result.is_success());
+        assert_eq!(result.gas_used(), 21_000 + 4 * 100 + 4 * 12);
+    }
+}
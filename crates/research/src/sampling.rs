@@ -0,0 +1,351 @@
+//! Choosing which transactions to analyze.
+//!
+//! [`Sampler`] analyzes a fixed fraction of transactions, picked deterministically from the
+//! transaction hash so a rerun over the same blocks analyzes the same transactions. With
+//! [`AdaptiveSampling`] it also keeps a per-contract count of recent divergences and analyzes
+//! transactions to contracts that diverged within the window at a higher rate, concentrating
+//! dual execution where it finds divergences.
+//!
+//! With a [`SkipFilter`] it also remembers the `(code_hash, selector)` shapes of calls analyzed
+//! without diverging in a [`NonDivergentFilter`], and skips later calls of the same shape apart
+//! from a fraction of re-checks. The bloom filter can report a shape it never saw, so this trades
+//! a small risk of wrongly skipped calls for throughput.
+
+use crate::config::ResearchConfig;
+use alloy_primitives::{keccak256, Address, B256};
+use std::collections::{HashMap, VecDeque};
+
+/// Bias of the sampling decision towards contracts that diverged recently.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    /// Number of blocks a divergence counts as recent for (default: 1000)
+    pub window_blocks: u64,
+
+    /// Fraction of transactions analyzed when the recipient diverged recently (default: 1.0,
+    /// always analyze)
+    pub divergent_sample_rate: f64,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self { window_blocks: 1000, divergent_sample_rate: 1.0 }
+    }
+}
+
+/// Skipping of calls whose shape was already analyzed without diverging.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkipFilter {
+    /// Size of the bloom filter of non-divergent call shapes in bits (default: 2^24, 2 MiB)
+    pub bits: usize,
+
+    /// Fraction of calls of a known non-divergent shape analyzed anyway, to catch divergences
+    /// that depend on state (default: 0.01)
+    pub recheck_rate: f64,
+}
+
+impl Default for SkipFilter {
+    fn default() -> Self {
+        Self { bits: 1 << 24, recheck_rate: 0.01 }
+    }
+}
+
+/// Number of bits each call shape sets in a [`NonDivergentFilter`].
+const FILTER_HASHES: usize = 4;
+
+/// Bloom filter of the `(code_hash, selector)` shapes of calls that did not diverge.
+#[derive(Debug, Clone)]
+pub struct NonDivergentFilter {
+    /// Filter bits, 64 to a word
+    words: Vec<u64>,
+}
+
+impl NonDivergentFilter {
+    /// Create an empty filter of at least `bits` bits.
+    pub fn new(bits: usize) -> Self {
+        Self { words: vec![0; bits.div_ceil(64).max(1)] }
+    }
+
+    /// Add a call to code with `code_hash` with `selector`.
+    pub fn insert(&mut self, code_hash: B256, selector: Option<[u8; 4]>) {
+        for bit in self.positions(code_hash, selector) {
+            self.words[bit / 64] |= 1u64 << (bit % 64);
+        }
+    }
+
+    /// Whether a call to code with `code_hash` with `selector` was added, or collides with
+    /// calls that were.
+    pub fn contains(&self, code_hash: B256, selector: Option<[u8; 4]>) -> bool {
+        self.positions(code_hash, selector)
+            .into_iter()
+            .all(|bit| self.words[bit / 64] & (1u64 << (bit % 64)) != 0)
+    }
+
+    /// Bits set for a call shape, derived from one digest by double hashing.
+    fn positions(&self, code_hash: B256, selector: Option<[u8; 4]>) -> [usize; FILTER_HASHES] {
+        let mut shape = [0u8; 37];
+        shape[..32].copy_from_slice(code_hash.as_slice());
+        if let Some(selector) = selector {
+            shape[32] = 1;
+            shape[33..].copy_from_slice(&selector);
+        }
+        let digest = keccak256(shape);
+        let first = u64::from_be_bytes(digest[..8].try_into().unwrap());
+        let second = u64::from_be_bytes(digest[8..16].try_into().unwrap());
+
+        let bits = self.words.len() as u64 * 64;
+        std::array::from_fn(|i| {
+            (first.wrapping_add((i as u64).wrapping_mul(second)) % bits) as usize
+        })
+    }
+}
+
+/// Decides which transactions to analyze.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    /// Fraction of transactions analyzed when no bias applies
+    sample_rate: f64,
+
+    /// Bias towards recently divergent contracts, if enabled
+    adaptive: Option<AdaptiveSampling>,
+
+    /// Divergences per block within the window, oldest first, per contract
+    recent: HashMap<Address, VecDeque<(u64, u64)>>,
+
+    /// Shapes of calls that did not diverge, if skipping them is enabled
+    non_divergent: Option<NonDivergentFilter>,
+
+    /// Fraction of calls of a known non-divergent shape analyzed anyway
+    recheck_rate: f64,
+}
+
+impl Sampler {
+    /// Create a sampler with the configured sample rate and adaptive bias.
+    pub fn new(config: &ResearchConfig) -> Self {
+        Self {
+            sample_rate: config.sample_rate,
+            adaptive: config.adaptive_sampling,
+            recent: HashMap::new(),
+            non_divergent: config.skip_filter.map(|filter| NonDivergentFilter::new(filter.bits)),
+            recheck_rate: config.skip_filter.map_or(1.0, |filter| filter.recheck_rate),
+        }
+    }
+
+    /// Whether to analyze the transaction with `tx_hash` sent to `to` in `block_number`.
+    pub fn should_analyze(&self, tx_hash: B256, to: Option<Address>, block_number: u64) -> bool {
+        sampled(tx_hash, self.sample_rate_for(to, block_number))
+    }
+
+    /// Fraction of transactions sent to `to` in `block_number` that are analyzed.
+    pub fn sample_rate_for(&self, to: Option<Address>, block_number: u64) -> f64 {
+        match (self.adaptive, to) {
+            (Some(adaptive), Some(to)) if self.divergence_rate(to, block_number) > 0.0 => {
+                adaptive.divergent_sample_rate.max(self.sample_rate)
+            }
+            _ => self.sample_rate,
+        }
+    }
+
+    /// Divergences per block recorded for `contract` within the window ending at
+    /// `block_number`.
+    pub fn divergence_rate(&self, contract: Address, block_number: u64) -> f64 {
+        let Some(adaptive) = self.adaptive else { return 0.0 };
+        let Some(recent) = self.recent.get(&contract) else { return 0.0 };
+
+        let divergences: u64 = recent
+            .iter()
+            .filter(|(block, _)| is_recent(*block, block_number, adaptive.window_blocks))
+            .map(|(_, count)| count)
+            .sum();
+        divergences as f64 / adaptive.window_blocks as f64
+    }
+
+    /// Record a divergence in a transaction sent to `contract` in `block_number`.
+    ///
+    /// Blocks are expected in ascending order.
+    pub fn record_divergence(&mut self, contract: Address, block_number: u64) {
+        if self.adaptive.is_none() {
+            return
+        }
+
+        let recent = self.recent.entry(contract).or_default();
+        match recent.back_mut() {
+            Some((block, count)) if *block == block_number => *count += 1,
+            _ => recent.push_back((block_number, 1)),
+        }
+    }
+
+    /// Whether calls of known non-divergent shapes are skipped, see [`SkipFilter`].
+    pub const fn skips_non_divergent(&self) -> bool {
+        self.non_divergent.is_some()
+    }
+
+    /// Whether to skip the transaction with `tx_hash` calling code with `code_hash` with
+    /// `selector`, because a call of that shape did not diverge before and the transaction is
+    /// not picked for a re-check.
+    pub fn is_known_non_divergent(
+        &self,
+        tx_hash: B256,
+        code_hash: B256,
+        selector: Option<[u8; 4]>,
+    ) -> bool {
+        self.non_divergent.as_ref().is_some_and(|filter| {
+            filter.contains(code_hash, selector) && !rechecked(tx_hash, self.recheck_rate)
+        })
+    }
+
+    /// Record a call to code with `code_hash` with `selector` that was analyzed and did not
+    /// diverge.
+    pub fn record_non_divergent(&mut self, code_hash: B256, selector: Option<[u8; 4]>) {
+        if let Some(filter) = &mut self.non_divergent {
+            filter.insert(code_hash, selector);
+        }
+    }
+
+    /// Forget divergences that are no longer recent as of `block_number`.
+    pub fn prune(&mut self, block_number: u64) {
+        let Some(adaptive) = self.adaptive else { return };
+
+        self.recent.retain(|_, recent| {
+            while recent
+                .front()
+                .is_some_and(|(block, _)| !is_recent(*block, block_number, adaptive.window_blocks))
+            {
+                recent.pop_front();
+            }
+            !recent.is_empty()
+        });
+    }
+}
+
+/// Whether a divergence in `block` is within the `window_blocks` blocks ending at
+/// `block_number`.
+const fn is_recent(block: u64, block_number: u64, window_blocks: u64) -> bool {
+    block_number.saturating_sub(block) < window_blocks
+}
+
+/// Whether `tx_hash` falls within the sampled fraction `rate`.
+///
+/// The hash is uniformly distributed, so its leading bytes select transactions evenly and
+/// reproducibly.
+fn sampled(tx_hash: B256, rate: f64) -> bool {
+    rate >= 1.0 || hash_position(&tx_hash[..8]) < rate
+}
+
+/// Whether `tx_hash` falls within the re-checked fraction `rate`.
+///
+/// Picked from the next bytes of the hash, so re-checks are independent of sampling.
+fn rechecked(tx_hash: B256, rate: f64) -> bool {
+    rate >= 1.0 || hash_position(&tx_hash[8..16]) < rate
+}
+
+/// Position of eight bytes of a hash in `[0, 1]`.
+fn hash_position(bytes: &[u8]) -> f64 {
+    u64::from_be_bytes(bytes.try_into().unwrap()) as f64 / u64::MAX as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::keccak256;
+
+    fn sampler(sample_rate: f64) -> Sampler {
+        Sampler::new(&ResearchConfig {
+            sample_rate,
+            adaptive_sampling: Some(AdaptiveSampling { window_blocks: 100, ..Default::default() }),
+            ..Default::default()
+        })
+    }
+
+    /// Number of `txs` transactions sent to `to` in `block_number` that are analyzed.
+    fn analyzed(sampler: &Sampler, to: Address, block_number: u64, txs: u64) -> u64 {
+        (0..txs)
+            .filter(|i| sampler.should_analyze(keccak256(i.to_be_bytes()), Some(to), block_number))
+            .count() as u64
+    }
+
+    #[test]
+    fn test_divergent_contract_always_analyzed() {
+        let divergent = Address::repeat_byte(0xdd);
+        let clean = Address::repeat_byte(0xcc);
+        let mut sampler = sampler(0.1);
+        sampler.record_divergence(divergent, 10);
+
+        assert!(sampler.divergence_rate(divergent, 50) > 0.0);
+        assert_eq!(sampler.divergence_rate(clean, 50), 0.0);
+
+        // Every transaction to the divergent contract, about 10% of those to the clean one
+        assert_eq!(analyzed(&sampler, divergent, 50, 2000), 2000);
+        let clean_analyzed = analyzed(&sampler, clean, 50, 2000);
+        assert!((150..=250).contains(&clean_analyzed), "{clean_analyzed}");
+
+        // Contract creations have no recipient to bias towards
+        assert_eq!(sampler.sample_rate_for(None, 50), 0.1);
+    }
+
+    #[test]
+    fn test_divergences_expire_after_window() {
+        let contract = Address::repeat_byte(0xdd);
+        let mut sampler = sampler(0.1);
+        sampler.record_divergence(contract, 10);
+        sampler.record_divergence(contract, 10);
+        sampler.record_divergence(contract, 60);
+
+        assert_eq!(sampler.divergence_rate(contract, 100), 0.03);
+        assert_eq!(sampler.divergence_rate(contract, 110), 0.01);
+        assert_eq!(sampler.sample_rate_for(Some(contract), 159), 1.0);
+        assert_eq!(sampler.sample_rate_for(Some(contract), 160), 0.1);
+
+        sampler.prune(160);
+        assert!(sampler.recent.is_empty());
+    }
+
+    #[test]
+    fn test_non_divergent_calls_skipped() {
+        let mut sampler = Sampler::new(&ResearchConfig {
+            skip_filter: Some(SkipFilter { bits: 1 << 16, recheck_rate: 0.1 }),
+            ..Default::default()
+        });
+        let (code_hash, selector) = (B256::repeat_byte(0xc0), Some([0xa9, 0x05, 0x9c, 0xbb]));
+        let skipped = |sampler: &Sampler, selector| {
+            (0..2000u64)
+                .filter(|i| {
+                    sampler.is_known_non_divergent(keccak256(i.to_be_bytes()), code_hash, selector)
+                })
+                .count()
+        };
+        assert_eq!(skipped(&sampler, selector), 0);
+
+        // Once the call did not diverge, all but about 10% re-checks of identical calls are
+        // skipped, while other selectors are still analyzed
+        sampler.record_non_divergent(code_hash, selector);
+        let rechecked = 2000 - skipped(&sampler, selector);
+        assert!((150..=250).contains(&rechecked), "{rechecked}");
+        assert_eq!(skipped(&sampler, Some([0x23, 0xb8, 0x72, 0xdd])), 0);
+        assert_eq!(skipped(&sampler, None), 0);
+
+        // Without re-checks every identical call is skipped
+        sampler.recheck_rate = 0.0;
+        assert_eq!(skipped(&sampler, selector), 2000);
+
+        // The filter is opt-in
+        let mut sampler = Sampler::new(&ResearchConfig::default());
+        sampler.record_non_divergent(code_hash, selector);
+        assert!(!sampler.skips_non_divergent());
+        assert_eq!(skipped(&sampler, selector), 0);
+    }
+
+    #[test]
+    fn test_static_sampling() {
+        let contract = Address::repeat_byte(0xdd);
+        let mut sampler = Sampler::new(&ResearchConfig { sample_rate: 0.5, ..Default::default() });
+
+        // Without the adaptive bias divergences are not tracked
+        sampler.record_divergence(contract, 10);
+        assert_eq!(sampler.sample_rate_for(Some(contract), 10), 0.5);
+
+        let all = Sampler::new(&ResearchConfig::default());
+        assert_eq!(analyzed(&all, contract, 10, 100), 100);
+        let none = Sampler::new(&ResearchConfig { sample_rate: 0.0, ..Default::default() });
+        assert_eq!(analyzed(&none, contract, 10, 100), 0);
+    }
+}
@@ -0,0 +1,142 @@
+//! Function names for selectors, resolved offline from a local signature database.
+//!
+//! The database is a text file in the format of the [4byte directory] dumps: one signature per
+//! line, the hex selector followed by the text signature, separated by a comma, tab or space:
+//!
+//! ```text
+//! 0xa9059cbb,transfer(address,uint256)
+//! 095ea7b3	approve(address,uint256)
+//! ```
+//!
+//! Lines that do not start with a selector, such as headers and `#` comments, are skipped.
+//! Selectors can collide, in which case the first signature listed is used.
+//!
+//! [4byte directory]: https://www.4byte.directory
+
+use crate::divergence::Divergence;
+use alloy_primitives::hex;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+/// Text signatures of function selectors.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignatureDatabase {
+    signatures: HashMap<[u8; 4], String>,
+}
+
+impl SignatureDatabase {
+    /// Load the signature database file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(BufReader::new(File::open(path)?))
+    }
+
+    /// Parse a signature database from `reader`.
+    pub fn parse(reader: impl BufRead) -> io::Result<Self> {
+        let mut signatures = HashMap::new();
+        for line in reader.lines() {
+            let line = line?;
+            let Some((selector, signature)) = line.trim().split_once([',', '\t', ' ']) else {
+                continue
+            };
+            let Ok(selector) = hex::decode_to_array::<_, 4>(selector) else { continue };
+            let signature = signature.trim();
+            if !signature.is_empty() {
+                signatures.entry(selector).or_insert_with(|| signature.to_string());
+            }
+        }
+        Ok(Self { signatures })
+    }
+
+    /// Number of selectors with a signature.
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    /// Whether the database has no signatures.
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    /// Text signature of `selector`, if known.
+    pub fn name(&self, selector: [u8; 4]) -> Option<&str> {
+        self.signatures.get(&selector).map(String::as_str)
+    }
+
+    /// Text signature of the function the divergence is located in, i.e. of the selector of the
+    /// deepest frame at its divergence location.
+    pub fn resolve(&self, divergence: &Divergence) -> Option<String> {
+        let location = divergence.divergence_location.as_ref()?;
+        let selector = location.function_selectors.last().copied().flatten()?;
+        self.name(selector).map(str::to_string)
+    }
+}
+
+impl From<HashMap<[u8; 4], String>> for SignatureDatabase {
+    fn from(signatures: HashMap<[u8; 4], String>) -> Self {
+        Self { signatures }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        divergence::{DivergenceLocation, DivergenceType},
+        test_utils::DivergenceBuilder,
+    };
+    use alloy_primitives::{Address, B256};
+
+    const TRANSFER: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+
+    #[test]
+    fn test_parse_dump() {
+        let dump = "\
+hex_signature,text_signature
+# comment
+0xa9059cbb,transfer(address,uint256)
+095ea7b3\tapprove(address,uint256)
+0xa9059cbb,many_msg_babbage(bytes1)
+0x12,short(uint256)
+";
+        let signatures = SignatureDatabase::parse(dump.as_bytes()).unwrap();
+        assert_eq!(signatures.len(), 2);
+        assert_eq!(signatures.name(TRANSFER), Some("transfer(address,uint256)"));
+        assert_eq!(signatures.name([0x09, 0x5e, 0xa7, 0xb3]), Some("approve(address,uint256)"));
+        assert_eq!(signatures.name([0; 4]), None);
+    }
+
+    #[test]
+    fn test_resolve_divergence_selector() {
+        let signatures = SignatureDatabase::from(HashMap::from([(
+            TRANSFER,
+            "transfer(address,uint256)".to_string(),
+        )]));
+        let location = |function_selectors| DivergenceLocation {
+            contract: Address::repeat_byte(0xcc),
+            code_address: Address::repeat_byte(0xcc),
+            function_selectors,
+            pc: 10,
+            call_depth: 2,
+            opcode: 0x54,
+            opcode_name: "SLOAD".to_string(),
+            code_hash: B256::ZERO,
+        };
+        let mut divergence = DivergenceBuilder::new(100)
+            .with_types([DivergenceType::Status])
+            .with_location(location(vec![Some([0x12; 4]), Some(TRANSFER)]))
+            .build();
+
+        // The deepest frame's selector is resolved
+        assert_eq!(signatures.resolve(&divergence).as_deref(), Some("transfer(address,uint256)"));
+
+        // Unknown selectors and frames without one resolve to nothing
+        divergence.divergence_location = Some(location(vec![Some(TRANSFER), Some([0x12; 4])]));
+        assert_eq!(signatures.resolve(&divergence), None);
+        divergence.divergence_location = Some(location(vec![Some(TRANSFER), None]));
+        assert_eq!(signatures.resolve(&divergence), None);
+    }
+}
@@ -0,0 +1,216 @@
+//! Storage backends for divergences and run bookkeeping.
+//!
+//! The ExEx and the [`DivergenceWriter`](crate::DivergenceWriter) record through the
+//! [`DivergenceStore`] trait. [`DivergenceDatabase`] (SQLite) is the default backend; a
+//! PostgreSQL backend for aggregating results across nodes is available with the `postgres`
+//! feature, see [`PostgresStore`](crate::postgres::PostgresStore).
+
+use crate::{
+    config::ResearchConfig,
+    database::{BlockSummary, Checkpoint, DatabaseError, DivergenceDatabase, DivergenceRecord},
+    divergence::{Divergence, DivergenceType},
+    executor::ResearchStats,
+};
+use std::{fmt::Debug, path::Path, sync::Arc};
+
+/// Storage operations shared by every divergence backend.
+///
+/// Methods block until the backend has completed the operation, so call them off the async
+/// runtime, e.g. from a [`DivergenceWriter`](crate::DivergenceWriter).
+pub trait DivergenceStore: Debug + Send + Sync {
+    /// Record a divergence, returning its id. A divergence already stored, matched by
+    /// [`Divergence::uid`], is not stored again and its existing id is returned.
+    fn record_divergence(&self, divergence: &Divergence) -> Result<i64, DatabaseError>;
+
+    /// Record the transactions analyzed in a block, replacing an earlier summary of the block.
+    fn record_block_summary(&self, summary: &BlockSummary) -> Result<(), DatabaseError>;
+
+    /// Record the last block a run finished analyzing, replacing the previous checkpoint.
+    fn record_checkpoint(&self, checkpoint: &Checkpoint) -> Result<(), DatabaseError>;
+
+    /// Get the last checkpoint recorded, if any.
+    fn last_checkpoint(&self) -> Result<Option<Checkpoint>, DatabaseError>;
+
+    /// Record the start of a run with `config`, returning its id for
+    /// [`Self::record_run_end`].
+    fn record_run_start(&self, config: &ResearchConfig) -> Result<i64, DatabaseError>;
+
+    /// Record the end of the run `run_id` with its final `stats`, unless it already ended.
+    fn record_run_end(&self, run_id: i64, stats: &ResearchStats) -> Result<(), DatabaseError>;
+
+    /// Count the divergences in blocks `from_block..=to_block`.
+    fn count_divergences(&self, from_block: u64, to_block: u64) -> Result<u64, DatabaseError>;
+
+    /// Count the divergences of type `dtype`.
+    fn count_by_type(&self, dtype: DivergenceType) -> Result<u64, DatabaseError>;
+
+    /// Get the highest divergence id, or 0 if nothing is stored.
+    fn max_id(&self) -> Result<i64, DatabaseError>;
+
+    /// Get up to `limit` divergences with an id greater than `last_id`, in insertion order.
+    fn since_id(&self, last_id: i64, limit: usize) -> Result<Vec<DivergenceRecord>, DatabaseError>;
+
+    /// Make everything recorded so far durable. Called once the writer has drained its queue.
+    fn flush(&self) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+}
+
+impl DivergenceStore for DivergenceDatabase {
+    fn record_divergence(&self, divergence: &Divergence) -> Result<i64, DatabaseError> {
+        Self::record_divergence(self, divergence)
+    }
+
+    fn record_block_summary(&self, summary: &BlockSummary) -> Result<(), DatabaseError> {
+        Self::record_block_summary(self, summary)
+    }
+
+    fn record_checkpoint(&self, checkpoint: &Checkpoint) -> Result<(), DatabaseError> {
+        Self::record_checkpoint(self, checkpoint)
+    }
+
+    fn last_checkpoint(&self) -> Result<Option<Checkpoint>, DatabaseError> {
+        Self::last_checkpoint(self)
+    }
+
+    fn record_run_start(&self, config: &ResearchConfig) -> Result<i64, DatabaseError> {
+        Self::record_run_start(self, config)
+    }
+
+    fn record_run_end(&self, run_id: i64, stats: &ResearchStats) -> Result<(), DatabaseError> {
+        Self::record_run_end(self, run_id, stats)
+    }
+
+    fn count_divergences(&self, from_block: u64, to_block: u64) -> Result<u64, DatabaseError> {
+        Self::count_divergences(self, from_block, to_block)
+    }
+
+    fn count_by_type(&self, dtype: DivergenceType) -> Result<u64, DatabaseError> {
+        Self::count_by_type(self, dtype)
+    }
+
+    fn max_id(&self) -> Result<i64, DatabaseError> {
+        Self::max_id(self)
+    }
+
+    fn since_id(&self, last_id: i64, limit: usize) -> Result<Vec<DivergenceRecord>, DatabaseError> {
+        Self::since_id(self, last_id, limit)
+    }
+
+    /// Checkpoint the WAL into the main database file.
+    fn flush(&self) -> Result<(), DatabaseError> {
+        self.checkpoint()
+    }
+}
+
+/// Whether `location` is a PostgreSQL connection URL rather than a SQLite database path.
+pub fn is_postgres_url(location: &Path) -> bool {
+    location
+        .to_str()
+        .is_some_and(|url| url.starts_with("postgres://") || url.starts_with("postgresql://"))
+}
+
+/// Open the store at `location`: a PostgreSQL database for a `postgres://` URL, otherwise a
+/// SQLite database at that path.
+pub fn open(location: &Path) -> Result<Arc<dyn DivergenceStore>, DatabaseError> {
+    if is_postgres_url(location) {
+        #[cfg(feature = "postgres")]
+        return Ok(Arc::new(crate::postgres::PostgresStore::connect(
+            location.to_str().expect("URLs are UTF-8"),
+        )?));
+        #[cfg(not(feature = "postgres"))]
+        return Err(DatabaseError::PostgresUnsupported);
+    }
+    Ok(Arc::new(DivergenceDatabase::open(location)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::DivergenceBuilder;
+    use alloy_primitives::B256;
+
+    /// Exercise every operation of `store`, recording divergences from `first_block` on.
+    ///
+    /// Checks are relative to what the store already holds, so a shared database can be used as
+    /// long as nothing else records to blocks `first_block..first_block + 10` concurrently.
+    fn check_store(store: &dyn DivergenceStore, first_block: u64) {
+        let last_id = store.max_id().unwrap();
+        let status_count = store.count_by_type(DivergenceType::Status).unwrap();
+
+        let status =
+            DivergenceBuilder::new(first_block).with_types([DivergenceType::Status]).build();
+        let id = store.record_divergence(&status).unwrap();
+        assert!(id > last_id);
+        let improved = DivergenceBuilder::new(first_block + 1)
+            .with_types([DivergenceType::StatusImproved])
+            .build();
+        store.record_divergence(&improved).unwrap();
+        let types = vec![DivergenceType::Status, DivergenceType::GasPattern];
+        let last = DivergenceBuilder::new(first_block + 9).with_types(types.clone()).build();
+        store.record_divergence(&last).unwrap();
+
+        // Recording a divergence again keeps the stored one
+        assert_eq!(store.record_divergence(&status).unwrap(), id);
+        assert_eq!(store.count_divergences(first_block, first_block + 9).unwrap(), 3);
+        assert_eq!(store.count_divergences(first_block + 1, first_block + 9).unwrap(), 2);
+        assert_eq!(store.count_by_type(DivergenceType::Status).unwrap(), status_count + 2);
+
+        let records = store.since_id(id, 10).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].divergence.block_number, first_block + 1);
+        assert_eq!(records[1].divergence.divergence_types, types);
+        assert_eq!(records[1].id, store.max_id().unwrap());
+        assert_eq!(store.since_id(id, 1).unwrap().len(), 1);
+
+        store
+            .record_block_summary(&BlockSummary {
+                block_number: first_block,
+                analyzed_tx_count: 5,
+                header_gas_used: 100_000,
+                normal_gas_used: None,
+            })
+            .unwrap();
+        let checkpoint =
+            Checkpoint { block_number: first_block + 9, config_hash: B256::repeat_byte(0x01) };
+        store.record_checkpoint(&checkpoint).unwrap();
+        assert_eq!(store.last_checkpoint().unwrap(), Some(checkpoint));
+
+        let run_id = store.record_run_start(&ResearchConfig::default()).unwrap();
+        let stats =
+            ResearchStats { blocks_processed: 10, divergences_found: 3, divergences_recorded: 3 };
+        store.record_run_end(run_id, &stats).unwrap();
+        store.flush().unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_store() {
+        check_store(&DivergenceDatabase::in_memory().unwrap(), 10);
+    }
+
+    #[test]
+    fn test_open_by_location() {
+        assert!(is_postgres_url(Path::new("postgres://localhost/research")));
+        assert!(is_postgres_url(Path::new("postgresql://user@localhost:5432/research")));
+        assert!(!is_postgres_url(Path::new("divergence.db")));
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = open(&dir.path().join("divergence.db")).unwrap();
+        check_store(store.as_ref(), 10);
+    }
+
+    /// Runs against the database at `RESEARCH_POSTGRES_URL`, and is skipped if it is not set.
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_postgres_store() {
+        let Ok(url) = std::env::var("RESEARCH_POSTGRES_URL") else { return };
+        let store = open(Path::new(&url)).unwrap();
+
+        // Unused blocks on every run, the database may hold earlier runs' divergences
+        let first_block = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+        check_store(store.as_ref(), first_block);
+    }
+}
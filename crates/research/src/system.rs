@@ -0,0 +1,66 @@
+//! System operations that change state every block.
+//!
+//! Since the merge, blocks change state outside of user transactions. Before the transactions,
+//! system calls from [`SYSTEM_ADDRESS`] store the parent beacon block root (EIP-4788) and the
+//! parent block hash (EIP-2935); after them, system calls dequeue withdrawal (EIP-7002) and
+//! consolidation (EIP-7251) requests. These changes are the same under any gas costs and only add
+//! noise, so by default:
+//!
+//! - transactions sent from or to one of the [`SYSTEM_ADDRESSES`] are not analyzed, unless
+//!   [`ResearchConfig::include_system_txs`] is set, and
+//! - the [`SYSTEM_ADDRESSES`] are left out of the post-transaction state comparison, unless
+//!   [`ResearchConfig::include_system_state`] is set.
+//!
+//! Withdrawals (EIP-4895) are balance increases applied after the transactions rather than
+//! executed, so they are never analyzed.
+//!
+//! [`ResearchConfig::include_system_txs`]: crate::config::ResearchConfig::include_system_txs
+//! [`ResearchConfig::include_system_state`]: crate::config::ResearchConfig::include_system_state
+
+use alloy_eips::{
+    eip2935::HISTORY_STORAGE_ADDRESS,
+    eip4788::{BEACON_ROOTS_ADDRESS, SYSTEM_ADDRESS},
+    eip7002::WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS,
+    eip7251::CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS,
+};
+use alloy_primitives::Address;
+
+/// Addresses treated as system: the system caller and the system contracts it calls.
+pub const SYSTEM_ADDRESSES: [Address; 5] = [
+    SYSTEM_ADDRESS,
+    BEACON_ROOTS_ADDRESS,
+    HISTORY_STORAGE_ADDRESS,
+    WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS,
+    CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS,
+];
+
+/// Whether `address` is one of the [`SYSTEM_ADDRESSES`].
+pub fn is_system_address(address: Address) -> bool {
+    SYSTEM_ADDRESSES.contains(&address)
+}
+
+/// Whether a transaction from `sender` to `to` is a system operation.
+pub fn is_system_tx(sender: Address, to: Option<Address>) -> bool {
+    is_system_address(sender) || to.is_some_and(is_system_address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_system_address() {
+        assert!(is_system_address(SYSTEM_ADDRESS));
+        assert!(is_system_address(HISTORY_STORAGE_ADDRESS));
+        assert!(!is_system_address(Address::repeat_byte(0xc0)));
+    }
+
+    #[test]
+    fn test_is_system_tx() {
+        let user = Address::repeat_byte(0xca);
+        assert!(is_system_tx(SYSTEM_ADDRESS, Some(BEACON_ROOTS_ADDRESS)));
+        assert!(is_system_tx(user, Some(HISTORY_STORAGE_ADDRESS)));
+        assert!(!is_system_tx(user, Some(Address::repeat_byte(0xc0))));
+        assert!(!is_system_tx(user, None));
+    }
+}
@@ -0,0 +1,325 @@
+//! Helpers for executing transactions against an in-memory database and building fixtures in
+//! tests.
+
+use crate::divergence::{
+    Divergence, DivergenceLocation, DivergenceType, GasAnalysis, OperationCounts,
+};
+use alloy_primitives::{address, Address, Bytes, B256, U256};
+use revm::{
+    context::{BlockEnv, Context, TxEnv},
+    context_interface::result::{EVMError, ResultAndState},
+    database::{CacheDB, EmptyDB},
+    handler::MainnetContext,
+    primitives::TxKind,
+    state::{AccountInfo, Bytecode},
+    InspectEvm, Inspector, MainBuilder, MainContext,
+};
+use std::convert::Infallible;
+#[cfg(test)]
+use crate::{
+    analysis::{BlockContext, TxContext},
+    config::ResearchConfig,
+};
+#[cfg(test)]
+use metrics_util::debugging::{DebugValue, Snapshotter};
+#[cfg(test)]
+use reth_evm_ethereum::EthEvmConfig;
+
+/// Funded sender of test transactions.
+pub const CALLER: Address = address!("0x00000000000000000000000000000000000000ca");
+
+/// Address test contracts are deployed to by default.
+pub const CONTRACT: Address = address!("0x00000000000000000000000000000000000000c0");
+
+/// Runtime code that forwards all remaining gas in a CALL to `target` and then stops.
+pub fn call_code(target: Address) -> Vec<u8> {
+    call_code_with(target, &[0x5a]) // GAS
+}
+
+/// Runtime code that CALLs `target` with a fixed `gas` limit and then stops, ignoring the
+/// call's outcome.
+pub fn call_code_with_gas(target: Address, gas: u16) -> Vec<u8> {
+    let [hi, lo] = gas.to_be_bytes();
+    call_code_with(target, &[0x61, hi, lo]) // PUSH2 gas
+}
+
+/// CALL `target` with the gas limit pushed by `push_gas`, then STOP.
+fn call_code_with(target: Address, push_gas: &[u8]) -> Vec<u8> {
+    let mut code = vec![
+        0x60, 0x00, // PUSH1 0 (retSize)
+        0x60, 0x00, // PUSH1 0 (retOffset)
+        0x60, 0x00, // PUSH1 0 (argsSize)
+        0x60, 0x00, // PUSH1 0 (argsOffset)
+        0x60, 0x00, // PUSH1 0 (value)
+        0x73, // PUSH20 target
+    ];
+    code.extend_from_slice(target.as_slice());
+    code.extend_from_slice(push_gas);
+    code.extend_from_slice(&[
+        0xf1, // CALL
+        0x00, // STOP
+    ]);
+    code
+}
+
+/// Block and transaction contexts of a 100,000 gas transaction to `to` with `input`, signed by
+/// a freshly generated key.
+#[cfg(test)]
+pub fn tx_context(
+    config: &ResearchConfig,
+    to: TxKind,
+    input: &[u8],
+) -> (EthEvmConfig, BlockContext<EthEvmConfig>, TxContext<EthEvmConfig>) {
+    use alloy_consensus::{Header, TxLegacy};
+    use reth_chainspec::ChainSpecBuilder;
+    use reth_ethereum_primitives::Transaction;
+    use reth_primitives_traits::{crypto::secp256k1::public_key_to_address, SignedTransaction};
+    use reth_testing_utils::generators::{self, sign_tx_with_key_pair};
+    use std::sync::Arc;
+
+    let mut rng = generators::rng();
+    let key_pair = generators::generate_key(&mut rng);
+    let sender = public_key_to_address(key_pair.public_key());
+
+    let chain_spec = Arc::new(ChainSpecBuilder::mainnet().shanghai_activated().build());
+    let evm_config = EthEvmConfig::new(chain_spec);
+    let header = Header {
+        number: 1,
+        timestamp: 12,
+        gas_limit: 30_000_000,
+        base_fee_per_gas: Some(1_000_000_000),
+        ..Default::default()
+    };
+    let block = BlockContext::new(&evm_config, config, &header).unwrap();
+
+    let signed = sign_tx_with_key_pair(
+        key_pair,
+        Transaction::Legacy(TxLegacy {
+            chain_id: Some(1),
+            gas_price: 2_000_000_000,
+            gas_limit: 100_000,
+            to,
+            input: Bytes::copy_from_slice(input),
+            ..Default::default()
+        }),
+    );
+    let tx = TxContext::new(&evm_config, &block, 0, signed.with_signer_ref(sender));
+
+    (evm_config, block, tx)
+}
+
+/// Value of the counter `name` recorded by a
+/// [`DebuggingRecorder`](metrics_util::debugging::DebuggingRecorder), or 0 if it was not
+/// recorded. With a `label` given as key and value, only a counter carrying it matches.
+#[cfg(test)]
+pub fn counter_value(snapshotter: &Snapshotter, name: &str, label: Option<(&str, &str)>) -> u64 {
+    snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .find_map(|(key, _, _, value)| {
+            let key = key.key();
+            let matches = key.name() == name &&
+                label.is_none_or(|(label_key, label_value)| {
+                    key.labels().any(|l| l.key() == label_key && l.value() == label_value)
+                });
+            match value {
+                DebugValue::Counter(v) if matches => Some(v),
+                _ => None,
+            }
+        })
+        .unwrap_or(0)
+}
+
+/// In-memory execution environment for tests.
+#[derive(Debug, Clone)]
+pub struct TestEnv {
+    db: CacheDB<EmptyDB>,
+    block: BlockEnv,
+}
+
+impl Default for TestEnv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestEnv {
+    /// Create an environment with a funded [`CALLER`] and a zero base fee.
+    pub fn new() -> Self {
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(
+            CALLER,
+            AccountInfo { balance: U256::from(10u128.pow(24)), ..Default::default() },
+        );
+        Self { db, block: BlockEnv { gas_limit: 30_000_000, ..Default::default() } }
+    }
+
+    /// Deploy runtime `code` at `address`.
+    pub fn with_contract(mut self, address: Address, code: impl Into<Bytes>) -> Self {
+        let bytecode = Bytecode::new_raw(code.into());
+        self.db.insert_account_info(
+            address,
+            AccountInfo {
+                code_hash: bytecode.hash_slow(),
+                code: Some(bytecode),
+                ..Default::default()
+            },
+        );
+        self
+    }
+
+    /// Set storage `slot` of the account at `address` to `value`.
+    pub fn with_storage(mut self, address: Address, slot: U256, value: U256) -> Self {
+        let Ok(()) = self.db.insert_account_storage(address, slot, value);
+        self
+    }
+
+    /// Set the block base fee.
+    pub const fn with_base_fee(mut self, base_fee: u64) -> Self {
+        self.block.basefee = base_fee;
+        self
+    }
+
+    /// Set the block beneficiary.
+    pub const fn with_beneficiary(mut self, beneficiary: Address) -> Self {
+        self.block.beneficiary = beneficiary;
+        self
+    }
+
+    /// The block environment transactions are executed in.
+    pub const fn block(&self) -> &BlockEnv {
+        &self.block
+    }
+
+    /// Build a call from [`CALLER`] to `to`.
+    pub fn call_tx(to: Address, input: impl Into<Bytes>, gas_limit: u64) -> TxEnv {
+        TxEnv {
+            caller: CALLER,
+            kind: TxKind::Call(to),
+            data: input.into(),
+            gas_limit,
+            ..Default::default()
+        }
+    }
+
+    /// Build a contract creation from [`CALLER`] with the given init code.
+    pub fn create_tx(init_code: impl Into<Bytes>, gas_limit: u64) -> TxEnv {
+        TxEnv {
+            caller: CALLER,
+            kind: TxKind::Create,
+            data: init_code.into(),
+            gas_limit,
+            ..Default::default()
+        }
+    }
+
+    /// A mainnet context on a copy of the environment's state.
+    pub fn context(&self) -> MainnetContext<CacheDB<EmptyDB>> {
+        Context::mainnet().with_db(self.db.clone()).with_block(self.block.clone())
+    }
+
+    /// Execute `tx` on a copy of the environment's state with `inspector` attached.
+    pub fn transact<I>(
+        &self,
+        tx: TxEnv,
+        inspector: I,
+    ) -> Result<ResultAndState, EVMError<Infallible>>
+    where
+        I: Inspector<MainnetContext<CacheDB<EmptyDB>>>,
+    {
+        self.context().build_mainnet_with_inspector(inspector).inspect_tx(tx)
+    }
+}
+
+/// Builder of [`Divergence`] fixtures: a [`DivergenceType::StateRoot`] divergence of the first
+/// transaction in a block, with every optional field unset.
+#[derive(Debug, Clone)]
+pub struct DivergenceBuilder {
+    divergence: Divergence,
+}
+
+impl DivergenceBuilder {
+    /// Start a divergence in block `block_number`.
+    pub fn new(block_number: u64) -> Self {
+        Self {
+            divergence: Divergence {
+                block_number,
+                tx_index: 0,
+                tx_hash: B256::ZERO,
+                timestamp: 1234567890,
+                divergence_types: vec![DivergenceType::StateRoot],
+                gas_analysis: GasAnalysis {
+                    normal_gas_used: 21000,
+                    experimental_gas_used: 2688000,
+                    gas_efficiency_ratio: 1.0,
+                    normal_gas_charged: 21000,
+                    experimental_gas_charged: 2688000,
+                },
+                normal_ops: OperationCounts::default(),
+                experimental_ops: OperationCounts::default(),
+                divergence_location: None,
+                oog_info: None,
+                griefing: None,
+                reverting_selector: None,
+                cause: None,
+                call_trees: None,
+                event_logs: None,
+                create2_deployments: None,
+                created_code: None,
+                gas_observations: None,
+                threshold_map: None,
+                truncated: false,
+                near_miss: false,
+                comparison: Default::default(),
+                function_name: None,
+                calldata: None,
+                access_list_diff: None,
+            },
+        }
+    }
+
+    /// Set the transaction index.
+    pub const fn with_tx_index(mut self, tx_index: u64) -> Self {
+        self.divergence.tx_index = tx_index;
+        self
+    }
+
+    /// Set the transaction hash.
+    pub const fn with_tx_hash(mut self, tx_hash: B256) -> Self {
+        self.divergence.tx_hash = tx_hash;
+        self
+    }
+
+    /// Set the divergence types.
+    pub fn with_types(mut self, types: impl IntoIterator<Item = DivergenceType>) -> Self {
+        self.divergence.divergence_types = types.into_iter().collect();
+        self
+    }
+
+    /// Locate the divergence at an SLOAD in the top-level frame of `contract`.
+    pub fn with_contract(mut self, contract: Address) -> Self {
+        self.divergence.divergence_location = Some(DivergenceLocation {
+            contract,
+            code_address: contract,
+            function_selectors: vec![],
+            pc: 0,
+            call_depth: 1,
+            opcode: 0x54,
+            opcode_name: "SLOAD".to_string(),
+            code_hash: B256::ZERO,
+        });
+        self
+    }
+
+    /// Set where the divergence first occurred.
+    pub fn with_location(mut self, location: DivergenceLocation) -> Self {
+        self.divergence.divergence_location = Some(location);
+        self
+    }
+
+    /// Build the divergence.
+    pub fn build(self) -> Divergence {
+        self.divergence
+    }
+}
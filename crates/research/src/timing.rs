@@ -0,0 +1,205 @@
+//! Time spent in each phase of transaction analysis.
+//!
+//! [`AnalysisTimings`] accumulates the time [`analyze_transaction`] spends executing normally,
+//! executing experimentally and comparing the results, across threads. Attach it to a block with
+//! [`BlockContext::with_timings`] and read it back with [`AnalysisTimings::snapshot`].
+//!
+//! [`analyze_transaction`]: crate::analysis::analyze_transaction
+//! [`BlockContext::with_timings`]: crate::analysis::BlockContext::with_timings
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// A phase of analyzing a transaction, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Executing with normal gas costs
+    NormalExecution,
+    /// Executing with the experimental gas costs
+    ExperimentalExecution,
+    /// Comparing the two executions and assembling the divergence
+    Comparison,
+}
+
+impl Phase {
+    /// The phase that runs after this one.
+    const fn next(self) -> Self {
+        match self {
+            Self::NormalExecution => Self::ExperimentalExecution,
+            Self::ExperimentalExecution | Self::Comparison => Self::Comparison,
+        }
+    }
+}
+
+/// Breakdown of the time spent analyzing transactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimingBreakdown {
+    /// Number of transactions analyzed
+    pub transactions: u64,
+    /// Time spent executing with normal gas costs
+    pub normal_execution: Duration,
+    /// Time spent executing with the experimental gas costs
+    pub experimental_execution: Duration,
+    /// Time spent comparing the executions
+    pub comparison: Duration,
+    /// Total time spent analyzing transactions
+    pub total: Duration,
+}
+
+impl TimingBreakdown {
+    /// Average time to analyze one transaction.
+    pub fn average_latency(&self) -> Duration {
+        if self.transactions == 0 {
+            return Duration::ZERO
+        }
+        self.total / self.transactions as u32
+    }
+
+    /// Transactions analyzed per second of analysis time.
+    pub fn transactions_per_second(&self) -> f64 {
+        if self.total.is_zero() {
+            return 0.0
+        }
+        self.transactions as f64 / self.total.as_secs_f64()
+    }
+
+    /// Share of the total time spent in `phase`, between 0 and 1.
+    pub fn share(&self, phase: Phase) -> f64 {
+        if self.total.is_zero() {
+            return 0.0
+        }
+        let elapsed = match phase {
+            Phase::NormalExecution => self.normal_execution,
+            Phase::ExperimentalExecution => self.experimental_execution,
+            Phase::Comparison => self.comparison,
+        };
+        elapsed.as_secs_f64() / self.total.as_secs_f64()
+    }
+}
+
+/// Timing counters that can be updated from multiple threads.
+#[derive(Debug, Default)]
+pub struct AnalysisTimings {
+    /// Number of transactions analyzed
+    transactions: AtomicU64,
+    /// Nanoseconds spent executing with normal gas costs
+    normal_execution: AtomicU64,
+    /// Nanoseconds spent executing with the experimental gas costs
+    experimental_execution: AtomicU64,
+    /// Nanoseconds spent comparing the executions
+    comparison: AtomicU64,
+    /// Total nanoseconds spent analyzing transactions
+    total: AtomicU64,
+}
+
+impl AnalysisTimings {
+    /// Add `elapsed` to the time spent in `phase`.
+    pub fn record(&self, phase: Phase, elapsed: Duration) {
+        let counter = match phase {
+            Phase::NormalExecution => &self.normal_execution,
+            Phase::ExperimentalExecution => &self.experimental_execution,
+            Phase::Comparison => &self.comparison,
+        };
+        counter.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Count an analyzed transaction that took `elapsed` in total.
+    pub fn record_transaction(&self, elapsed: Duration) {
+        self.transactions.fetch_add(1, Ordering::Relaxed);
+        self.total.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Take a snapshot of the current timings.
+    pub fn snapshot(&self) -> TimingBreakdown {
+        let load = |counter: &AtomicU64| Duration::from_nanos(counter.load(Ordering::Relaxed));
+        TimingBreakdown {
+            transactions: self.transactions.load(Ordering::Relaxed),
+            normal_execution: load(&self.normal_execution),
+            experimental_execution: load(&self.experimental_execution),
+            comparison: load(&self.comparison),
+            total: load(&self.total),
+        }
+    }
+}
+
+/// Times the phases of one transaction's analysis.
+///
+/// Every moment between creation and drop is attributed to exactly one phase, so the phases add
+/// up to the total whichever way the analysis returns.
+#[derive(Debug)]
+pub(crate) struct TransactionTimer<'a> {
+    timings: Option<&'a AnalysisTimings>,
+    phase: Phase,
+    start: Instant,
+    phase_start: Instant,
+}
+
+impl<'a> TransactionTimer<'a> {
+    /// Start timing the normal execution, recording into `timings` if set.
+    pub(crate) fn start(timings: Option<&'a AnalysisTimings>) -> Self {
+        let now = Instant::now();
+        Self { timings, phase: Phase::NormalExecution, start: now, phase_start: now }
+    }
+
+    /// End the current phase and start the next one.
+    pub(crate) fn next_phase(&mut self) {
+        let now = Instant::now();
+        if let Some(timings) = self.timings {
+            timings.record(self.phase, now - self.phase_start);
+        }
+        self.phase = self.phase.next();
+        self.phase_start = now;
+    }
+}
+
+impl Drop for TransactionTimer<'_> {
+    fn drop(&mut self) {
+        let Some(timings) = self.timings else { return };
+        let now = Instant::now();
+        timings.record(self.phase, now - self.phase_start);
+        timings.record_transaction(now - self.start);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer_phases_sum_to_total() {
+        let timings = AnalysisTimings::default();
+        for phases in 0..3 {
+            let mut timer = TransactionTimer::start(Some(&timings));
+            for _ in 0..phases {
+                std::thread::sleep(Duration::from_millis(1));
+                timer.next_phase();
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let breakdown = timings.snapshot();
+        assert_eq!(breakdown.transactions, 3);
+        assert!(breakdown.comparison >= Duration::from_millis(1));
+        assert_eq!(
+            breakdown.normal_execution + breakdown.experimental_execution + breakdown.comparison,
+            breakdown.total
+        );
+        assert_eq!(breakdown.average_latency(), breakdown.total / 3);
+
+        let shares: f64 = [Phase::NormalExecution, Phase::ExperimentalExecution, Phase::Comparison]
+            .into_iter()
+            .map(|phase| breakdown.share(phase))
+            .sum();
+        assert!((shares - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_empty_breakdown() {
+        let breakdown = AnalysisTimings::default().snapshot();
+        assert_eq!(breakdown.average_latency(), Duration::ZERO);
+        assert_eq!(breakdown.transactions_per_second(), 0.0);
+        assert_eq!(breakdown.share(Phase::Comparison), 0.0);
+    }
+}
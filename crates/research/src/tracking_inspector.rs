@@ -1,7 +1,10 @@
 //! Simple inspector that only tracks operations without modifying execution.
 
-use crate::divergence::{CallFrame, CallType, OperationCounts};
-use alloy_primitives::{Address, Bytes};
+use crate::{
+    divergence::{CallFrame, CallType, Create2Deployment, OperationCounts},
+    inspector::{is_out_of_gas, warm_accounts},
+};
+use alloy_primitives::{Address, Bytes, B256};
 use revm::{
     context_interface::ContextTr,
     interpreter::{CallInputs, CallOutcome, CreateInputs, CreateOutcome, Interpreter},
@@ -24,8 +27,20 @@ pub struct TrackingInspector {
     /// Recorded call frames
     call_frames: Vec<CallFrame>,
 
+    /// Completed CREATE2 deployments
+    create2_deployments: Vec<Create2Deployment>,
+
+    /// Index assigned to the next call frame (in call order)
+    next_call_index: usize,
+
     /// Event logs captured
     event_logs: Vec<EventLogEntry>,
+
+    /// Accounts loaded before the top-level frame runs so they start warm
+    warm_addresses: Vec<Address>,
+
+    /// Whether call frames keep copies of their input and output data, not just the lengths
+    capture_call_data: bool,
 }
 
 /// Entry in the call stack.
@@ -39,6 +54,7 @@ struct CallStackEntry {
     gas_provided: u64,
     #[allow(dead_code)]
     function_selector: Option<[u8; 4]>,
+    create2: Option<Create2Deployment>,
 }
 
 /// Captured event log.
@@ -61,10 +77,27 @@ impl TrackingInspector {
             op_counts: OperationCounts::default(),
             call_stack: Vec::new(),
             call_frames: Vec::new(),
+            create2_deployments: Vec::new(),
+            next_call_index: 0,
             event_logs: Vec::new(),
+            warm_addresses: Vec::new(),
+            capture_call_data: false,
         }
     }
 
+    /// Warm `addresses` before the top-level frame runs.
+    pub fn with_warm_addresses(mut self, addresses: Vec<Address>) -> Self {
+        self.warm_addresses = addresses;
+        self
+    }
+
+    /// Keep copies of each call frame's input and output data, which is only needed for
+    /// detailed traces. Lengths are always recorded.
+    pub const fn with_call_data(mut self, capture: bool) -> Self {
+        self.capture_call_data = capture;
+        self
+    }
+
     /// Get the operation counts.
     pub fn operation_counts(&self) -> &OperationCounts {
         &self.op_counts
@@ -75,6 +108,11 @@ impl TrackingInspector {
         &self.call_frames
     }
 
+    /// Get the completed CREATE2 deployments.
+    pub fn create2_deployments(&self) -> &[Create2Deployment] {
+        &self.create2_deployments
+    }
+
     /// Get the event logs.
     pub fn event_logs(&self) -> &[EventLogEntry] {
         &self.event_logs
@@ -110,6 +148,18 @@ where
                 // SSTORE
                 self.op_counts.sstore_count += 1;
             }
+            0x5C => {
+                // TLOAD
+                self.op_counts.tload_count += 1;
+            }
+            0x5D => {
+                // TSTORE
+                self.op_counts.tstore_count += 1;
+            }
+            0x5E => {
+                // MCOPY
+                self.op_counts.mcopy_count += 1;
+            }
             0xF1 | 0xF2 | 0xF4 | 0xFA => {
                 // CALL, CALLCODE, DELEGATECALL, STATICCALL
                 self.op_counts.call_count += 1;
@@ -122,11 +172,15 @@ where
                 // LOG0-LOG4
                 self.op_counts.log_count += 1;
             }
+            0x3B | 0x3C | 0x3F => {
+                // EXTCODESIZE, EXTCODECOPY, EXTCODEHASH
+                self.op_counts.extcode_count += 1;
+            }
             _ => {}
         }
 
         // Track memory allocation
-        let memory_words = interp.memory.len() / 32;
+        let memory_words = interp.memory.len().div_ceil(32);
         if memory_words as u64 > self.op_counts.memory_words_allocated {
             self.op_counts.memory_words_allocated = memory_words as u64;
         }
@@ -134,10 +188,15 @@ where
 
     fn call(
         &mut self,
-        _context: &mut CTX,
+        context: &mut CTX,
         inputs: &mut CallInputs,
     ) -> Option<CallOutcome> {
-        let call_index = self.call_frames.len();
+        if self.call_stack.is_empty() {
+            warm_accounts(context, &self.warm_addresses);
+        }
+
+        let call_index = self.next_call_index;
+        self.next_call_index += 1;
         let depth = self.call_stack.len();
 
         let call_type = match inputs.scheme {
@@ -157,6 +216,7 @@ where
             call_type,
             gas_provided: inputs.gas_limit,
             function_selector,
+            create2: None,
         });
 
         None
@@ -170,9 +230,11 @@ where
     ) {
         if let Some(entry) = self.call_stack.pop() {
             // Extract input bytes based on CallInput enum
-            let input_bytes = match &inputs.input {
-                revm::interpreter::CallInput::Bytes(bytes) => Some(bytes.clone()),
-                revm::interpreter::CallInput::SharedBuffer(_) => None,
+            let (input_len, input_bytes) = match &inputs.input {
+                revm::interpreter::CallInput::Bytes(bytes) => {
+                    (bytes.len(), self.capture_call_data.then(|| bytes.clone()))
+                }
+                revm::interpreter::CallInput::SharedBuffer(range) => (range.len(), None),
             };
 
             // Calculate gas used (gas_provided - gas_remaining)
@@ -187,18 +249,26 @@ where
                 gas_provided: entry.gas_provided,
                 gas_used,
                 success: outcome.result.result.is_ok(),
+                out_of_gas: is_out_of_gas(outcome.result.result),
+                input_len,
+                output_len: outcome.result.output.len(),
                 input: input_bytes,
-                output: Some(outcome.result.output.clone()),
+                output: self.capture_call_data.then(|| outcome.result.output.clone()),
             });
         }
     }
 
     fn create(
         &mut self,
-        _context: &mut CTX,
+        context: &mut CTX,
         inputs: &mut CreateInputs,
     ) -> Option<CreateOutcome> {
-        let call_index = self.call_frames.len();
+        if self.call_stack.is_empty() {
+            warm_accounts(context, &self.warm_addresses);
+        }
+
+        let call_index = self.next_call_index;
+        self.next_call_index += 1;
         let depth = self.call_stack.len();
 
         let call_type = match inputs.scheme {
@@ -207,6 +277,13 @@ where
             revm::context_interface::CreateScheme::Custom { .. } => CallType::Create2,
         };
 
+        let create2 = match inputs.scheme {
+            revm::context_interface::CreateScheme::Create2 { salt } => {
+                Some(Create2Deployment::new(inputs.caller, B256::from(salt), &inputs.init_code))
+            }
+            _ => None,
+        };
+
         self.call_stack.push(CallStackEntry {
             call_index,
             depth,
@@ -215,6 +292,7 @@ where
             call_type,
             gas_provided: inputs.gas_limit,
             function_selector: None, // CREATE operations don't have function selectors
+            create2,
         });
 
         None
@@ -230,6 +308,12 @@ where
             let created_address = outcome.address.unwrap_or(Address::ZERO);
             let gas_used = entry.gas_provided.saturating_sub(outcome.result.gas.remaining());
 
+            if let Some(mut deployment) = entry.create2 {
+                deployment.actual_address =
+                    outcome.result.result.is_ok().then_some(outcome.address).flatten();
+                self.create2_deployments.push(deployment);
+            }
+
             self.call_frames.push(CallFrame {
                 call_index: entry.call_index,
                 depth: entry.depth,
@@ -239,8 +323,11 @@ where
                 gas_provided: entry.gas_provided,
                 gas_used,
                 success: outcome.result.result.is_ok(),
-                input: Some(inputs.init_code.clone()),
-                output: Some(outcome.result.output.clone()),
+                out_of_gas: is_out_of_gas(outcome.result.result),
+                input_len: inputs.init_code.len(),
+                output_len: outcome.result.output.len(),
+                input: self.capture_call_data.then(|| inputs.init_code.clone()),
+                output: self.capture_call_data.then(|| outcome.result.output.clone()),
             });
         }
     }
@@ -422,6 +422,8 @@ where
                 normal_gas_used: normal_gas,
                 experimental_gas_used: experimental_gas,
                 gas_efficiency_ratio: gas_ratio,
+                normal_gas_charged: normal_gas,
+                experimental_gas_charged: experimental_gas,
             };
 
             if gas_analysis.is_structural_divergence() {
@@ -432,11 +434,18 @@ where
                     tx_idx,
                     normal_gas,
                     experimental_gas,
-                    gas_ratio,
+                    gas_ratio = config.quantize_ratio(gas_ratio),
                     "DIVERGENCE: Gas pattern differs structurally"
                 );
             }
 
+            // Detection is done, the ratio is only reported from here on
+            let gas_ratio = config.quantize_ratio(gas_ratio);
+            let gas_analysis = reth_research::divergence::GasAnalysis {
+                gas_efficiency_ratio: gas_ratio,
+                ..gas_analysis
+            };
+
             // 3. Compare state changes (state root comparison via state_changes)
             // We compare the number of state changes as a proxy for state differences
             let normal_state_len = normal_result.state.len();
@@ -559,8 +568,21 @@ where
                     experimental_ops,
                     divergence_location,
                     oog_info,
+                    griefing: experimental_inspector.griefing_info().cloned(),
+                    reverting_selector: experimental_inspector.reverting_selector(),
+                    cause: None,
                     call_trees: None, // TODO: Extract from execution results
                     event_logs: None, // TODO: Convert logs to EventLogs structure
+                    create2_deployments: None,
+                    created_code: None,
+                    gas_observations: None,
+                    threshold_map: None,
+                    truncated: false,
+                    near_miss: false,
+                    comparison: Default::default(),
+                    function_name: None,
+                    calldata: None,
+                    access_list_diff: None,
                 };
 
                 // Record metrics
@@ -615,8 +637,30 @@ where
         let tx_count = block.body().transactions().len();
         let block_duration = block_start.elapsed().as_secs_f64();
 
-        // Record block-level metrics
-        reth_research::metrics::record_block_processed(block_number, tx_count, block_duration);
+        // Record block-level metrics; every transaction is analyzed here
+        reth_research::metrics::record_block_processed(
+            block_number,
+            tx_count,
+            tx_count as u64,
+            block_duration,
+        );
+        if let Some(ref db) = self.research_db {
+            // Transactions execute against the pre-block state, so their gas is not summed
+            let summary = reth_research::database::BlockSummary {
+                block_number,
+                analyzed_tx_count: tx_count as u64,
+                header_gas_used: block.header().gas_used(),
+                normal_gas_used: None,
+            };
+            if let Err(e) = db.record_block_summary(&summary) {
+                warn!(
+                    target: "sync::stages::execution::research",
+                    block = block_number,
+                    error = %e,
+                    "Failed to record block summary to database"
+                );
+            }
+        }
 
         info!(
             target: "sync::stages::execution::research",